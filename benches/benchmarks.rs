@@ -13,8 +13,10 @@ fn gen_test_traj(num_atoms: usize, num_frames: usize) -> Result<NamedTempFile> {
     let mut frame = Frame {
         step: 1,
         time: 1.0,
-        box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
+        box_vector: BoxVector::new([[3.0, 0.0, 0.0], [1.0, 2.0, 0.0], [1.0, 0.5, 1.0]]),
         coords: vec![[1.0, 1.1, 1.2]; num_atoms],
+        velocities: None,
+        forces: None,
     };
 
     for _ in 0..num_frames {
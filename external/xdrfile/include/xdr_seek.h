@@ -8,10 +8,13 @@
     #include <stdint.h>
 #endif
 
+#include <stddef.h>
 #include "xdrfile.h"
 
 int64_t xdr_tell(XDRFILE *xd);
 int xdr_seek(XDRFILE *xd, int64_t pos, int whence);
 int xdr_flush(XDRFILE* xd);
+int64_t xdr_read_at(XDRFILE* xd, int64_t offset, char* buf, size_t len);
+int xdr_setvbuf(XDRFILE* xd, char* buf, size_t size);
 
 #endif
@@ -73,6 +73,9 @@
 #ifndef _XDRFILE_H_
 #define _XDRFILE_H_
 
+#ifdef _WIN32
+#include <wchar.h>
+#endif
 
 #ifdef __cplusplus
 extern "C" 
@@ -123,6 +126,24 @@ extern "C"
 					 const char *    mode);
 
 
+#ifdef _WIN32
+	/*! \brief Open a portable binary file with a UTF-16 path, like _wfopen()
+	 *
+	 *  Works like xdrfile_open(), but \a path may contain characters
+	 *  outside of the ANSI code page and, when prefixed with \\\\?\\, be
+	 *  longer than MAX_PATH. Only available on Windows.
+	 *
+	 *  \param path  Full or relative path (including name) of the file
+	 *  \param mode  "r" for reading, "w" for writing, "a" for append.
+	 *
+	 *  \return Pointer to abstract xdr file datatype, or NULL if an error occurs.
+	 */
+	XDRFILE *
+	xdrfile_wopen   (const wchar_t * path, 
+					 const char *    mode);
+#endif
+
+
 	/*! \brief Close a previously opened portable binary file, just like fclose()
 	 *
 	 *  Use this routine much like calls to the standard library function
@@ -137,6 +158,42 @@ extern "C"
 	xdrfile_close   (XDRFILE *       xfp);
 
 
+	/*! \brief Callbacks to back an XDRFILE by an arbitrary stream
+	 *
+	 *  All callbacks receive \a data as their first argument. \a read and
+	 *  \a write return 1 if exactly \a len bytes were transferred and 0
+	 *  otherwise. \a seek uses the same \a whence values as fseek() and
+	 *  returns 0 on success. \a tell returns the current position or -1 on
+	 *  error. \a write and \a flush may be NULL for read-only streams.
+	 */
+	typedef struct
+	{
+		void *       data;
+		int       (*read)  (void *data, char *buf, unsigned int len);
+		int       (*write) (void *data, char *buf, unsigned int len);
+		int       (*seek)  (void *data, long long pos, int whence);
+		long long (*tell)  (void *data);
+		int       (*flush) (void *data);
+	} xdrfile_io;
+
+
+	/*! \brief Open a portable binary file on top of a custom stream
+	 *
+	 *  Works like xdrfile_open(), but all IO is performed by the callbacks
+	 *  in \a io instead of a standard library FILE handle. The callbacks
+	 *  are copied, but \a io->data must stay valid until the file is closed.
+	 *  xdrfile_close() flushes the stream but does not free \a io->data.
+	 *
+	 *  \param io    Callbacks performing the actual IO
+	 *  \param mode  "r" for reading, "w" for writing, "a" for append.
+	 *
+	 *  \return Pointer to abstract xdr file datatype, or NULL if an error occurs.
+	 */
+	XDRFILE *
+	xdrfile_open_io (const xdrfile_io * io,
+					 const char *       mode);
+
+
 
 
 	/*! \brief Read one or more \a char type variable(s) 
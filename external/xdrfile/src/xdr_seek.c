@@ -13,6 +13,7 @@ struct XDRFILE
     int      buf1size; /**< Current allocated length of buf1          */
     int *    buf2;     /**< Buffer for internal use                   */
     int      buf2size; /**< Current allocated length of buf2          */
+    xdrfile_io * io;   /**< Stream callbacks, NULL if fp is used      */
 };
 //// end of copied
 
@@ -20,6 +21,9 @@ int64_t xdr_tell(XDRFILE *xd)
 {
     FILE* fptr = xd->fp;
 
+    if (xd->io)
+        return xd->io->tell(xd->io->data);
+
 #ifndef _WIN32
     // use posix 64 bit ftell version
     return ftello(fptr);
@@ -35,6 +39,9 @@ int xdr_seek(XDRFILE *xd, int64_t pos, int whence)
     int result = 1;
     FILE* fptr = xd->fp;
 
+    if (xd->io)
+        return xd->io->seek(xd->io->data, pos, whence) != 0 ? exdrNR : exdrOK;
+
 #ifndef _WIN32
     // use posix 64 bit ftell version
     result = fseeko(fptr, pos, whence) < 0 ? exdrNR : exdrOK;
@@ -51,5 +58,40 @@ int xdr_seek(XDRFILE *xd, int64_t pos, int whence)
 
 int xdr_flush(XDRFILE* xdr)
 {
+    if (xdr->io)
+        return xdr->io->flush ? xdr->io->flush(xdr->io->data) : 0;
     return fflush(xdr->fp);
 }
+
+int64_t xdr_read_at(XDRFILE* xd, int64_t offset, char* buf, size_t len)
+{
+    // Read up to len bytes at offset through the file handle, which is
+    // opened for reading and writing, and keep the position. Pending writes
+    // must be flushed before. Streams read at an offset on their own.
+    int64_t pos;
+    size_t count;
+    int failed;
+
+    if (xd->io)
+        return -1;
+    pos = xdr_tell(xd);
+    if (pos < 0 || xdr_seek(xd, offset, SEEK_SET) != exdrOK)
+        return -1;
+    count = fread(buf, 1, len, xd->fp);
+    failed = ferror(xd->fp);
+    clearerr(xd->fp);
+    if (xdr_seek(xd, pos, SEEK_SET) != exdrOK || failed)
+        return -1;
+    return (int64_t)count;
+}
+
+int xdr_setvbuf(XDRFILE* xd, char* buf, size_t size)
+{
+    // Must be called before any other operation on the file. Streams
+    // buffer on their own.
+    if (xd->io)
+        return exdrNR;
+    if (setvbuf(xd->fp, buf, buf ? _IOFBF : _IONBF, size) != 0)
+        return exdrNR;
+    return exdrOK;
+}
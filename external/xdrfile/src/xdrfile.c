@@ -145,6 +145,7 @@ static int  xdr_double      (XDR *xdrs, double *ip);
 static int  xdr_string      (XDR *xdrs, char **ip, unsigned int maxsize);
 static int  xdr_opaque      (XDR *xdrs, char *cp, unsigned int cnt);
 static void xdrstdio_create (XDR *xdrs, FILE *fp, enum xdr_op xop);
+static void xdrio_create    (XDR *xdrs, xdrfile_io *io, enum xdr_op xop);
 
 #define xdr_getpos(xdrs)                                \
         (*(xdrs)->x_ops->x_getpostn)(xdrs)
@@ -177,6 +178,7 @@ struct XDRFILE
     int      buf1size; /**< Current allocated length of buf1          */    
     int *    buf2;     /**< Buffer for internal use                   */
     int      buf2size; /**< Current allocated length of buf2          */ 
+    xdrfile_io * io;   /**< Stream callbacks, NULL if fp is used      */
 };
 
 
@@ -188,44 +190,119 @@ struct XDRFILE
  * called from C - see further down for Fortran77 wrappers.  *
  *************************************************************/
 
-XDRFILE *
-xdrfile_open(const char *path, const char *mode)
+/* Determine the mode for fopen() and the XDR direction of a file mode,
+ * returns 0 if the mode is not valid */
+static int
+xdrfile_mode(const char *mode, char *newmode, enum xdr_op *xdrmode)
 {
-    char newmode[5];
-    enum xdr_op xdrmode;
-    XDRFILE *xfp;
-  
     /* make sure XDR files are opened in binary mode... */
     if(*mode=='w' || *mode=='W') 
     {
         sprintf(newmode,"wb+");
-        xdrmode=XDR_ENCODE;
+        *xdrmode=XDR_ENCODE;
     } else if(*mode == 'a' || *mode == 'A') 
     {
         sprintf(newmode,"ab+");
-        xdrmode = XDR_ENCODE;
+        *xdrmode = XDR_ENCODE;
     } else if(*mode == 'r' || *mode == 'R')
     {
         sprintf(newmode,"rb");
-        xdrmode = XDR_DECODE;
+        *xdrmode = XDR_DECODE;
     } else /* cannot determine mode */
+        return 0;
+    return 1;
+}
+
+/* Wrap an open file in an XDRFILE, the file is closed on failure */
+static XDRFILE *
+xdrfile_from_fp(FILE *fp, const char *mode, enum xdr_op xdrmode)
+{
+    XDRFILE *xfp;
+
+    if((xfp=(XDRFILE *)malloc(sizeof(XDRFILE)))==NULL)
+    {
+        fclose(fp);
+        return NULL;
+    }
+    xfp->fp=fp;
+    if((xfp->xdr=(XDR *)malloc(sizeof(XDR)))==NULL) 
+    {
+        fclose(xfp->fp);
+        free(xfp);
         return NULL;
+    }
+    xfp->mode=*mode;
+    xdrstdio_create((XDR *)(xfp->xdr),xfp->fp,xdrmode);
+    xfp->buf1 = xfp->buf2 = NULL;
+    xfp->buf1size = xfp->buf2size = 0;
+    xfp->io = NULL;
+    return xfp;
+}
+
+XDRFILE *
+xdrfile_open(const char *path, const char *mode)
+{
+    char newmode[5];
+    enum xdr_op xdrmode;
+    FILE *fp;
   
+    if(!xdrfile_mode(mode,newmode,&xdrmode))
+        return NULL;
+    if((fp=fopen(path,newmode))==NULL)
+        return NULL;
+    return xdrfile_from_fp(fp,mode,xdrmode);
+}
+
+#ifdef _WIN32
+XDRFILE *
+xdrfile_wopen(const wchar_t *path, const char *mode)
+{
+    char newmode[5];
+    wchar_t wmode[5];
+    enum xdr_op xdrmode;
+    FILE *fp;
+    int i;
+
+    if(!xdrfile_mode(mode,newmode,&xdrmode))
+        return NULL;
+    for(i=0;i<5;i++)
+        wmode[i]=(wchar_t)newmode[i];
+    if((fp=_wfopen(path,wmode))==NULL)
+        return NULL;
+    return xdrfile_from_fp(fp,mode,xdrmode);
+}
+#endif
+
+XDRFILE *
+xdrfile_open_io(const xdrfile_io *io, const char *mode)
+{
+    enum xdr_op xdrmode;
+    XDRFILE *xfp;
+
+    if(*mode=='w' || *mode=='W' || *mode == 'a' || *mode == 'A')
+        xdrmode=XDR_ENCODE;
+    else if(*mode == 'r' || *mode == 'R')
+        xdrmode = XDR_DECODE;
+    else /* cannot determine mode */
+        return NULL;
+
     if((xfp=(XDRFILE *)malloc(sizeof(XDRFILE)))==NULL)
         return NULL;
-    if((xfp->fp=fopen(path,newmode))==NULL)
+    if((xfp->io=(xdrfile_io *)malloc(sizeof(xdrfile_io)))==NULL)
     {
         free(xfp);
         return NULL;
     }
-    if((xfp->xdr=(XDR *)malloc(sizeof(XDR)))==NULL) 
+    if((xfp->xdr=(XDR *)malloc(sizeof(XDR)))==NULL)
     {
-        fclose(xfp->fp);
+        free(xfp->io);
         free(xfp);
         return NULL;
     }
+    *(xfp->io) = *io;
+    xfp->fp = NULL;
     xfp->mode=*mode;
-    xdrstdio_create((XDR *)(xfp->xdr),xfp->fp,xdrmode);
+    xdrio_create((XDR *)(xfp->xdr),xfp->io,xdrmode);
     xfp->buf1 = xfp->buf2 = NULL;
     xfp->buf1size = xfp->buf2size = 0;
     return xfp;
@@ -241,8 +318,14 @@ xdrfile_close(XDRFILE *xfp)
         if(xfp->xdr)
             xdr_destroy((XDR *)(xfp->xdr));
         free(xfp->xdr);
-        /* close the file */
-        ret=fclose(xfp->fp);
+        /* close the file. Streams are owned by the caller */
+        if(xfp->io)
+        {
+            ret=0;
+            free(xfp->io);
+        }
+        else
+            ret=fclose(xfp->fp);
         if(xfp->buf1size)
             free(xfp->buf1);
         if(xfp->buf2size)
@@ -1070,7 +1153,7 @@ xdrfile_compress_coord_float(float   *ptr,
             lf = *lfp * precision + 0.5;
         else
             lf = *lfp * precision - 0.5;
-        if (fabs(lf) > INT_MAX-2) 
+        if (!(fabs(lf) <= INT_MAX-2)) 
         {
             /* scaling would cause overflow */
             fprintf(stderr, "(xdrfile error) Internal overflow compressing coordinates.\n");
@@ -1085,7 +1168,7 @@ xdrfile_compress_coord_float(float   *ptr,
             lf = *lfp * precision + 0.5;
         else
             lf = *lfp * precision - 0.5;
-        if (fabs(lf) > INT_MAX-2)
+        if (!(fabs(lf) <= INT_MAX-2))
         {
             /* scaling would cause overflow */
             fprintf(stderr, "(xdrfile error) Internal overflow compressing coordinates.\n");
@@ -1100,7 +1183,7 @@ xdrfile_compress_coord_float(float   *ptr,
             lf = *lfp * precision + 0.5;
         else
             lf = *lfp * precision - 0.5;
-        if (fabs(lf) > INT_MAX-2) 
+        if (!(fabs(lf) <= INT_MAX-2)) 
         {
             errval=0;      
         }
@@ -1116,9 +1199,6 @@ xdrfile_compress_coord_float(float   *ptr,
         oldlint2 = lint2;
         oldlint3 = lint3;
     }  
-    xdrfile_write_int(minint,3,xfp);
-    xdrfile_write_int(maxint,3,xfp);
-  
     if ((float)maxint[0] - (float)minint[0] >= INT_MAX-2 ||
         (float)maxint[1] - (float)minint[1] >= INT_MAX-2 ||
         (float)maxint[2] - (float)minint[2] >= INT_MAX-2) {
@@ -1128,6 +1208,11 @@ xdrfile_compress_coord_float(float   *ptr,
         fprintf(stderr, "(xdrfile error) Internal overflow compressing coordinates.\n");
         errval=0;
     }
+    /* do not write coordinates that cannot be restored */
+    if (errval == 0)
+        return -1;
+    xdrfile_write_int(minint,3,xfp);
+    xdrfile_write_int(maxint,3,xfp);
     sizeint[0] = maxint[0] - minint[0]+1;
     sizeint[1] = maxint[1] - minint[1]+1;
     sizeint[2] = maxint[2] - minint[2]+1;
@@ -2630,5 +2715,101 @@ xdrstdio_setpos (XDR *xdrs, unsigned int pos)
 }
 
 
+static int xdrio_getlong (XDR *, int32_t *);
+static int xdrio_putlong (XDR *, int32_t *);
+static int xdrio_getbytes (XDR *, char *, unsigned int);
+static int xdrio_putbytes (XDR *, char *, unsigned int);
+static unsigned int xdrio_getpos (XDR *);
+static int xdrio_setpos (XDR *, unsigned int);
+static void xdrio_destroy (XDR *);
+
+/*
+ * Ops vector for xdr streams backed by xdrfile_io callbacks
+ */
+static const struct xdr_ops xdrio_ops =
+    {
+        xdrio_getlong,          /* deserialize a long int */
+        xdrio_putlong,          /* serialize a long int */
+        xdrio_getbytes,         /* deserialize counted bytes */
+        xdrio_putbytes,         /* serialize counted bytes */
+        xdrio_getpos,           /* get offset in the stream */
+        xdrio_setpos,           /* set offset in the stream */
+        xdrio_destroy,          /* destroy stream */
+    };
+
+/*
+ * Initialize a callback xdr stream.
+ * Works like xdrstdio_create, but performs IO through the callbacks in io.
+ */
+static void
+xdrio_create (XDR *xdrs, xdrfile_io *io, enum xdr_op op)
+{
+    xdrs->x_op = op;
+
+    xdrs->x_ops = (struct xdr_ops *) &xdrio_ops;
+    xdrs->x_private = (char *) io;
+}
+
+static void
+xdrio_destroy (XDR *xdrs)
+{
+    xdrfile_io *io = (xdrfile_io *) xdrs->x_private;
+    if (io->flush)
+        (void) io->flush (io->data);
+}
+
+static int
+xdrio_getlong (XDR *xdrs, int32_t *lp)
+{
+    int32_t mycopy;
+
+    if (!xdrio_getbytes (xdrs, (char *) & mycopy, 4))
+        return 0;
+    *lp = (int32_t) xdr_ntohl (mycopy);
+    return 1;
+}
+
+static int
+xdrio_putlong (XDR *xdrs, int32_t *lp)
+{
+    int32_t mycopy = xdr_htonl (*lp);
+    return xdrio_putbytes (xdrs, (char *) & mycopy, 4);
+}
+
+static int
+xdrio_getbytes (XDR *xdrs, char *addr, unsigned int len)
+{
+    xdrfile_io *io = (xdrfile_io *) xdrs->x_private;
+    if (len == 0)
+        return 1;
+    return io->read (io->data, addr, len);
+}
+
+static int
+xdrio_putbytes (XDR *xdrs, char *addr, unsigned int len)
+{
+    xdrfile_io *io = (xdrfile_io *) xdrs->x_private;
+    if (len == 0)
+        return 1;
+    if (io->write == NULL)
+        return 0;
+    return io->write (io->data, addr, len);
+}
+
+static unsigned int
+xdrio_getpos (XDR *xdrs)
+{
+    xdrfile_io *io = (xdrfile_io *) xdrs->x_private;
+    return (unsigned int) io->tell (io->data);
+}
+
+static int
+xdrio_setpos (XDR *xdrs, unsigned int pos)
+{
+    xdrfile_io *io = (xdrfile_io *) xdrs->x_private;
+    return io->seek (io->data, pos, 0) < 0 ? 0 : 1;
+}
+
+
 
 #endif /* HAVE_RPC_XDR_H not defined */
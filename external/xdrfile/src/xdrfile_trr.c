@@ -531,3 +531,10 @@ int read_trr(XDRFILE *xd,int natoms,int *step,float *t,float *lambda,
     return do_trn(xd,1,step,t,lambda,box,&natoms,x,v,f);
 }
 
+/* Read the data of a frame after its header was read into *sh with
+   do_trnheader. NULL arrays are read from the file but not used. */
+extern int read_trr_data(XDRFILE *xd,t_trnheader *sh,matrix box,rvec *x,rvec *v,rvec *f)
+{
+    return do_htrn(xd,1,sh,box,x,v,f);
+}
+
@@ -0,0 +1,20 @@
+//! Helpers shared by the command line tools
+
+use std::path::Path;
+use xdrfile::*;
+
+/// Get the format of an output file from its extension
+pub fn output_format(path: &Path) -> Result<TrajectoryFormat, String> {
+    TrajectoryFormat::from_extension(path).ok_or_else(|| {
+        format!(
+            "Cannot determine the format of {:?}, expected .xtc or .trr",
+            path
+        )
+    })
+}
+
+/// Print `message` and the usage of the tool, then exit with an error
+pub fn usage_error(message: &str, usage: &str) -> ! {
+    eprintln!("{}\n\n{}", message, usage);
+    std::process::exit(2);
+}
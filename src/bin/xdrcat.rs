@@ -0,0 +1,110 @@
+//! Concatenate trajectory parts into a single file, like simple uses of
+//! `gmx trjcat`
+
+mod common;
+
+use std::error::Error;
+use std::path::PathBuf;
+use xdrfile::*;
+
+const USAGE: &str = "\
+Usage: xdrcat [-a] -o OUTPUT PART...
+
+Concatenate XTC or TRR trajectory parts into OUTPUT. If a part starts before
+the previous part ends, the overlapping frames of the previous part are
+dropped. Restarted step numbers continue after the previous part.
+
+Options:
+  -o, --output OUTPUT  file to write, its format is given by the extension
+  -a, --append         append to OUTPUT, skipping frames up to its last time
+  -h, --help           print this help";
+
+struct Args {
+    output: PathBuf,
+    append: bool,
+    parts: Vec<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Args {
+    let mut output = None;
+    let mut append = false;
+    let mut parts = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => common::usage_error("Missing value for --output", USAGE),
+            },
+            "-a" | "--append" => append = true,
+            _ if arg.starts_with('-') => {
+                common::usage_error(&format!("Unknown option {}", arg), USAGE)
+            }
+            _ => parts.push(PathBuf::from(arg)),
+        }
+    }
+    let output = output.unwrap_or_else(|| common::usage_error("Missing --output", USAGE));
+    if parts.is_empty() {
+        common::usage_error("No trajectory parts given", USAGE);
+    }
+    Args {
+        output,
+        append,
+        parts,
+    }
+}
+
+fn run(args: &Args) -> Result<usize, Box<dyn Error>> {
+    let parts = args
+        .parts
+        .iter()
+        .map(open_any)
+        .collect::<Result<Vec<_>>>()?;
+    let mut chain = ChainedTrajectory::new(parts)?;
+
+    // When appending, continue after the last frame already in the output
+    let mut mode = FileMode::Write;
+    let mut last_time = None;
+    if args.append && args.output.exists() {
+        let last = open_any(&args.output)?.last_frame()?;
+        match chain.seek_to_time(last.time) {
+            Ok(()) => {}
+            Err(e) if e.is_eof() => return Ok(0),
+            Err(e) => return Err(e.into()),
+        }
+        mode = FileMode::Append;
+        last_time = Some(last.time);
+    }
+    let mut output = common::output_format(&args.output)?.open(&args.output, mode)?;
+
+    let mut frame = Frame::with_len(chain.get_num_atoms()?);
+    let mut num_written = 0;
+    loop {
+        match chain.read(&mut frame) {
+            Ok(()) => {}
+            Err(e) if e.is_eof() => break,
+            Err(e) => return Err(e.into()),
+        }
+        if last_time.is_some_and(|time| frame.time <= time) {
+            continue;
+        }
+        output.write(&frame)?;
+        num_written += 1;
+    }
+    output.flush()?;
+    Ok(num_written)
+}
+
+fn main() {
+    let args = parse_args(std::env::args().skip(1));
+    match run(&args) {
+        Ok(num_frames) => println!("Wrote {} frames to {:?}", num_frames, args.output),
+        Err(e) => {
+            eprintln!("xdrcat: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Convert trajectories between XTC and TRR, optionally selecting frames by
+//! stride and time and changing the XTC precision
+
+mod common;
+
+use std::error::Error;
+use std::path::PathBuf;
+use std::str::FromStr;
+use xdrfile::*;
+
+const USAGE: &str = "\
+Usage: xdrconvert [OPTIONS] -o OUTPUT INPUT
+
+Convert the XTC or TRR trajectory INPUT to OUTPUT. The formats are given by
+the file extensions, so this can also rewrite a file in the same format, e.g.
+with a different precision or fewer frames.
+
+Options:
+  -o, --output OUTPUT  file to write
+  -p, --precision P    precision of XTC output, 1000 stores coordinates with
+                       1/1000 nm resolution (default 1000)
+  -s, --stride N       only write every N-th frame (default 1)
+  -b, --begin TIME     skip frames before TIME in ps
+  -e, --end TIME       skip frames after TIME in ps
+  -h, --help           print this help";
+
+struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    precision: Option<f32>,
+    options: ConvertOptions,
+}
+
+/// Parse the value of `option`, exiting with the usage if it is invalid
+fn value<T: FromStr>(option: &str, value: Option<String>) -> T {
+    match value.map(|v| v.parse()) {
+        Some(Ok(value)) => value,
+        Some(Err(_)) => common::usage_error(&format!("Invalid value for {}", option), USAGE),
+        None => common::usage_error(&format!("Missing value for {}", option), USAGE),
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Args {
+    let mut input = None;
+    let mut output = None;
+    let mut precision = None;
+    let mut options = ConvertOptions::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            "-o" | "--output" => output = Some(value::<PathBuf>(&arg, args.next())),
+            "-p" | "--precision" => precision = Some(value(&arg, args.next())),
+            "-s" | "--stride" => match value(&arg, args.next()) {
+                0 => common::usage_error("The stride must be at least 1", USAGE),
+                n => options = options.stride(n),
+            },
+            "-b" | "--begin" => options = options.start_time(value(&arg, args.next())),
+            "-e" | "--end" => options = options.end_time(value(&arg, args.next())),
+            _ if arg.starts_with('-') => {
+                common::usage_error(&format!("Unknown option {}", arg), USAGE)
+            }
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => common::usage_error("Only one input trajectory can be given", USAGE),
+        }
+    }
+    Args {
+        input: input.unwrap_or_else(|| common::usage_error("Missing input trajectory", USAGE)),
+        output: output.unwrap_or_else(|| common::usage_error("Missing --output", USAGE)),
+        precision,
+        options,
+    }
+}
+
+fn run(args: &Args) -> Result<usize, Box<dyn Error>> {
+    let mut input = open_any(&args.input)?;
+    let format = common::output_format(&args.output)?;
+    let mut output: Box<dyn Trajectory> = match (format, args.precision) {
+        (TrajectoryFormat::Xtc, precision) => {
+            let mut output = XTCTrajectory::open_write(&args.output)?;
+            if let Some(precision) = precision {
+                output.set_precision(precision);
+            }
+            Box::new(output)
+        }
+        (TrajectoryFormat::Trr, None) => Box::new(TRRTrajectory::open_write(&args.output)?),
+        (TrajectoryFormat::Trr, Some(_)) => {
+            return Err("--precision only applies to XTC output".into())
+        }
+    };
+    Ok(convert_with(&mut input, &mut output, &args.options)?)
+}
+
+fn main() {
+    let args = parse_args(std::env::args().skip(1));
+    match run(&args) {
+        Ok(num_frames) => println!("Wrote {} frames to {:?}", num_frames, args.output),
+        Err(e) => {
+            eprintln!("xdrconvert: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
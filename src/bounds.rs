@@ -0,0 +1,134 @@
+use crate::Frame;
+
+/// Axis-aligned bounding box and statistics of the coordinates of a frame,
+/// computed with `Frame::bounds`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bounds {
+    /// Smallest coordinate along every axis
+    pub min: [f32; 3],
+    /// Largest coordinate along every axis
+    pub max: [f32; 3],
+    /// Mean coordinate along every axis, the centroid of all atoms
+    pub mean: [f32; 3],
+    /// Standard deviation of the coordinates along every axis
+    pub std_dev: [f32; 3],
+}
+
+impl Bounds {
+    /// Edge lengths of the bounding box
+    pub fn size(&self) -> [f32; 3] {
+        [
+            self.max[0] - self.min[0],
+            self.max[1] - self.min[1],
+            self.max[2] - self.min[2],
+        ]
+    }
+
+    /// Center of the bounding box, which differs from `mean` unless the
+    /// atoms are distributed symmetrically
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.min[0] + self.max[0]) / 2.0,
+            (self.min[1] + self.max[1]) / 2.0,
+            (self.min[2] + self.max[2]) / 2.0,
+        ]
+    }
+}
+
+impl Frame {
+    /// Bounding box, mean and standard deviation of the coordinates of all
+    /// atoms, e.g. to check that a system did not blow up or to place a
+    /// camera. None if the frame has no atoms.
+    ///
+    /// The coordinates are read in a single pass. Sums are accumulated in
+    /// double precision, so that the mean and the standard deviation of
+    /// large systems stay accurate.
+    ///
+    /// ```rust
+    /// use xdrfile::*;
+    ///
+    /// let frame = Frame::from_coords(
+    ///     0,
+    ///     0.0,
+    ///     BoxVector::default(),
+    ///     vec![[0.0, 1.0, 2.0], [2.0, 1.0, -2.0]],
+    /// );
+    /// let bounds = frame.bounds().unwrap();
+    /// assert_eq!(bounds.min, [0.0, 1.0, -2.0]);
+    /// assert_eq!(bounds.size(), [2.0, 0.0, 4.0]);
+    /// assert_eq!(bounds.std_dev, [1.0, 0.0, 2.0]);
+    /// ```
+    pub fn bounds(&self) -> Option<Bounds> {
+        if self.coords.is_empty() {
+            return None;
+        }
+        let mut min = [f32::INFINITY; 3];
+        let mut max = [f32::NEG_INFINITY; 3];
+        let mut sum = [0.0f64; 3];
+        let mut sum_squares = [0.0f64; 3];
+        for coords in &self.coords {
+            for dim in 0..3 {
+                let c = coords[dim];
+                min[dim] = min[dim].min(c);
+                max[dim] = max[dim].max(c);
+                sum[dim] += f64::from(c);
+                sum_squares[dim] += f64::from(c) * f64::from(c);
+            }
+        }
+
+        let n = self.coords.len() as f64;
+        let mut mean = [0.0; 3];
+        let mut std_dev = [0.0; 3];
+        for dim in 0..3 {
+            let m = sum[dim] / n;
+            // Rounding can make the variance slightly negative
+            let variance = (sum_squares[dim] / n - m * m).max(0.0);
+            mean[dim] = m as f32;
+            std_dev[dim] = variance.sqrt() as f32;
+        }
+        Some(Bounds {
+            min,
+            max,
+            mean,
+            std_dev,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    fn test_bounds() -> Result<()> {
+        assert_eq!(Frame::new().bounds(), None);
+
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(304);
+        trajectory.read(&mut frame)?;
+        let bounds = frame.bounds().unwrap();
+        let centroid = frame.centroid(&AtomSelection::new(0..304)).unwrap();
+        for dim in 0..3 {
+            let values = frame.coords.iter().map(|c| c[dim]);
+            assert_eq!(bounds.min[dim], values.clone().fold(f32::MAX, f32::min));
+            assert_eq!(bounds.max[dim], values.clone().fold(f32::MIN, f32::max));
+            assert!(bounds.min[dim] <= bounds.center()[dim]);
+            assert!(bounds.center()[dim] <= bounds.max[dim]);
+            assert_approx_eq!(bounds.mean[dim], centroid[dim], 1e-5);
+
+            let variance = values
+                .map(|v| (v - centroid[dim]) * (v - centroid[dim]))
+                .sum::<f32>()
+                / 304.0;
+            assert_approx_eq!(bounds.std_dev[dim], variance.sqrt(), 1e-4);
+        }
+
+        // A single atom has no extent
+        frame.coords.truncate(1);
+        let bounds = frame.bounds().unwrap();
+        assert_eq!(bounds.size(), [0.0; 3]);
+        assert_eq!(bounds.std_dev, [0.0; 3]);
+        assert_eq!(bounds.mean, frame[0]);
+        Ok(())
+    }
+}
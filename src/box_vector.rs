@@ -0,0 +1,436 @@
+use crate::{Error, Result};
+use std::ops::{Index, IndexMut};
+
+/// Tolerance for the skewness of triclinic boxes, as used by GROMACS
+const BOX_MARGIN: f32 = 1.0010;
+
+/// The 3x3 box of a frame.
+///
+/// Boxes are single precision by default. Double precision frames
+/// (`FrameF64`) have a `BoxVector<f64>`, convert it with `to_f32` to use
+/// the methods for single precision boxes.
+///
+/// Like in GROMACS, the rows are the three box vectors a, b and c. Valid
+/// boxes form a lower triangular matrix (a along the x axis, b in the xy
+/// plane) and are not skewed by more than half a box length.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// let box_vector = BoxVector::rectangular(2.0, 3.0, 4.0);
+/// assert_eq!(box_vector.shape(), BoxShape::Rectangular);
+/// assert_eq!(box_vector[1], [0.0, 3.0, 0.0]);
+/// assert!(box_vector.validate().is_ok());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct BoxVector<T = f32>([[T; 3]; 3]);
+
+/// A box described by the lengths of its vectors a, b and c and the angles
+/// between them in degrees, the representation of PDB `CRYST1` records and
+/// of many analysis tools. See `BoxVector::lattice_parameters` and
+/// `BoxVector::from_lattice_parameters`.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     // Rhombic dodecahedron with a square xy plane
+///     let parameters = LatticeParameters {
+///         a: 5.0,
+///         b: 5.0,
+///         c: 5.0,
+///         alpha: 60.0,
+///         beta: 60.0,
+///         gamma: 90.0,
+///     };
+///     let box_vector = BoxVector::from_lattice_parameters(&parameters)?;
+///     assert_eq!(box_vector[1], [0.0, 5.0, 0.0]);
+///     assert_eq!(box_vector.shape(), BoxShape::Triclinic);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LatticeParameters {
+    /// Length of the first box vector
+    pub a: f32,
+    /// Length of the second box vector
+    pub b: f32,
+    /// Length of the third box vector
+    pub c: f32,
+    /// Angle between b and c
+    pub alpha: f32,
+    /// Angle between a and c
+    pub beta: f32,
+    /// Angle between a and b
+    pub gamma: f32,
+}
+
+/// Classification of a box by the shape it describes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoxShape {
+    /// All entries are zero, i.e. the system has no box
+    None,
+    /// A rectangular box with equal edge lengths
+    Cubic,
+    /// A box with only diagonal entries
+    Rectangular,
+    /// A box with off-diagonal entries
+    Triclinic,
+}
+
+impl<T> BoxVector<T> {
+    /// Creates a box from the raw matrix with the box vectors as rows
+    pub fn new(matrix: [[T; 3]; 3]) -> BoxVector<T> {
+        BoxVector(matrix)
+    }
+
+    /// The raw matrix with the box vectors as rows
+    pub fn as_matrix(&self) -> &[[T; 3]; 3] {
+        &self.0
+    }
+
+    /// Mutable access to the raw matrix with the box vectors as rows
+    pub fn as_mut_matrix(&mut self) -> &mut [[T; 3]; 3] {
+        &mut self.0
+    }
+}
+
+impl BoxVector {
+    /// Creates a rectangular box with the given edge lengths
+    pub fn rectangular(x: f32, y: f32, z: f32) -> BoxVector {
+        BoxVector([[x, 0.0, 0.0], [0.0, y, 0.0], [0.0, 0.0, z]])
+    }
+
+    /// Copy the box into a double precision box
+    pub fn to_f64(&self) -> BoxVector<f64> {
+        BoxVector(self.0.map(|v| v.map(f64::from)))
+    }
+
+    /// Classify the box by its entries
+    pub fn shape(&self) -> BoxShape {
+        let m = &self.0;
+        let off_diagonal = (0..3).any(|i| (0..3).any(|j| i != j && m[i][j] != 0.0));
+        if off_diagonal {
+            BoxShape::Triclinic
+        } else if m[0][0] == 0.0 && m[1][1] == 0.0 && m[2][2] == 0.0 {
+            BoxShape::None
+        } else if m[0][0] == m[1][1] && m[1][1] == m[2][2] {
+            BoxShape::Cubic
+        } else {
+            BoxShape::Rectangular
+        }
+    }
+
+    /// Check that the box follows the GROMACS conventions: either no box, or
+    /// a lower triangular matrix with positive diagonal that is not skewed by
+    /// more than half a box length.
+    pub fn validate(&self) -> Result<()> {
+        let m = &self.0;
+        let invalid = |reason| {
+            Err(Error::InvalidBox {
+                box_vector: *self,
+                reason,
+            })
+        };
+        if self.shape() == BoxShape::None {
+            return Ok(());
+        }
+        if m[0][1] != 0.0 || m[0][2] != 0.0 || m[1][2] != 0.0 {
+            return invalid("the first vector must be parallel to the x axis and the second vector must lie in the xy plane");
+        }
+        if m[0][0] <= 0.0 || m[1][1] <= 0.0 || m[2][2] <= 0.0 {
+            return invalid("the box lengths must be positive");
+        }
+        if m[1][0].abs() > BOX_MARGIN * 0.5 * m[0][0]
+            || m[2][0].abs() > BOX_MARGIN * 0.5 * m[0][0]
+            || m[2][1].abs() > BOX_MARGIN * 0.5 * m[1][1]
+        {
+            return invalid("the box is too skewed");
+        }
+        Ok(())
+    }
+
+    /// True if `validate` succeeds
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_ok()
+    }
+
+    /// Lengths of the box vectors and angles between them. Angles with a
+    /// vector of zero length are reported as 90 degrees, so a frame without
+    /// a box has lengths of zero and right angles.
+    pub fn lattice_parameters(&self) -> LatticeParameters {
+        let vectors = self.0.map(|v| v.map(f64::from));
+        let length = |v: &[f64; 3]| (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        let lengths = vectors.each_ref().map(length);
+        let angle = |i: usize, j: usize| {
+            if lengths[i] == 0.0 || lengths[j] == 0.0 {
+                return 90.0;
+            }
+            let (u, v) = (&vectors[i], &vectors[j]);
+            let cos = (u[0] * v[0] + u[1] * v[1] + u[2] * v[2]) / (lengths[i] * lengths[j]);
+            // Rounding can push the cosine of parallel vectors beyond 1
+            cos.clamp(-1.0, 1.0).acos().to_degrees() as f32
+        };
+        LatticeParameters {
+            a: lengths[0] as f32,
+            b: lengths[1] as f32,
+            c: lengths[2] as f32,
+            alpha: angle(1, 2),
+            beta: angle(0, 2),
+            gamma: angle(0, 1),
+        }
+    }
+
+    /// Creates the box with the given lattice parameters in the orientation
+    /// used by GROMACS: a along the x axis and b in the xy plane. Right
+    /// angles give exactly rectangular boxes, and parameters with all
+    /// lengths zero give no box.
+    ///
+    /// Returns an error if the lengths are not positive or the angles do
+    /// not describe a box, and `Error::InvalidBox` if the box is more skewed
+    /// than GROMACS allows, see `validate`.
+    pub fn from_lattice_parameters(parameters: &LatticeParameters) -> Result<BoxVector> {
+        let p = parameters;
+        let invalid = |reason| {
+            Err(Error::InvalidLatticeParameters {
+                parameters: *parameters,
+                reason,
+            })
+        };
+        if p.a == 0.0 && p.b == 0.0 && p.c == 0.0 {
+            return Ok(BoxVector::default());
+        }
+        // Also rejects NaN
+        if !(p.a > 0.0 && p.b > 0.0 && p.c > 0.0) {
+            return invalid("the lengths must be positive");
+        }
+        if ![p.alpha, p.beta, p.gamma]
+            .iter()
+            .all(|&angle| angle > 0.0 && angle < 180.0)
+        {
+            return invalid("the angles must be between 0 and 180 degrees");
+        }
+
+        // cos(90°) is not exactly zero in floating point
+        let cos = |angle: f32| {
+            if angle == 90.0 {
+                0.0
+            } else {
+                f64::from(angle).to_radians().cos()
+            }
+        };
+        let (cos_alpha, cos_beta, cos_gamma) = (cos(p.alpha), cos(p.beta), cos(p.gamma));
+        let sin_gamma = f64::from(p.gamma).to_radians().sin();
+        let (b, c) = (f64::from(p.b), f64::from(p.c));
+        let c_x = c * cos_beta;
+        let c_y = c * (cos_alpha - cos_beta * cos_gamma) / sin_gamma;
+        let c_z_squared = c * c - c_x * c_x - c_y * c_y;
+        if c_z_squared <= 0.0 {
+            return invalid("the angles do not describe a box");
+        }
+
+        let box_vector = BoxVector([
+            [p.a, 0.0, 0.0],
+            [(b * cos_gamma) as f32, (b * sin_gamma) as f32, 0.0],
+            [c_x as f32, c_y as f32, c_z_squared.sqrt() as f32],
+        ]);
+        box_vector.validate()?;
+        Ok(box_vector)
+    }
+
+    /// Volume spanned by the box vectors (absolute value of the determinant)
+    pub fn volume(&self) -> f32 {
+        let [a, b, c] = self.0;
+        let det = a[0] * (b[1] * c[2] - b[2] * c[1]) - a[1] * (b[0] * c[2] - b[2] * c[0])
+            + a[2] * (b[0] * c[1] - b[1] * c[0]);
+        det.abs()
+    }
+}
+
+impl BoxVector<f64> {
+    /// Round the box to single precision
+    pub fn to_f32(&self) -> BoxVector {
+        BoxVector(self.0.map(|v| v.map(|c| c as f32)))
+    }
+}
+
+impl<T> From<[[T; 3]; 3]> for BoxVector<T> {
+    fn from(matrix: [[T; 3]; 3]) -> Self {
+        BoxVector(matrix)
+    }
+}
+
+impl<T> From<BoxVector<T>> for [[T; 3]; 3] {
+    fn from(box_vector: BoxVector<T>) -> Self {
+        box_vector.0
+    }
+}
+
+impl<T> Index<usize> for BoxVector<T> {
+    type Output = [T; 3];
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl<T> IndexMut<usize> for BoxVector<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        &mut self.0[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shape() {
+        assert_eq!(BoxVector::default().shape(), BoxShape::None);
+        assert_eq!(
+            BoxVector::rectangular(2.0, 2.0, 2.0).shape(),
+            BoxShape::Cubic
+        );
+        assert_eq!(
+            BoxVector::rectangular(2.0, 3.0, 2.0).shape(),
+            BoxShape::Rectangular
+        );
+        let triclinic = BoxVector::new([[2.0, 0.0, 0.0], [1.0, 2.0, 0.0], [0.0, 0.0, 2.0]]);
+        assert_eq!(triclinic.shape(), BoxShape::Triclinic);
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(BoxVector::default().is_valid());
+        assert!(BoxVector::rectangular(1.0, 2.0, 3.0).is_valid());
+        assert!(BoxVector::new([[2.0, 0.0, 0.0], [1.0, 2.0, 0.0], [-1.0, 1.0, 2.0]]).is_valid());
+
+        // not lower triangular
+        assert!(!BoxVector::new([[2.0, 1.0, 0.0], [0.0, 2.0, 0.0], [0.0, 0.0, 2.0]]).is_valid());
+        // negative or missing length
+        assert!(!BoxVector::rectangular(1.0, -2.0, 3.0).is_valid());
+        assert!(!BoxVector::rectangular(1.0, 0.0, 3.0).is_valid());
+        // too skewed
+        let skewed = BoxVector::new([[2.0, 0.0, 0.0], [1.5, 2.0, 0.0], [0.0, 0.0, 2.0]]);
+        match skewed.validate() {
+            Err(Error::InvalidBox { box_vector, .. }) => assert_eq!(box_vector, skewed),
+            other => panic!("Expected InvalidBox, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_volume() {
+        assert_eq!(BoxVector::rectangular(2.0, 3.0, 4.0).volume(), 24.0);
+        let triclinic = BoxVector::new([[2.0, 0.0, 0.0], [1.0, 2.0, 0.0], [-1.0, 1.0, 2.0]]);
+        assert_eq!(triclinic.volume(), 8.0);
+        assert_eq!(BoxVector::default().volume(), 0.0);
+    }
+
+    #[test]
+    fn test_lattice_parameters() -> Result<()> {
+        let rectangular = BoxVector::rectangular(2.0, 3.0, 4.0);
+        let parameters = rectangular.lattice_parameters();
+        assert_eq!(
+            parameters,
+            LatticeParameters {
+                a: 2.0,
+                b: 3.0,
+                c: 4.0,
+                alpha: 90.0,
+                beta: 90.0,
+                gamma: 90.0,
+            }
+        );
+        assert_eq!(
+            BoxVector::from_lattice_parameters(&parameters)?,
+            rectangular
+        );
+
+        let none = BoxVector::default().lattice_parameters();
+        assert_eq!((none.a, none.alpha), (0.0, 90.0));
+        assert_eq!(
+            BoxVector::from_lattice_parameters(&none)?,
+            BoxVector::default()
+        );
+
+        // Truncated octahedron and rhombic dodecahedron as set up by GROMACS
+        let octahedron = BoxVector::new([
+            [7.0, 0.0, 0.0],
+            [2.333333, 6.599663, 0.0],
+            [-2.333333, 3.299831, 5.715476],
+        ]);
+        let dodecahedron = BoxVector::new([[5.0, 0.0, 0.0], [0.0, 5.0, 0.0], [2.5, 2.5, 3.535534]]);
+        for (box_vector, angles) in [
+            (octahedron, [70.52878, 109.47122, 70.52878]),
+            (dodecahedron, [60.0, 60.0, 90.0]),
+        ] {
+            let parameters = box_vector.lattice_parameters();
+            let converted = [parameters.alpha, parameters.beta, parameters.gamma];
+            for (&angle, expected) in converted.iter().zip(angles) {
+                assert_approx_eq!(angle, expected, 1e-3);
+            }
+            let back = BoxVector::from_lattice_parameters(&parameters)?;
+            for (row, expected) in back.as_matrix().iter().zip(box_vector.as_matrix()) {
+                for dim in 0..3 {
+                    assert_approx_eq!(row[dim], expected[dim], 1e-5);
+                }
+            }
+            assert!(back.is_valid());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_lattice_parameters() {
+        let valid = BoxVector::rectangular(2.0, 2.0, 2.0).lattice_parameters();
+        for parameters in [
+            LatticeParameters { b: -1.0, ..valid },
+            LatticeParameters {
+                c: f32::NAN,
+                ..valid
+            },
+            LatticeParameters { beta: 0.0, ..valid },
+            LatticeParameters {
+                gamma: 180.0,
+                ..valid
+            },
+            // c can not be at 10 degrees from both a and b
+            LatticeParameters {
+                alpha: 10.0,
+                beta: 10.0,
+                ..valid
+            },
+        ] {
+            let result = BoxVector::from_lattice_parameters(&parameters);
+            assert!(
+                matches!(result, Err(Error::InvalidLatticeParameters { .. })),
+                "{:?}",
+                result
+            );
+        }
+
+        // Valid parameters, but too skewed for GROMACS
+        let skewed = LatticeParameters {
+            gamma: 30.0,
+            ..valid
+        };
+        let result = BoxVector::from_lattice_parameters(&skewed);
+        assert!(matches!(result, Err(Error::InvalidBox { .. })));
+    }
+
+    #[test]
+    fn test_matrix_conversion() {
+        let matrix = [[2.0, 0.0, 0.0], [1.0, 2.0, 0.0], [0.0, 0.0, 2.0]];
+        let mut box_vector = BoxVector::from(matrix);
+        assert_eq!(box_vector.as_matrix(), &matrix);
+        box_vector[2][2] = 3.0;
+        let converted: [[f32; 3]; 3] = box_vector.into();
+        assert_eq!(converted[2], [0.0, 0.0, 3.0]);
+    }
+}
@@ -1,5 +1,5 @@
 //! # Low level bindings to the c library from GROMACS
-#![allow(non_upper_case_globals, non_camel_case_types)]
+#![allow(non_upper_case_globals, non_camel_case_types, non_snake_case)]
 
 pub mod xdr_seek;
 pub mod xdrfile;
@@ -102,6 +102,21 @@ extern "C" {
 extern "C" {
     pub fn xdr_flush(xd: *mut XDRFILE) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn xdr_read_at(
+        xd: *mut XDRFILE,
+        offset: i64,
+        buf: *mut ::std::os::raw::c_char,
+        len: usize,
+    ) -> i64;
+}
+extern "C" {
+    pub fn xdr_setvbuf(
+        xd: *mut XDRFILE,
+        buf: *mut ::std::os::raw::c_char,
+        size: usize,
+    ) -> ::std::os::raw::c_int;
+}
 
 #[cfg(test)]
 mod tests {
@@ -46,6 +46,20 @@ extern "C" {
         mode: *const ::std::os::raw::c_char,
     ) -> *mut XDRFILE;
 }
+#[cfg(windows)]
+extern "C" {
+    #[doc = " \\brief Open a portable binary file with a UTF-16 path, like _wfopen()"]
+    #[doc = ""]
+    #[doc = "  Works like xdrfile_open(), but \\a path may contain characters"]
+    #[doc = "  outside of the ANSI code page and, when prefixed with \\\\\\\\?\\\\, be"]
+    #[doc = "  longer than MAX_PATH. Only available on Windows."]
+    #[doc = ""]
+    #[doc = "  \\param path  Full or relative path (including name) of the file"]
+    #[doc = "  \\param mode  \"r\" for reading, \"w\" for writing, \"a\" for append."]
+    #[doc = ""]
+    #[doc = "  \\return Pointer to abstract xdr file datatype, or NULL if an error occurs."]
+    pub fn xdrfile_wopen(path: *const u16, mode: *const ::std::os::raw::c_char) -> *mut XDRFILE;
+}
 extern "C" {
     #[doc = " \\brief Close a previously opened portable binary file, just like fclose()"]
     #[doc = ""]
@@ -58,6 +72,62 @@ extern "C" {
     #[doc = "  \\return     0 on success, non-zero on error."]
     pub fn xdrfile_close(xfp: *mut XDRFILE) -> ::std::os::raw::c_int;
 }
+#[doc = " \\brief Callbacks to back an XDRFILE by an arbitrary stream"]
+#[doc = ""]
+#[doc = "  All callbacks receive \\a data as their first argument. \\a read and"]
+#[doc = "  \\a write return 1 if exactly \\a len bytes were transferred and 0"]
+#[doc = "  otherwise. \\a seek uses the same \\a whence values as fseek() and"]
+#[doc = "  returns 0 on success. \\a tell returns the current position or -1 on"]
+#[doc = "  error. \\a write and \\a flush may be NULL for read-only streams."]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct xdrfile_io {
+    pub data: *mut ::std::os::raw::c_void,
+    pub read: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            buf: *mut ::std::os::raw::c_char,
+            len: ::std::os::raw::c_uint,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub write: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            buf: *mut ::std::os::raw::c_char,
+            len: ::std::os::raw::c_uint,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub seek: ::std::option::Option<
+        unsafe extern "C" fn(
+            data: *mut ::std::os::raw::c_void,
+            pos: ::std::os::raw::c_longlong,
+            whence: ::std::os::raw::c_int,
+        ) -> ::std::os::raw::c_int,
+    >,
+    pub tell: ::std::option::Option<
+        unsafe extern "C" fn(data: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_longlong,
+    >,
+    pub flush: ::std::option::Option<
+        unsafe extern "C" fn(data: *mut ::std::os::raw::c_void) -> ::std::os::raw::c_int,
+    >,
+}
+extern "C" {
+    #[doc = " \\brief Open a portable binary file on top of a custom stream"]
+    #[doc = ""]
+    #[doc = "  Works like xdrfile_open(), but all IO is performed by the callbacks"]
+    #[doc = "  in \\a io instead of a standard library FILE handle. The callbacks"]
+    #[doc = "  are copied, but \\a io->data must stay valid until the file is closed."]
+    #[doc = "  xdrfile_close() flushes the stream but does not free \\a io->data."]
+    #[doc = ""]
+    #[doc = "  \\param io    Callbacks performing the actual IO"]
+    #[doc = "  \\param mode  \"r\" for reading, \"w\" for writing, \"a\" for append."]
+    #[doc = ""]
+    #[doc = "  \\return Pointer to abstract xdr file datatype, or NULL if an error occurs."]
+    pub fn xdrfile_open_io(
+        io: *const xdrfile_io,
+        mode: *const ::std::os::raw::c_char,
+    ) -> *mut XDRFILE;
+}
 extern "C" {
     #[doc = " \\brief Read one or more \\a char type variable(s)"]
     #[doc = ""]
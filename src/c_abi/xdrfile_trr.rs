@@ -1,15 +1,45 @@
 use super::xdrfile::*;
 
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct t_trnheader {
+    pub bDouble: Mybool,
+    pub ir_size: ::std::os::raw::c_int,
+    pub e_size: ::std::os::raw::c_int,
+    pub box_size: ::std::os::raw::c_int,
+    pub vir_size: ::std::os::raw::c_int,
+    pub pres_size: ::std::os::raw::c_int,
+    pub top_size: ::std::os::raw::c_int,
+    pub sym_size: ::std::os::raw::c_int,
+    pub x_size: ::std::os::raw::c_int,
+    pub v_size: ::std::os::raw::c_int,
+    pub f_size: ::std::os::raw::c_int,
+    pub natoms: ::std::os::raw::c_int,
+    pub step: ::std::os::raw::c_int,
+    pub nre: ::std::os::raw::c_int,
+    pub tf: ::std::os::raw::c_float,
+    pub lambdaf: ::std::os::raw::c_float,
+    pub td: ::std::os::raw::c_double,
+    pub lambdad: ::std::os::raw::c_double,
+}
+
+extern "C" {
+    pub fn do_trnheader(
+        xd: *mut XDRFILE,
+        bRead: Mybool,
+        sh: *mut t_trnheader,
+    ) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn read_trr_natoms(
         fn_: *const ::std::os::raw::c_char,
-        natoms: *const ::std::os::raw::c_int,
+        natoms: *mut ::std::os::raw::c_int,
     ) -> ::std::os::raw::c_int;
 }
 extern "C" {
     pub fn read_trr_nframes(
         fn_: *const ::std::os::raw::c_char,
-        nframes: *const ::std::os::raw::c_ulong,
+        nframes: *mut ::std::os::raw::c_ulong,
     ) -> ::std::os::raw::c_int;
 }
 extern "C" {
@@ -25,6 +55,16 @@ extern "C" {
         f: *mut Rvec,
     ) -> ::std::os::raw::c_int;
 }
+extern "C" {
+    pub fn read_trr_data(
+        xd: *mut XDRFILE,
+        sh: *mut t_trnheader,
+        box_vec: *mut Matrix,
+        x: *mut Rvec,
+        v: *mut Rvec,
+        f: *mut Rvec,
+    ) -> ::std::os::raw::c_int;
+}
 extern "C" {
     pub fn write_trr(
         xd: *mut XDRFILE,
@@ -58,6 +98,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_do_trnheader() -> Result<(), Box<dyn std::error::Error>> {
+        let path = CString::new("tests/1l2y.trr")?;
+        let mut header = t_trnheader::default();
+
+        unsafe {
+            let mode = CString::new("r")?;
+            let xdr = xdrfile_open(path.as_ptr(), mode.as_ptr());
+            let code = do_trnheader(xdr, 1, &mut header);
+            assert!(code == exdrOK);
+            xdrfile_close(xdr);
+        }
+        assert!(header.natoms == 304);
+        assert!(header.step == 1);
+        assert!(header.x_size == 304 * 3 * 4);
+        Ok(())
+    }
+
     #[test]
     fn test_read_trr_nframes() -> Result<(), Box<dyn std::error::Error>> {
         let path = CString::new("tests/1l2y.trr")?;
@@ -0,0 +1,390 @@
+use crate::*;
+
+/// Several trajectory parts presented as a single continuous trajectory,
+/// like `gmx trjcat`.
+///
+/// If a part starts at a time that is not later than the end of the previous
+/// part, the overlapping frames of the previous part are dropped in favour of
+/// the later part. If the step numbering restarts in a part, its steps are
+/// shifted to continue after the previous part. Frame times are expected to
+/// increase within each part.
+///
+/// Parts of different formats can be chained as `Box<dyn Trajectory>`.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let parts = vec![
+///         XTCTrajectory::open_read("tests/1l2y.xtc")?,
+///         XTCTrajectory::open_read("tests/1l2y.xtc")?,
+///     ];
+///     // The second part covers the same time as the first one
+///     let mut chain = ChainedTrajectory::new(parts)?;
+///     assert_eq!(chain.num_frames()?, 38);
+///     Ok(())
+/// }
+/// ```
+pub struct ChainedTrajectory<T> {
+    parts: Vec<Part<T>>,
+    /// Index of the part containing the next frame
+    current: usize,
+    /// Index of the next frame within the current part
+    local: usize,
+}
+
+struct Part<T> {
+    trajectory: T,
+    /// Number of frames before the start of the next part
+    num_frames: usize,
+    /// Offset added to the steps of the part
+    step_offset: usize,
+}
+
+impl<T: Trajectory> ChainedTrajectory<T> {
+    /// Chain the given parts in order.
+    ///
+    /// The start of every part and the end of its non-overlapping frames are
+    /// read to determine the overlaps and step offsets.
+    ///
+    /// # Panics
+    /// Panics if `parts` is empty.
+    pub fn new(parts: Vec<T>) -> Result<ChainedTrajectory<T>> {
+        assert!(
+            !parts.is_empty(),
+            "a chained trajectory needs at least one part"
+        );
+        let mut parts: Vec<Part<T>> = parts
+            .into_iter()
+            .map(|trajectory| Part {
+                trajectory,
+                num_frames: 0,
+                step_offset: 0,
+            })
+            .collect();
+
+        let mut first_frames = Vec::with_capacity(parts.len());
+        for part in &mut parts {
+            first_frames.push(read_header_at(&mut part.trajectory, 0)?);
+        }
+
+        let mut last_step: Option<usize> = None;
+        for (i, part) in parts.iter_mut().enumerate() {
+            let total = part.trajectory.num_frames()?;
+            let next_start = first_frames[i + 1..].iter().flatten().next();
+            part.num_frames = match next_start {
+                Some(&(_, time)) => first_frame_at(&mut part.trajectory, total, time)?,
+                None => total,
+            };
+            if part.num_frames == 0 {
+                continue;
+            }
+
+            let (first_step, _) = first_frames[i].expect("Part with frames has a first frame");
+            if let Some(last_step) = last_step.filter(|&last_step| first_step <= last_step) {
+                part.step_offset = last_step + 1 - first_step;
+            }
+            let (step, _) = read_header_at(&mut part.trajectory, part.num_frames - 1)?
+                .expect("Part has a last frame");
+            last_step = Some(step + part.step_offset);
+        }
+
+        Ok(ChainedTrajectory {
+            parts,
+            current: 0,
+            local: 0,
+        })
+    }
+
+    /// Read the next frame from the part containing it with `read`
+    fn read_with(
+        &mut self,
+        frame: &mut Frame,
+        read: impl FnOnce(&mut T, &mut Frame) -> Result<()>,
+    ) -> Result<()> {
+        loop {
+            let part = match self.parts.get_mut(self.current) {
+                Some(part) => part,
+                None => return Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Read))),
+            };
+            if self.local < part.num_frames {
+                // Parts are shared with lookups, so always position explicitly
+                part.trajectory.seek_to_frame(self.local)?;
+                read(&mut part.trajectory, frame)?;
+                frame.step += part.step_offset;
+                self.local += 1;
+                return Ok(());
+            }
+            self.current += 1;
+            self.local = 0;
+        }
+    }
+
+    /// Get the parts of the trajectory back
+    pub fn into_parts(self) -> Vec<T> {
+        self.parts.into_iter().map(|part| part.trajectory).collect()
+    }
+}
+
+/// Read step and time of the frame with the given index, None if the
+/// trajectory has fewer frames
+fn read_header_at(trajectory: &mut impl Trajectory, frame: usize) -> Result<Option<(usize, f32)>> {
+    match trajectory.seek_to_frame(frame) {
+        Ok(()) => {}
+        Err(e) if e.is_eof() => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    trajectory.read(&mut frame)?;
+    Ok(Some((frame.step, frame.time)))
+}
+
+/// Index of the first of the first `num_frames` frames with a time greater
+/// than or equal to `time`, or `num_frames` if there is none
+fn first_frame_at(trajectory: &mut impl Trajectory, num_frames: usize, time: f32) -> Result<usize> {
+    let (mut low, mut high) = (0, num_frames);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match read_header_at(trajectory, mid)? {
+            Some((_, t)) if t < time => low = mid + 1,
+            _ => high = mid,
+        }
+    }
+    Ok(low)
+}
+
+impl<T: Trajectory> Trajectory for ChainedTrajectory<T> {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        self.read_with(frame, |trajectory, frame| trajectory.read(frame))
+    }
+
+    fn peek(&mut self, frame: &mut Frame) -> Result<()> {
+        let (current, local) = (self.current, self.local);
+        let result = self.read(frame);
+        self.current = current;
+        self.local = local;
+        result
+    }
+
+    /// Write the frame to the last part
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        let last = self.parts.len() - 1;
+        self.parts[last].trajectory.write(frame)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let last = self.parts.len() - 1;
+        self.parts[last].trajectory.flush()
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        self.parts[0].trajectory.get_num_atoms()
+    }
+
+    fn num_frames(&mut self) -> Result<usize> {
+        Ok(self.parts.iter().map(|part| part.num_frames).sum())
+    }
+
+    fn known_num_frames(&self) -> Option<usize> {
+        Some(self.parts.iter().map(|part| part.num_frames).sum())
+    }
+
+    fn current_frame(&self) -> Option<usize> {
+        let before: usize = self.parts[..self.current]
+            .iter()
+            .map(|part| part.num_frames)
+            .sum();
+        Some(before + self.local)
+    }
+
+    fn seek_to_frame(&mut self, frame: usize) -> Result<()> {
+        let mut remaining = frame;
+        for (i, part) in self.parts.iter().enumerate() {
+            if remaining < part.num_frames {
+                self.current = i;
+                self.local = remaining;
+                return Ok(());
+            }
+            remaining -= part.num_frames;
+        }
+        Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Seek)))
+    }
+
+    fn seek_to_time(&mut self, time: f32) -> Result<()> {
+        for (i, part) in self.parts.iter_mut().enumerate() {
+            let local = first_frame_at(&mut part.trajectory, part.num_frames, time)?;
+            if local < part.num_frames {
+                self.current = i;
+                self.local = local;
+                return Ok(());
+            }
+        }
+        Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Seek)))
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        let remaining: usize = self.parts[self.current..]
+            .iter()
+            .map(|part| part.num_frames)
+            .sum();
+        if remaining < self.local + n {
+            return Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Seek)));
+        }
+        self.local += n;
+        while self.current + 1 < self.parts.len()
+            && self.local >= self.parts[self.current].num_frames
+        {
+            self.local -= self.parts[self.current].num_frames;
+            self.current += 1;
+        }
+        Ok(())
+    }
+
+    fn is_at_end(&mut self) -> Result<bool> {
+        let remaining: usize = self.parts[self.current..]
+            .iter()
+            .map(|part| part.num_frames)
+            .sum();
+        Ok(remaining <= self.local)
+    }
+
+    fn get_times(&mut self) -> Result<Vec<f32>> {
+        let mut times = Vec::new();
+        for part in &mut self.parts {
+            let part_times = part.trajectory.get_times()?;
+            times.extend_from_slice(&part_times[..part.num_frames]);
+        }
+        Ok(times)
+    }
+
+    fn get_steps(&mut self) -> Result<Vec<usize>> {
+        let mut steps = Vec::new();
+        for part in &mut self.parts {
+            let part_steps = part.trajectory.get_steps()?;
+            let part_steps = part_steps[..part.num_frames].iter();
+            steps.extend(part_steps.map(|step| step + part.step_offset));
+        }
+        Ok(steps)
+    }
+
+    fn read_selection(&mut self, selection: &AtomSelection, frame: &mut Frame) -> Result<()> {
+        self.read_with(frame, |trajectory, frame| {
+            trajectory.read_selection(selection, frame)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    /// Write the frames of the test trajectory within a time range to a file
+    fn write_part(start_time: f32, end_time: f32) -> Result<NamedTempFile> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut src = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut dst = XTCTrajectory::open_write(tempfile.path())?;
+        let options = ConvertOptions::new()
+            .start_time(start_time)
+            .end_time(end_time);
+        convert_with(&mut src, &mut dst, &options)?;
+        Ok(tempfile)
+    }
+
+    fn steps(chain: ChainedTrajectory<impl Trajectory>) -> Result<Vec<usize>> {
+        chain
+            .into_iter()
+            .map(|frame| frame.map(|frame| frame.step))
+            .collect()
+    }
+
+    #[test]
+    fn test_chain_overlapping() -> Result<()> {
+        // Frames of the test trajectory have the same time and step
+        let first = write_part(1.0, 20.0)?;
+        let second = write_part(15.0, 38.0)?;
+        let parts = vec![
+            XTCTrajectory::open_read(first.path())?,
+            XTCTrajectory::open_read(second.path())?,
+        ];
+        let mut chain = ChainedTrajectory::new(parts)?;
+        assert_eq!(chain.get_num_atoms()?, 304);
+        assert_eq!(chain.num_frames()?, 38);
+
+        let mut frame = Frame::with_len(304);
+        chain.seek_to_frame(13)?;
+        chain.read(&mut frame)?;
+        assert_eq!(frame.step, 14);
+        chain.read(&mut frame)?;
+        assert_eq!(frame.step, 15);
+        chain.seek_to_time(19.5)?;
+        chain.read(&mut frame)?;
+        assert_eq!(frame.step, 20);
+        assert!(chain.seek_to_time(100.0).unwrap_err().is_eof());
+        assert!(chain.seek_to_frame(38).unwrap_err().is_eof());
+        chain.seek_to_frame(19)?;
+        chain.peek(&mut frame)?;
+        assert_eq!(frame.step, 20);
+        chain.read(&mut frame)?;
+        chain.peek(&mut frame)?;
+        assert_eq!(frame.step, 21);
+        chain.seek_to_frame(10)?;
+        chain.skip(15)?;
+        chain.read(&mut frame)?;
+        assert_eq!(frame.step, 26);
+        assert!(chain.skip(13).unwrap_err().is_eof());
+        chain.skip(12)?;
+        assert!(chain.is_at_end()?);
+        chain.seek_to_frame(37)?;
+        assert!(!chain.is_at_end()?);
+        chain.read(&mut frame)?;
+        assert!(chain.is_at_end()?);
+
+        assert_eq!(chain.get_steps()?, (1..=38).collect::<Vec<_>>());
+        assert_eq!(chain.get_times()?.len(), 38);
+        chain.seek_to_frame(0)?;
+        assert_eq!(steps(chain)?, (1..=38).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_restarted_steps() -> Result<()> {
+        let first = write_part(1.0, 10.0)?;
+        let second = NamedTempFile::new().expect("Could not create temporary file");
+        let mut traj = XTCTrajectory::open_write(second.path())?;
+        let mut frame = Frame::with_len(304);
+        for step in 0..5 {
+            frame.step = step;
+            frame.time = 11.0 + step as f32;
+            traj.write(&frame)?;
+        }
+        traj.flush()?;
+
+        let parts = vec![
+            XTCTrajectory::open_read(first.path())?,
+            XTCTrajectory::open_read(second.path())?,
+        ];
+        let mut chain = ChainedTrajectory::new(parts)?;
+        assert_eq!(chain.get_steps()?, (1..=15).collect::<Vec<_>>());
+        assert_eq!(steps(chain)?, (1..=15).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_mixed_formats() -> Result<()> {
+        let second = write_part(30.0, 38.0)?;
+        let parts: Vec<Box<dyn Trajectory>> = vec![
+            Box::new(TRRTrajectory::open_read("tests/1l2y.trr")?),
+            Box::new(XTCTrajectory::open_read(second.path())?),
+        ];
+        let mut chain = ChainedTrajectory::new(parts)?;
+        assert_eq!(chain.num_frames()?, 38);
+
+        let mut frame = Frame::with_len(chain.get_num_atoms()?);
+        chain.seek_to_frame(29)?;
+        chain.read(&mut frame)?;
+        assert_eq!(frame.step, 30);
+        assert_eq!(chain.into_parts().len(), 2);
+        Ok(())
+    }
+}
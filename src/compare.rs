@@ -0,0 +1,321 @@
+use crate::*;
+
+/// Largest differences that `compare` accepts between two frames. Steps and
+/// numbers of atoms always have to match exactly.
+///
+/// The default tolerances are zero, so that frames have to be identical. To
+/// compare an XTC trajectory with its source, use a coordinate tolerance of
+/// half the inverse of the XTC precision or more.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tolerances {
+    /// Largest difference in time
+    pub time: f32,
+    /// Largest difference of any component of the box
+    pub box_vector: f32,
+    /// Largest difference of any coordinate of any atom
+    pub coords: f32,
+}
+
+/// The first difference between two trajectories found by `compare`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Divergence {
+    /// One trajectory ended before the other. `a_ended` is true if the first
+    /// trajectory ended.
+    Ended { a_ended: bool },
+    /// The trajectories have a different number of atoms
+    NumAtoms { a: usize, b: usize },
+    /// The frames have different steps
+    Step { a: usize, b: usize },
+    /// The times of the frames differ by more than the tolerance
+    Time { a: f32, b: f32 },
+    /// A component of the boxes differs by more than the tolerance
+    BoxVector { a: BoxVector, b: BoxVector },
+    /// A coordinate of the atom at index `atom` differs by more than the
+    /// tolerance
+    Coords {
+        atom: usize,
+        a: [f32; 3],
+        b: [f32; 3],
+    },
+}
+
+/// Outcome of `compare`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ComparisonReport {
+    /// Number of frames that matched, which is also the index of the frame
+    /// at which the trajectories diverged, if they did
+    pub num_matching: usize,
+    /// Largest difference of any coordinate in the matching frames
+    pub max_coord_difference: f32,
+    /// The first difference found, None if the trajectories match
+    pub divergence: Option<Divergence>,
+}
+
+impl ComparisonReport {
+    /// True if the trajectories matched within the tolerances
+    pub fn matches(&self) -> bool {
+        self.divergence.is_none()
+    }
+}
+
+/// Compare the frames from the current positions of `a` and `b` up to the
+/// first frame that differs by more than `tolerances`, e.g. to check the
+/// output of a converter or whether a simulation was reproduced.
+///
+/// Frames are compared by step, time, box and coordinates, in that order.
+/// Velocities and forces are not compared. Errors other than the end of
+/// either trajectory are returned.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+///     let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+///     let tolerances = Tolerances {
+///         coords: 1e-3,
+///         ..Default::default()
+///     };
+///     let report = compare(&mut trr, &mut xtc, &tolerances)?;
+///     assert!(report.matches());
+///     assert_eq!(report.num_matching, 38);
+///     Ok(())
+/// }
+/// ```
+pub fn compare(
+    a: &mut impl Trajectory,
+    b: &mut impl Trajectory,
+    tolerances: &Tolerances,
+) -> Result<ComparisonReport> {
+    let mut report = ComparisonReport {
+        num_matching: 0,
+        max_coord_difference: 0.0,
+        divergence: None,
+    };
+    let (num_atoms_a, num_atoms_b) = (a.get_num_atoms()?, b.get_num_atoms()?);
+    if num_atoms_a != num_atoms_b {
+        report.divergence = Some(Divergence::NumAtoms {
+            a: num_atoms_a,
+            b: num_atoms_b,
+        });
+        return Ok(report);
+    }
+
+    let mut frame_a = Frame::with_len(num_atoms_a);
+    let mut frame_b = Frame::with_len(num_atoms_b);
+    loop {
+        let ended_a = read_or_end(a, &mut frame_a)?;
+        let ended_b = read_or_end(b, &mut frame_b)?;
+        if ended_a || ended_b {
+            if ended_a != ended_b {
+                report.divergence = Some(Divergence::Ended { a_ended: ended_a });
+            }
+            return Ok(report);
+        }
+        match compare_frames(&frame_a, &frame_b, tolerances) {
+            Ok(difference) => {
+                report.num_matching += 1;
+                report.max_coord_difference = report.max_coord_difference.max(difference);
+            }
+            Err(divergence) => {
+                report.divergence = Some(divergence);
+                return Ok(report);
+            }
+        }
+    }
+}
+
+/// Read the next frame, returning true at the end of the trajectory
+fn read_or_end(trajectory: &mut impl Trajectory, frame: &mut Frame) -> Result<bool> {
+    match trajectory.read(frame) {
+        Ok(()) => Ok(false),
+        Err(e) if e.is_eof() => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+/// Compare two frames of the same size. Returns the largest difference of
+/// any coordinate if they match.
+fn compare_frames(
+    a: &Frame,
+    b: &Frame,
+    tolerances: &Tolerances,
+) -> std::result::Result<f32, Divergence> {
+    // NaN coordinates never match
+    let within = |x: f32, y: f32, tolerance: f32| (x - y).abs() <= tolerance;
+    if a.step != b.step {
+        return Err(Divergence::Step {
+            a: a.step,
+            b: b.step,
+        });
+    }
+    if !within(a.time, b.time, tolerances.time) {
+        return Err(Divergence::Time {
+            a: a.time,
+            b: b.time,
+        });
+    }
+    let boxes = a
+        .box_vector
+        .as_matrix()
+        .iter()
+        .zip(b.box_vector.as_matrix());
+    if boxes
+        .flat_map(|(x, y)| x.iter().zip(y))
+        .any(|(&x, &y)| !within(x, y, tolerances.box_vector))
+    {
+        return Err(Divergence::BoxVector {
+            a: a.box_vector,
+            b: b.box_vector,
+        });
+    }
+
+    let mut max_difference: f32 = 0.0;
+    for (atom, (x, y)) in a.coords.iter().zip(&b.coords).enumerate() {
+        for dim in 0..3 {
+            if !within(x[dim], y[dim], tolerances.coords) {
+                return Err(Divergence::Coords { atom, a: *x, b: *y });
+            }
+            max_difference = max_difference.max((x[dim] - y[dim]).abs());
+        }
+    }
+    Ok(max_difference)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trajectory in memory with the frames of the XTC test file, changed by
+    /// `modify` with the index of every frame
+    fn modified_copy(modify: impl Fn(usize, &mut Frame)) -> Result<XTCTrajectory> {
+        let mut trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        // Same precision as the test file, so that coordinates are kept
+        trajectory.set_precision(10000.0);
+        for (i, frame) in XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_iter()
+            .enumerate()
+        {
+            let mut frame = (*frame?).clone();
+            modify(i, &mut frame);
+            trajectory.write(&frame)?;
+        }
+        let bytes = trajectory.into_bytes().unwrap();
+        XTCTrajectory::from_bytes(bytes, FileMode::Read)
+    }
+
+    fn compare_with(
+        trajectory: &mut XTCTrajectory,
+        tolerances: &Tolerances,
+    ) -> Result<ComparisonReport> {
+        let mut original = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        Trajectory::rewind(trajectory)?;
+        compare(&mut original, trajectory, tolerances)
+    }
+
+    #[test]
+    fn test_compare_identical() -> Result<()> {
+        let mut copy = modified_copy(|_, _| {})?;
+        let report = compare_with(&mut copy, &Tolerances::default())?;
+        assert_eq!(
+            report,
+            ComparisonReport {
+                num_matching: 38,
+                max_coord_difference: 0.0,
+                divergence: None,
+            }
+        );
+        assert!(report.matches());
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_divergence() -> Result<()> {
+        let mut copy = modified_copy(|i, frame| {
+            if i == 5 {
+                frame.time += 0.5;
+            }
+            if i >= 10 {
+                frame.coords[3][1] += 0.5;
+            }
+        })?;
+        let report = compare_with(&mut copy, &Tolerances::default())?;
+        assert_eq!(report.num_matching, 5);
+        assert_eq!(report.divergence, Some(Divergence::Time { a: 6.0, b: 6.5 }));
+
+        let tolerances = Tolerances {
+            time: 1.0,
+            ..Default::default()
+        };
+        let report = compare_with(&mut copy, &tolerances)?;
+        assert_eq!(report.num_matching, 10);
+        assert!(matches!(
+            report.divergence,
+            Some(Divergence::Coords { atom: 3, .. })
+        ));
+
+        let tolerances = Tolerances {
+            time: 1.0,
+            coords: 0.6,
+            ..Default::default()
+        };
+        let report = compare_with(&mut copy, &tolerances)?;
+        assert!(report.matches());
+        assert_approx_eq!(report.max_coord_difference, 0.5, 1e-3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_structure() -> Result<()> {
+        let mut copy = modified_copy(|i, frame| frame.step = i * 2 + 1)?;
+        let report = compare_with(&mut copy, &Tolerances::default())?;
+        assert_eq!(report.divergence, Some(Divergence::Step { a: 2, b: 3 }));
+
+        let mut copy = modified_copy(|_, frame| frame.box_vector[2][2] *= 2.0)?;
+        let report = compare_with(&mut copy, &Tolerances::default())?;
+        assert!(matches!(
+            report.divergence,
+            Some(Divergence::BoxVector { .. })
+        ));
+
+        // Positions are kept, so parts of trajectories can be compared
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        xtc.seek_to_frame(30)?;
+        trr.seek_to_frame(30)?;
+        let tolerances = Tolerances {
+            coords: 1e-3,
+            ..Default::default()
+        };
+        let report = compare(&mut xtc, &mut trr, &tolerances)?;
+        assert_eq!(report.num_matching, 8);
+        assert!(report.matches());
+
+        xtc.seek_to_frame(30)?;
+        trr.seek_to_frame(31)?;
+        let report = compare(&mut trr, &mut xtc, &tolerances)?;
+        assert_eq!(report.divergence, Some(Divergence::Step { a: 32, b: 31 }));
+
+        // Only the steps are compared with infinite tolerances
+        let mut copy = modified_copy(|i, frame| frame.step = i + 37)?;
+        xtc.seek_to_frame(36)?;
+        let tolerances = Tolerances {
+            time: f32::INFINITY,
+            box_vector: f32::INFINITY,
+            coords: f32::INFINITY,
+        };
+        let report = compare(&mut xtc, &mut copy, &tolerances)?;
+        assert_eq!(report.num_matching, 2);
+        assert_eq!(report.divergence, Some(Divergence::Ended { a_ended: true }));
+
+        let mut small = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        small.write(&Frame::with_len(2))?;
+        let mut small = XTCTrajectory::from_bytes(small.into_bytes().unwrap(), FileMode::Read)?;
+        let report = compare(&mut xtc, &mut small, &tolerances)?;
+        assert_eq!(
+            report.divergence,
+            Some(Divergence::NumAtoms { a: 304, b: 2 })
+        );
+        Ok(())
+    }
+}
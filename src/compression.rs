@@ -0,0 +1,230 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Compression formats of trajectory files that can be read transparently
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Compression {
+    #[cfg(feature = "gzip")]
+    Gzip,
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Detect the compression format of a file from its magic bytes.
+    ///
+    /// Returns None for uncompressed or unreadable files.
+    pub fn detect(path: &Path) -> Option<Compression> {
+        let mut magic = [0; 4];
+        File::open(path).ok()?.read_exact(&mut magic).ok()?;
+        match magic {
+            #[cfg(feature = "gzip")]
+            [0x1f, 0x8b, _, _] => Some(Compression::Gzip),
+            #[cfg(feature = "zstd")]
+            [0x28, 0xb5, 0x2f, 0xfd] => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    fn decoder(self, file: File) -> io::Result<Box<dyn Read + Send>> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Box::new(flate2::read::MultiGzDecoder::new(BufReader::new(
+                file,
+            )))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(zstd::Decoder::with_buffer(BufReader::new(file))?)),
+        }
+    }
+}
+
+/// Reader that decompresses a file on the fly.
+///
+/// Compressed streams cannot be seeked, so seeking forward decompresses and
+/// discards the data in between, while seeking backward starts over at the
+/// beginning of the file.
+pub(crate) struct Decompressor {
+    path: PathBuf,
+    compression: Compression,
+    decoder: Box<dyn Read + Send>,
+    /// Position in the decompressed data
+    pos: u64,
+}
+
+impl Decompressor {
+    pub fn open(path: &Path, compression: Compression) -> io::Result<Decompressor> {
+        Ok(Decompressor {
+            path: path.to_owned(),
+            compression,
+            decoder: compression.decoder(File::open(path)?)?,
+            pos: 0,
+        })
+    }
+
+    /// Decompress and discard up to `n` bytes, stopping at the end of the data
+    fn skip(&mut self, n: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut (&mut self.decoder).take(n), &mut io::sink())?;
+        self.pos += skipped;
+        Ok(())
+    }
+
+    fn rewind(&mut self) -> io::Result<()> {
+        self.decoder = self.compression.decoder(File::open(&self.path)?)?;
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl Read for Decompressor {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.decoder.read(buf)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for Decompressor {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(n) => (0, n as i64),
+            SeekFrom::Current(n) => (self.pos, n),
+            SeekFrom::End(n) => {
+                // The size is only known after decompressing everything
+                self.skip(u64::MAX)?;
+                (self.pos, n)
+            }
+        };
+        let target = base.checked_add_signed(offset).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )
+        })?;
+
+        if target < self.pos {
+            self.rewind()?;
+        }
+        self.skip(target - self.pos)?;
+        // Like for files, seeking beyond the end succeeds and reads return nothing
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    /// Any of the enabled compression formats
+    #[cfg(feature = "gzip")]
+    const ANY: Compression = Compression::Gzip;
+    #[cfg(not(feature = "gzip"))]
+    const ANY: Compression = Compression::Zstd;
+
+    fn compress(path: &str, compression: Compression) -> NamedTempFile {
+        let data = std::fs::read(path).expect("Could not read test file");
+        let mut tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        match compression {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(&mut tempfile, Default::default());
+                encoder.write_all(&data).expect("Could not compress");
+                encoder.finish().expect("Could not compress");
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                let compressed = zstd::encode_all(&data[..], 0).expect("Could not compress");
+                tempfile.write_all(&compressed).expect("Could not compress");
+            }
+        }
+        tempfile
+    }
+
+    fn check_compressed(compression: Compression) -> Result<()> {
+        let tempfile = compress("tests/1l2y.xtc", compression);
+        assert_eq!(Compression::detect(tempfile.path()), Some(compression));
+
+        let mut compressed = XTCTrajectory::open_read(tempfile.path())?;
+        let mut file = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert_eq!(compressed.get_num_atoms()?, 304);
+        assert_eq!(compressed.num_frames()?, 38);
+
+        let mut expected = Frame::with_len(304);
+        let mut frame = Frame::with_len(304);
+        file.read(&mut expected)?;
+        compressed.read(&mut frame)?;
+        assert_eq!(expected.coords, frame.coords);
+
+        compressed.seek_to_frame(20)?;
+        compressed.read(&mut frame)?;
+        assert_eq!(frame.step, 21);
+        compressed.seek_to_frame(2)?;
+        compressed.read(&mut frame)?;
+        assert_eq!(frame.step, 3);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_gzip() -> Result<()> {
+        check_compressed(Compression::Gzip)
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_zstd() -> Result<()> {
+        check_compressed(Compression::Zstd)
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_gzip_corrupt() -> Result<()> {
+        // Break the checksum at the end of the gzip stream
+        let tempfile = compress("tests/1l2y.xtc", Compression::Gzip);
+        let mut data = std::fs::read(tempfile.path()).expect("Could not read test file");
+        let crc = data.len() - 8;
+        data[crc] ^= 0xff;
+        std::fs::write(tempfile.path(), data).expect("Could not write test file");
+
+        let mut traj = XTCTrajectory::open_read(tempfile.path())?;
+        let err = traj.read_all().unwrap_err();
+        assert!(!err.is_eof());
+        assert!(matches!(
+            &err,
+            Error::InFrame { source, .. } if matches!(**source, Error::Io { .. })
+        ));
+        let err = XTCTrajectory::open_read(tempfile.path())?.num_frames();
+        assert!(matches!(err, Err(Error::Io { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_uncompressed() {
+        assert_eq!(Compression::detect(Path::new("tests/1l2y.xtc")), None);
+        assert_eq!(Compression::detect(Path::new("not/a/file")), None);
+    }
+
+    #[test]
+    fn test_seek() -> io::Result<()> {
+        let tempfile = compress("tests/1l2y.trr", ANY);
+        let expected = std::fs::read("tests/1l2y.trr")?;
+        let mut reader = Decompressor::open(tempfile.path(), ANY)?;
+
+        let mut buf = [0; 8];
+        reader.seek(SeekFrom::Start(100))?;
+        reader.read_exact(&mut buf)?;
+        assert_eq!(buf, expected[100..108]);
+        reader.seek(SeekFrom::Current(-58))?;
+        reader.read_exact(&mut buf)?;
+        assert_eq!(buf, expected[50..58]);
+        assert_eq!(reader.seek(SeekFrom::End(-8))?, expected.len() as u64 - 8);
+        reader.read_exact(&mut buf)?;
+        assert_eq!(buf, expected[expected.len() - 8..]);
+        assert!(reader.seek(SeekFrom::Current(-1_000_000)).is_err());
+        Ok(())
+    }
+}
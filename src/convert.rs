@@ -0,0 +1,168 @@
+use crate::*;
+
+/// Options for converting trajectories with `convert_with`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConvertOptions {
+    stride: usize,
+    start_time: Option<f32>,
+    end_time: Option<f32>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> ConvertOptions {
+        ConvertOptions {
+            stride: 1,
+            start_time: None,
+            end_time: None,
+        }
+    }
+}
+
+impl ConvertOptions {
+    /// Create options that convert every frame of a trajectory
+    pub fn new() -> ConvertOptions {
+        ConvertOptions::default()
+    }
+
+    /// Only convert every `n`-th frame, starting with the first converted frame
+    ///
+    /// # Panics
+    /// Panics if `n` is zero.
+    pub fn stride(mut self, n: usize) -> Self {
+        assert!(n > 0, "stride must be at least 1");
+        self.stride = n;
+        self
+    }
+
+    /// Skip frames with a time before `time`
+    pub fn start_time(mut self, time: f32) -> Self {
+        self.start_time = Some(time);
+        self
+    }
+
+    /// Stop at the first frame with a time after `time`
+    pub fn end_time(mut self, time: f32) -> Self {
+        self.end_time = Some(time);
+        self
+    }
+}
+
+/// Copy all frames from the current position of `src` to `dst`, e.g. to
+/// convert a TRR trajectory to XTC. Returns the number of frames written.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let tempfile = tempfile::NamedTempFile::new().expect("Could not create temporary file");
+///     let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+///     let mut xtc = XTCTrajectory::open_write(tempfile.path())?;
+///     assert_eq!(convert(&mut trr, &mut xtc)?, 38);
+///     Ok(())
+/// }
+/// ```
+pub fn convert(src: &mut impl Trajectory, dst: &mut impl Trajectory) -> Result<usize> {
+    convert_with(src, dst, &ConvertOptions::default())
+}
+
+/// Like `convert`, but only copies the frames selected by `options`.
+///
+/// If a start time is set, `src` first seeks to the first frame at or after
+/// that time. `dst` is flushed once all frames are written.
+pub fn convert_with(
+    src: &mut impl Trajectory,
+    dst: &mut impl Trajectory,
+    options: &ConvertOptions,
+) -> Result<usize> {
+    let mut frame = Frame::with_len(src.get_num_atoms()?);
+    if let Some(time) = options.start_time {
+        match src.seek_to_time(time) {
+            Ok(()) => {}
+            Err(e) if e.is_eof() => return Ok(0),
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut num_read = 0;
+    let mut num_written = 0;
+    loop {
+        match src.read(&mut frame) {
+            Ok(()) => {}
+            Err(e) if e.is_eof() => break,
+            Err(e) => return Err(e),
+        }
+        if options.end_time.is_some_and(|end| frame.time > end) {
+            break;
+        }
+        if num_read % options.stride == 0 {
+            dst.write(&frame)?;
+            num_written += 1;
+        }
+        num_read += 1;
+    }
+    dst.flush()?;
+    Ok(num_written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_convert_trr_to_xtc() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut xtc = XTCTrajectory::open_write(tempfile.path())?;
+        assert_eq!(convert(&mut trr, &mut xtc)?, 38);
+
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut xtc = XTCTrajectory::open_read(tempfile.path())?;
+        let mut expected = Frame::with_len(trr.get_num_atoms()?);
+        let mut frame = Frame::with_len(xtc.get_num_atoms()?);
+        for _ in 0..38 {
+            trr.read(&mut expected)?;
+            xtc.read(&mut frame)?;
+            assert_eq!(expected.step, frame.step);
+            assert_approx_eq!(expected.time, frame.time);
+            for (e, f) in expected.coords.iter().zip(&frame.coords) {
+                for dim in 0..3 {
+                    assert_approx_eq!(e[dim], f[dim], 1e-3);
+                }
+            }
+        }
+        assert!(xtc.read(&mut frame).unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_convert_with_options() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut src = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut dst = XTCTrajectory::open_write(tempfile.path())?;
+        // Frames of the test trajectory have the same time and step
+        let options = ConvertOptions::new()
+            .start_time(4.5)
+            .end_time(20.0)
+            .stride(5);
+        assert_eq!(convert_with(&mut src, &mut dst, &options)?, 4);
+
+        let steps: Vec<usize> = XTCTrajectory::open_read(tempfile.path())?
+            .into_iter()
+            .map(|frame| frame.map(|frame| frame.step))
+            .collect::<Result<_>>()?;
+        assert_eq!(steps, vec![5, 10, 15, 20]);
+
+        // Nothing to convert after the end of the trajectory
+        let mut src = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let options = ConvertOptions::new().start_time(1000.0);
+        assert_eq!(convert_with(&mut src, &mut dst, &options)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "stride must be at least 1")]
+    fn test_zero_stride() {
+        let _ = ConvertOptions::new().stride(0);
+    }
+}
@@ -0,0 +1,278 @@
+use crate::*;
+
+/// Temperatures of the replicas of a replica exchange simulation over time,
+/// as written to `replica_temp.xvg` by the `demux.pl` script of GROMACS.
+///
+/// Every row starts at the time of an exchange and lists the temperature
+/// index of every replica, i.e. which of the trajectories written by the
+/// simulation holds the replica. The rows must be permutations of the
+/// temperature indices, in order of increasing time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicaMap {
+    num_replicas: usize,
+    rows: Vec<(f32, Vec<usize>)>,
+}
+
+impl ReplicaMap {
+    /// Create a map of `num_replicas` replicas without any rows
+    pub fn new(num_replicas: usize) -> ReplicaMap {
+        ReplicaMap {
+            num_replicas,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Read a map from an xvg file like `replica_temp.xvg`. Lines starting
+    /// with `#` or `@` are skipped, all others contain the time followed by
+    /// the temperature index of every replica.
+    pub fn from_xvg(path: impl AsRef<Path>) -> Result<ReplicaMap> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| Error::from((path, FileMode::Read, err)))?;
+        ReplicaMap::parse_xvg(&text)
+    }
+
+    /// Parse a map from the contents of an xvg file, see `from_xvg`
+    pub fn parse_xvg(text: &str) -> Result<ReplicaMap> {
+        let malformed = || Error::InvalidReplicaMap {
+            reason: "malformed line in xvg file",
+        };
+        let mut map: Option<ReplicaMap> = None;
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with('@') {
+                continue;
+            }
+            let mut columns = line.split_whitespace();
+            let time = columns
+                .next()
+                .and_then(|time| time.parse().ok())
+                .ok_or_else(malformed)?;
+            let temperatures = columns
+                .map(|index| index.parse().map_err(|_| malformed()))
+                .collect::<Result<Vec<usize>>>()?;
+            map.get_or_insert_with(|| ReplicaMap::new(temperatures.len()))
+                .push(time, temperatures)?;
+        }
+        map.ok_or(Error::InvalidReplicaMap {
+            reason: "no rows in xvg file",
+        })
+    }
+
+    /// Add the temperature indices of all replicas from `time` on. Returns
+    /// an error if they are not a permutation of the temperature indices or
+    /// if `time` is not after the time of the previous row.
+    pub fn push(&mut self, time: f32, temperatures: Vec<usize>) -> Result<()> {
+        if temperatures.len() != self.num_replicas {
+            return Err(Error::InvalidReplicaMap {
+                reason: "wrong number of replicas in row",
+            });
+        }
+        let mut seen = vec![false; self.num_replicas];
+        for &temperature in &temperatures {
+            match seen.get_mut(temperature) {
+                Some(seen) if !*seen => *seen = true,
+                _ => {
+                    return Err(Error::InvalidReplicaMap {
+                        reason: "row is not a permutation of the temperature indices",
+                    })
+                }
+            }
+        }
+        if self.rows.last().is_some_and(|(last, _)| *last >= time) {
+            return Err(Error::InvalidReplicaMap {
+                reason: "times of rows do not increase",
+            });
+        }
+        self.rows.push((time, temperatures));
+        Ok(())
+    }
+
+    /// Number of replicas, which is also the number of temperatures
+    pub fn num_replicas(&self) -> usize {
+        self.num_replicas
+    }
+
+    /// Get the temperature index of every replica at `time`, from the last
+    /// row at or before `time`. Times before the first row use the first
+    /// row. Returns None if the map has no rows.
+    pub fn temperatures_at(&self, time: f32) -> Option<&[usize]> {
+        let row = self.rows.partition_point(|(start, _)| *start <= time);
+        self.rows
+            .get(row.saturating_sub(1))
+            .map(|(_, temperatures)| temperatures.as_slice())
+    }
+}
+
+/// Demultiplex the trajectories of a replica exchange simulation into
+/// continuous trajectories of every replica, like `gmx trjcat -demux`.
+///
+/// `inputs` are the trajectories written by the simulation, one per
+/// temperature index, and `outputs` receive the frames of every replica.
+/// All inputs are read frame by frame in lockstep, and every frame is
+/// written to the replica that `map` places at its temperature at the time
+/// of the frame. Returns the number of frames written to every output.
+///
+/// Demultiplexing stops at the end of the shortest input. An error is
+/// returned if the number of inputs or outputs differs from the number of
+/// replicas, if `map` has no rows or if frames read together differ in
+/// time. The outputs are flushed once all frames are written.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let map = ReplicaMap::parse_xvg("0 0 1\n10 1 0\n")?;
+///     let mut inputs = vec![
+///         XTCTrajectory::open_read("tests/1l2y.xtc")?,
+///         XTCTrajectory::open_read("tests/1l2y.xtc")?,
+///     ];
+///     let mut outputs = vec![
+///         XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?,
+///         XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?,
+///     ];
+///     assert_eq!(demux(&mut inputs, &map, &mut outputs)?, 38);
+///     Ok(())
+/// }
+/// ```
+pub fn demux<T: Trajectory, U: Trajectory>(
+    inputs: &mut [T],
+    map: &ReplicaMap,
+    outputs: &mut [U],
+) -> Result<usize> {
+    if inputs.len() != map.num_replicas() || outputs.len() != map.num_replicas() {
+        return Err(Error::InvalidReplicaMap {
+            reason: "number of trajectories does not match the number of replicas",
+        });
+    }
+    if map.temperatures_at(0.0).is_none() {
+        return Err(Error::InvalidReplicaMap {
+            reason: "no rows in replica map",
+        });
+    }
+    let mut frames = inputs
+        .iter_mut()
+        .map(|input| input.get_num_atoms().map(Frame::with_len))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut num_written = 0;
+    'frames: loop {
+        for (input, frame) in inputs.iter_mut().zip(frames.iter_mut()) {
+            match input.read(frame) {
+                Ok(()) => {}
+                Err(e) if e.is_eof() => break 'frames,
+                Err(e) => return Err(e),
+            }
+        }
+        let time = frames[0].time;
+        if frames.iter().any(|frame| frame.time != time) {
+            return Err(Error::InvalidReplicaMap {
+                reason: "input trajectories are not at the same time",
+            });
+        }
+        let temperatures = map.temperatures_at(time).unwrap_or_default();
+        for (output, &temperature) in outputs.iter_mut().zip(temperatures) {
+            output.write(&frames[temperature])?;
+        }
+        num_written += 1;
+    }
+    for output in outputs.iter_mut() {
+        output.flush()?;
+    }
+    Ok(num_written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trajectory at a single temperature, with the replica of every frame
+    /// as the x coordinate of its atom
+    fn temperature_trajectory(replicas: &[usize]) -> Result<XTCTrajectory> {
+        let mut trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        for (step, &replica) in replicas.iter().enumerate() {
+            let coords = vec![[replica as f32, 0.0, 0.0]];
+            trajectory.write(&Frame::from_coords(
+                step,
+                step as f32,
+                BoxVector::default(),
+                coords,
+            ))?;
+        }
+        let bytes = trajectory.into_bytes().unwrap();
+        XTCTrajectory::from_bytes(bytes, FileMode::Read)
+    }
+
+    #[test]
+    fn test_demux() -> Result<()> {
+        let map = ReplicaMap::parse_xvg(
+            "# demux.pl output\n@ title \"Replica temperatures\"\n\
+             0 0 1 2\n2 1 0 2\n3.5 2 0 1\n",
+        )?;
+        assert_eq!(map.num_replicas(), 3);
+        assert_eq!(map.temperatures_at(-1.0), Some(&[0, 1, 2][..]));
+        assert_eq!(map.temperatures_at(3.0), Some(&[1, 0, 2][..]));
+
+        // Replica at every temperature and frame (times 0 to 4), the last
+        // temperature has one frame more
+        let mut inputs = vec![
+            temperature_trajectory(&[0, 0, 1, 1, 1])?,
+            temperature_trajectory(&[1, 1, 0, 0, 2])?,
+            temperature_trajectory(&[2, 2, 2, 2, 0, 0])?,
+        ];
+        let mut outputs = (0..3)
+            .map(|_| XTCTrajectory::from_bytes(Vec::new(), FileMode::Write))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(demux(&mut inputs, &map, &mut outputs)?, 5);
+
+        for (replica, output) in outputs.into_iter().enumerate() {
+            let bytes = output.into_bytes().unwrap();
+            let frames = XTCTrajectory::from_bytes(bytes, FileMode::Read)?
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(frames.len(), 5);
+            for (step, frame) in frames.iter().enumerate() {
+                assert_eq!(frame.step, step);
+                assert_eq!(frame[0][0], replica as f32);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_demux_errors() -> Result<()> {
+        let mut map = ReplicaMap::new(2);
+        let mut inputs = vec![
+            temperature_trajectory(&[0, 0])?,
+            temperature_trajectory(&[1, 1])?,
+        ];
+        let mut outputs = vec![XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?];
+        let result = demux(&mut inputs, &map, &mut outputs);
+        assert!(matches!(result, Err(Error::InvalidReplicaMap { .. })));
+        outputs.push(XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?);
+        let result = demux(&mut inputs, &map, &mut outputs);
+        assert!(matches!(result, Err(Error::InvalidReplicaMap { .. })));
+
+        for row in [vec![0], vec![0, 0], vec![0, 2]] {
+            let result = map.push(0.0, row);
+            assert!(matches!(result, Err(Error::InvalidReplicaMap { .. })));
+        }
+        map.push(1.0, vec![1, 0])?;
+        assert!(map.push(1.0, vec![0, 1]).is_err());
+
+        for text in [
+            "",
+            "# comment only\n",
+            "0 0 x\n",
+            "zero 0 1\n",
+            "0 0 1\n1 0\n",
+        ] {
+            let result = ReplicaMap::parse_xvg(text);
+            assert!(matches!(result, Err(Error::InvalidReplicaMap { .. })));
+        }
+        assert!(matches!(
+            ReplicaMap::from_xvg("does/not/exist.xvg"),
+            Err(Error::CouldNotOpen { .. })
+        ));
+        Ok(())
+    }
+}
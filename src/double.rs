@@ -0,0 +1,363 @@
+use crate::c_abi::xdrfile_trr;
+use crate::*;
+use std::os::raw::{c_double, c_float, c_int};
+
+/// A frame with double precision time and per-atom data, as written by
+/// double precision builds of GROMACS, see `TRRTrajectory::read_f64`
+pub type FrameF64 = Frame<f64>;
+
+/// Size of a double in a TRR file
+const DOUBLE_SIZE: c_int = 8;
+
+/// Convert every component of a block of per-atom vectors
+fn map_block<A: Copy, B>(block: &[[A; 3]], f: impl Fn(A) -> B) -> Vec<[B; 3]> {
+    block.iter().map(|v| v.map(&f)).collect()
+}
+
+impl Frame {
+    /// Copy the frame into a double precision frame
+    pub fn to_f64(&self) -> FrameF64 {
+        Frame {
+            step: self.step,
+            time: f64::from(self.time),
+            box_vector: self.box_vector.to_f64(),
+            coords: map_block(&self.coords, f64::from),
+            velocities: self.velocities.as_ref().map(|v| map_block(v, f64::from)),
+            forces: self.forces.as_ref().map(|f| map_block(f, f64::from)),
+        }
+    }
+}
+
+impl FrameF64 {
+    /// Round the frame to single precision, e.g. to write it to an XTC
+    /// trajectory
+    pub fn to_f32(&self) -> Frame {
+        let round = |value: f64| value as f32;
+        Frame {
+            step: self.step,
+            time: self.time as f32,
+            box_vector: self.box_vector.to_f32(),
+            coords: map_block(&self.coords, round),
+            velocities: self.velocities.as_ref().map(|v| map_block(v, round)),
+            forces: self.forces.as_ref().map(|f| map_block(f, round)),
+        }
+    }
+}
+
+impl TRRTrajectory {
+    /// Read the next step of the trajectory into a double precision frame.
+    /// The frame is resized to the number of atoms, and its velocities and
+    /// forces are set if the step has them. The coordinates are zero if the
+    /// step has none.
+    ///
+    /// Steps written in double precision are read without rounding, steps
+    /// in single precision are converted.
+    pub fn read_f64(&mut self, frame: &mut FrameF64) -> Result<()> {
+        self.handle.check_position()?;
+        let start = self.handle.tell()?;
+        match read_frame(&mut self.handle, frame) {
+            Ok(()) => {
+                self.handle.frame_done();
+                self.handle.report_progress();
+                Ok(())
+            }
+            Err(err) => {
+                let err = self.handle.frame_error(err);
+                Err(self
+                    .index
+                    .skip_corrupt(&mut self.handle, start, err, index::skip_trr_frame))
+            }
+        }
+    }
+
+    /// Write a double precision frame, like a double precision build of
+    /// GROMACS does. Velocities and forces are written if present.
+    ///
+    /// Other programs may not support double precision TRR files, write
+    /// frames rounded with `FrameF64::to_f32` to keep files readable by
+    /// them.
+    pub fn write_f64(&mut self, frame: &FrameF64) -> Result<()> {
+        self.handle.check_position()?;
+        frame.box_vector.to_f32().validate()?;
+        let num_atoms = frame.coords.len();
+        let block_size = |block: Option<&Vec<[f64; 3]>>| match block {
+            None => Ok(0),
+            Some(block) if block.len() == num_atoms => {
+                to(num_atoms * 3, ErrorTask::Write, "num_atoms").map(|n: c_int| n * DOUBLE_SIZE)
+            }
+            Some(block) => Err(Error::WrongSizeFrame {
+                expected: num_atoms,
+                found: block.len(),
+            }),
+        };
+        let mut header = xdrfile_trr::t_trnheader {
+            box_size: 9 * DOUBLE_SIZE,
+            x_size: block_size(Some(&frame.coords))?,
+            v_size: block_size(frame.velocities.as_ref())?,
+            f_size: block_size(frame.forces.as_ref())?,
+            natoms: to(num_atoms, ErrorTask::Write, "num_atoms")?,
+            step: to(frame.step, ErrorTask::Write, "step")?,
+            td: frame.time,
+            ..Default::default()
+        };
+
+        let result = unsafe { xdrfile_trr::do_trnheader(self.handle.xdrfile, 0, &mut header) };
+        if let Some(err) = check_code(result, ErrorTask::Write) {
+            return Err(self.handle.frame_error(err));
+        }
+        let blocks = [
+            Some(&frame.coords),
+            frame.velocities.as_ref(),
+            frame.forces.as_ref(),
+        ];
+        let result = write_doubles(&mut self.handle, frame.box_vector.as_matrix()).and_then(|()| {
+            blocks
+                .iter()
+                .flatten()
+                .try_for_each(|block| write_doubles(&mut self.handle, block))
+        });
+        match result {
+            Ok(()) => {
+                self.handle.frame_done();
+                count_written_frame(&mut self.num_atoms, &mut self.num_frames);
+                Ok(())
+            }
+            Err(err) => Err(self.handle.frame_error(err)),
+        }
+    }
+}
+
+/// Read the TRR frame at the current position into `frame`
+fn read_frame(handle: &mut XDRFile, frame: &mut FrameF64) -> Result<()> {
+    let header = index::read_trr_header(handle, ErrorTask::Read)?;
+    let num_atoms: usize = to(header.natoms, ErrorTask::Read, "num_atoms")?;
+    let double = header.bDouble != 0;
+    let real_size = if double { DOUBLE_SIZE } else { 4 };
+    let header_error = || Error::from((ErrorCode::ExdrHeader, ErrorTask::Read));
+    let block_size = header
+        .natoms
+        .checked_mul(3 * real_size)
+        .ok_or_else(header_error)?;
+
+    let mut matrix = [[0.0; 3]; 3];
+    if header.box_size != 0 {
+        read_reals(handle, double, &mut matrix)?;
+    }
+    let pressure_size = header.vir_size + header.pres_size;
+    handle.seek_to(SeekFrom::Current(i64::from(pressure_size)))?;
+
+    let mut read_block = |size: c_int, block: &mut Vec<[f64; 3]>| {
+        if size != 0 && size != block_size {
+            return Err(header_error());
+        }
+        block.resize(num_atoms, [0.0; 3]);
+        if size == 0 {
+            // Do not keep the values of the previous step
+            block.fill([0.0; 3]);
+            return Ok(false);
+        }
+        read_reals(handle, double, block)?;
+        Ok(true)
+    };
+    read_block(header.x_size, &mut frame.coords)?;
+    let mut velocities = frame.velocities.take().unwrap_or_default();
+    if read_block(header.v_size, &mut velocities)? {
+        frame.velocities = Some(velocities);
+    }
+    let mut forces = frame.forces.take().unwrap_or_default();
+    if read_block(header.f_size, &mut forces)? {
+        frame.forces = Some(forces);
+    }
+
+    frame.step = to(header.step, ErrorTask::Read, "step")?;
+    frame.time = header.td;
+    frame.box_vector = BoxVector::new(matrix);
+    Ok(())
+}
+
+/// Read per-atom vectors stored as doubles, or as floats if `double` is
+/// false
+fn read_reals(handle: &mut XDRFile, double: bool, values: &mut [[f64; 3]]) -> Result<()> {
+    let values = values.as_flattened_mut();
+    let len: c_int = to(values.len(), ErrorTask::Read, "values.len()")?;
+    let count = if double {
+        unsafe {
+            xdrfile::xdrfile_read_double(values.as_mut_ptr() as *mut c_double, len, handle.xdrfile)
+        }
+    } else {
+        let mut floats = vec![0.0f32; values.len()];
+        let count = unsafe {
+            xdrfile::xdrfile_read_float(floats.as_mut_ptr() as *mut c_float, len, handle.xdrfile)
+        };
+        for (value, float) in values.iter_mut().zip(floats) {
+            *value = f64::from(float);
+        }
+        count
+    };
+    if count == len {
+        Ok(())
+    } else {
+        let code = if double {
+            ErrorCode::ExdrDouble
+        } else {
+            ErrorCode::ExdrFloat
+        };
+        Err((code, ErrorTask::Read).into())
+    }
+}
+
+/// Write per-atom vectors as doubles
+fn write_doubles(handle: &mut XDRFile, values: &[[f64; 3]]) -> Result<()> {
+    let values = values.as_flattened();
+    let len: c_int = to(values.len(), ErrorTask::Write, "values.len()")?;
+    // SAFETY: values is not mutated by the C code when writing
+    let count = unsafe {
+        xdrfile::xdrfile_write_double(values.as_ptr() as *mut c_double, len, handle.xdrfile)
+    };
+    if count == len {
+        Ok(())
+    } else {
+        Err((ErrorCode::ExdrDouble, ErrorTask::Write).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Frame with values that can not be represented in single precision
+    fn double_frame(step: usize) -> FrameF64 {
+        FrameF64 {
+            step,
+            time: 0.1 * step as f64,
+            box_vector: BoxVector::new([[1.0 / 3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.1, 0.0, 3.0]]),
+            coords: (0..20)
+                .map(|i| [0.1 * i as f64, 1.0 / 3.0, -1e-9])
+                .collect(),
+            velocities: None,
+            forces: Some(vec![[1.0 + 1e-12, 0.0, 2.0]; 20]),
+        }
+    }
+
+    #[test]
+    fn test_read_write_f64() -> Result<()> {
+        let mut trajectory = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        for step in 0..3 {
+            trajectory.write_f64(&double_frame(step))?;
+        }
+        let bytes = trajectory.into_bytes().unwrap();
+
+        let mut trajectory = TRRTrajectory::from_bytes(bytes.clone(), FileMode::Read)?;
+        let mut frame = FrameF64::default();
+        for step in 0..3 {
+            trajectory.read_f64(&mut frame)?;
+            let expected = double_frame(step);
+            assert_eq!(frame.step, step);
+            assert_eq!(frame.time, expected.time);
+            assert_eq!(frame.box_vector, expected.box_vector);
+            assert_eq!(frame.coords, expected.coords);
+            assert_eq!(frame.velocities, None);
+            assert_eq!(frame.forces, expected.forces);
+        }
+        assert!(trajectory.read_f64(&mut frame).unwrap_err().is_eof());
+
+        // The single precision methods read double precision files rounded
+        let mut trajectory = TRRTrajectory::from_bytes(bytes, FileMode::Read)?;
+        assert_eq!(trajectory.num_frames()?, 3);
+        trajectory.seek_to_frame(2)?;
+        let mut rounded = Frame::with_len(20);
+        trajectory.read(&mut rounded)?;
+        assert_eq!(rounded.coords, double_frame(2).to_f32().coords);
+        assert_eq!(rounded.box_vector, double_frame(2).box_vector.to_f32());
+        assert_eq!(rounded.time, 0.2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_f64_single_precision() -> Result<()> {
+        let mut trajectory = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut expected = Frame::with_len(304);
+        trajectory.read(&mut expected)?;
+        Trajectory::rewind(&mut trajectory)?;
+
+        let mut frame = FrameF64::default();
+        trajectory.read_f64(&mut frame)?;
+        assert_eq!(frame.coords.len(), 304);
+        assert_eq!(frame.to_f32().coords, expected.coords);
+        assert_eq!(frame.to_f32().header(), expected.header());
+        assert_eq!(expected.to_f64().coords, frame.coords);
+
+        // Reading continues with the next frame
+        trajectory.read(&mut expected)?;
+        assert_eq!(expected.step, 2);
+        Ok(())
+    }
+
+    /// Write a TRR step with the given header and data, which need not match
+    fn write_raw(
+        trajectory: &mut TRRTrajectory,
+        mut header: xdrfile_trr::t_trnheader,
+        data: &[[f64; 3]],
+    ) -> Result<()> {
+        let code = unsafe { xdrfile_trr::do_trnheader(trajectory.handle.xdrfile, 0, &mut header) };
+        check_code(code, ErrorTask::Write).map_or(Ok(()), Err)?;
+        write_doubles(&mut trajectory.handle, data)
+    }
+
+    #[test]
+    fn test_read_f64_without_coords() -> Result<()> {
+        let mut trajectory = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        trajectory.write_f64(&double_frame(0))?;
+        let header = xdrfile_trr::t_trnheader {
+            v_size: 20 * 3 * DOUBLE_SIZE,
+            natoms: 20,
+            step: 1,
+            ..Default::default()
+        };
+        write_raw(&mut trajectory, header, &[[2.0; 3]; 20])?;
+        let bytes = trajectory.into_bytes().unwrap();
+
+        let mut trajectory = TRRTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let mut frame = FrameF64::default();
+        trajectory.read_f64(&mut frame)?;
+        trajectory.read_f64(&mut frame)?;
+        assert_eq!(frame.step, 1);
+        assert_eq!(frame.coords, vec![[0.0; 3]; 20]);
+        assert_eq!(frame.velocities, Some(vec![[2.0; 3]; 20]));
+        assert_eq!(frame.forces, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_f64_header_overflow() -> Result<()> {
+        let mut trajectory = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let header = xdrfile_trr::t_trnheader {
+            box_size: 9 * DOUBLE_SIZE,
+            x_size: 3 * DOUBLE_SIZE,
+            natoms: c_int::MAX / 3,
+            ..Default::default()
+        };
+        write_raw(&mut trajectory, header, &[[1.0; 3]; 4])?;
+        let bytes = trajectory.into_bytes().unwrap();
+
+        let mut trajectory = TRRTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let err = trajectory.read_f64(&mut FrameF64::default()).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::ExdrHeader));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_f64_errors() -> Result<()> {
+        let mut trajectory = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let mut frame = double_frame(0);
+        frame.velocities = Some(vec![[0.0; 3]; 3]);
+        assert!(matches!(
+            trajectory.write_f64(&frame),
+            Err(Error::WrongSizeFrame {
+                expected: 20,
+                found: 3
+            })
+        ));
+        Ok(())
+    }
+}
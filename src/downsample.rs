@@ -0,0 +1,209 @@
+use crate::*;
+
+/// Which written frames `DownsampledTrajectory` passes on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Downsampling {
+    /// Every n-th frame, starting with the first one
+    Every(usize),
+    /// The first frame and every frame whose time is at least this much
+    /// after the last frame passed on
+    MinInterval(f32),
+}
+
+/// A trajectory that only writes some of the frames passed to `write`, see
+/// `Downsampling`. Frames that are left out are dropped without an error.
+///
+/// Everything else, including reading and seeking, is passed on to the
+/// wrapped trajectory unchanged.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+///     let mut trajectory = trajectory.downsample(Downsampling::Every(10));
+///     for frame in XTCTrajectory::open_read("tests/1l2y.xtc")? {
+///         trajectory.write(&*frame?)?;
+///     }
+///     assert_eq!(trajectory.num_passed(), 4);
+///     Ok(())
+/// }
+/// ```
+pub struct DownsampledTrajectory<T> {
+    trajectory: T,
+    downsampling: Downsampling,
+    num_seen: usize,
+    num_passed: usize,
+    /// Time of the last frame passed on
+    last_time: Option<f32>,
+}
+
+impl<T: Trajectory> DownsampledTrajectory<T> {
+    /// Wrap `trajectory` so that only the frames selected by `downsampling`
+    /// are written to it
+    ///
+    /// # Panics
+    /// Panics if `downsampling` is `Every(0)`.
+    pub fn new(trajectory: T, downsampling: Downsampling) -> DownsampledTrajectory<T> {
+        assert!(
+            downsampling != Downsampling::Every(0),
+            "downsampling interval must be at least 1"
+        );
+        DownsampledTrajectory {
+            trajectory,
+            downsampling,
+            num_seen: 0,
+            num_passed: 0,
+            last_time: None,
+        }
+    }
+
+    /// Number of frames passed to `write`, including those left out
+    pub fn num_seen(&self) -> usize {
+        self.num_seen
+    }
+
+    /// Number of frames written to the wrapped trajectory
+    pub fn num_passed(&self) -> usize {
+        self.num_passed
+    }
+
+    /// The wrapped trajectory
+    pub fn inner(&self) -> &T {
+        &self.trajectory
+    }
+
+    /// Unwrap the trajectory
+    pub fn into_inner(self) -> T {
+        self.trajectory
+    }
+
+    /// Check whether a frame at `time` is passed on, as the next frame seen
+    fn passes(&self, time: f32) -> bool {
+        match self.downsampling {
+            Downsampling::Every(n) => self.num_seen.is_multiple_of(n),
+            Downsampling::MinInterval(interval) => {
+                self.last_time.is_none_or(|last| time - last >= interval)
+            }
+        }
+    }
+}
+
+impl<T: Trajectory> Trajectory for DownsampledTrajectory<T> {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        self.trajectory.read(frame)
+    }
+
+    fn peek(&mut self, frame: &mut Frame) -> Result<()> {
+        self.trajectory.peek(frame)
+    }
+
+    fn read_into(&mut self, coords: &mut [[f32; 3]], header: &mut FrameHeader) -> Result<()> {
+        self.trajectory.read_into(coords, header)
+    }
+
+    /// Write the frame if it is selected, otherwise do nothing
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        if self.passes(frame.time) {
+            self.trajectory.write(frame)?;
+            self.num_passed += 1;
+            self.last_time = Some(frame.time);
+        }
+        self.num_seen += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.trajectory.flush()
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        self.trajectory.get_num_atoms()
+    }
+
+    fn num_frames(&mut self) -> Result<usize> {
+        self.trajectory.num_frames()
+    }
+
+    fn known_num_frames(&self) -> Option<usize> {
+        self.trajectory.known_num_frames()
+    }
+
+    fn current_frame(&self) -> Option<usize> {
+        self.trajectory.current_frame()
+    }
+
+    fn seek_to_frame(&mut self, frame: usize) -> Result<()> {
+        self.trajectory.seek_to_frame(frame)
+    }
+
+    fn seek_to_time(&mut self, time: f32) -> Result<()> {
+        self.trajectory.seek_to_time(time)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.trajectory.skip(n)
+    }
+
+    fn is_at_end(&mut self) -> Result<bool> {
+        self.trajectory.is_at_end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write the frames of the XTC test file to a downsampled trajectory in
+    /// memory and get the steps of the frames written
+    fn written_steps(downsampling: Downsampling) -> Result<Vec<usize>> {
+        let trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let mut trajectory = trajectory.downsample(downsampling);
+        for frame in XTCTrajectory::open_read("tests/1l2y.xtc")? {
+            trajectory.write(&*frame?)?;
+        }
+        trajectory.flush()?;
+        assert_eq!(trajectory.num_seen(), 38);
+
+        let bytes = trajectory.into_inner().into_bytes().unwrap();
+        XTCTrajectory::from_bytes(bytes, FileMode::Read)?
+            .into_iter()
+            .map(|frame| frame.map(|frame| frame.step))
+            .collect()
+    }
+
+    #[test]
+    fn test_downsample_every() -> Result<()> {
+        assert_eq!(written_steps(Downsampling::Every(10))?, vec![1, 11, 21, 31]);
+        assert_eq!(written_steps(Downsampling::Every(1))?.len(), 38);
+        Ok(())
+    }
+
+    #[test]
+    fn test_downsample_min_interval() -> Result<()> {
+        // Frames of the test trajectory have the same time and step
+        let steps = written_steps(Downsampling::MinInterval(7.5))?;
+        assert_eq!(steps, vec![1, 9, 17, 25, 33]);
+        assert_eq!(written_steps(Downsampling::MinInterval(0.0))?.len(), 38);
+
+        // The interval is measured from the last frame written, not from the
+        // first one
+        let mut trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?
+            .downsample(Downsampling::MinInterval(1.0));
+        let mut frame = Frame::with_len(3);
+        for time in [0.0, 0.6, 1.2, 1.8, 2.1, 2.3] {
+            frame.time = time;
+            trajectory.write(&frame)?;
+        }
+        assert_eq!(trajectory.num_passed(), 3);
+        assert_eq!(trajectory.num_seen(), 6);
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic(expected = "downsampling interval must be at least 1")]
+    fn test_downsample_zero() {
+        let trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write).unwrap();
+        let _ = trajectory.downsample(Downsampling::Every(0));
+    }
+}
@@ -1,18 +1,36 @@
 use crate::c_abi;
+use crate::BoxVector;
 use crate::FileMode;
 use crate::Frame;
+use crate::LatticeParameters;
+use std::convert::TryFrom;
 use std::error::Error as StdError;
+use std::num::TryFromIntError;
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-/// Error type for the xdrfile library
+/// Error type for the xdrfile library. New variants may be added in minor
+/// versions.
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum Error {
     /// An error code from the C API
     CApiError { code: ErrorCode, task: ErrorTask },
     /// Passed in a frame of the wrong size
     WrongSizeFrame { expected: usize, found: usize },
-    /// C API failed to open a file (No return code provided)
-    CouldNotOpen { path: PathBuf, mode: FileMode },
+    /// A frame could not be built by `FrameBuilder`
+    InvalidFrame { reason: &'static str },
+    /// C API failed to open a file (No return code provided). `source` is
+    /// the IO error reported when opening failed, if any.
+    CouldNotOpen {
+        path: PathBuf,
+        mode: FileMode,
+        source: Option<IoError>,
+    },
+    /// The stream or compressed file backing a trajectory failed while
+    /// `task` was attempted, e.g. because compressed data is corrupt
+    Io { task: ErrorTask, source: IoError },
     /// A path could not be converted to &OsStr
     InvalidOsStr(Option<std::ffi::NulError>),
     /// Checking the number of atoms failed while reading a frame
@@ -23,6 +41,76 @@ pub enum Error {
         task: ErrorTask,
         value: String,
         target: &'static str,
+        source: TryFromIntError,
+    },
+    /// An error of the C API or the stream while reading or writing a frame,
+    /// with the path of the file (None for streams) and the index of the
+    /// frame, if known
+    InFrame {
+        path: Option<PathBuf>,
+        frame: Option<usize>,
+        source: Box<Error>,
+    },
+    /// A frame could not be decoded in recovery mode, so the bytes from
+    /// `start` to `end` were skipped to continue with the next valid frame
+    CorruptRegion {
+        start: u64,
+        end: u64,
+        source: Box<Error>,
+    },
+    /// An index file could not be saved or loaded
+    InvalidIndex { path: PathBuf, reason: &'static str },
+    /// A box vector does not follow the GROMACS conventions
+    InvalidBox {
+        box_vector: BoxVector,
+        reason: &'static str,
+    },
+    /// Lattice parameters do not describe a box
+    InvalidLatticeParameters {
+        parameters: LatticeParameters,
+        reason: &'static str,
+    },
+    /// A second handle for a trajectory could not be opened with `try_clone`
+    CannotClone { reason: &'static str },
+    /// A trajectory does not support `operation`, e.g. a custom trajectory
+    /// that relies on a default method of `Trajectory` which cannot seek
+    Unsupported { operation: &'static str },
+    /// A replica exchange mapping is malformed or does not match the
+    /// trajectories to demultiplex
+    InvalidReplicaMap { reason: &'static str },
+    /// A topology file could not be parsed, or the masses of its atoms could
+    /// not be guessed. `line` is the number of the offending line, if known.
+    InvalidTopology {
+        line: Option<usize>,
+        reason: &'static str,
+    },
+    /// A frame has a value that cannot be written, found by the validation
+    /// enabled with `set_validate`. `atom` is None for the time and the box.
+    InvalidValue {
+        field: &'static str,
+        atom: Option<usize>,
+        value: f32,
+        reason: &'static str,
+    },
+    /// Coordinates could not be compressed with the XTC precision, because
+    /// a coordinate of `atom` is not finite or too large or, if `atom` is
+    /// None, because they span too large a range
+    XtcCompression {
+        atom: Option<usize>,
+        precision: f32,
+        reason: &'static str,
+    },
+    /// A seek failed and the position before it could not be restored, so
+    /// the trajectory cannot be read or written until it is moved to a
+    /// known position, e.g. with `seek_to_frame` or `rewind`. `path` is None
+    /// for streams.
+    PositionLost { path: Option<PathBuf> },
+    /// A trajectory to append to ends with an incomplete frame, which takes
+    /// up the bytes from `complete_len` to `len`
+    IncompleteFrame {
+        path: PathBuf,
+        complete_len: u64,
+        len: u64,
     },
 }
 
@@ -38,9 +126,10 @@ impl Error {
         }
     }
 
-    /// Get the task being attempted when the C API returned an error, if any
+    /// Get the task being attempted when the C API or the stream backing a
+    /// trajectory returned an error, if any
     pub fn task(&self) -> Option<ErrorTask> {
-        if let Error::CApiError { task, .. } = self {
+        if let Error::CApiError { task, .. } | Error::Io { task, .. } = self {
             Some(*task)
         } else if let Some(e) = self.source() {
             e.downcast_ref::<Self>().and_then(Self::task)
@@ -49,9 +138,22 @@ impl Error {
         }
     }
 
+    /// Get the OS error that caused opening a file to fail, if any. This
+    /// distinguishes e.g. missing permissions from a missing directory.
+    pub fn os_error(&self) -> Option<std::io::Error> {
+        if let Error::CouldNotOpen {
+            source: Some(err), ..
+        } = self
+        {
+            err.raw_os_error().map(std::io::Error::from_raw_os_error)
+        } else {
+            None
+        }
+    }
+
     /// True if the error is an end of file error, false otherwise
     pub fn is_eof(&self) -> bool {
-        self.code().map_or(false, |e| e.is_eof())
+        self.code().is_some_and(|e| e.is_eof())
     }
 }
 
@@ -65,12 +167,70 @@ impl std::error::Error for Error {
                     None
                 }
             }
+            Error::CApiError { code, .. } => Some(code),
+            Error::CouldNotOpen { source, .. } => source
+                .as_ref()
+                .map(|err| err.get_ref() as &(dyn StdError + 'static)),
+            Error::Io { source, .. } => Some(source.get_ref()),
+            Error::OutOfRange { source, .. } => Some(source),
             Error::CouldNotCheckNAtoms(err) => Some(err.as_ref()),
+            Error::InFrame { source, .. } | Error::CorruptRegion { source, .. } => {
+                Some(source.as_ref())
+            }
             _ => None,
         }
     }
 }
 
+impl Error {
+    /// The kind of `std::io::Error` that matches this error best
+    fn io_kind(&self) -> std::io::ErrorKind {
+        use std::io::ErrorKind;
+        match self {
+            Error::CApiError { code, .. } => match code {
+                ErrorCode::ExdrEndOfFile => ErrorKind::UnexpectedEof,
+                ErrorCode::ExdrFileNotFound => ErrorKind::NotFound,
+                ErrorCode::ExdrNoMem => ErrorKind::OutOfMemory,
+                ErrorCode::ExdrClose | ErrorCode::ExdrNr | ErrorCode::UnmatchedCode(_) => {
+                    ErrorKind::Other
+                }
+                _ => ErrorKind::InvalidData,
+            },
+            Error::CouldNotOpen { source, .. } => match source {
+                Some(err) => err.kind(),
+                None => ErrorKind::Other,
+            },
+            Error::Io { source, .. } => source.kind(),
+            Error::WrongSizeFrame { .. }
+            | Error::InvalidFrame { .. }
+            | Error::InvalidOsStr(_)
+            | Error::InvalidBox { .. }
+            | Error::InvalidLatticeParameters { .. }
+            | Error::InvalidReplicaMap { .. }
+            | Error::InvalidValue { .. }
+            | Error::XtcCompression { .. }
+            | Error::OutOfRange { .. } => ErrorKind::InvalidInput,
+            Error::CouldNotCheckNAtoms(source)
+            | Error::InFrame { source, .. }
+            | Error::CorruptRegion { source, .. } => source.io_kind(),
+            Error::InvalidIndex { .. }
+            | Error::InvalidTopology { .. }
+            | Error::IncompleteFrame { .. } => ErrorKind::InvalidData,
+            Error::CannotClone { .. } | Error::Unsupported { .. } => ErrorKind::Unsupported,
+            Error::PositionLost { .. } => ErrorKind::Other,
+        }
+    }
+}
+
+/// Convert to an `std::io::Error` of the matching kind, e.g. `UnexpectedEof`
+/// for end of file errors, so that `?` works in functions returning
+/// `io::Result`. The original error is kept as the inner error.
+impl From<Error> for std::io::Error {
+    fn from(err: Error) -> Self {
+        std::io::Error::new(err.io_kind(), err)
+    }
+}
+
 impl From<(ErrorCode, ErrorTask)> for Error {
     fn from(value: (ErrorCode, ErrorTask)) -> Self {
         let (code, task) = value;
@@ -84,6 +244,18 @@ impl From<(&Path, FileMode)> for Error {
         Error::CouldNotOpen {
             path: path.to_owned(),
             mode,
+            source: None,
+        }
+    }
+}
+
+impl From<(&Path, FileMode, std::io::Error)> for Error {
+    fn from(value: (&Path, FileMode, std::io::Error)) -> Self {
+        let (path, mode, err) = value;
+        Error::CouldNotOpen {
+            path: path.to_owned(),
+            mode,
+            source: Some(err.into()),
         }
     }
 }
@@ -103,7 +275,7 @@ impl std::fmt::Display for Error {
         match self {
             Error::CApiError { code, task } => write!(
                 f,
-                "Error while {task}: C API returned error code {code}",
+                "Error while {task}: C API returned error: {code}",
                 task = task,
                 code = code
             ),
@@ -112,9 +284,17 @@ impl std::fmt::Display for Error {
                 "Expected frame of size {:?}, found {:?}",
                 expected, found
             ),
-            Error::CouldNotOpen { path, mode } => {
-                write!(f, "Could not open file at {:?} in mode {:?}", path, mode)
+            Error::CouldNotOpen { path, mode, .. } => {
+                write!(f, "Could not open file at {:?} in mode {:?}", path, mode)?;
+                if let Error::CouldNotOpen {
+                    source: Some(err), ..
+                } = self
+                {
+                    write!(f, ": {}", err)?;
+                }
+                Ok(())
             }
+            Error::Io { task, source } => write!(f, "IO error while {}: {}", task, source),
             Error::InvalidOsStr(_) => write!(f, "Cannot convert path to CString."),
             Error::CouldNotCheckNAtoms(_) => {
                 write!(f, "Failed to read number of atoms in trajectory file")
@@ -124,6 +304,7 @@ impl std::fmt::Display for Error {
                 task,
                 value,
                 target,
+                ..
             } => write!(
                 f,
                 "Illegal {name} while {task}: Failed to cast {value} to {target}",
@@ -132,15 +313,101 @@ impl std::fmt::Display for Error {
                 value = value,
                 target = target
             ),
+            Error::InFrame {
+                path,
+                frame,
+                source,
+            } => {
+                match frame {
+                    Some(frame) => write!(f, "Error in frame {}", frame)?,
+                    None => write!(f, "Error in unknown frame")?,
+                }
+                if let Some(path) = path {
+                    write!(f, " of {:?}", path)?;
+                }
+                write!(f, ": {}", source)
+            }
+            Error::CorruptRegion { start, end, source } => {
+                write!(f, "Skipped corrupt bytes {}..{}: {}", start, end, source)
+            }
+            Error::InvalidIndex { path, reason } => {
+                write!(f, "Invalid index file {:?}: {}", path, reason)
+            }
+            Error::InvalidBox { box_vector, reason } => write!(
+                f,
+                "Invalid box vector {:?}: {}",
+                box_vector.as_matrix(),
+                reason
+            ),
+            Error::InvalidLatticeParameters { parameters, reason } => {
+                write!(f, "Invalid lattice parameters {:?}: {}", parameters, reason)
+            }
+            Error::CannotClone { reason } => write!(f, "Cannot clone trajectory: {}", reason),
+            Error::Unsupported { operation } => {
+                write!(f, "{} is not supported by this trajectory", operation)
+            }
+            Error::InvalidFrame { reason } => write!(f, "Invalid frame: {}", reason),
+            Error::InvalidReplicaMap { reason } => write!(f, "Invalid replica map: {}", reason),
+            Error::InvalidTopology {
+                line: Some(line),
+                reason,
+            } => write!(f, "Invalid topology in line {}: {}", line, reason),
+            Error::InvalidTopology { line: None, reason } => {
+                write!(f, "Invalid topology: {}", reason)
+            }
+            Error::InvalidValue {
+                field,
+                atom,
+                value,
+                reason,
+            } => {
+                write!(f, "Cannot write {} {}", field, value)?;
+                if let Some(atom) = atom {
+                    write!(f, " of atom {}", atom)?;
+                }
+                write!(f, ": {}", reason)
+            }
+            Error::XtcCompression {
+                atom,
+                precision,
+                reason,
+            } => {
+                write!(f, "Cannot compress coordinates")?;
+                if let Some(atom) = atom {
+                    write!(f, " of atom {}", atom)?;
+                }
+                write!(f, " with precision {}: {}", precision, reason)
+            }
+            Error::IncompleteFrame {
+                path,
+                complete_len,
+                len,
+            } => write!(
+                f,
+                "File {:?} ends with an incomplete frame at bytes {}..{}",
+                path, complete_len, len
+            ),
+            Error::PositionLost { path: Some(path) } => write!(
+                f,
+                "Position in {:?} is unknown after a failed seek, seek to a frame to continue",
+                path
+            ),
+            Error::PositionLost { path: None } => write!(
+                f,
+                "Position in stream is unknown after a failed seek, seek to a frame to continue"
+            ),
         }
     }
 }
 
 /// The task being attempted when the C API returns an error
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
 pub enum ErrorTask {
     /// The number of atoms was being read from a file
     ReadNumAtoms,
+    /// The number of frames was being read from a file
+    ReadNumFrames,
     /// A frame was being read from a file
     Read,
     /// A frame was being written to a file
@@ -149,16 +416,23 @@ pub enum ErrorTask {
     Flush,
     /// A seek operation was being run on a file
     Seek,
+    /// A file was being closed
+    Close,
+    /// The IO buffer of a file was being set up
+    SetBuffer,
 }
 
 impl std::fmt::Display for ErrorTask {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
             ErrorTask::ReadNumAtoms => write!(f, "reading atom number from trajectory"),
+            ErrorTask::ReadNumFrames => write!(f, "reading frame number from trajectory"),
             ErrorTask::Read => write!(f, "reading trajectory"),
             ErrorTask::Write => write!(f, "writing trajectory"),
             ErrorTask::Flush => write!(f, "flushing trajectory"),
             ErrorTask::Seek => write!(f, "seeking in trajectory"),
+            ErrorTask::Close => write!(f, "closing trajectory"),
+            ErrorTask::SetBuffer => write!(f, "setting the buffer size of trajectory"),
         }
     }
 }
@@ -203,6 +477,41 @@ impl ErrorCode {
     pub fn is_eof(self) -> bool {
         matches!(self, Self::ExdrEndOfFile)
     }
+
+    /// The code as returned by the C API
+    pub fn code(self) -> i32 {
+        match self {
+            Self::ExdrOk => c_abi::xdrfile::exdrOK,
+            Self::ExdrHeader => c_abi::xdrfile::exdrHEADER,
+            Self::ExdrString => c_abi::xdrfile::exdrSTRING,
+            Self::ExdrDouble => c_abi::xdrfile::exdrDOUBLE,
+            Self::ExdrInt => c_abi::xdrfile::exdrINT,
+            Self::ExdrFloat => c_abi::xdrfile::exdrFLOAT,
+            Self::ExdrUint => c_abi::xdrfile::exdrUINT,
+            Self::Exdr3dx => c_abi::xdrfile::exdr3DX,
+            Self::ExdrClose => c_abi::xdrfile::exdrCLOSE,
+            Self::ExdrMagic => c_abi::xdrfile::exdrMAGIC,
+            Self::ExdrNoMem => c_abi::xdrfile::exdrNOMEM,
+            Self::ExdrEndOfFile => c_abi::xdrfile::exdrENDOFFILE,
+            Self::ExdrFileNotFound => c_abi::xdrfile::exdrFILENOTFOUND,
+            Self::ExdrNr => c_abi::xdrfile::exdrNR,
+            Self::UnmatchedCode(code) => code,
+        }
+    }
+
+    /// Description of the error from the `exdr_message` table of the C
+    /// library, None for codes without a description
+    pub fn message(self) -> Option<String> {
+        let index = usize::try_from(self.code()).ok()?;
+        // The table is only read, never modified
+        let messages = unsafe { &*std::ptr::addr_of!(c_abi::xdrfile::exdr_message) };
+        let message = *messages.get(index)?;
+        if message.is_null() {
+            return None;
+        }
+        let message = unsafe { std::ffi::CStr::from_ptr(message) };
+        Some(message.to_string_lossy().into_owned())
+    }
 }
 
 impl From<i32> for ErrorCode {
@@ -229,14 +538,55 @@ impl From<i32> for ErrorCode {
 
 impl std::fmt::Display for ErrorCode {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if let Self::UnmatchedCode(i) = self {
-            write!(f, "{}", i)
-        } else {
-            write!(f, "{:?}", self)
+        match (self.message(), self) {
+            (Some(message), _) => write!(f, "{} ({:?})", message, self),
+            (None, Self::UnmatchedCode(i)) => write!(f, "{}", i),
+            (None, _) => write!(f, "{:?}", self),
         }
     }
 }
 
+impl std::error::Error for ErrorCode {}
+
+/// An `std::io::Error` that can be cloned and compared, so that `Error` can
+/// keep the IO error that caused it. Two errors are equal if they have the
+/// same kind and OS error code.
+#[derive(Debug, Clone)]
+pub struct IoError(Arc<std::io::Error>);
+
+impl IoError {
+    /// Get the wrapped IO error
+    pub fn get_ref(&self) -> &std::io::Error {
+        &self.0
+    }
+}
+
+impl Deref for IoError {
+    type Target = std::io::Error;
+
+    fn deref(&self) -> &std::io::Error {
+        &self.0
+    }
+}
+
+impl From<std::io::Error> for IoError {
+    fn from(err: std::io::Error) -> Self {
+        IoError(Arc::new(err))
+    }
+}
+
+impl PartialEq for IoError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind() == other.kind() && self.raw_os_error() == other.raw_os_error()
+    }
+}
+
+impl std::fmt::Display for IoError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
 /// `Result` type for errors in the `xdrfile` crate
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
@@ -279,10 +629,119 @@ mod tests {
         let error = Error::CouldNotOpen {
             path: PathBuf::from("not/a/file"),
             mode: FileMode::Read,
+            source: None,
         };
         assert!(!error.is_eof());
     }
 
+    #[test]
+    fn test_error_code_message() {
+        assert_eq!(
+            ErrorCode::ExdrMagic.message().as_deref(),
+            Some("Magic number")
+        );
+        assert_eq!(ErrorCode::ExdrMagic.to_string(), "Magic number (ExdrMagic)");
+        assert_eq!(ErrorCode::ExdrNr.message(), None);
+        assert_eq!(ErrorCode::ExdrNr.to_string(), "ExdrNr");
+        assert_eq!(ErrorCode::UnmatchedCode(-1).to_string(), "-1");
+        for code in 0..=c_abi::xdrfile::exdrNR {
+            assert_eq!(ErrorCode::from(code).code(), code);
+        }
+
+        let error = Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Read));
+        assert_eq!(
+            error.to_string(),
+            "Error while reading trajectory: C API returned error: End of file (ExdrEndOfFile)"
+        );
+    }
+
+    #[test]
+    fn test_into_io_error() {
+        use std::io::ErrorKind;
+
+        fn read() -> std::io::Result<()> {
+            Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Read)))?;
+            Ok(())
+        }
+        let err = read().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+        let inner = err.get_ref().and_then(|e| e.downcast_ref::<Error>());
+        assert!(inner.is_some_and(Error::is_eof));
+
+        let not_found = Error::CouldNotOpen {
+            path: PathBuf::from("not/a/file"),
+            mode: FileMode::Read,
+            // ENOENT on Unix, ERROR_FILE_NOT_FOUND on Windows
+            source: Some(std::io::Error::from_raw_os_error(2).into()),
+        };
+        assert_eq!(std::io::Error::from(not_found).kind(), ErrorKind::NotFound);
+
+        let in_frame = Error::InFrame {
+            path: None,
+            frame: Some(3),
+            source: Box::new(Error::from((ErrorCode::ExdrMagic, ErrorTask::Read))),
+        };
+        let err = std::io::Error::from(in_frame);
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        // The chain of sources is kept
+        let source = err.get_ref().and_then(|e| e.source());
+        let source = source.and_then(|e| e.downcast_ref::<Error>());
+        assert_eq!(source.and_then(Error::code), Some(ErrorCode::ExdrMagic));
+
+        let wrong_size = Error::WrongSizeFrame {
+            expected: 1,
+            found: 2,
+        };
+        assert_eq!(
+            std::io::Error::from(wrong_size).kind(),
+            ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn test_source_chain() {
+        let sources = |err: &Error| {
+            let mut sources = Vec::new();
+            let mut source = err.source();
+            while let Some(err) = source {
+                sources.push(err.to_string());
+                source = err.source();
+            }
+            sources
+        };
+
+        let not_found = std::io::Error::new(std::io::ErrorKind::NotFound, "no such file");
+        let could_not_open = Error::from((Path::new("a.xtc"), FileMode::Read, not_found));
+        let in_frame = Error::InFrame {
+            path: None,
+            frame: None,
+            source: Box::new(could_not_open.clone()),
+        };
+        assert_eq!(
+            sources(&in_frame),
+            vec![could_not_open.to_string(), "no such file".to_string()]
+        );
+        let io = in_frame.source().and_then(|err| err.source());
+        let io = io.and_then(|err| err.downcast_ref::<std::io::Error>());
+        assert_eq!(io.map(|err| err.kind()), Some(std::io::ErrorKind::NotFound));
+
+        let c_api = Error::from((ErrorCode::ExdrMagic, ErrorTask::Read));
+        let code = c_api
+            .source()
+            .and_then(|err| err.downcast_ref::<ErrorCode>());
+        assert_eq!(code, Some(&ErrorCode::ExdrMagic));
+
+        let out_of_range = Error::OutOfRange {
+            name: "step",
+            task: ErrorTask::Write,
+            value: "-1".to_string(),
+            target: "usize",
+            source: usize::try_from(-1).unwrap_err(),
+        };
+        let source = out_of_range.source().map(|err| err.is::<TryFromIntError>());
+        assert_eq!(source, Some(true));
+    }
+
     #[test]
     fn test_from_correct_type() {
         let code = 3.into();
@@ -296,6 +755,7 @@ mod tests {
         let expected = Error::CouldNotOpen {
             path: path.to_path_buf(),
             mode: mode.to_owned(),
+            source: None,
         };
         let err = Error::from((path, mode));
         assert_eq!(expected, err);
@@ -0,0 +1,150 @@
+use crate::*;
+use index::{TRR_MAGIC, XTC_MAGIC};
+use std::fs::File;
+use std::io::Read;
+
+/// File formats of trajectories
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrajectoryFormat {
+    Xtc,
+    Trr,
+}
+
+impl TrajectoryFormat {
+    /// Detect the format of the trajectory at `path` from the magic number
+    /// of its first frame. If the file does not start with a known magic
+    /// number, e.g. because it is compressed or empty, the extension is
+    /// used instead.
+    pub fn detect(path: impl AsRef<Path>) -> Result<TrajectoryFormat> {
+        let path = path.as_ref();
+        let mut magic = [0; 4];
+        let read_magic = File::open(path).and_then(|mut file| file.read_exact(&mut magic));
+        if read_magic.is_ok() {
+            if let Some(format) = Self::from_magic(i32::from_be_bytes(magic)) {
+                return Ok(format);
+            }
+        }
+        match (Self::from_extension(path), read_magic) {
+            (Some(format), _) => Ok(format),
+            (None, Err(err)) if err.kind() != io::ErrorKind::UnexpectedEof => {
+                Err(Error::from((path, FileMode::Read, err)))
+            }
+            (None, _) => Err(Error::from((ErrorCode::ExdrMagic, ErrorTask::Read))),
+        }
+    }
+
+    /// Get the format from the extension of `path`. Extensions of compressed
+    /// files are skipped, so `traj.xtc.gz` is an XTC file.
+    pub fn from_extension(path: impl AsRef<Path>) -> Option<TrajectoryFormat> {
+        let mut path = path.as_ref();
+        loop {
+            let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+            match extension.as_str() {
+                "xtc" => return Some(TrajectoryFormat::Xtc),
+                "trr" => return Some(TrajectoryFormat::Trr),
+                "gz" | "zst" => path = Path::new(path.file_stem()?),
+                _ => return None,
+            }
+        }
+    }
+
+    /// Get the format of frames starting with the given magic number
+    pub(crate) fn from_magic(magic: i32) -> Option<TrajectoryFormat> {
+        match magic {
+            XTC_MAGIC => Some(TrajectoryFormat::Xtc),
+            TRR_MAGIC => Some(TrajectoryFormat::Trr),
+            _ => None,
+        }
+    }
+
+    /// Open the trajectory at `path` in this format
+    pub fn open(self, path: impl AsRef<Path>, filemode: FileMode) -> Result<Box<dyn Trajectory>> {
+        Ok(match self {
+            TrajectoryFormat::Xtc => Box::new(XTCTrajectory::open(path, filemode)?),
+            TrajectoryFormat::Trr => Box::new(TRRTrajectory::open(path, filemode)?),
+        })
+    }
+
+    /// Open the trajectory at `path` in this format, configured with
+    /// `options`
+    pub fn open_with(
+        self,
+        path: impl AsRef<Path>,
+        filemode: FileMode,
+        options: &TrajectoryOptions,
+    ) -> Result<Box<dyn Trajectory>> {
+        Ok(match self {
+            TrajectoryFormat::Xtc => Box::new(XTCTrajectory::open_with(path, filemode, options)?),
+            TrajectoryFormat::Trr => Box::new(TRRTrajectory::open_with(path, filemode, options)?),
+        })
+    }
+}
+
+/// Open a trajectory for reading without knowing its format in advance. The
+/// format is detected with `TrajectoryFormat::detect`.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     for path in &["tests/1l2y.xtc", "tests/1l2y.trr"] {
+///         let mut trajectory = open_any(path)?;
+///         assert_eq!(trajectory.get_num_atoms()?, 304);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn open_any(path: impl AsRef<Path>) -> Result<Box<dyn Trajectory>> {
+    let path = path.as_ref();
+    TrajectoryFormat::detect(path)?.open(path, FileMode::Read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_detect_by_magic() -> Result<()> {
+        // The extension does not matter if the magic number is known
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        std::fs::copy("tests/1l2y.trr", tempfile.path()).expect("Could not copy test file");
+        assert_eq!(
+            TrajectoryFormat::detect(tempfile.path())?,
+            TrajectoryFormat::Trr
+        );
+        let mut trajectory = open_any(tempfile.path())?;
+        assert_eq!(trajectory.num_frames()?, 38);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_by_extension() -> Result<()> {
+        let path = Path::new("does/not/exist.XTC.gz");
+        assert_eq!(TrajectoryFormat::detect(path)?, TrajectoryFormat::Xtc);
+        assert_eq!(
+            TrajectoryFormat::from_extension("traj.trr.zst"),
+            Some(TrajectoryFormat::Trr)
+        );
+        assert_eq!(TrajectoryFormat::from_extension("traj.pdb"), None);
+        assert_eq!(TrajectoryFormat::from_extension("traj"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_with() -> Result<()> {
+        let options = TrajectoryOptions::new().resync(true);
+        let mut trajectory =
+            TrajectoryFormat::Trr.open_with("tests/1l2y.trr", FileMode::Read, &options)?;
+        assert_eq!(trajectory.num_frames()?, 38);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_unknown() {
+        let err = TrajectoryFormat::detect("README.md").unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::ExdrMagic));
+        let err = TrajectoryFormat::detect("does/not/exist").unwrap_err();
+        assert!(matches!(err, Error::CouldNotOpen { .. }));
+    }
+}
@@ -1,28 +1,59 @@
+use crate::{AtomSelection, BoxVector, Error, Result};
 use std::ops::{Index, IndexMut};
 
 /// A frame represents a single step in a trajectory.
+///
+/// Time and per-atom data are single precision by default. Frames with
+/// double precision (`FrameF64`) are read and written with
+/// `TRRTrajectory::read_f64` and `TRRTrajectory::write_f64`, all other
+/// methods work on single precision frames.
 #[derive(Clone, Debug)]
-pub struct Frame {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Frame<T = f32> {
     /// Trajectory step
     pub step: usize,
 
     /// Time step (usually in picoseconds)
-    pub time: f32,
+    pub time: T,
 
     /// 3x3 box vector
-    pub box_vector: [[f32; 3]; 3],
+    pub box_vector: BoxVector<T>,
 
     /// 3D coordinates for N atoms where N is num_atoms
-    pub coords: Vec<[f32; 3]>,
+    pub coords: Vec<[T; 3]>,
+
+    /// Velocities for the same N atoms, if present. Only TRR trajectories
+    /// store velocities.
+    pub velocities: Option<Vec<[T; 3]>>,
+
+    /// Forces on the same N atoms, if present. Only TRR trajectories store
+    /// forces.
+    pub forces: Option<Vec<[T; 3]>>,
+}
+
+/// Step, time and box of a frame, without the per-atom data. Used to read
+/// coordinates into memory owned by the caller with `Trajectory::read_into`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameHeader {
+    /// Trajectory step
+    pub step: usize,
+
+    /// Time step (usually in picoseconds)
+    pub time: f32,
+
+    /// 3x3 box vector
+    pub box_vector: BoxVector,
 }
 
-impl Default for Frame {
-    fn default() -> Frame {
+impl<T: Default> Default for Frame<T> {
+    fn default() -> Frame<T> {
         Frame {
             step: 0,
-            time: 0.0,
-            box_vector: [[0.0; 3]; 3],
+            time: T::default(),
+            box_vector: BoxVector::default(),
             coords: Vec::with_capacity(0),
+            velocities: None,
+            forces: None,
         }
     }
 }
@@ -41,15 +72,111 @@ impl Frame {
         }
     }
 
+    /// Get the step, time and box of the frame
+    pub fn header(&self) -> FrameHeader {
+        FrameHeader {
+            step: self.step,
+            time: self.time,
+            box_vector: self.box_vector,
+        }
+    }
+
+    /// Creates a frame with the given header and coordinates
+    pub fn from_coords(
+        step: usize,
+        time: f32,
+        box_vector: BoxVector,
+        coords: Vec<[f32; 3]>,
+    ) -> Frame {
+        Frame {
+            step,
+            time,
+            box_vector,
+            coords,
+            velocities: None,
+            forces: None,
+        }
+    }
+
+    /// Creates a builder that validates the frame before building it, see
+    /// `FrameBuilder`
+    pub fn builder() -> FrameBuilder {
+        FrameBuilder::default()
+    }
+
+    /// Adds zeroed velocities for all atoms of the frame
+    pub fn with_velocities(mut self) -> Frame {
+        self.velocities = Some(vec![[0.0, 0.0, 0.0]; self.coords.len()]);
+        self
+    }
+
+    /// Adds zeroed forces for all atoms of the frame
+    pub fn with_forces(mut self) -> Frame {
+        self.forces = Some(vec![[0.0, 0.0, 0.0]; self.coords.len()]);
+        self
+    }
+
     /// Filters the frame by removing all atoms not matching the given indeces.
+    ///
+    /// To filter many frames by the same indices, create an `AtomSelection`
+    /// once and use `apply` instead.
     pub fn filter_coords(self: &mut Frame, indices: &[usize]) {
-        self.coords = self
-            .coords
-            .iter()
-            .enumerate()
-            .filter(|(i, _)| indices.contains(i))
-            .map(|(_, elem)| *elem)
-            .collect();
+        self.apply(&AtomSelection::new(indices.iter().copied()))
+    }
+
+    /// Copies the atoms matching the given indices into `dst`, along with
+    /// step, time and box vector. The coordinates buffer of `dst` is reused,
+    /// so no allocation happens once it is large enough and the indices are
+    /// in ascending order. Other indices are sorted first.
+    ///
+    /// To filter many frames by the same indices, create an `AtomSelection`
+    /// once and use `apply_into` instead.
+    pub fn filter_coords_into(&self, indices: &[usize], dst: &mut Frame) {
+        if indices.windows(2).all(|pair| pair[0] < pair[1]) {
+            let len = self.coords.len();
+            self.select_into(indices.iter().copied().take_while(|&i| i < len), dst);
+        } else {
+            self.apply_into(&AtomSelection::new(indices.iter().copied()), dst);
+        }
+    }
+
+    /// Keep only the atoms at the given ascending indices
+    pub(crate) fn select(&mut self, selected: impl Iterator<Item = usize> + Clone) {
+        compact(&mut self.coords, selected.clone());
+        for block in self.blocks_mut() {
+            compact(block, selected.clone());
+        }
+    }
+
+    /// Copy the atoms at the given indices into `dst`, along with step, time
+    /// and box vector
+    pub(crate) fn select_into(
+        &self,
+        selected: impl Iterator<Item = usize> + Clone,
+        dst: &mut Frame,
+    ) {
+        dst.copy_header_from(self);
+        copy_selected(&self.coords, &mut dst.coords, selected.clone());
+        let blocks = [
+            (&self.velocities, &mut dst.velocities),
+            (&self.forces, &mut dst.forces),
+        ];
+        for (block, dst_block) in blocks {
+            match block {
+                Some(block) => {
+                    let dst_block = dst_block.get_or_insert_with(Vec::new);
+                    copy_selected(block, dst_block, selected.clone());
+                }
+                None => *dst_block = None,
+            }
+        }
+    }
+
+    /// Copy step, time and box vector from another frame
+    pub(crate) fn copy_header_from(&mut self, other: &Frame) {
+        self.step = other.step;
+        self.time = other.time;
+        self.box_vector = other.box_vector;
     }
 
     /// Length of the frame (number of atoms)
@@ -62,12 +189,226 @@ impl Frame {
         self.coords.len()
     }
 
-    /// Resize the frame to have exactly `num_atoms` atoms, filling coords with zeros if necessary
+    /// Volume of the box of the frame, None if the box has no volume (e.g. if
+    /// the trajectory has no box)
+    pub fn box_volume(&self) -> Option<f32> {
+        let volume = self.box_vector.volume();
+        if volume > 0.0 {
+            Some(volume)
+        } else {
+            None
+        }
+    }
+
+    /// True if both frames have the same step and number of atoms, and time,
+    /// box vector and coordinates differ by at most `tolerance`.
+    ///
+    /// Velocities and forces are not compared, so a frame can be compared to
+    /// itself after a round trip through a lossy XTC file:
+    ///
+    /// ```rust
+    /// use xdrfile::*;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut frame = Frame::with_len(20).with_velocities();
+    ///     frame[3] = [0.12345, 1.0, 2.0];
+    ///
+    ///     let mut trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+    ///     trajectory.write(&frame)?;
+    ///     let bytes = trajectory.into_bytes().unwrap();
+    ///     let read = XTCTrajectory::from_bytes(bytes, FileMode::Read)?.last_frame()?;
+    ///
+    ///     // The default precision stores coordinates with 0.001 nm resolution
+    ///     assert!(read.approx_eq(&frame, 1e-3));
+    ///     assert!(!read.approx_eq(&frame, 1e-6));
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn approx_eq(&self, other: &Frame, tolerance: f32) -> bool {
+        let close = |a: &f32, b: &f32| (a - b).abs() <= tolerance;
+        let box_a = self.box_vector.as_matrix().iter().flatten();
+        let box_b = other.box_vector.as_matrix().iter().flatten();
+        let coords_a = self.coords.iter().flatten();
+        let coords_b = other.coords.iter().flatten();
+        self.step == other.step
+            && self.coords.len() == other.coords.len()
+            && close(&self.time, &other.time)
+            && box_a.zip(box_b).all(|(a, b)| close(a, b))
+            && coords_a.zip(coords_b).all(|(a, b)| close(a, b))
+    }
+
+    /// Resize the frame to have exactly `num_atoms` atoms, filling coords
+    /// (and velocities and forces, if present) with zeros if necessary
     pub fn resize(&mut self, num_atoms: usize) {
-        self.coords.resize(num_atoms, [0.0; 3])
+        self.coords.resize(num_atoms, [0.0; 3]);
+        for block in self.blocks_mut() {
+            block.resize(num_atoms, [0.0; 3]);
+        }
+    }
+
+    /// Optional per-atom blocks (velocities and forces) present in the frame
+    fn blocks_mut(&mut self) -> impl Iterator<Item = &mut Vec<[f32; 3]>> {
+        self.velocities.iter_mut().chain(self.forces.iter_mut())
     }
 }
 
+/// Builder for frames, created with `Frame::builder()`.
+///
+/// Unlike constructing a `Frame` directly, `build` checks that the
+/// coordinates, velocities and forces all have the declared number of atoms
+/// and that all values are finite.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let frame = Frame::builder()
+///         .step(10)
+///         .time(0.5)
+///         .box_vector(BoxVector::rectangular(3.0, 3.0, 3.0))
+///         .num_atoms(2)
+///         .coords(vec![[0.0; 3], [1.0, 2.0, 3.0]])
+///         .build()?;
+///     assert_eq!(frame.len(), 2);
+///
+///     let result = Frame::builder().num_atoms(3).coords(vec![[0.0; 3]]).build();
+///     assert!(result.is_err());
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FrameBuilder {
+    step: usize,
+    time: f32,
+    box_vector: BoxVector,
+    num_atoms: Option<usize>,
+    coords: Option<Vec<[f32; 3]>>,
+    velocities: Option<Vec<[f32; 3]>>,
+    forces: Option<Vec<[f32; 3]>>,
+}
+
+impl FrameBuilder {
+    /// Trajectory step (default 0)
+    pub fn step(mut self, step: usize) -> Self {
+        self.step = step;
+        self
+    }
+
+    /// Time of the frame (default 0)
+    pub fn time(mut self, time: f32) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Box of the frame (default no box)
+    pub fn box_vector(mut self, box_vector: BoxVector) -> Self {
+        self.box_vector = box_vector;
+        self
+    }
+
+    /// Number of atoms the coordinates, velocities and forces must have. If
+    /// no coordinates are given, the atoms are placed at the origin.
+    pub fn num_atoms(mut self, num_atoms: usize) -> Self {
+        self.num_atoms = Some(num_atoms);
+        self
+    }
+
+    /// Coordinates of the atoms
+    pub fn coords(mut self, coords: Vec<[f32; 3]>) -> Self {
+        self.coords = Some(coords);
+        self
+    }
+
+    /// Velocities of the atoms
+    pub fn velocities(mut self, velocities: Vec<[f32; 3]>) -> Self {
+        self.velocities = Some(velocities);
+        self
+    }
+
+    /// Forces on the atoms
+    pub fn forces(mut self, forces: Vec<[f32; 3]>) -> Self {
+        self.forces = Some(forces);
+        self
+    }
+
+    /// Validate and build the frame. Fails with `Error::WrongSizeFrame` if
+    /// the per-atom data does not match the number of atoms and with
+    /// `Error::InvalidFrame` if any value is not finite.
+    pub fn build(self) -> Result<Frame> {
+        let coords = match (self.coords, self.num_atoms) {
+            (Some(coords), Some(num_atoms)) if coords.len() != num_atoms => {
+                return Err(Error::WrongSizeFrame {
+                    expected: num_atoms,
+                    found: coords.len(),
+                })
+            }
+            (Some(coords), _) => coords,
+            (None, num_atoms) => vec![[0.0; 3]; num_atoms.unwrap_or(0)],
+        };
+        for block in self.velocities.iter().chain(self.forces.iter()) {
+            if block.len() != coords.len() {
+                return Err(Error::WrongSizeFrame {
+                    expected: coords.len(),
+                    found: block.len(),
+                });
+            }
+        }
+
+        let all_finite = |block: &[[f32; 3]]| block.iter().flatten().all(|v| v.is_finite());
+        if !self.time.is_finite() {
+            return Err(Error::InvalidFrame {
+                reason: "time is not finite",
+            });
+        }
+        if !all_finite(&self.box_vector.as_matrix()[..]) {
+            return Err(Error::InvalidFrame {
+                reason: "box vector is not finite",
+            });
+        }
+        if !all_finite(&coords) {
+            return Err(Error::InvalidFrame {
+                reason: "coordinates are not finite",
+            });
+        }
+        if !self.velocities.iter().all(|block| all_finite(block)) {
+            return Err(Error::InvalidFrame {
+                reason: "velocities are not finite",
+            });
+        }
+        if !self.forces.iter().all(|block| all_finite(block)) {
+            return Err(Error::InvalidFrame {
+                reason: "forces are not finite",
+            });
+        }
+
+        Ok(Frame {
+            step: self.step,
+            time: self.time,
+            box_vector: self.box_vector,
+            coords,
+            velocities: self.velocities,
+            forces: self.forces,
+        })
+    }
+}
+
+/// Move the elements at the given ascending indices to the front and drop
+/// the rest
+fn compact(block: &mut Vec<[f32; 3]>, selected: impl Iterator<Item = usize>) {
+    let mut len = 0;
+    for i in selected {
+        block[len] = block[i];
+        len += 1;
+    }
+    block.truncate(len);
+}
+
+/// Replace the contents of `dst` with the elements of `src` at the given indices
+fn copy_selected(src: &[[f32; 3]], dst: &mut Vec<[f32; 3]>, selected: impl Iterator<Item = usize>) {
+    dst.clear();
+    dst.extend(selected.map(|i| src[i]));
+}
+
 impl Index<usize> for Frame {
     type Output = [f32; 3];
 
@@ -93,6 +434,87 @@ mod tests {
         assert_eq!(frame.coords.len(), 10);
     }
 
+    #[test]
+    fn test_from_coords() {
+        let box_vector = BoxVector::rectangular(1.0, 2.0, 3.0);
+        let frame = Frame::from_coords(4, 0.5, box_vector, vec![[1.0; 3]; 2]);
+        assert_eq!(frame.step, 4);
+        assert_eq!(frame.time, 0.5);
+        assert_eq!(frame.box_vector, box_vector);
+        assert_eq!(frame.coords, vec![[1.0; 3]; 2]);
+        assert_eq!(frame.velocities, None);
+    }
+
+    #[test]
+    fn test_builder() -> Result<()> {
+        let frame = Frame::builder()
+            .step(2)
+            .num_atoms(3)
+            .velocities(vec![[1.0; 3]; 3])
+            .build()?;
+        assert_eq!(frame.step, 2);
+        assert_eq!(frame.coords, vec![[0.0; 3]; 3]);
+        assert_eq!(frame.velocities, Some(vec![[1.0; 3]; 3]));
+        assert_eq!(frame.forces, None);
+        assert_eq!(Frame::builder().build()?.len(), 0);
+
+        let err = Frame::builder()
+            .coords(vec![[0.0; 3]; 2])
+            .forces(vec![[0.0; 3]])
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            Error::WrongSizeFrame {
+                expected: 2,
+                found: 1
+            }
+        );
+
+        let err = Frame::builder()
+            .coords(vec![[0.0, f32::NAN, 0.0]])
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err,
+            Error::InvalidFrame {
+                reason: "coordinates are not finite"
+            }
+        );
+        let result = Frame::builder().time(f32::INFINITY).build();
+        assert!(matches!(result, Err(Error::InvalidFrame { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let mut frame = Frame::with_len(2);
+        frame.time = 1.0;
+        frame[1] = [1.0, 2.0, 3.0];
+        let mut other = frame.clone().with_forces();
+        assert!(frame.approx_eq(&other, 0.0));
+
+        other[1][2] += 0.01;
+        assert!(frame.approx_eq(&other, 0.02));
+        assert!(!frame.approx_eq(&other, 0.001));
+
+        let mut other = frame.clone();
+        other.time += 0.5;
+        assert!(!frame.approx_eq(&other, 0.1));
+        other.time = frame.time;
+        other.box_vector[0][0] = 0.5;
+        assert!(!frame.approx_eq(&other, 0.1));
+
+        let mut other = frame.clone();
+        other.step += 1;
+        assert!(!frame.approx_eq(&other, 1.0));
+        assert!(!frame.approx_eq(&Frame::with_len(3), 10.0));
+
+        other = frame.clone();
+        other[0][0] = f32::NAN;
+        assert!(!frame.approx_eq(&other, 1.0));
+    }
+
     #[test]
     fn test_frame_filter_atoms() {
         let mut frame = Frame::with_len(3);
@@ -107,6 +529,83 @@ mod tests {
         assert!(frame_new.coords[1] == frame[2]);
     }
 
+    #[test]
+    fn test_filter_coords_into() {
+        let mut frame = Frame::with_len(3);
+        frame.step = 7;
+        frame[0] = [1.0, 2.0, 3.0];
+        frame[1] = [4.0, 5.0, 6.0];
+        frame[2] = [7.0, 8.0, 9.0];
+        let mut dst = Frame::with_len(3);
+        let capacity = dst.coords.capacity();
+        frame.filter_coords_into(&[2, 0], &mut dst);
+        assert_eq!(dst.coords, vec![frame[0], frame[2]]);
+        assert_eq!(dst.step, 7);
+        assert_eq!(dst.coords.capacity(), capacity);
+
+        frame.filter_coords_into(&[1, 2, 5], &mut dst);
+        assert_eq!(dst.coords, vec![frame[1], frame[2]]);
+        frame.filter_coords_into(&[2, 1, 2, 7], &mut dst);
+        assert_eq!(dst.coords, vec![frame[1], frame[2]]);
+    }
+
+    #[test]
+    fn test_velocities() {
+        let mut frame = Frame::with_len(3).with_velocities();
+        frame.resize(4);
+        assert_eq!(frame.velocities.as_ref().map(Vec::len), Some(4));
+        frame.velocities = Some(vec![[0.0; 3], [1.0; 3], [2.0; 3], [3.0; 3]]);
+
+        let mut dst = Frame::new();
+        frame.filter_coords_into(&[3, 1], &mut dst);
+        assert_eq!(dst.velocities, Some(vec![[1.0; 3], [3.0; 3]]));
+
+        frame.filter_coords(&[2]);
+        assert_eq!(frame.velocities, Some(vec![[2.0; 3]]));
+        assert_eq!(Frame::with_len(2).velocities, None);
+    }
+
+    #[test]
+    fn test_forces() {
+        let mut frame = Frame::with_len(3).with_forces();
+        frame.resize(2);
+        assert_eq!(frame.forces, Some(vec![[0.0; 3]; 2]));
+        assert_eq!(frame.velocities, None);
+        frame.forces = Some(vec![[0.0; 3], [1.0; 3]]);
+
+        let mut dst = Frame::new().with_velocities();
+        frame.filter_coords_into(&[1], &mut dst);
+        assert_eq!(dst.forces, Some(vec![[1.0; 3]]));
+        assert_eq!(dst.velocities, None);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_roundtrip() {
+        let mut frame = Frame::with_len(2).with_forces();
+        frame.step = 3;
+        frame.time = 1.5;
+        frame.box_vector[1][1] = 2.0;
+        frame[1] = [1.0, 2.0, 3.0];
+
+        let json = serde_json::to_string(&frame).expect("Could not serialize frame");
+        let decoded: Frame = serde_json::from_str(&json).expect("Could not deserialize frame");
+        assert_eq!(decoded.step, frame.step);
+        assert_eq!(decoded.time, frame.time);
+        assert_eq!(decoded.box_vector, frame.box_vector);
+        assert_eq!(decoded.coords, frame.coords);
+        assert_eq!(decoded.velocities, None);
+        assert_eq!(decoded.forces, frame.forces);
+    }
+
+    #[test]
+    fn test_box_volume() {
+        let mut frame = Frame::with_len(1);
+        assert_eq!(frame.box_volume(), None);
+        frame.box_vector = BoxVector::rectangular(1.0, 2.0, 3.0);
+        assert_eq!(frame.box_volume(), Some(6.0));
+    }
+
     #[test]
     fn test_frame_len() {
         let frame = Frame::with_len(10);
@@ -118,8 +617,10 @@ mod tests {
         let mut frame = Frame {
             step: 0,
             time: 0.0,
-            box_vector: [[0.0; 3]; 3],
-            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]]
+            box_vector: BoxVector::default(),
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]],
+            velocities: None,
+            forces: None,
         };
 
         frame.filter_coords(&[1]);
@@ -135,8 +636,10 @@ mod tests {
         let frame = Frame {
             step: 0,
             time: 0.0,
-            box_vector: [[0.0; 3]; 3],
-            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]]
+            box_vector: BoxVector::default(),
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]],
+            velocities: None,
+            forces: None,
         };
         for i in 0..frame.len() {
             for j in 0..3 {
@@ -148,8 +651,10 @@ mod tests {
         let mut frame = Frame {
             step: 0,
             time: 0.0,
-            box_vector: [[0.0; 3]; 3],
-            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]]
+            box_vector: BoxVector::default(),
+            coords: vec![[0.0; 3], [1.0; 3], [2.0; 3]],
+            velocities: None,
+            forces: None,
         };
         for i in 0..frame.len() {
             for j in 0..3 {
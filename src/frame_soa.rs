@@ -0,0 +1,197 @@
+use crate::{BoxVector, Frame, FrameHeader};
+
+/// A frame with its coordinates stored as separate x, y and z vectors
+/// (structure of arrays) instead of one vector of `[x, y, z]` triples.
+///
+/// Loops over a single component touch contiguous memory, so the compiler
+/// can vectorize them, which pays off for analysis kernels over many atoms.
+/// Frames are converted with `From` in both directions, or with `fill_from`
+/// and `write_to` to reuse the allocated vectors. Velocities and forces are
+/// not kept.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// let mut frame = Frame::with_len(2);
+/// frame[1] = [1.0, 2.0, 3.0];
+/// let mut soa = FrameSoA::from(&frame);
+/// soa.translate([1.0, 0.0, 0.0]);
+/// assert_eq!(soa.x, vec![1.0, 2.0]);
+/// assert_eq!(Frame::from(&soa)[1], [2.0, 2.0, 3.0]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FrameSoA {
+    /// Trajectory step
+    pub step: usize,
+
+    /// Time step (usually in picoseconds)
+    pub time: f32,
+
+    /// 3x3 box vector
+    pub box_vector: BoxVector,
+
+    /// x coordinates of the N atoms
+    pub x: Vec<f32>,
+
+    /// y coordinates of the N atoms
+    pub y: Vec<f32>,
+
+    /// z coordinates of the N atoms
+    pub z: Vec<f32>,
+}
+
+impl FrameSoA {
+    /// Creates a frame with all coordinates of `num_atoms` atoms at zero
+    pub fn with_len(num_atoms: usize) -> FrameSoA {
+        FrameSoA {
+            x: vec![0.0; num_atoms],
+            y: vec![0.0; num_atoms],
+            z: vec![0.0; num_atoms],
+            ..Default::default()
+        }
+    }
+
+    /// Get the step, time and box of the frame
+    pub fn header(&self) -> FrameHeader {
+        FrameHeader {
+            step: self.step,
+            time: self.time,
+            box_vector: self.box_vector,
+        }
+    }
+
+    /// Number of atoms in the frame
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// True if the frame has no atoms
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Get the coordinates of the atom at `index`, if it exists
+    pub fn get(&self, index: usize) -> Option<[f32; 3]> {
+        Some([
+            *self.x.get(index)?,
+            *self.y.get(index)?,
+            *self.z.get(index)?,
+        ])
+    }
+
+    /// Replace the contents of this frame with `frame`, reusing the
+    /// allocated coordinate vectors
+    pub fn fill_from(&mut self, frame: &Frame) {
+        self.step = frame.step;
+        self.time = frame.time;
+        self.box_vector = frame.box_vector;
+        self.x.clear();
+        self.y.clear();
+        self.z.clear();
+        self.x.extend(frame.coords.iter().map(|c| c[0]));
+        self.y.extend(frame.coords.iter().map(|c| c[1]));
+        self.z.extend(frame.coords.iter().map(|c| c[2]));
+    }
+
+    /// Replace the header and coordinates of `frame` with those of this
+    /// frame, reusing its allocated coordinates. Velocities and forces of
+    /// `frame` are removed.
+    pub fn write_to(&self, frame: &mut Frame) {
+        frame.step = self.step;
+        frame.time = self.time;
+        frame.box_vector = self.box_vector;
+        frame.coords.clear();
+        frame.coords.extend(
+            self.x
+                .iter()
+                .zip(&self.y)
+                .zip(&self.z)
+                .map(|((&x, &y), &z)| [x, y, z]),
+        );
+        frame.velocities = None;
+        frame.forces = None;
+    }
+
+    /// Move all atoms by `offset`
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        self.x.iter_mut().for_each(|v| *v += offset[0]);
+        self.y.iter_mut().for_each(|v| *v += offset[1]);
+        self.z.iter_mut().for_each(|v| *v += offset[2]);
+    }
+}
+
+impl From<&Frame> for FrameSoA {
+    fn from(frame: &Frame) -> FrameSoA {
+        let mut soa = FrameSoA::default();
+        soa.fill_from(frame);
+        soa
+    }
+}
+
+impl From<Frame> for FrameSoA {
+    fn from(frame: Frame) -> FrameSoA {
+        FrameSoA::from(&frame)
+    }
+}
+
+impl From<&FrameSoA> for Frame {
+    fn from(soa: &FrameSoA) -> Frame {
+        let mut frame = Frame::new();
+        soa.write_to(&mut frame);
+        frame
+    }
+}
+
+impl From<FrameSoA> for Frame {
+    fn from(soa: FrameSoA) -> Frame {
+        Frame::from(&soa)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let box_vector = BoxVector::new([[3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 3.0]]);
+        let mut frame =
+            Frame::from_coords(2, 1.5, box_vector, vec![[0.0, 1.0, 2.0], [3.0, 4.0, 5.0]]);
+        frame.velocities = Some(vec![[1.0; 3]; 2]);
+
+        let soa = FrameSoA::from(&frame);
+        assert_eq!(soa.len(), 2);
+        assert_eq!(soa.x, vec![0.0, 3.0]);
+        assert_eq!(soa.y, vec![1.0, 4.0]);
+        assert_eq!(soa.z, vec![2.0, 5.0]);
+        assert_eq!(soa.get(1), Some([3.0, 4.0, 5.0]));
+        assert_eq!(soa.get(2), None);
+        assert_eq!(soa.header(), frame.header());
+
+        let back = Frame::from(soa);
+        assert_eq!(back.header(), frame.header());
+        assert_eq!(back.coords, frame.coords);
+        assert!(back.velocities.is_none());
+    }
+
+    #[test]
+    fn test_reuse() {
+        let mut soa = FrameSoA::with_len(3);
+        assert_eq!(soa.get(2), Some([0.0; 3]));
+        soa.fill_from(&Frame::from_coords(
+            1,
+            0.0,
+            BoxVector::default(),
+            vec![[1.0, 2.0, 3.0]],
+        ));
+        soa.translate([1.0, -1.0, 0.5]);
+        assert_eq!(soa.len(), 1);
+
+        let mut frame = Frame::with_len(5);
+        soa.write_to(&mut frame);
+        assert_eq!(frame.step, 1);
+        assert_eq!(frame.coords, vec![[2.0, 1.0, 3.5]]);
+        assert!(FrameSoA::default().is_empty());
+    }
+}
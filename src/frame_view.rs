@@ -0,0 +1,187 @@
+use crate::{BoxVector, Frame};
+use std::ops::Index;
+
+/// A borrowed subset of the atoms of a frame.
+///
+/// Unlike `Frame::filter_coords`, creating a view neither copies nor modifies
+/// coordinates, which makes selecting the same atoms in every frame cheap.
+/// The atoms of the view are the atoms of the frame at the given indices, in
+/// the order of the indices.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// let mut frame = Frame::with_len(3);
+/// frame[2] = [1.0, 2.0, 3.0];
+/// let view = frame.view(&[2, 0]);
+/// assert_eq!(view.len(), 2);
+/// assert_eq!(view[0], [1.0, 2.0, 3.0]);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct FrameView<'a> {
+    frame: &'a Frame,
+    indices: &'a [usize],
+}
+
+impl<'a> FrameView<'a> {
+    /// Creates a view of the atoms of `frame` at the given indices
+    pub fn new(frame: &'a Frame, indices: &'a [usize]) -> FrameView<'a> {
+        FrameView { frame, indices }
+    }
+
+    /// The frame the view borrows from
+    pub fn frame(&self) -> &'a Frame {
+        self.frame
+    }
+
+    /// Indices of the atoms of the view in the frame
+    pub fn indices(&self) -> &'a [usize] {
+        self.indices
+    }
+
+    /// Trajectory step of the frame
+    pub fn step(&self) -> usize {
+        self.frame.step
+    }
+
+    /// Time of the frame
+    pub fn time(&self) -> f32 {
+        self.frame.time
+    }
+
+    /// Box vector of the frame
+    pub fn box_vector(&self) -> &'a BoxVector {
+        &self.frame.box_vector
+    }
+
+    /// Number of atoms in the view
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// True if the view contains no atoms
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Coordinates of the `index`-th atom of the view, None if either the
+    /// view or the frame has no such atom
+    pub fn get(&self, index: usize) -> Option<&'a [f32; 3]> {
+        self.indices
+            .get(index)
+            .and_then(|&i| self.frame.coords.get(i))
+    }
+
+    /// Iterate over the coordinates of the atoms in the view
+    ///
+    /// # Panics
+    /// Panics while iterating if an index is out of bounds of the frame.
+    pub fn iter(&self) -> impl Iterator<Item = &'a [f32; 3]> + 'a {
+        let coords = &self.frame.coords;
+        self.indices.iter().map(move |&i| &coords[i])
+    }
+
+    /// Mean position of the atoms in the view, None if the view is empty
+    pub fn center_of_geometry(&self) -> Option<[f32; 3]> {
+        if self.is_empty() {
+            return None;
+        }
+        let mut sum = [0.0; 3];
+        for coords in self.iter() {
+            for dim in 0..3 {
+                sum[dim] += coords[dim];
+            }
+        }
+        let n = self.len() as f32;
+        Some([sum[0] / n, sum[1] / n, sum[2] / n])
+    }
+
+    /// Copy the atoms of the view into a new frame with the same step, time
+    /// and box vector
+    ///
+    /// # Panics
+    /// Panics if an index is out of bounds of the frame.
+    pub fn to_frame(&self) -> Frame {
+        let mut frame = Frame::new();
+        self.frame
+            .select_into(self.indices.iter().copied(), &mut frame);
+        frame
+    }
+}
+
+impl Index<usize> for FrameView<'_> {
+    type Output = [f32; 3];
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.frame.coords[self.indices[index]]
+    }
+}
+
+impl Frame {
+    /// Creates a view of the atoms at the given indices without copying them
+    pub fn view<'a>(&'a self, indices: &'a [usize]) -> FrameView<'a> {
+        FrameView::new(self, indices)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_frame() -> Frame {
+        let mut frame = Frame::with_len(4);
+        frame.step = 3;
+        frame.time = 1.5;
+        frame[0] = [1.0, 2.0, 3.0];
+        frame[1] = [4.0, 5.0, 6.0];
+        frame[2] = [7.0, 8.0, 9.0];
+        frame[3] = [3.0, 2.0, 1.0];
+        frame
+    }
+
+    #[test]
+    fn test_view_index() {
+        let frame = test_frame();
+        let view = frame.view(&[3, 1]);
+        assert_eq!(view.len(), 2);
+        assert_eq!(view[0], frame[3]);
+        assert_eq!(view[1], frame[1]);
+        assert_eq!(view.get(1), Some(&frame[1]));
+        assert_eq!(view.get(2), None);
+        assert_eq!(view.step(), 3);
+        assert_eq!(view.time(), 1.5);
+
+        let coords: Vec<_> = view.iter().copied().collect();
+        assert_eq!(coords, vec![frame[3], frame[1]]);
+    }
+
+    #[test]
+    fn test_view_out_of_bounds() {
+        let frame = test_frame();
+        let view = frame.view(&[0, 4]);
+        assert_eq!(view.get(1), None);
+    }
+
+    #[test]
+    fn test_view_center_of_geometry() {
+        let frame = test_frame();
+        assert_eq!(
+            frame.view(&[0, 2]).center_of_geometry(),
+            Some([4.0, 5.0, 6.0])
+        );
+        assert_eq!(frame.view(&[]).center_of_geometry(), None);
+    }
+
+    #[test]
+    fn test_view_to_frame() {
+        let frame = test_frame();
+        let indices = [1, 2];
+        let subset = frame.view(&indices).to_frame();
+
+        let mut filtered = frame.clone();
+        filtered.filter_coords(&indices);
+        assert_eq!(subset.coords, filtered.coords);
+        assert_eq!(subset.step, frame.step);
+        assert_eq!(subset.time, frame.time);
+    }
+}
@@ -0,0 +1,802 @@
+use crate::c_abi::xdrfile_trr;
+use crate::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::time::UNIX_EPOCH;
+
+/// Magic number at the start of every XTC frame
+pub(crate) const XTC_MAGIC: c_int = 1995;
+
+/// Magic number at the start of every TRR frame
+pub(crate) const TRR_MAGIC: c_int = 1993;
+
+/// Size of the smallest possible XTC frame: header, box and no coordinates
+pub(crate) const MIN_XTC_FRAME_SIZE: u64 = 56;
+
+/// Size of the smallest possible TRR frame: a single precision header
+/// without box, coordinates, velocities or forces
+pub(crate) const MIN_TRR_FRAME_SIZE: u64 = 84;
+
+/// Size of the 3x3 box of an XTC frame
+const XTC_BOX_SIZE: i64 = 4 * 9;
+
+/// Size of the fields preceding the byte count of compressed XTC coordinates
+/// (precision, minint[3], maxint[3] and smallidx)
+const XTC_COMPRESSION_HEADER: i64 = 4 * (1 + 3 + 3 + 1);
+
+/// Magic bytes at the start of index files, including the format version
+const INDEX_MAGIC: &[u8; 8] = b"XDRIDX01";
+
+/// Location and header information of a single frame in a trajectory file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct IndexEntry {
+    /// Byte offset of the start of the frame
+    pub offset: u64,
+    /// Number of atoms in the frame
+    pub num_atoms: usize,
+    /// Trajectory step of the frame
+    pub step: usize,
+    /// Time of the frame
+    pub time: f32,
+}
+
+/// Function that skips over the frame at the current position of a file,
+/// returning its index entry
+pub(crate) type SkipFrame = fn(&mut XDRFile) -> Result<IndexEntry>;
+
+/// Byte offsets and headers of the frames of a trajectory file.
+///
+/// The index is filled lazily: frames are only visited once they are
+/// requested, by skipping over them using the sizes stored in their headers.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrajectoryIndex {
+    /// Entries of the frames found so far
+    entries: Vec<IndexEntry>,
+    /// Offset right after the last frame in `entries`
+    end: u64,
+}
+
+impl TrajectoryIndex {
+    /// Create an index of the frames with the given entries, where `end` is
+    /// the offset right after the last frame
+    pub fn from_entries(entries: Vec<IndexEntry>, end: u64) -> TrajectoryIndex {
+        TrajectoryIndex { entries, end }
+    }
+
+    /// Get the index entry of the frame with the given index.
+    ///
+    /// If the frame was not yet visited, the file is scanned forward from the
+    /// last known frame with `skip_frame`. This moves the position of `handle`.
+    pub fn entry(
+        &mut self,
+        handle: &mut XDRFile,
+        frame: usize,
+        skip_frame: SkipFrame,
+    ) -> Result<IndexEntry> {
+        while frame >= self.entries.len() {
+            self.visit_next(handle, skip_frame)?;
+        }
+        Ok(self.entries[frame])
+    }
+
+    /// Make sure that the offsets of all frames up to the given index are
+    /// known, without moving the position of `handle`
+    pub fn visit_up_to(
+        &mut self,
+        handle: &mut XDRFile,
+        frame: usize,
+        skip_frame: SkipFrame,
+    ) -> Result<()> {
+        handle.restoring_position(|handle| self.entry(handle, frame, skip_frame).map(|_| ()))
+    }
+
+    /// Move `handle` forward over `n` frames without decoding them.
+    ///
+    /// If the index of the current frame is known, the offsets of known
+    /// frames are used. Otherwise, frames are skipped one by one from the
+    /// current position. If fewer than `n` frames are left, an end of file
+    /// error is returned and the position is kept.
+    pub fn skip(&mut self, handle: &mut XDRFile, n: usize, skip_frame: SkipFrame) -> Result<()> {
+        let (start, current) = (handle.tell()?, handle.frame);
+        let offset = match current {
+            Some(frame) => self.offset_of(handle, frame.saturating_add(n), skip_frame),
+            None => (0..n)
+                .try_for_each(|_| skip_frame(handle).map(|_| ()))
+                .and_then(|()| handle.tell())
+                .map_err(|err| handle.stream_error(err)),
+        };
+        match offset {
+            Ok(offset) => {
+                handle.seek_to(SeekFrom::Start(offset))?;
+                handle.frame = current.map(|frame| frame + n);
+                Ok(())
+            }
+            Err(err) => {
+                handle.seek_to(SeekFrom::Start(start))?;
+                handle.frame = current;
+                Err(err)
+            }
+        }
+    }
+
+    /// Get the offset of the frame with the given index. The index right
+    /// after the last frame is the end of the file.
+    fn offset_of(
+        &mut self,
+        handle: &mut XDRFile,
+        frame: usize,
+        skip_frame: SkipFrame,
+    ) -> Result<u64> {
+        while self.entries.len() < frame {
+            self.visit_next(handle, skip_frame)?;
+        }
+        Ok(self
+            .entries
+            .get(frame)
+            .map_or(self.end, |entry| entry.offset))
+    }
+
+    /// Get the index of the first frame for which `predicate` returns true.
+    ///
+    /// Known frames are searched first, then the file is scanned forward
+    /// frame by frame. If no frame matches, an end of file error is returned.
+    pub fn find(
+        &mut self,
+        handle: &mut XDRFile,
+        skip_frame: SkipFrame,
+        predicate: impl Fn(&IndexEntry) -> bool,
+    ) -> Result<usize> {
+        if let Some(frame) = self.entries.iter().position(&predicate) {
+            return Ok(frame);
+        }
+        loop {
+            let entry = self.visit_next(handle, skip_frame)?;
+            if predicate(&entry) {
+                return Ok(self.entries.len() - 1);
+            }
+        }
+    }
+
+    /// Move `handle` to the first frame with a time greater than or equal to
+    /// `time`, assuming that the times of the frames increase. If no frame
+    /// matches, an end of file error is returned and the position is kept.
+    ///
+    /// Known frames are bisected first. The frames after them are bisected
+    /// by their offsets if all frames have the size of the first one, which
+    /// only reads a few headers. Otherwise, the file is scanned forward like
+    /// in `find`.
+    pub fn seek_to_time(
+        &mut self,
+        handle: &mut XDRFile,
+        time: f32,
+        skip_frame: SkipFrame,
+    ) -> Result<()> {
+        let (start, current) = (handle.tell()?, handle.frame);
+        match self.find_time(handle, time, skip_frame) {
+            Ok((frame, offset)) => {
+                handle.seek_to(SeekFrom::Start(offset))?;
+                handle.frame = Some(frame);
+                Ok(())
+            }
+            Err(err) => {
+                handle.seek_to(SeekFrom::Start(start))?;
+                handle.frame = current;
+                Err(err)
+            }
+        }
+    }
+
+    /// Get the index and offset of the first frame with a time greater than
+    /// or equal to `time`, see `seek_to_time`. This moves the position of
+    /// `handle`.
+    fn find_time(
+        &mut self,
+        handle: &mut XDRFile,
+        time: f32,
+        skip_frame: SkipFrame,
+    ) -> Result<(usize, u64)> {
+        // The first two frames give the frame size for the bisection
+        let uniform = match self.entry(handle, 1, skip_frame) {
+            Ok(_) => true,
+            Err(err) if err.is_eof() => false,
+            Err(err) => return Err(err),
+        };
+        let frame = self.entries.partition_point(|e| e.time < time);
+        if let Some(entry) = self.entries.get(frame) {
+            return Ok((frame, entry.offset));
+        }
+        if uniform {
+            if let Some(found) = self.bisect_uniform(handle, time, skip_frame)? {
+                return Ok(found);
+            }
+        }
+        let frame = self.find(handle, skip_frame, |e| e.time >= time)?;
+        Ok((frame, self.entries[frame].offset))
+    }
+
+    /// Bisect the frames after the known ones by their offsets, assuming
+    /// that all frames have the size of the first one. Only the headers of
+    /// the frames tried are read and they are not added to the index.
+    ///
+    /// Returns None if the frames turn out to differ in size, and an end of
+    /// file error if no frame matches. This moves the position of `handle`.
+    fn bisect_uniform(
+        &mut self,
+        handle: &mut XDRFile,
+        time: f32,
+        skip_frame: SkipFrame,
+    ) -> Result<Option<(usize, u64)>> {
+        let first = self.entries[0];
+        let size = self.entries[1].offset - first.offset;
+        let len = handle.seek_to(SeekFrom::End(0))?;
+        let known = self.entries.len() as u64;
+        if self.end != first.offset + known * size || !(len - first.offset).is_multiple_of(size) {
+            return Ok(None);
+        }
+
+        let (mut low, mut high) = (known, (len - first.offset) / size);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let offset = first.offset + mid * size;
+            handle.seek_to(SeekFrom::Start(offset))?;
+            let entry = match skip_frame(handle) {
+                Ok(entry) if handle.tell().ok() == Some(offset + size) => entry,
+                _ => return Ok(None),
+            };
+            if entry.num_atoms != first.num_atoms {
+                return Ok(None);
+            }
+            if entry.time >= time {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        if low * size == len - first.offset {
+            return Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Seek)));
+        }
+        Ok(Some((low as usize, first.offset + low * size)))
+    }
+
+    /// Get the number of atoms from the header of the first frame, without
+    /// moving the position of `handle`
+    pub fn num_atoms(&mut self, handle: &mut XDRFile, skip_frame: SkipFrame) -> Result<usize> {
+        // Looking at the first header is not worth reporting as progress
+        let progress = handle.progress.take();
+        let entry = handle.restoring_position(|handle| self.entry(handle, 0, skip_frame));
+        handle.progress = progress;
+        entry.map(|entry| entry.num_atoms).map_err(|err| match err {
+            Error::CApiError { code, .. } => Error::from((code, ErrorTask::ReadNumAtoms)),
+            err => err,
+        })
+    }
+
+    /// Get the entries of all frames of the file, without moving the
+    /// position of `handle`
+    pub fn entries(
+        &mut self,
+        handle: &mut XDRFile,
+        skip_frame: SkipFrame,
+    ) -> Result<&[IndexEntry]> {
+        self.num_frames(handle, skip_frame)?;
+        Ok(&self.entries)
+    }
+
+    /// Count the frames of the file by skipping over all of them, without
+    /// moving the position of `handle`
+    pub fn num_frames(&mut self, handle: &mut XDRFile, skip_frame: SkipFrame) -> Result<usize> {
+        handle.restoring_position(|handle| loop {
+            match self.visit_next(handle, skip_frame) {
+                Ok(_) => {}
+                Err(err) if err.is_eof() => return Ok(self.entries.len()),
+                Err(err) => return Err(err),
+            }
+        })
+    }
+
+    /// Find the last frame of the file by searching backwards from the end of
+    /// the file for a frame that ends exactly there. This moves the position
+    /// of `handle`.
+    ///
+    /// Frames start at multiples of 4 bytes with the same magic number as
+    /// the first frame, so only those positions are tried. If no such frame
+    /// is found, e.g. because the file is truncated, the whole file is
+    /// scanned instead.
+    pub fn last_entry(
+        &mut self,
+        handle: &mut XDRFile,
+        skip_frame: SkipFrame,
+    ) -> Result<IndexEntry> {
+        let (first, magic) = self.first_magic(handle, skip_frame)?;
+        let end = handle.seek_to(SeekFrom::End(0))?;
+
+        let mut pos = end - end % 4;
+        while pos > self.end {
+            pos -= 4;
+            handle.seek_to(SeekFrom::Start(pos))?;
+            if handle.read_int() != Some(magic) {
+                continue;
+            }
+            handle.seek_to(SeekFrom::Start(pos))?;
+            match skip_frame(handle) {
+                Ok(entry)
+                    if handle.tell().ok() == Some(end) && entry.num_atoms == first.num_atoms =>
+                {
+                    return Ok(entry)
+                }
+                _ => {}
+            }
+        }
+
+        let num_frames = self.num_frames(handle, skip_frame)?;
+        match num_frames.checked_sub(1) {
+            Some(last) => self.entry(handle, last, skip_frame),
+            None => Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Seek))),
+        }
+    }
+
+    /// After reading the frame at `start` failed with `err`, move `handle`
+    /// to the next valid frame if it is in resync mode. Returns the error to
+    /// report, which describes the skipped region if the frame was skipped.
+    ///
+    /// Only errors of the C API while decoding are recovered from. If the
+    /// search for the next frame fails, `err` is returned unchanged.
+    pub fn skip_corrupt(
+        &mut self,
+        handle: &mut XDRFile,
+        start: u64,
+        err: Error,
+        skip_frame: SkipFrame,
+    ) -> Error {
+        if !handle.resync || !matches!(err, Error::InFrame { .. }) {
+            return err;
+        }
+        let next = self
+            .next_valid(handle, start, skip_frame)
+            .and_then(|end| handle.seek_to(SeekFrom::Start(end)));
+        match next {
+            Ok(end) => Error::CorruptRegion {
+                start,
+                end,
+                source: Box::new(err),
+            },
+            Err(_) => err,
+        }
+    }
+
+    /// Find the start of the first valid frame after `start`, or the end of
+    /// the file if there is none. This moves the position of `handle`.
+    ///
+    /// Like in `last_entry`, only positions at multiples of 4 bytes with the
+    /// magic number of the first frame are tried. A frame is only accepted
+    /// if it is followed by the end of the file or another magic number, so
+    /// a magic number within compressed data is unlikely to be mistaken for
+    /// a frame.
+    fn next_valid(
+        &mut self,
+        handle: &mut XDRFile,
+        start: u64,
+        skip_frame: SkipFrame,
+    ) -> Result<u64> {
+        let (first, magic) = self.first_magic(handle, skip_frame)?;
+        let end = handle.seek_to(SeekFrom::End(0))?;
+
+        let mut pos = start - start % 4 + 4;
+        while pos + 4 <= end {
+            handle.seek_to(SeekFrom::Start(pos))?;
+            if handle.read_int() == Some(magic) {
+                handle.seek_to(SeekFrom::Start(pos))?;
+                if let Ok(entry) = skip_frame(handle) {
+                    let next = handle.tell()?;
+                    if entry.num_atoms == first.num_atoms
+                        && (next == end || handle.read_int() == Some(magic))
+                    {
+                        return Ok(pos);
+                    }
+                }
+            }
+            pos += 4;
+        }
+        Ok(end)
+    }
+
+    /// Get the entry and the magic number of the first frame. This moves the
+    /// position of `handle`.
+    fn first_magic(
+        &mut self,
+        handle: &mut XDRFile,
+        skip_frame: SkipFrame,
+    ) -> Result<(IndexEntry, c_int)> {
+        let first = self.entry(handle, 0, skip_frame)?;
+        handle.seek_to(SeekFrom::Start(first.offset))?;
+        let magic = handle
+            .read_int()
+            .ok_or_else(|| Error::from((ErrorCode::ExdrInt, ErrorTask::Seek)))?;
+        Ok((first, magic))
+    }
+
+    /// Visit all frames of the file and save the index to `path`, together
+    /// with the size and modification time of the trajectory file
+    pub fn save(&mut self, handle: &mut XDRFile, skip_frame: SkipFrame, path: &Path) -> Result<()> {
+        self.num_frames(handle, skip_frame)?;
+        let stamp = FileStamp::of(handle, path)?;
+        let io_err = |err| Error::from((path, FileMode::Write, err));
+
+        let mut writer = BufWriter::new(File::create(path).map_err(io_err)?);
+        let mut header = INDEX_MAGIC.to_vec();
+        for value in [stamp.len, stamp.secs, u64::from(stamp.nanos)] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+        header.extend_from_slice(&self.end.to_le_bytes());
+        writer.write_all(&header).map_err(io_err)?;
+        for entry in &self.entries {
+            let mut bytes = [0; 28];
+            bytes[0..8].copy_from_slice(&entry.offset.to_le_bytes());
+            bytes[8..16].copy_from_slice(&(entry.num_atoms as u64).to_le_bytes());
+            bytes[16..24].copy_from_slice(&(entry.step as u64).to_le_bytes());
+            bytes[24..28].copy_from_slice(&entry.time.to_le_bytes());
+            writer.write_all(&bytes).map_err(io_err)?;
+        }
+        writer.flush().map_err(io_err)
+    }
+
+    /// Load an index saved with `save`. Returns None if the size or the
+    /// modification time of the trajectory file changed since.
+    pub fn load(handle: &XDRFile, path: &Path) -> Result<Option<TrajectoryIndex>> {
+        let stamp = FileStamp::of(handle, path)?;
+        let io_err = |err: io::Error| match err.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => Error::InvalidIndex {
+                path: path.to_owned(),
+                reason: "malformed index file",
+            },
+            _ => Error::from((path, FileMode::Read, err)),
+        };
+
+        let mut reader = BufReader::new(File::open(path).map_err(io_err)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).map_err(io_err)?;
+        if &magic != INDEX_MAGIC {
+            return Err(io_err(io::ErrorKind::InvalidData.into()));
+        }
+        let mut read_u64 = || -> Result<u64> {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes).map_err(io_err)?;
+            Ok(u64::from_le_bytes(bytes))
+        };
+        let saved = FileStamp {
+            len: read_u64()?,
+            secs: read_u64()?,
+            nanos: read_u64()? as u32,
+        };
+        if saved != stamp {
+            return Ok(None);
+        }
+        let num_entries = read_u64()?;
+        let end = read_u64()?;
+
+        let mut entries = Vec::new();
+        for _ in 0..num_entries {
+            let mut bytes = [0; 28];
+            reader.read_exact(&mut bytes).map_err(io_err)?;
+            let u64_at = |i: usize| {
+                let mut value = [0; 8];
+                value.copy_from_slice(&bytes[i..i + 8]);
+                u64::from_le_bytes(value)
+            };
+            let mut time = [0; 4];
+            time.copy_from_slice(&bytes[24..28]);
+            entries.push(IndexEntry {
+                offset: u64_at(0),
+                num_atoms: u64_at(8) as usize,
+                step: u64_at(16) as usize,
+                time: f32::from_le_bytes(time),
+            });
+        }
+        Ok(Some(TrajectoryIndex::from_entries(entries, end)))
+    }
+
+    /// Skip over the first frame after the known ones and record it
+    fn visit_next(&mut self, handle: &mut XDRFile, skip_frame: SkipFrame) -> Result<IndexEntry> {
+        handle.seek_to(SeekFrom::Start(self.end))?;
+        let entry = skip_frame(handle).map_err(|err| handle.stream_error(err))?;
+        self.entries.push(entry);
+        self.end = handle.tell()?;
+        handle.report_progress();
+        Ok(entry)
+    }
+}
+
+/// Size and modification time of a trajectory file, to detect whether an
+/// index file is outdated
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileStamp {
+    len: u64,
+    secs: u64,
+    nanos: u32,
+}
+
+impl FileStamp {
+    /// Get the stamp of the file of `handle`. Index files at `index_path`
+    /// are only supported for trajectories on disk.
+    fn of(handle: &XDRFile, index_path: &Path) -> Result<FileStamp> {
+        let path = handle.path.as_ref().ok_or_else(|| Error::InvalidIndex {
+            path: index_path.to_owned(),
+            reason: "trajectory is not a file on disk",
+        })?;
+        let io_err = |err| Error::from((path.as_path(), FileMode::Read, err));
+        let metadata = std::fs::metadata(path).map_err(io_err)?;
+        let modified = metadata.modified().map_err(io_err)?;
+        let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+        Ok(FileStamp {
+            len: metadata.len(),
+            secs: since_epoch.as_secs(),
+            nanos: since_epoch.subsec_nanos(),
+        })
+    }
+}
+
+/// Bytes read from the start of a file opened for writing to find its
+/// number of atoms, more than the header of any XTC or TRR frame
+const HEAD_SIZE: usize = 1024;
+
+/// Get the number of atoms from the header of the first frame of a file
+/// opened for writing or appending. The XDR handle cannot read in these
+/// modes, so the start of the file or stream is read separately.
+pub(crate) fn num_atoms_written(handle: &mut XDRFile, skip_frame: SkipFrame) -> Result<usize> {
+    let head = handle.read_head(HEAD_SIZE)?;
+    let (stream, io) = Stream::reader(io::Cursor::new(head));
+    let mut reader = XDRFile::open_stream(stream, io, FileMode::Read);
+    TrajectoryIndex::default().num_atoms(&mut reader, skip_frame)
+}
+
+/// Skip over the XTC frame at the current position without decompressing it
+pub(crate) fn skip_xtc_frame(handle: &mut XDRFile) -> Result<IndexEntry> {
+    let err = |code| Error::from((code, ErrorTask::Seek));
+    let offset = handle.tell()?;
+
+    match handle.read_int() {
+        Some(XTC_MAGIC) => {}
+        Some(_) => return Err(err(ErrorCode::ExdrMagic)),
+        None => return Err(err(ErrorCode::ExdrEndOfFile)),
+    }
+    let num_atoms = handle.read_int().ok_or_else(|| err(ErrorCode::ExdrInt))?;
+    let step = handle.read_int().ok_or_else(|| err(ErrorCode::ExdrInt))?;
+    let time = handle
+        .read_float()
+        .ok_or_else(|| err(ErrorCode::ExdrFloat))?;
+    handle.seek_to(SeekFrom::Current(XTC_BOX_SIZE))?;
+
+    let num_coords = handle.read_int().ok_or_else(|| err(ErrorCode::ExdrInt))?;
+    let num_coords: u32 = to(num_coords, ErrorTask::Seek, "num_coords")?;
+    if num_coords <= 9 {
+        // Small systems are stored uncompressed
+        handle.seek_to(SeekFrom::Current(i64::from(num_coords) * 3 * 4))?;
+    } else {
+        handle.seek_to(SeekFrom::Current(XTC_COMPRESSION_HEADER))?;
+        let num_bytes = handle.read_int().ok_or_else(|| err(ErrorCode::ExdrInt))?;
+        let num_bytes: u32 = to(num_bytes, ErrorTask::Seek, "num_bytes")?;
+        // XDR pads opaque data to a multiple of 4 bytes
+        let padded = (i64::from(num_bytes) + 3) / 4 * 4;
+        handle.seek_to(SeekFrom::Current(padded))?;
+    }
+
+    Ok(IndexEntry {
+        offset,
+        num_atoms: to(num_atoms, ErrorTask::Seek, "num_atoms")?,
+        step: to(step, ErrorTask::Seek, "step")?,
+        time,
+    })
+}
+
+/// Read the header of the TRR frame at the current position
+pub(crate) fn read_trr_header(
+    handle: &mut XDRFile,
+    task: ErrorTask,
+) -> Result<xdrfile_trr::t_trnheader> {
+    let mut header = xdrfile_trr::t_trnheader::default();
+    let code = unsafe { xdrfile_trr::do_trnheader(handle.xdrfile, 1, &mut header) };
+    match check_code(code, task) {
+        None => Ok(header),
+        Some(err) => Err(err),
+    }
+}
+
+/// Skip over the TRR frame at the current position without reading its data
+pub(crate) fn skip_trr_frame(handle: &mut XDRFile) -> Result<IndexEntry> {
+    let offset = handle.tell()?;
+    let header = read_trr_header(handle, ErrorTask::Seek)?;
+
+    let body_size: i64 = [
+        header.box_size,
+        header.vir_size,
+        header.pres_size,
+        header.x_size,
+        header.v_size,
+        header.f_size,
+    ]
+    .iter()
+    .map(|&size| i64::from(size))
+    .sum();
+    handle.seek_to(SeekFrom::Current(body_size))?;
+
+    Ok(IndexEntry {
+        offset,
+        num_atoms: to(header.natoms, ErrorTask::Seek, "num_atoms")?,
+        step: to(header.step, ErrorTask::Seek, "step")?,
+        time: header.tf,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_offsets() -> Result<()> {
+        let mut handle = XDRFile::open("tests/1l2y.trr", FileMode::Read)?;
+        let mut index = TrajectoryIndex::default();
+
+        assert_eq!(index.entry(&mut handle, 0, skip_trr_frame)?.offset, 0);
+        let second = index.entry(&mut handle, 1, skip_trr_frame)?;
+        let third = index.entry(&mut handle, 2, skip_trr_frame)?;
+        // TRR frames of the same system all have the same size
+        assert_eq!(third.offset, 2 * second.offset);
+        assert_eq!(second.step, 2);
+        assert_eq!(third.step, 3);
+        assert_eq!(index.entries.len(), 3);
+
+        // Entries of visited frames are not recomputed
+        assert_eq!(index.entry(&mut handle, 1, skip_trr_frame)?, second);
+        assert_eq!(index.entries.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_last_entry() -> Result<()> {
+        for (path, skip_frame) in [
+            ("tests/1l2y.xtc", skip_xtc_frame as SkipFrame),
+            ("tests/1l2y.trr", skip_trr_frame),
+        ] {
+            let mut handle = XDRFile::open(path, FileMode::Read)?;
+            let mut index = TrajectoryIndex::default();
+            let last = index.last_entry(&mut handle, skip_frame)?;
+            assert_eq!(last.step, 38);
+            // Only the first frame was visited to find the last one
+            assert_eq!(index.entries.len(), 1);
+            assert_eq!(index.entry(&mut handle, 37, skip_frame)?, last);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_find() -> Result<()> {
+        let mut handle = XDRFile::open("tests/1l2y.xtc", FileMode::Read)?;
+        let mut index = TrajectoryIndex::default();
+
+        assert_eq!(index.find(&mut handle, skip_xtc_frame, |e| e.step == 5)?, 4);
+        assert_eq!(index.entries.len(), 5);
+        assert_eq!(index.find(&mut handle, skip_xtc_frame, |e| e.step == 2)?, 1);
+        assert_eq!(index.entries.len(), 5);
+
+        let result = index.find(&mut handle, skip_xtc_frame, |_| false);
+        assert!(result.unwrap_err().is_eof());
+        assert_eq!(index.entries.len(), 38);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_seek_to_time() -> Result<()> {
+        // TRR frames of the same system are bisected by their offsets
+        let mut handle = XDRFile::open("tests/1l2y.trr", FileMode::Read)?;
+        let mut index = TrajectoryIndex::default();
+        index.seek_to_time(&mut handle, 29.5, skip_trr_frame)?;
+        assert_eq!(handle.frame, Some(29));
+        assert_eq!(skip_trr_frame(&mut handle)?.time, 30.0);
+        assert_eq!(index.entries.len(), 2);
+
+        // The position is kept if no frame matches
+        let pos = handle.tell()?;
+        let result = index.seek_to_time(&mut handle, 38.5, skip_trr_frame);
+        assert!(result.unwrap_err().is_eof());
+        assert_eq!(handle.tell()?, pos);
+        index.seek_to_time(&mut handle, 38.0, skip_trr_frame)?;
+        assert_eq!(handle.frame, Some(37));
+
+        // XTC frames differ in size, so they are scanned once and bisected
+        // afterwards
+        let mut handle = XDRFile::open("tests/1l2y.xtc", FileMode::Read)?;
+        let mut index = TrajectoryIndex::default();
+        index.seek_to_time(&mut handle, 10.0, skip_xtc_frame)?;
+        assert_eq!(handle.frame, Some(9));
+        assert_eq!(index.entries.len(), 10);
+        index.num_frames(&mut handle, skip_xtc_frame)?;
+        index.seek_to_time(&mut handle, 3.5, skip_xtc_frame)?;
+        assert_eq!(handle.frame, Some(3));
+        assert_eq!(skip_xtc_frame(&mut handle)?.step, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_xtc_frame() -> Result<()> {
+        let mut handle = XDRFile::open("tests/1l2y.xtc", FileMode::Read)?;
+        for _ in 0..38 {
+            skip_xtc_frame(&mut handle)?;
+        }
+        let result = skip_xtc_frame(&mut handle);
+        assert!(result.unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_small_xtc_frame() -> Result<()> {
+        // Systems with up to 9 atoms are stored without compression
+        let tempfile = tempfile::NamedTempFile::new().expect("Could not create temporary file");
+        let mut frame = Frame::with_len(2);
+        let mut traj = XTCTrajectory::open_write(tempfile.path())?;
+        for step in 0..3 {
+            frame.step = step;
+            traj.write(&frame)?;
+        }
+        traj.flush()?;
+
+        let mut handle = XDRFile::open(tempfile.path(), FileMode::Read)?;
+        for _ in 0..3 {
+            skip_xtc_frame(&mut handle)?;
+        }
+        assert!(skip_xtc_frame(&mut handle).unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_trr_frame() -> Result<()> {
+        let mut handle = XDRFile::open("tests/1l2y.trr", FileMode::Read)?;
+        for _ in 0..38 {
+            skip_trr_frame(&mut handle)?;
+        }
+        let result = skip_trr_frame(&mut handle);
+        assert!(result.unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_wrong_format() -> Result<()> {
+        let mut handle = XDRFile::open("tests/1l2y.trr", FileMode::Read)?;
+        let result = skip_xtc_frame(&mut handle);
+        assert_eq!(Some(ErrorCode::ExdrMagic), result.unwrap_err().code());
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_file() -> Result<()> {
+        let trajectory = tempfile::NamedTempFile::new().expect("Could not create temporary file");
+        std::fs::copy("tests/1l2y.xtc", trajectory.path()).expect("Could not copy test file");
+        let index_file = tempfile::NamedTempFile::new().expect("Could not create temporary file");
+
+        let mut traj = XTCTrajectory::open_read(trajectory.path())?;
+        traj.save_index(index_file.path())?;
+        assert_eq!(traj.index.entries.len(), 38);
+
+        let mut reopened = XTCTrajectory::open_read(trajectory.path())?;
+        assert!(reopened.load_index(index_file.path())?);
+        assert_eq!(reopened.index.entries, traj.index.entries);
+        assert_eq!(reopened.index.end, traj.index.end);
+        let mut frame = Frame::with_len(304);
+        reopened.seek_to_frame(20)?;
+        reopened.read(&mut frame)?;
+        assert_eq!(frame.step, 21);
+
+        // The index is outdated once the trajectory changes
+        let mut traj = XTCTrajectory::open_append(trajectory.path())?;
+        traj.write(&frame)?;
+        traj.close()?;
+        let mut reopened = XTCTrajectory::open_read(trajectory.path())?;
+        assert!(!reopened.load_index(index_file.path())?);
+        assert!(reopened.index.entries.is_empty());
+
+        // Other files are rejected
+        let err = reopened.load_index("tests/1l2y.xtc").unwrap_err();
+        assert!(matches!(err, Error::InvalidIndex { .. }));
+        Ok(())
+    }
+}
@@ -1,8 +1,14 @@
 use crate::*;
+use std::mem;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::task::Poll;
+use std::time::Duration;
 
-fn into_iter_inner<T: Trajectory>(mut traj: T) -> TrajectoryIterator<T> {
+pub(crate) fn into_iter_inner<T: Trajectory>(mut traj: T) -> TrajectoryIterator<T> {
     let num_atoms = traj.get_num_atoms();
+    // Frames that were read before are not yielded again
+    let next_frame = traj.current_frame().unwrap_or(0);
     let frame = match &num_atoms {
         Ok(num_atoms) => Frame::with_len(*num_atoms),
         Err(_) => Frame::new(),
@@ -10,7 +16,12 @@ fn into_iter_inner<T: Trajectory>(mut traj: T) -> TrajectoryIterator<T> {
     TrajectoryIterator {
         trajectory: traj,
         item: Rc::new(frame),
-        has_error: false,
+        done: false,
+        stride: 1,
+        next_frame,
+        retry: None,
+        retries: 0,
+        reread: false,
     }
 }
 
@@ -32,17 +43,179 @@ impl IntoIterator for TRRTrajectory {
     }
 }
 
+impl<T: Trajectory> IntoIterator for ChainedTrajectory<T> {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<ChainedTrajectory<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+impl<T: Trajectory> IntoIterator for TransformedTrajectory<T> {
+    type Item = Result<Rc<Frame>>;
+    type IntoIter = TrajectoryIterator<TransformedTrajectory<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        into_iter_inner(self)
+    }
+}
+
+/// How a `TrajectoryIterator` waits for frames that are not written yet,
+/// see `TrajectoryIterator::retry_on_eof`.
+///
+/// The delay starts at `initial_delay` and doubles after every retry without
+/// new frames, up to `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Number of times to wait for new frames in a row before stopping
+    pub max_retries: usize,
+    /// Delay before the first retry
+    pub initial_delay: Duration,
+    /// Longest delay between retries
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// 10 retries starting at 100 ms, with delays of at most 5 s
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 10,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Delay before the retry following `retries` unsuccessful ones
+    fn delay(&self, retries: usize) -> Duration {
+        let factor = 2u32.saturating_pow(u32::try_from(retries).unwrap_or(u32::MAX));
+        self.initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay)
+    }
+}
+
 /// Iterator for trajectories.
 /// This iterator yields a Result<Frame, Error> for each frame in the
 /// trajectory file and stops with yielding None once the trajectory is
-/// EOF. Also yields None after the first occurrence of an error
+/// EOF. Also yields None after the first occurrence of an error, except for
+/// corrupt regions skipped in recovery mode (see `XTCTrajectory::set_resync`)
+///
+/// The frames are not counted when the iterator is created, so that the
+/// first frame is available right away. `size_hint` reports the number of
+/// remaining frames only if the trajectory already knows its number of
+/// frames, e.g. because `num_frames` was called before.
+///
+/// To read a trajectory while it is being written, e.g. by a running
+/// simulation, use `retry_on_eof` to wait for new frames at the end instead
+/// of stopping.
 pub struct TrajectoryIterator<T> {
     trajectory: T,
     item: Rc<Frame>,
-    has_error: bool,
+    /// Set once the end of the trajectory or an error was reached
+    done: bool,
+    stride: usize,
+    /// Index of the next frame to read
+    next_frame: usize,
+    /// Waits for new frames at the end if set, see `retry_on_eof`
+    retry: Option<RetryPolicy>,
+    /// Number of retries since the last frame was read
+    retries: usize,
+    /// Set when the next frame is read again because it was completed
+    /// after reading it failed
+    reread: bool,
 }
 
 impl<T: Trajectory> TrajectoryIterator<T> {
+    /// Only yield every `n`-th frame, starting with the next frame of the
+    /// trajectory. Frames in between are skipped by seeking over them, so
+    /// their coordinates are never decompressed.
+    ///
+    /// # Panics
+    /// Panics if `n` is zero.
+    pub fn stride(mut self, n: usize) -> Self {
+        assert!(n > 0, "stride must be at least 1");
+        self.stride = n;
+        self
+    }
+
+    /// Treat the end of the trajectory as "no data yet" and wait for new
+    /// frames as configured by `policy`, before stopping like at the end
+    /// of a complete trajectory.
+    ///
+    /// Besides the end of the file, a frame that cannot be read because it
+    /// extends past the end of the file counts as not written yet. The
+    /// iterator waits for the rest of the frame and reads it again from its
+    /// start. If no new frame appears after `policy.max_retries` retries,
+    /// the iterator stops, or yields the error of the incomplete frame.
+    ///
+    /// As frames may be added, `size_hint` has no upper bound anymore. The
+    /// first frame must be complete when the iterator is created, so that
+    /// the number of atoms is known.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    /// use xdrfile::*;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let policy = RetryPolicy {
+    ///         max_retries: 2,
+    ///         initial_delay: Duration::from_millis(1),
+    ///         ..Default::default()
+    ///     };
+    ///     let trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+    ///     let frames = trajectory.into_iter().retry_on_eof(policy);
+    ///     assert_eq!(frames.count(), 38);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn retry_on_eof(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Go back to the first frame of the trajectory, so that it is iterated
+    /// again from the start, even after the end or an error was reached
+    pub fn rewind(&mut self) -> Result<()> {
+        self.trajectory.rewind()?;
+        self.done = false;
+        self.next_frame = 0;
+        Ok(())
+    }
+
+    /// Yield owned frames instead of shared ones, see `FrameIterator`
+    pub fn into_frames(self) -> FrameIterator<T> {
+        FrameIterator { inner: self }
+    }
+
+    /// Yield frames behind an `Arc` instead of an `Rc`, see
+    /// `ArcFrameIterator`
+    pub fn into_iter_arc(self) -> ArcFrameIterator<T> {
+        let num_atoms = self.item.len();
+        ArcFrameIterator {
+            inner: self,
+            item: Arc::new(Frame::with_len(num_atoms)),
+        }
+    }
+
+    /// Average the coordinates over a sliding window of the last `window`
+    /// frames, see `SmoothedFrames`
+    ///
+    /// # Panics
+    /// Panics if `window` is zero.
+    pub fn smooth(self, window: usize) -> SmoothedFrames<Self> {
+        SmoothedFrames::new(self, window)
+    }
+
+    /// Take the frame that was read last, leaving a new buffer in its place
+    pub(crate) fn take_item(&mut self) -> Frame {
+        let num_atoms = self.item.len();
+        let item = mem::replace(&mut self.item, Rc::new(Frame::with_len(num_atoms)));
+        Rc::try_unwrap(item).unwrap_or_else(|item| (*item).clone())
+    }
+
     /// Inner function for `next()`  to seperate error handling from iteration logic
     fn next_inner(&mut self) -> <Self as Iterator>::Item {
         // If we couldn't read the number of frames when we called into_iter, return that error now
@@ -57,14 +230,99 @@ impl<T: Trajectory> TrajectoryIterator<T> {
             Some(item) => item,
             None => {
                 // caller kept frame. Create new one
-                self.item = Rc::new(Frame::with_len(num_atoms as usize));
+                self.item = Rc::new(Frame::with_len(num_atoms));
                 Rc::get_mut(&mut self.item).expect("Could not get mutable access to new Rc")
             }
         };
 
+        if self.stride > 1 {
+            self.trajectory.seek_to_frame(self.next_frame)?;
+        }
         self.trajectory.read(item)?;
+        self.next_frame += self.stride;
         Ok(Rc::clone(&self.item))
     }
+
+    /// Read the next frame like `next`, but return `Poll::Pending` instead
+    /// of waiting if it is not completely written yet and the retry policy
+    /// allows waiting for it. The trajectory is then at the start of the
+    /// frame, so that reading it can be tried again.
+    pub(crate) fn poll_frame(&mut self) -> Poll<Option<Result<Rc<Frame>>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        match self.next_inner() {
+            Ok(item) => {
+                self.retries = 0;
+                self.reread = false;
+                Poll::Ready(Some(Ok(item)))
+            }
+            Err(e @ Error::CorruptRegion { .. }) => Poll::Ready(Some(Err(e))),
+            Err(e) => match self.frame_pending(&e) {
+                Some(true) => Poll::Pending,
+                // The frame may have been completed since reading it
+                // failed, so it is read once more before it is reported
+                Some(false) if !self.reread => {
+                    self.reread = true;
+                    self.poll_frame()
+                }
+                _ => {
+                    self.done = true;
+                    Poll::Ready(if e.is_eof() { None } else { Some(Err(e)) })
+                }
+            },
+        }
+    }
+
+    /// Check whether reading the next frame failed with `err` because it is
+    /// not completely written yet. Returns None if the retry policy does
+    /// not allow waiting for it or the frame cannot be found.
+    fn frame_pending(&mut self, err: &Error) -> Option<bool> {
+        match self.retry {
+            Some(policy) if self.retries < policy.max_retries => {}
+            _ => return None,
+        }
+        if err.is_eof() {
+            self.seek_to_next_frame().ok()?;
+            Some(true)
+        } else {
+            self.frame_incomplete()
+        }
+    }
+
+    /// Sleep before trying to read a pending frame again
+    fn wait(&mut self) {
+        if let Some(policy) = self.retry {
+            std::thread::sleep(policy.delay(self.retries));
+        }
+        self.retries += 1;
+    }
+
+    /// Check whether the next frame extends past the end of the file, so
+    /// that it is probably still being written. Afterwards, the trajectory
+    /// is at the start of the next frame. Returns None if the start of the
+    /// frame cannot be found.
+    fn frame_incomplete(&mut self) -> Option<bool> {
+        self.seek_to_next_frame().ok()?;
+        let incomplete =
+            self.trajectory.skip(1).is_err() || self.trajectory.is_at_end().unwrap_or(false);
+        self.seek_to_next_frame().ok()?;
+        Some(incomplete)
+    }
+
+    /// Move the trajectory to the start of the next frame. The frame is
+    /// reached from the frame before it, as its own header may be
+    /// incomplete.
+    fn seek_to_next_frame(&mut self) -> Result<()> {
+        match self.next_frame.checked_sub(1) {
+            Some(previous) => {
+                self.trajectory.seek_to_frame(previous)?;
+                self.trajectory.skip(1)
+            }
+            None => self.trajectory.rewind(),
+        }
+    }
 }
 
 impl<T> Iterator for TrajectoryIterator<T>
@@ -74,15 +332,315 @@ where
     type Item = Result<Rc<Frame>>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.has_error {
+        loop {
+            match self.poll_frame() {
+                Poll::Ready(item) => return item,
+                Poll::Pending => self.wait(),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.trajectory.known_num_frames() {
+            _ if self.done => (0, Some(0)),
+            Some(num_frames) => {
+                let remaining = num_frames
+                    .saturating_sub(self.next_frame)
+                    .div_ceil(self.stride);
+                // Frames written while waiting are not counted
+                let upper = if self.retry.is_some() {
+                    None
+                } else {
+                    Some(remaining)
+                };
+                (remaining, upper)
+            }
+            None => (0, None),
+        }
+    }
+}
+
+/// Iterator over the frames of a trajectory that yields owned frames.
+///
+/// Each frame is read into a new buffer that is handed out directly, so
+/// frames never have to be cloned. Created with `Trajectory::into_frames` or
+/// `TrajectoryIterator::into_frames`.
+pub struct FrameIterator<T> {
+    inner: TrajectoryIterator<T>,
+}
+
+impl<T: Trajectory> FrameIterator<T> {
+    /// Average the coordinates over a sliding window of the last `window`
+    /// frames, see `SmoothedFrames`
+    ///
+    /// # Panics
+    /// Panics if `window` is zero.
+    pub fn smooth(self, window: usize) -> SmoothedFrames<Self> {
+        SmoothedFrames::new(self, window)
+    }
+}
+
+impl<T: Trajectory> Iterator for FrameIterator<T> {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.inner.next()?;
+        Some(result.map(|shared| {
+            // Release our reference, so the frame is usually owned by the
+            // inner iterator alone and can be taken without cloning it
+            drop(shared);
+            self.inner.take_item()
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Iterator over the frames of a trajectory that yields frames behind an
+/// `Arc`, so that they can be sent to other threads, e.g. to analyze them in
+/// a thread pool. Created with `Trajectory::into_iter_arc` or
+/// `TrajectoryIterator::into_iter_arc`.
+///
+/// Like `TrajectoryIterator`, the buffer of a frame is reused for the next
+/// frame once all clones of the `Arc` were dropped, so frames are only
+/// allocated while other threads hold on to them.
+///
+/// ```rust
+/// use std::sync::mpsc;
+/// use std::thread;
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let (sender, receiver) = mpsc::channel();
+///     let worker = thread::spawn(move || {
+///         receiver.iter().map(|frame: std::sync::Arc<Frame>| frame.step).sum::<usize>()
+///     });
+///     for frame in XTCTrajectory::open_read("tests/1l2y.xtc")?.into_iter_arc() {
+///         sender.send(frame?).unwrap();
+///     }
+///     drop(sender);
+///     assert_eq!(worker.join().unwrap(), (1..=38).sum::<usize>());
+///     Ok(())
+/// }
+/// ```
+pub struct ArcFrameIterator<T> {
+    inner: TrajectoryIterator<T>,
+    item: Arc<Frame>,
+}
+
+impl<T: Trajectory> Iterator for ArcFrameIterator<T> {
+    type Item = Result<Arc<Frame>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.inner.next()?;
+        Some(result.map(|shared| {
+            drop(shared);
+            // Swap the buffers if no other thread holds the last frame, so
+            // the inner iterator reads the next frame into it
+            let swapped = match (
+                Arc::get_mut(&mut self.item),
+                Rc::get_mut(&mut self.inner.item),
+            ) {
+                (Some(item), Some(read)) => {
+                    mem::swap(item, read);
+                    true
+                }
+                _ => false,
+            };
+            if !swapped {
+                self.item = Arc::new(self.inner.take_item());
+            }
+            Arc::clone(&self.item)
+        }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+/// Iterator over the step, time and byte offset of every frame of a
+/// trajectory, created with `XTCTrajectory::iter_headers` or
+/// `TRRTrajectory::iter_headers`.
+///
+/// Only the headers are read: the coordinates of every frame are skipped by
+/// seeking over them, so neither memory for them is allocated nor are they
+/// decompressed. The headers are recorded in the frame index of the
+/// trajectory, so seeking and counting frames afterwards is instant. The
+/// position of the trajectory is not changed. Iteration stops after the
+/// first error.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let mut trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+///     let times: Vec<f32> = trj
+///         .iter_headers()
+///         .map(|header| header.map(|(_step, time, _offset)| time))
+///         .collect::<Result<_>>()?;
+///     assert_eq!(times.len(), 38);
+///     Ok(())
+/// }
+/// ```
+pub struct HeaderIterator<'a> {
+    handle: &'a mut XDRFile,
+    index: &'a mut TrajectoryIndex,
+    skip_frame: index::SkipFrame,
+    /// Index of the next frame
+    next_frame: usize,
+    /// Set once the end of the trajectory or an error was reached
+    done: bool,
+}
+
+impl<'a> HeaderIterator<'a> {
+    pub(crate) fn new(
+        handle: &'a mut XDRFile,
+        index: &'a mut TrajectoryIndex,
+        skip_frame: index::SkipFrame,
+    ) -> HeaderIterator<'a> {
+        HeaderIterator {
+            handle,
+            index,
+            skip_frame,
+            next_frame: 0,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for HeaderIterator<'_> {
+    type Item = Result<(usize, f32, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
             return None;
         }
+        let (index, frame, skip_frame) = (&mut *self.index, self.next_frame, self.skip_frame);
+        let entry = self
+            .handle
+            .restoring_position(|handle| index.entry(handle, frame, skip_frame));
+        match entry {
+            Ok(entry) => {
+                self.next_frame += 1;
+                Some(Ok((entry.step, entry.time, entry.offset)))
+            }
+            Err(e) => {
+                self.done = true;
+                if e.is_eof() {
+                    None
+                } else {
+                    Some(Err(e))
+                }
+            }
+        }
+    }
+}
 
-        match self.next_inner() {
-            Ok(item) => Some(Ok(item)),
-            Err(e) if e.is_eof() => None,
+/// Reads the frames of a trajectory one by one into a single reused buffer.
+///
+/// Unlike `TrajectoryIterator`, no `Rc` is involved: every frame is borrowed
+/// from the reader until the next one is read. Created with
+/// `Trajectory::frames_mut`.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let mut trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+///     let mut frames = trj.frames_mut();
+///     while let Some(frame) = frames.next()? {
+///         assert!(frame.step > 0);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct FrameReader<'a, T: ?Sized> {
+    trajectory: &'a mut T,
+    frame: Frame,
+}
+
+impl<'a, T: Trajectory + ?Sized> FrameReader<'a, T> {
+    pub(crate) fn new(trajectory: &'a mut T) -> FrameReader<'a, T> {
+        FrameReader {
+            trajectory,
+            frame: Frame::new(),
+        }
+    }
+
+    /// Read the next frame, None at the end of the trajectory
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Result<Option<&mut Frame>> {
+        let num_atoms = self
+            .trajectory
+            .get_num_atoms()
+            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
+        self.frame.resize(num_atoms);
+        match self.trajectory.read(&mut self.frame) {
+            Ok(()) => Ok(Some(&mut self.frame)),
+            Err(e) if e.is_eof() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Iterator over the frames of a trajectory in batches, for analyses that
+/// work on several frames at once. Created with `Trajectory::iter_chunks`.
+///
+/// Every chunk holds `chunk_size` frames, except for the last one, which
+/// holds the remaining frames. Iteration stops after the first error.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let mut trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+///     let sizes = trj
+///         .iter_chunks(16)
+///         .map(|chunk| chunk.map(|frames| frames.len()))
+///         .collect::<Result<Vec<_>>>()?;
+///     assert_eq!(sizes, vec![16, 16, 6]);
+///     Ok(())
+/// }
+/// ```
+pub struct ChunkIterator<'a, T: ?Sized> {
+    trajectory: &'a mut T,
+    chunk_size: usize,
+    /// Set once the end of the trajectory or an error was reached
+    done: bool,
+}
+
+impl<'a, T: Trajectory + ?Sized> ChunkIterator<'a, T> {
+    pub(crate) fn new(trajectory: &'a mut T, chunk_size: usize) -> ChunkIterator<'a, T> {
+        assert!(chunk_size > 0, "chunk size must be at least 1");
+        ChunkIterator {
+            trajectory,
+            chunk_size,
+            done: false,
+        }
+    }
+}
+
+impl<T: Trajectory + ?Sized> Iterator for ChunkIterator<'_, T> {
+    type Item = Result<Vec<Frame>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.trajectory.read_up_to(self.chunk_size) {
+            Ok(frames) => {
+                self.done = frames.len() < self.chunk_size;
+                if frames.is_empty() {
+                    None
+                } else {
+                    Some(Ok(frames))
+                }
+            }
             Err(e) => {
-                self.has_error = true;
+                self.done = true;
                 Some(Err(e))
             }
         }
@@ -99,7 +657,7 @@ mod tests {
         let frames: Result<Vec<Rc<Frame>>> = traj.into_iter().collect();
         let frames = frames?;
         assert!(frames.len() == 38);
-        assert!(frames[0].step == 1, frames[0].step);
+        assert!(frames[0].step == 1, "{}", frames[0].step);
         assert!(frames[37].step == 38);
         Ok(())
     }
@@ -110,8 +668,310 @@ mod tests {
         let frames: Result<Vec<Rc<Frame>>> = traj.into_iter().collect();
         let frames = frames?;
         assert!(frames.len() == 38);
-        assert!(frames[0].step == 1, frames[0].step);
+        assert!(frames[0].step == 1, "{}", frames[0].step);
         assert!(frames[37].step == 38);
         Ok(())
     }
+
+    #[test]
+    pub fn test_strided_iterator() -> Result<()> {
+        let traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let steps = traj
+            .into_iter()
+            .stride(5)
+            .map(|frame| frame.map(|f| f.step))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(steps, vec![1, 6, 11, 16, 21, 26, 31, 36]);
+
+        let traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let frames: Vec<Rc<Frame>> = traj.into_iter().stride(37).collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[1].step, 38);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_stride_after_read() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        traj.read_up_to(3)?;
+        let steps = traj
+            .into_iter()
+            .stride(2)
+            .map(|frame| frame.map(|f| f.step))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(steps, (4..=38).step_by(2).collect::<Vec<_>>());
+
+        let mut chain = ChainedTrajectory::new(vec![
+            XTCTrajectory::open_read("tests/1l2y.xtc")?,
+            XTCTrajectory::open_read("tests/1l2y.xtc")?,
+        ])?;
+        chain.skip(10)?;
+        assert_eq!(chain.current_frame(), Some(10));
+        let steps = chain
+            .into_iter()
+            .stride(2)
+            .map(|frame| frame.map(|f| f.step))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(steps, (11..=38).step_by(2).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_iterator_size_hint() -> Result<()> {
+        // Frames are not counted just for the size hint
+        let mut iter = XTCTrajectory::open_read("tests/1l2y.xtc")?.into_iter();
+        assert_eq!(iter.size_hint(), (0, None));
+        iter.next();
+        assert!(iter.trajectory.known_num_frames().is_none());
+
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert_eq!(traj.num_frames()?, 38);
+        let mut iter = traj.into_iter();
+        assert_eq!(iter.size_hint(), (38, Some(38)));
+        iter.next();
+        assert_eq!(iter.size_hint(), (37, Some(37)));
+        assert_eq!(iter.by_ref().count(), 37);
+        assert_eq!(iter.size_hint(), (0, Some(0)));
+
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        traj.num_frames()?;
+        let iter = traj.into_iter().stride(5);
+        assert_eq!(iter.size_hint(), (8, Some(8)));
+        let frames: Vec<Rc<Frame>> = iter.collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 8);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_iterator_rewind() -> Result<()> {
+        let mut iter = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_iter()
+            .stride(10);
+        assert_eq!(iter.by_ref().count(), 4);
+        assert!(iter.next().is_none());
+
+        iter.rewind()?;
+        let steps = iter
+            .map(|frame| frame.map(|f| f.step))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(steps, vec![1, 11, 21, 31]);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_into_frames() -> Result<()> {
+        let traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let frames: Vec<Frame> = traj.into_frames().collect::<Result<_>>()?;
+        assert_eq!(frames.len(), 38);
+        assert_eq!(frames[0].step, 1);
+        assert_eq!(frames[37].step, 38);
+        assert_ne!(frames[0].coords, frames[37].coords);
+
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        traj.num_frames()?;
+        let frames = traj.into_iter().stride(10).into_frames();
+        assert_eq!(frames.size_hint(), (4, Some(4)));
+        let steps = frames
+            .map(|frame| frame.map(|f| f.step))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(steps, vec![1, 11, 21, 31]);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_into_iter_arc() -> Result<()> {
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        traj.num_frames()?;
+        let mut frames = traj.into_iter_arc();
+        assert_eq!(frames.size_hint(), (38, Some(38)));
+
+        // Frames that are not kept alternate between two buffers
+        let pointers: Vec<_> = (0..3)
+            .map(|_| frames.next().unwrap().map(|f| f.coords.as_ptr()))
+            .collect::<Result<_>>()?;
+        assert_eq!(pointers[0], pointers[2]);
+        let second = frames.next().unwrap()?;
+        assert_eq!(second.step, 4);
+        assert_eq!(second.coords.as_ptr(), pointers[1]);
+
+        // Frames that are kept are not overwritten
+        let third = frames.next().unwrap()?;
+        assert_eq!(second.step, 4);
+        assert_eq!(third.step, 5);
+        assert_ne!(second.coords, third.coords);
+
+        let rest: Vec<Arc<Frame>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = frames
+                .map(|frame| scope.spawn(move || frame.map(|f| (f.step, f))))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap().map(|(_, f)| f))
+                .collect::<Result<_>>()
+        })?;
+        assert_eq!(rest.len(), 33);
+        assert_eq!(rest[32].step, 38);
+        assert_eq!(rest[0].step, 6);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_iter_headers() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(304);
+        traj.read(&mut frame)?;
+        let position = traj.tell()?;
+
+        let headers = traj.iter_headers().collect::<Result<Vec<_>>>()?;
+        assert_eq!(headers.len(), 38);
+        assert_eq!(traj.tell()?, position);
+        let bytes = std::fs::read("tests/1l2y.xtc").unwrap();
+        let expected = xtc_frame_headers(&bytes).collect::<Result<Vec<_>>>()?;
+        for (&(step, time, offset), (expected_offset, expected)) in headers.iter().zip(expected) {
+            assert_eq!(step, expected.step);
+            assert_eq!(time, expected.time);
+            assert_eq!(offset, expected_offset as u64);
+        }
+
+        // Reading continues where it was before
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 2);
+
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let steps: Vec<usize> = traj
+            .iter_headers()
+            .map(|header| header.map(|(step, _, _)| step))
+            .collect::<Result<_>>()?;
+        assert_eq!(steps, (1..=38).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_frames_mut() -> Result<()> {
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut steps = Vec::new();
+        let mut frames = traj.frames_mut();
+        while let Some(frame) = frames.next()? {
+            steps.push(frame.step);
+        }
+        assert_eq!(steps, (1..=38).collect::<Vec<_>>());
+        assert!(frames.next()?.is_none());
+
+        let mut empty = XTCTrajectory::from_bytes(Vec::new(), FileMode::Read)?;
+        assert!(empty.frames_mut().next().is_err());
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_iter_chunks() -> Result<()> {
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        traj.seek_to_frame(2)?;
+        let chunks = traj.iter_chunks(12).collect::<Result<Vec<_>>>()?;
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].len(), 12);
+        assert_eq!(chunks[0][0].step, 3);
+        assert_eq!(chunks[2][11].step, 38);
+        assert_eq!(traj.iter_chunks(12).count(), 0);
+
+        let mut empty = XTCTrajectory::from_bytes(Vec::new(), FileMode::Read)?;
+        let mut chunks = empty.iter_chunks(4);
+        assert!(chunks.next().unwrap().is_err());
+        assert!(chunks.next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_zero_chunk_size() {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc").unwrap();
+        let _ = traj.iter_chunks(0);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_zero_stride() {
+        let traj = XTCTrajectory::open_read("tests/1l2y.xtc").unwrap();
+        let _ = traj.into_iter().stride(0);
+    }
+
+    #[test]
+    pub fn test_retry_delay() {
+        let policy = RetryPolicy {
+            max_retries: 100,
+            initial_delay: Duration::from_millis(10),
+            max_delay: Duration::from_millis(50),
+        };
+        assert_eq!(policy.delay(0), Duration::from_millis(10));
+        assert_eq!(policy.delay(2), Duration::from_millis(40));
+        assert_eq!(policy.delay(3), Duration::from_millis(50));
+        assert_eq!(policy.delay(usize::MAX), Duration::from_millis(50));
+    }
+
+    #[test]
+    pub fn test_retry_on_eof() -> Result<()> {
+        use std::io::Write;
+        for source in &["tests/1l2y.xtc", "tests/1l2y.trr"] {
+            let bytes = std::fs::read(source).unwrap();
+            let tempfile = tempfile::NamedTempFile::new().unwrap();
+            let mut file = tempfile.reopen().unwrap();
+            // Start with a few complete frames, then write the rest in
+            // chunks that end in the middle of frames
+            let start = bytes.len() / 38 * 3;
+            file.write_all(&bytes[..start]).unwrap();
+            file.flush().unwrap();
+            let writer = std::thread::spawn(move || {
+                for chunk in bytes[start..].chunks(1999) {
+                    std::thread::sleep(Duration::from_millis(1));
+                    file.write_all(chunk).unwrap();
+                    file.flush().unwrap();
+                }
+            });
+
+            let policy = RetryPolicy {
+                max_retries: 100,
+                initial_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(4),
+            };
+            let iter = into_iter_inner(open_any(tempfile.path())?).retry_on_eof(policy);
+            assert_eq!(iter.size_hint().1, None);
+            let steps = iter
+                .map(|frame| frame.map(|frame| frame.step))
+                .collect::<Result<Vec<usize>>>()?;
+            writer.join().unwrap();
+            assert_eq!(steps, (1..=38).collect::<Vec<usize>>());
+        }
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_retry_incomplete_frame() -> Result<()> {
+        // Without new data, an incomplete frame is still an error after
+        // all retries
+        let bytes = std::fs::read("tests/1l2y.xtc").unwrap();
+        let traj = XTCTrajectory::from_bytes(bytes[..bytes.len() - 100].to_vec(), FileMode::Read)?;
+        let policy = RetryPolicy {
+            max_retries: 3,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        };
+        let results: Vec<Result<Rc<Frame>>> = traj.into_iter().retry_on_eof(policy).collect();
+        assert_eq!(results.len(), 38);
+        assert!(results[..37].iter().all(|frame| frame.is_ok()));
+        assert!(matches!(results[37], Err(Error::InFrame { .. })));
+
+        // Corrupt frames in the middle are not waited for
+        let mut bytes = std::fs::read("tests/1l2y.xtc").unwrap();
+        let offset = bytes.len() / 2;
+        bytes[offset..offset + 100]
+            .iter_mut()
+            .for_each(|b| *b = 0xff);
+        let traj = XTCTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let policy = RetryPolicy {
+            initial_delay: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let results: Vec<Result<Rc<Frame>>> = traj.into_iter().retry_on_eof(policy).collect();
+        assert!(results.last().unwrap().is_err());
+        Ok(())
+    }
 }
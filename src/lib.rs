@@ -61,19 +61,96 @@
 extern crate assert_approx_eq;
 extern crate lazy_init;
 
+mod bounds;
+mod box_vector;
 pub mod c_abi;
+mod chain;
+mod compare;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compression;
+mod convert;
+mod demux;
+mod double;
+mod downsample;
 mod errors;
+mod format;
 mod frame;
+mod frame_soa;
+mod frame_view;
+mod index;
 mod iterator;
+#[cfg(feature = "mdanalysis")]
+mod mdanalysis;
+mod options;
+mod parallel;
+mod pbc;
+mod pipeline;
+mod pool;
+mod prefetch;
+mod progress;
+#[cfg(feature = "python")]
+pub mod python;
+mod repair;
+mod selection;
+#[cfg(feature = "simd")]
+mod simd;
+mod smooth;
+mod stats;
+mod storage;
+mod stream;
+mod superpose;
+mod topology;
+mod transform;
+mod units;
+#[cfg(feature = "notify")]
+mod watch;
+mod whole;
+mod writer;
+mod xdr_io;
+mod xtc_header;
+pub use bounds::Bounds;
+pub use box_vector::{BoxShape, BoxVector, LatticeParameters};
+pub use chain::*;
+pub use compare::{compare, ComparisonReport, Divergence, Tolerances};
+pub use convert::*;
+pub use demux::{demux, ReplicaMap};
+pub use double::FrameF64;
+pub use downsample::{DownsampledTrajectory, Downsampling};
 pub use errors::*;
-pub use frame::Frame;
+pub use format::{open_any, TrajectoryFormat};
+pub use frame::{Frame, FrameBuilder, FrameHeader};
+pub use frame_soa::FrameSoA;
+pub use frame_view::FrameView;
 pub use iterator::*;
+#[cfg(feature = "mdanalysis")]
+pub use mdanalysis::mdanalysis_offsets_path;
+pub use options::TrajectoryOptions;
+pub use pipeline::*;
+pub use pool::{FramePool, PooledFrame};
+pub use prefetch::PrefetchingTrajectory;
+pub use progress::Progress;
+pub use repair::{repair, RepairReport};
+pub use selection::AtomSelection;
+pub use smooth::SmoothedFrames;
+pub use stats::TrajectoryStats;
+pub use storage::CoordStorage;
+pub use topology::Topology;
+pub use units::{LengthUnit, TimeUnit};
+#[cfg(feature = "notify")]
+pub use watch::TrajectoryWatcher;
+pub use whole::MakeWhole;
+pub use writer::*;
+pub use xdr_io::{compress_coords, decompress_coords, XdrReader, XdrWriter};
+pub use xtc_header::{xtc_frame_headers, XtcFrameHeader, XtcHeaders};
 
 use c_abi::xdr_seek;
 use c_abi::xdrfile;
 use c_abi::xdrfile::XDRFILE;
 use c_abi::xdrfile_trr;
 use c_abi::xdrfile_xtc;
+use index::TrajectoryIndex;
+use progress::ProgressTracker;
+use stream::Stream;
 
 use lazy_init::Lazy;
 use std::cell::Cell;
@@ -81,7 +158,7 @@ use std::convert::{TryFrom, TryInto};
 use std::ffi::CString;
 use std::io;
 use std::io::SeekFrom;
-use std::os::raw::{c_float, c_int};
+use std::os::raw::{c_char, c_float, c_int, c_ulong};
 use std::path::{Path, PathBuf};
 
 /// File Mode for accessing trajectories.
@@ -113,15 +190,111 @@ fn path_to_cstring(path: impl AsRef<Path>) -> Result<CString> {
     }
 }
 
+/// Count the frames of the trajectory at `path` with the C function
+/// `nframes`. The C library cannot open every path on Windows, so the frames
+/// are counted with a new handle there.
+fn count_frames(
+    path: &Path,
+    nframes: unsafe extern "C" fn(*const c_char, *mut c_ulong) -> c_int,
+    skip_frame: index::SkipFrame,
+) -> Result<usize> {
+    if cfg!(windows) {
+        let mut handle = XDRFile::open(path, FileMode::Read)?;
+        return TrajectoryIndex::default().num_frames(&mut handle, skip_frame);
+    }
+    let path = path_to_cstring(path)?;
+    let mut num_frames: c_ulong = 0;
+    let code = unsafe { nframes(path.as_ptr(), &mut num_frames) };
+    if let Some(err) = check_code(code, ErrorTask::ReadNumFrames) {
+        Err(err)
+    } else {
+        to(num_frames, ErrorTask::ReadNumFrames, "num_frames")
+    }
+}
+
+/// Open the file at `path` with the C library
+#[cfg(not(windows))]
+fn open_c_file(path: &Path, filemode: &FileMode) -> Result<*mut xdrfile::XDRFILE> {
+    let path_c = path_to_cstring(path)?;
+    // SAFETY: mode_p must not be mutated by the C code
+    let mode_p = filemode.to_cstr().as_ptr();
+    let xdrfile = unsafe { xdrfile::xdrfile_open(path_c.as_ptr(), mode_p) };
+    // Capture errno before any other call can overwrite it
+    let os_error = io::Error::last_os_error();
+    if xdrfile.is_null() {
+        // The C api does not tell us what went wrong, but fopen sets errno
+        Err((path, filemode.clone(), os_error).into())
+    } else {
+        Ok(xdrfile)
+    }
+}
+
+/// Open the file at `path` with the C library. The path is passed as UTF-16,
+/// as the narrow C functions only accept paths in the ANSI code page and
+/// shorter than MAX_PATH.
+#[cfg(windows)]
+fn open_c_file(path: &Path, filemode: &FileMode) -> Result<*mut xdrfile::XDRFILE> {
+    let path_w = path_to_wide(path).map_err(|err| Error::from((path, filemode.clone(), err)))?;
+    // SAFETY: mode_p must not be mutated by the C code
+    let mode_p = filemode.to_cstr().as_ptr();
+    let xdrfile = unsafe { xdrfile::xdrfile_wopen(path_w.as_ptr(), mode_p) };
+    // Capture the error before any other call can overwrite it
+    let os_error = io::Error::last_os_error();
+    if xdrfile.is_null() {
+        Err((path, filemode.clone(), os_error).into())
+    } else {
+        Ok(xdrfile)
+    }
+}
+
+/// Convert `path` to a nul terminated UTF-16 string. Paths that are too
+/// long once made absolute get the \\?\ prefix, which lifts the MAX_PATH
+/// limit.
+#[cfg(windows)]
+fn path_to_wide(path: &Path) -> io::Result<Vec<u16>> {
+    use std::os::windows::ffi::OsStrExt;
+    // MAX_PATH minus the 12 characters Windows reserves for file names in
+    // directories, the limit also used by the standard library
+    const MAX_SHORT_PATH: usize = 248;
+    let wide = |path: &Path| -> Vec<u16> { path.as_os_str().encode_wide().collect() };
+    let verbatim = wide(Path::new(r"\\?\"));
+    let mut path_w = wide(path);
+    if path_w.contains(&0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "path contains a nul character",
+        ));
+    }
+    if !path_w.starts_with(&verbatim) {
+        let absolute = wide(&std::path::absolute(path)?);
+        if absolute.len() >= MAX_SHORT_PATH {
+            path_w = if absolute.starts_with(&verbatim) {
+                absolute
+            } else if absolute.starts_with(&wide(Path::new(r"\\"))) {
+                // \\server\share becomes \\?\UNC\server\share
+                let mut unc = wide(Path::new(r"\\?\UNC"));
+                unc.extend_from_slice(&absolute[1..]);
+                unc
+            } else {
+                verbatim.into_iter().chain(absolute).collect()
+            };
+        }
+    }
+    path_w.push(0);
+    Ok(path_w)
+}
+
 fn to<I, O>(value: I, task: ErrorTask, name: &'static str) -> Result<O>
 where
     I: TryInto<O> + std::fmt::Display + Copy,
+    I::Error: Into<std::num::TryFromIntError>,
 {
-    value.try_into().map_err(|_| Error::OutOfRange {
+    value.try_into().map_err(|err| Error::OutOfRange {
         name,
         value: format!("{}", &value),
         target: std::any::type_name::<O>(),
         task,
+        source: err.into(),
     })
 }
 
@@ -145,53 +318,343 @@ fn check_code(code: impl Into<ErrorCode>, task: ErrorTask) -> Option<Error> {
     }
 }
 
+/// Index of the frame at the start of a file opened in the given mode. Files
+/// opened for appending start after an unknown number of frames.
+fn first_frame(filemode: &FileMode) -> Option<usize> {
+    match filemode {
+        FileMode::Append => None,
+        FileMode::Read | FileMode::Write => Some(0),
+    }
+}
+
+/// Copy the value of `lazy` if it was already computed
+fn clone_lazy<T: Clone + Sync>(lazy: &Lazy<T>) -> Lazy<T> {
+    let clone = Lazy::new();
+    if let Some(value) = lazy.get() {
+        clone.get_or_create(|| value.clone());
+    }
+    clone
+}
+
+/// Account for a frame written to the end of a trajectory in the cached
+/// counts: the number of frames is increased if it is known, and errors of
+/// counting atoms or frames, e.g. in a file without frames, are forgotten
+fn count_written_frame(num_atoms: &mut Lazy<Result<usize>>, num_frames: &mut Lazy<Result<usize>>) {
+    if let Some(Err(_)) = num_atoms.get() {
+        *num_atoms = Lazy::new();
+    }
+    let count = match num_frames.get() {
+        Some(Ok(count)) => Some(count + 1),
+        _ => None,
+    };
+    *num_frames = Lazy::new();
+    if let Some(count) = count {
+        num_frames.get_or_create(|| Ok(count));
+    }
+}
+
+/// Prepare an in-memory buffer to be opened in the given mode
+fn memory_buffer(mut bytes: Vec<u8>, filemode: &FileMode) -> io::Cursor<Vec<u8>> {
+    let pos = match filemode {
+        FileMode::Write => {
+            bytes.clear();
+            0
+        }
+        FileMode::Append => bytes.len() as u64,
+        FileMode::Read => 0,
+    };
+    let mut buffer = io::Cursor::new(bytes);
+    buffer.set_position(pos);
+    buffer
+}
+
 /// A safe wrapper around the c implementation of an XDRFile
 struct XDRFile {
     xdrfile: *mut XDRFILE,
-    #[allow(dead_code)]
     filemode: FileMode,
-    path: PathBuf,
+    /// Path of the file, None if the file is backed by a stream
+    path: Option<PathBuf>,
+    /// Stream performing the IO if the file is not on disk. It is dropped
+    /// after `xdrfile` is closed in the Drop implementation.
+    stream: Option<Stream>,
+    /// Panic on drop if flushing or closing the file fails
+    flush_on_drop: bool,
+    /// Index of the frame at the current position, None if unknown
+    frame: Option<usize>,
+    /// Skip corrupt frames when reading, see `set_resync`
+    resync: bool,
+    /// Check the values of frames before writing them, see `set_validate`
+    validate: bool,
+    /// Reports frames read or scanned, see `set_progress`
+    progress: Option<ProgressTracker>,
+    /// Set if a seek failed and the position before it could not be
+    /// restored, until a seek to an absolute position succeeds
+    position_lost: bool,
+    /// Buffer of the C file set with `set_buffer_size`. It must outlive
+    /// `xdrfile`, so it is dropped after closing the file.
+    io_buffer: Option<Box<[u8]>>,
 }
 
 impl XDRFile {
     pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<XDRFile> {
         let path = path.as_ref();
-        unsafe {
-            let path_p = path_to_cstring(path)?.into_raw();
-            // SAFETY: mode_p must not be mutated by the C code
-            let mode_p = filemode.to_cstr().as_ptr();
+        #[cfg(any(feature = "gzip", feature = "zstd"))]
+        if filemode == FileMode::Read {
+            if let Some(compression) = compression::Compression::detect(path) {
+                let reader = compression::Decompressor::open(path, compression)
+                    .map_err(|err| Error::from((path, filemode.clone(), err)))?;
+                let (stream, io) = Stream::reader(reader);
+                return Ok(XDRFile::open_stream(stream, io, filemode));
+            }
+        }
+        let xdrfile = open_c_file(path, &filemode)?;
+        let frame = first_frame(&filemode);
+        Ok(XDRFile {
+            xdrfile,
+            filemode,
+            path: Some(path.to_owned()),
+            stream: None,
+            flush_on_drop: false,
+            frame,
+            resync: false,
+            validate: false,
+            progress: None,
+            position_lost: false,
+            io_buffer: None,
+        })
+    }
+
+    /// Open an XDR file on top of a stream instead of a file on disk
+    pub(crate) fn open_stream(
+        stream: Stream,
+        io: xdrfile::xdrfile_io,
+        filemode: FileMode,
+    ) -> XDRFile {
+        // SAFETY: mode_p must not be mutated by the C code
+        let mode_p = filemode.to_cstr().as_ptr();
+        let xdrfile = unsafe { xdrfile::xdrfile_open_io(&io, mode_p) };
+        // The C code only fails if it cannot allocate the handle
+        assert!(!xdrfile.is_null(), "Failed to allocate XDR file handle");
+        let frame = first_frame(&filemode);
+        XDRFile {
+            xdrfile,
+            filemode,
+            path: None,
+            stream: Some(stream),
+            flush_on_drop: false,
+            frame,
+            resync: false,
+            validate: false,
+            progress: None,
+            position_lost: false,
+            io_buffer: None,
+        }
+    }
 
-            let xdrfile = xdrfile::xdrfile_open(path_p, mode_p);
+    /// Open a second handle for reading the same file at the same position
+    fn try_clone(&self) -> Result<XDRFile> {
+        if self.filemode != FileMode::Read {
+            return Err(Error::CannotClone {
+                reason: "only trajectories opened for reading can be cloned",
+            });
+        }
+        let path = self.path.as_ref().ok_or(Error::CannotClone {
+            reason: "trajectories read from streams or buffers have no path to reopen",
+        })?;
+        let mut handle = XDRFile::open(path, FileMode::Read)?;
+        if let Some(buffer) = &self.io_buffer {
+            handle.set_buffer_size(buffer.len())?;
+        }
+        handle.seek_to(SeekFrom::Start(self.tell()?))?;
+        handle.frame = self.frame;
+        handle.resync = self.resync;
+        Ok(handle)
+    }
 
-            // Reconstitute the CString so it is deallocated correctly
-            let _ = CString::from_raw(path_p);
+    /// Close the file and take back the stream performing its IO, if any
+    fn into_stream(mut self) -> Option<Stream> {
+        // Dropping self closes the file while the stream is still alive
+        self.stream.take()
+    }
 
-            if !xdrfile.is_null() {
-                let path = path.to_owned();
-                Ok(XDRFile {
-                    xdrfile,
-                    filemode,
-                    path,
-                })
+    /// Replace the default buffer of the C file with one of `size` bytes, or
+    /// disable buffering if `size` is 0. Must be called right after opening
+    /// the file, before any reading, writing or seeking. Files backed by a
+    /// stream are not buffered by the C library and are left unchanged.
+    fn set_buffer_size(&mut self, size: usize) -> Result<()> {
+        if self.stream.is_some() {
+            return Ok(());
+        }
+        let mut buffer = vec![0u8; size].into_boxed_slice();
+        let buf = if size == 0 {
+            std::ptr::null_mut()
+        } else {
+            buffer.as_mut_ptr().cast()
+        };
+        let code = unsafe { xdr_seek::xdr_setvbuf(self.xdrfile, buf, size) };
+        if let Some(err) = check_code(code, ErrorTask::SetBuffer) {
+            return Err(err);
+        }
+        self.io_buffer = Some(buffer);
+        Ok(())
+    }
+
+    /// Read up to `len` bytes from the start of the file past the XDR
+    /// handle, which cannot decode in write and append mode. Frames written
+    /// by this handle are flushed first, the position is kept.
+    pub(crate) fn read_head(&mut self, len: usize) -> Result<Vec<u8>> {
+        self.flush_written()?;
+        let io_error = |err: std::io::Error| {
+            if err.kind() == std::io::ErrorKind::UnexpectedEof {
+                Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::ReadNumAtoms))
             } else {
-                // Something went wrong. But the C api does not tell us what
-                Err((path, filemode).into())
+                Error::Io {
+                    task: ErrorTask::ReadNumAtoms,
+                    source: err.into(),
+                }
             }
+        };
+        if let Some(stream) = &mut self.stream {
+            return stream.read_at(0, len).map_err(io_error);
         }
+        let mut bytes = vec![0u8; len];
+        let count = unsafe {
+            xdr_seek::xdr_read_at(self.xdrfile, 0, bytes.as_mut_ptr() as *mut c_char, len)
+        };
+        let count =
+            usize::try_from(count).map_err(|_| io_error(std::io::Error::last_os_error()))?;
+        bytes.truncate(count);
+        Ok(bytes)
     }
 
-    /// Get the current position in the file
-    pub fn tell(&self) -> u64 {
-        unsafe {
-            xdr_seek::xdr_tell(self.xdrfile)
-                .try_into()
-                .expect("i64 could not be converted to u64")
+    /// Flush frames written by this handle, so that they can be read past
+    /// the XDR handle. Nothing is done in read mode.
+    fn flush_written(&mut self) -> Result<()> {
+        if self.filemode == FileMode::Read {
+            return Ok(());
         }
+        let code = unsafe { xdr_seek::xdr_flush(self.xdrfile) };
+        check_code(code, ErrorTask::Flush).map_or(Ok(()), Err)
     }
-}
 
-impl io::Seek for XDRFile {
-    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+    /// Flush and close the file. Afterwards, the handle must not be used
+    /// anymore except for dropping it.
+    fn close(&mut self) -> Result<()> {
+        if self.xdrfile.is_null() {
+            return Ok(());
+        }
+        let flushed = self.flush_written().err();
+        let code = unsafe { xdrfile::xdrfile_close(self.xdrfile) };
+        self.xdrfile = std::ptr::null_mut();
+        match flushed {
+            Some(err) => Err(err),
+            // fclose reports failure as EOF instead of an xdrfile error code
+            None if code != 0 => Err((ErrorCode::ExdrClose, ErrorTask::Close).into()),
+            None => Ok(()),
+        }
+    }
+
+    /// Run `f` and restore the current position in the file afterwards
+    fn restoring_position<T>(&mut self, f: impl FnOnce(&mut XDRFile) -> Result<T>) -> Result<T> {
+        let pos = self.tell()?;
+        let frame = self.frame;
+        let result = f(self);
+        self.seek_to(SeekFrom::Start(pos))?;
+        self.frame = frame;
+        result
+    }
+
+    /// Replace an error of the C API with the IO error of the stream backing
+    /// the file, if it recorded one. The C API only sees that a read failed
+    /// and reports e.g. corrupt compressed data as the end of the file.
+    fn stream_error(&mut self, err: Error) -> Error {
+        match self.stream.as_ref().and_then(Stream::take_error) {
+            Some(source) => Error::Io {
+                task: err.task().unwrap_or(ErrorTask::Read),
+                source: source.into(),
+            },
+            None => err,
+        }
+    }
+
+    /// Add the path and the index of the current frame to an error of the C
+    /// API or the stream while reading or writing. End of file errors are
+    /// returned as is.
+    fn frame_error(&mut self, err: Error) -> Error {
+        match self.stream_error(err) {
+            err @ (Error::Io { .. } | Error::CApiError { .. }) if !err.is_eof() => Error::InFrame {
+                path: self.path.clone(),
+                frame: self.frame,
+                source: Box::new(err),
+            },
+            err => err,
+        }
+    }
+
+    /// Move on to the next frame after reading or writing the current one
+    fn frame_done(&mut self) {
+        if let Some(frame) = &mut self.frame {
+            *frame += 1;
+        }
+    }
+
+    /// Report a frame that was read or scanned to the progress callback
+    fn report_progress(&mut self) {
+        if self.progress.is_some() {
+            // Progress is only informative, a failing stream is reported by
+            // the next read
+            let Ok(bytes) = self.tell() else { return };
+            if let Some(progress) = &mut self.progress {
+                progress.frame_done(bytes);
+            }
+        }
+    }
+
+    /// Report frames read or scanned to `callback` from now on
+    fn set_progress(&mut self, callback: Box<dyn FnMut(Progress) + Send>) -> Result<()> {
+        let len = self.restoring_position(|handle| handle.seek_to(SeekFrom::End(0)))?;
+        self.progress = Some(ProgressTracker::new(callback, len));
+        Ok(())
+    }
+
+    /// True if fewer than `min_frame_size` bytes are left to read
+    fn is_at_end(&mut self, min_frame_size: u64) -> Result<bool> {
+        if self.filemode != FileMode::Read {
+            return Ok(true);
+        }
+        let pos = self.tell()?;
+        let len = self.restoring_position(|handle| handle.seek_to(SeekFrom::End(0)))?;
+        Ok(len.saturating_sub(pos) < min_frame_size)
+    }
+
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        let pos = unsafe { xdr_seek::xdr_tell(self.xdrfile) };
+        u64::try_from(pos).map_err(|_| {
+            let source = match self.stream.as_ref().and_then(Stream::take_error) {
+                Some(err) => err,
+                None => io::Error::last_os_error(),
+            };
+            Error::Io {
+                task: ErrorTask::Seek,
+                source: source.into(),
+            }
+        })
+    }
+
+    /// Seek to a position in the file and return the new position. The
+    /// index of the current frame is unknown afterwards.
+    ///
+    /// A failed seek may have moved the position anyway, so the position
+    /// before the seek is restored. If that fails as well, reading and
+    /// writing return `Error::PositionLost` until a seek to an absolute
+    /// position succeeds.
+    fn seek_to(&mut self, pos: SeekFrom) -> Result<u64> {
+        if let SeekFrom::Current(_) = pos {
+            self.check_position()?;
+        }
+        let start = self.tell()?;
+        let frame = self.frame.take();
         let (whence, pos) = match pos {
             SeekFrom::Start(u) => (
                 0,
@@ -203,18 +666,79 @@ impl io::Seek for XDRFile {
         unsafe {
             let code = xdr_seek::xdr_seek(self.xdrfile, pos, whence);
             match check_code(code, ErrorTask::Seek) {
-                None => Ok(self.tell()),
-                Some(err) => Err(io::Error::new(io::ErrorKind::Other, err)),
+                None => {
+                    self.position_lost = false;
+                    self.tell()
+                }
+                Some(err) => {
+                    let start = i64::try_from(start).expect("Seek position did not fit in i64");
+                    let restored = xdr_seek::xdr_seek(self.xdrfile, start, 0) == 0;
+                    if restored && !self.position_lost {
+                        self.frame = frame;
+                    } else {
+                        self.position_lost = true;
+                    }
+                    Err(self.stream_error(err))
+                }
             }
         }
     }
+
+    /// Return an error if the position is unknown after a failed seek, to
+    /// not read from or write to a random position
+    fn check_position(&self) -> Result<()> {
+        if self.position_lost {
+            Err(Error::PositionLost {
+                path: self.path.clone(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read a single int from the file. Returns None if no int could be read
+    fn read_int(&mut self) -> Option<c_int> {
+        let mut value: c_int = 0;
+        let count = unsafe { xdrfile::xdrfile_read_int(&mut value, 1, self.xdrfile) };
+        if count == 1 {
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    /// Read a single float from the file. Returns None if no float could be read
+    fn read_float(&mut self) -> Option<c_float> {
+        let mut value: c_float = 0.0;
+        let count = unsafe { xdrfile::xdrfile_read_float(&mut value, 1, self.xdrfile) };
+        if count == 1 {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+impl io::Seek for XDRFile {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        Ok(self.seek_to(pos)?)
+    }
 }
 
+// SAFETY: The handle is exclusively owned by the XDRFile and only used
+// through &mut self, except for `tell`. The C library keeps no state per
+// thread, so the handle may be used from another thread than the one that
+// opened it. Streams are required to be `Send` themselves. XDRFile is not
+// `Sync`; wrap trajectories in a Mutex to share them between threads.
+unsafe impl Send for XDRFile {}
+
 impl Drop for XDRFile {
     /// Close the underlying xdr file on drop
     fn drop(&mut self) {
-        unsafe {
-            xdrfile::xdrfile_close(self.xdrfile);
+        if let Err(err) = self.close() {
+            if self.flush_on_drop {
+                eprintln!("Failed to close trajectory on drop: {}", err);
+            }
         }
     }
 }
@@ -224,266 +748,1591 @@ pub trait Trajectory {
     /// Read the next step of the trajectory into the frame object
     fn read(&mut self, frame: &mut Frame) -> Result<()>;
 
+    /// Read the next step of the trajectory into the frame object without
+    /// advancing, so that the following call to `read` returns the same frame
+    ///
+    /// The default implementation reads the frame and seeks back to it with
+    /// `seek_to_frame`, which requires `current_frame` to be known.
+    fn peek(&mut self, frame: &mut Frame) -> Result<()> {
+        let current = self
+            .current_frame()
+            .ok_or(Error::Unsupported { operation: "peek" })?;
+        self.read(frame)?;
+        self.seek_to_frame(current)
+    }
+
+    /// Read the coordinates of the next step directly into `coords`, e.g. a
+    /// part of a larger preallocated array, and its step, time and box into
+    /// `header`. `coords` must have one entry per atom. Velocities and forces
+    /// are skipped.
+    ///
+    /// XTC and TRR trajectories decode into `coords` without allocating. The
+    /// default implementation reads a frame and copies it.
+    fn read_into(&mut self, coords: &mut [[f32; 3]], header: &mut FrameHeader) -> Result<()> {
+        let mut frame = Frame::with_len(coords.len());
+        self.read(&mut frame)?;
+        coords.copy_from_slice(&frame.coords);
+        *header = frame.header();
+        Ok(())
+    }
+
+    /// Read the coordinates of the next step into a flat buffer of
+    /// `3 * num_atoms` values ordered x, y, z per atom, the layout expected by
+    /// most numeric libraries, see `read_into`
+    fn read_flat(&mut self, coords: &mut [f32], header: &mut FrameHeader) -> Result<()> {
+        match coords.as_chunks_mut::<3>() {
+            (coords, []) => self.read_into(coords, header),
+            _ => Err(Error::InvalidFrame {
+                reason: "flat coordinates must have 3 values per atom",
+            }),
+        }
+    }
+
+    /// Read the coordinates of the next step into any `CoordStorage`, such
+    /// as a flat `Vec<f32>` or, with the `ndarray` and `nalgebra` features,
+    /// an array or matrix, and its step, time and box into `header`. Storage
+    /// that can grow is resized to the number of atoms first, then the
+    /// coordinates are decoded into it as with `read_into`.
+    fn read_coords<S: CoordStorage + ?Sized>(
+        &mut self,
+        coords: &mut S,
+        header: &mut FrameHeader,
+    ) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let num_atoms = self.get_num_atoms()?;
+        self.read_into(coords.as_coords_mut(num_atoms)?, header)
+    }
+
     /// Write the frame to the trajectory file
     fn write(&mut self, frame: &Frame) -> Result<()>;
 
+    /// Write all `frames` and flush the trajectory once at the end. Returns
+    /// the number of frames written.
+    ///
+    /// All frames are validated before the first one is written, so if a
+    /// frame has a different number of atoms than the first one or an
+    /// invalid box, an error is returned and nothing is written.
+    fn write_all<'a>(&mut self, frames: impl IntoIterator<Item = &'a Frame>) -> Result<usize>
+    where
+        Self: Sized,
+    {
+        let frames: Vec<&Frame> = frames.into_iter().collect();
+        validate_frames(&frames)?;
+        for frame in &frames {
+            self.write(frame)?;
+        }
+        self.flush()?;
+        Ok(frames.len())
+    }
+
+    /// Write a frame with the given header and coordinates from a flat buffer
+    /// of `3 * num_atoms` values ordered x, y, z per atom, see `read_flat`.
+    /// The coordinates are copied into a frame first.
+    fn write_flat(&mut self, coords: &[f32], header: &FrameHeader) -> Result<()> {
+        match coords.as_chunks::<3>() {
+            (coords, []) => self.write(&Frame::from_coords(
+                header.step,
+                header.time,
+                header.box_vector,
+                coords.to_vec(),
+            )),
+            _ => Err(Error::InvalidFrame {
+                reason: "flat coordinates must have 3 values per atom",
+            }),
+        }
+    }
+
+    /// Write a frame with the given header and the coordinates from any
+    /// `CoordStorage`, see `read_coords`. The coordinates are copied into a
+    /// frame first.
+    fn write_coords<S: CoordStorage + ?Sized>(
+        &mut self,
+        coords: &S,
+        header: &FrameHeader,
+    ) -> Result<()>
+    where
+        Self: Sized,
+    {
+        let coords = coords.as_coords()?.to_vec();
+        self.write(&Frame::from_coords(
+            header.step,
+            header.time,
+            header.box_vector,
+            coords,
+        ))
+    }
+
     /// Flush the trajectory file
     fn flush(&mut self) -> Result<()>;
 
     /// Get the number of atoms from the give trajectory
     fn get_num_atoms(&mut self) -> Result<usize>;
 
-}
-
-/// Handle to Read/Write XTC Trajectories
-pub struct XTCTrajectory {
-    handle: XDRFile,
-    precision: Cell<c_float>, // internal mutability required for read method
-    num_atoms: Lazy<Result<usize>>,
-}
-
-impl XTCTrajectory {
-    pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<XTCTrajectory> {
-        let xdr = XDRFile::open(path, filemode)?;
-        Ok(XTCTrajectory {
-            handle: xdr,
-            precision: Cell::new(1000.0),
-            num_atoms: Lazy::new(),
+    /// Get the number of frames in the trajectory.
+    ///
+    /// The number of frames is determined once by scanning the whole file
+    /// and cached afterwards. Frames written through the same handle are
+    /// added to the cached count, so it stays correct while appending.
+    ///
+    /// The default implementation returns `known_num_frames`, or an
+    /// `Error::Unsupported` if it is not known.
+    fn num_frames(&mut self) -> Result<usize> {
+        self.known_num_frames().ok_or(Error::Unsupported {
+            operation: "num_frames",
         })
     }
 
-    /// Open a file in read mode
-    pub fn open_read(path: impl AsRef<Path>) -> Result<Self> {
-        Self::open(path, FileMode::Read)
+    /// Get the number of frames if it is known without scanning the
+    /// trajectory, e.g. because `num_frames` was called before. Iterators
+    /// use this for their size hints.
+    fn known_num_frames(&self) -> Option<usize> {
+        None
     }
 
-    /// Open a file in append mode
-    pub fn open_append(path: impl AsRef<Path>) -> Result<Self> {
-        Self::open(path, FileMode::Append)
+    /// Get the index of the frame that the next call to `read` returns, if
+    /// it is known
+    fn current_frame(&self) -> Option<usize> {
+        None
     }
 
-    /// Open a file in write mode
-    pub fn open_write(path: impl AsRef<Path>) -> Result<Self> {
-        Self::open(path, FileMode::Write)
+    /// Seek to the frame with the given (zero-based) index, so that the
+    /// next call to `read` returns that frame.
+    ///
+    /// Frames are located by skipping over their headers, so no coordinates
+    /// are decompressed. Frame offsets are remembered, which makes repeated
+    /// seeks within the already visited part of the file cheap.
+    ///
+    /// The default implementation can only seek forward from a known
+    /// `current_frame`, using `skip`. Other seeks return an
+    /// `Error::Unsupported`.
+    fn seek_to_frame(&mut self, frame: usize) -> Result<()> {
+        match self.current_frame() {
+            Some(current) if current <= frame => self.skip(frame - current),
+            _ => Err(Error::Unsupported {
+                operation: "seek_to_frame",
+            }),
+        }
     }
-}
 
-impl Trajectory for XTCTrajectory {
-    fn read(&mut self, frame: &mut Frame) -> Result<()> {
-        let mut step: c_int = 0;
+    /// Seek to the first frame with a time greater than or equal to `time`,
+    /// so that the next call to `read` returns that frame.
+    ///
+    /// If no such frame exists, an end of file error is returned.
+    ///
+    /// XTC and TRR files assume that times increase and bisect the frames
+    /// whose offsets are known. Frames of the same size, like those of TRR
+    /// files, are bisected by their offsets, so only a few headers are read.
+    /// Otherwise, frame headers are scanned forward. The position is kept on
+    /// errors.
+    ///
+    /// The default implementation looks at the following frames with `peek`
+    /// and skips them until the time is reached, starting from the current
+    /// position.
+    fn seek_to_time(&mut self, time: f32) -> Result<()> {
+        let mut frame = Frame::with_len(self.get_num_atoms()?);
+        loop {
+            self.peek(&mut frame)?;
+            if frame.time >= time {
+                return Ok(());
+            }
+            self.skip(1)?;
+        }
+    }
 
-        let num_atoms = self
-            .get_num_atoms()
-            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
-        if num_atoms != frame.coords.len() {
-            return Err((&*frame, num_atoms).into());
+    /// Advance over the next `n` frames, so that the following call to
+    /// `read` returns the frame after them. Skipping to the end is allowed,
+    /// but if fewer than `n` frames are left, an end of file error is
+    /// returned.
+    ///
+    /// XTC and TRR files skip frames using the sizes in their headers, so no
+    /// coordinates are decompressed and the position is kept on errors. The
+    /// default implementation reads the frames.
+    fn skip(&mut self, n: usize) -> Result<()> {
+        if n == 0 {
+            return Ok(());
         }
+        let mut frame = Frame::with_len(self.get_num_atoms()?);
+        for _ in 0..n {
+            self.read(&mut frame)?;
+        }
+        Ok(())
+    }
 
-        unsafe {
-            let code = xdrfile_xtc::read_xtc(
-                self.handle.xdrfile,
-                to!(num_atoms, ErrorTask::Read)?,
-                &mut step,
-                &mut frame.time,
-                &mut frame.box_vector,
-                frame.coords.as_mut_ptr(),
-                &mut self.precision.get(),
-            );
-            if let Some(err) = check_code(code, ErrorTask::Read) {
-                return Err(err);
-            }
-            frame.step = to!(step, ErrorTask::Read)?;
-            Ok(())
+    /// Check whether the trajectory is at its end without reading, so that
+    /// loops can stop before `read` fails.
+    ///
+    /// XTC and TRR files compare the number of remaining bytes to the size of
+    /// the smallest possible frame, so a few trailing bytes of a truncated
+    /// frame also count as the end. Files opened for writing or appending are
+    /// always at their end.
+    ///
+    /// The default implementation compares `current_frame` to
+    /// `known_num_frames` and returns an `Error::Unsupported` if either is
+    /// not known.
+    fn is_at_end(&mut self) -> Result<bool> {
+        match (self.current_frame(), self.known_num_frames()) {
+            (Some(current), Some(num_frames)) => Ok(current >= num_frames),
+            _ => Err(Error::Unsupported {
+                operation: "is_at_end",
+            }),
         }
     }
 
-    fn write(&mut self, frame: &Frame) -> Result<()> {
-        unsafe {
-            let code = xdrfile_xtc::write_xtc(
-                self.handle.xdrfile,
-                to!(frame.num_atoms(), ErrorTask::Write)?,
-                to!(frame.step, ErrorTask::Write)?,
-                frame.time,
-                &frame.box_vector,
-                frame.coords.as_ptr(),
-                1000.0,
-            );
-            if let Some(err) = check_code(code, ErrorTask::Write) {
-                Err(err)
-            } else {
-                Ok(())
-            }
+    /// Go back to the first frame, so that the trajectory can be read again
+    /// without reopening it. Rewinding an empty trajectory succeeds.
+    ///
+    /// XTC and TRR trajectories also implement `std::io::Seek::rewind`, so
+    /// call this as `Trajectory::rewind(&mut trajectory)` if both traits are
+    /// in scope.
+    fn rewind(&mut self) -> Result<()> {
+        match self.seek_to_frame(0) {
+            Err(e) if e.is_eof() => Ok(()),
+            result => result,
         }
     }
 
-    fn flush(&mut self) -> Result<()> {
-        unsafe {
-            let code = xdr_seek::xdr_flush(self.handle.xdrfile);
-            if let Some(err) = check_code(code, ErrorTask::Flush) {
-                Err(err)
-            } else {
-                Ok(())
+    /// Read the last frame of the trajectory. Afterwards, the trajectory is
+    /// positioned at its end.
+    ///
+    /// XTC and TRR files are searched backwards from their end, so the frames
+    /// before the last one do not have to be scanned.
+    fn last_frame(&mut self) -> Result<Frame> {
+        let num_frames = self.num_frames()?;
+        let last = num_frames
+            .checked_sub(1)
+            .ok_or_else(|| Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Read)))?;
+        self.seek_to_frame(last)?;
+        let mut frame = Frame::with_len(self.get_num_atoms()?);
+        self.read(&mut frame)?;
+        Ok(frame)
+    }
+
+    /// Get the times of all frames in the trajectory, e.g. for plotting.
+    ///
+    /// XTC and TRR files only scan the frame headers and keep their position.
+    /// The default implementation reads all frames from the start, leaving
+    /// the trajectory at its end.
+    fn get_times(&mut self) -> Result<Vec<f32>> {
+        let headers = read_headers(self)?;
+        Ok(headers.into_iter().map(|(_, time)| time).collect())
+    }
+
+    /// Get the steps of all frames in the trajectory, see `get_times`
+    fn get_steps(&mut self) -> Result<Vec<usize>> {
+        let headers = read_headers(self)?;
+        Ok(headers.into_iter().map(|(step, _)| step).collect())
+    }
+
+    /// Summarize the trajectory from its frame headers, see `TrajectoryStats`
+    fn stats(&mut self) -> Result<TrajectoryStats> {
+        TrajectoryStats::compute(self)
+    }
+
+    /// Read all remaining frames of the trajectory into memory.
+    ///
+    /// This is meant for small trajectories, use `into_iter` to process
+    /// large trajectories frame by frame instead.
+    fn read_all(&mut self) -> Result<Vec<Frame>> {
+        self.read_up_to(usize::MAX)
+    }
+
+    /// Read at most `max_frames` of the remaining frames of the trajectory
+    /// into memory. Fewer frames are returned if the trajectory ends before,
+    /// and an empty vector once it is at its end, so repeated calls process
+    /// a trajectory in windows of a fixed size.
+    fn read_up_to(&mut self, max_frames: usize) -> Result<Vec<Frame>> {
+        let num_atoms = self.get_num_atoms()?;
+        let mut frames = Vec::new();
+        while frames.len() < max_frames {
+            let mut frame = Frame::with_len(num_atoms);
+            match self.read(&mut frame) {
+                Ok(()) => frames.push(frame),
+                Err(e) if e.is_eof() => break,
+                Err(e) => return Err(e),
             }
         }
+        Ok(frames)
     }
 
-    fn get_num_atoms(&mut self) -> Result<usize> {
-        self.num_atoms
-            .get_or_create(|| {
-                let mut num_atoms: c_int = 0;
+    /// Read the next `n` frames into memory, or fewer if the trajectory ends
+    /// before, like `read_up_to`
+    ///
+    /// ```rust
+    /// use xdrfile::*;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+    ///     let frames = trajectory.read_n(10)?;
+    ///     assert_eq!(frames.len(), 10);
+    ///     assert_eq!(frames[9].step, 10);
+    ///     assert_eq!(trajectory.read_n(100)?.len(), 28);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn read_n(&mut self, n: usize) -> Result<Vec<Frame>> {
+        self.read_up_to(n)
+    }
 
-                unsafe {
-                    let path = path_to_cstring(&self.handle.path)?;
-                    let path_p = path.into_raw();
-                    let code = xdrfile_xtc::read_xtc_natoms(path_p, &mut num_atoms);
-                    // Reconstitute the CString so it is deallocated correctly
-                    let _ = CString::from_raw(path_p);
+    /// Read the remaining frames one by one into a single reused frame, see
+    /// `FrameReader`
+    fn frames_mut(&mut self) -> FrameReader<'_, Self>
+    where
+        Self: Sized,
+    {
+        FrameReader::new(self)
+    }
 
-                    if let Some(err) = check_code(code, ErrorTask::ReadNumAtoms) {
-                        Err(err)
-                    } else {
-                        to!(num_atoms, ErrorTask::ReadNumAtoms)
-                    }
-                }
-            })
-            .clone()
+    /// Evaluate `f` for every remaining frame and collect the results in
+    /// the order of the frames, e.g. to compute an observable of every
+    /// frame. The first error while reading is returned.
+    ///
+    /// With the `rayon` feature, frames are still read one after another,
+    /// but `f` is evaluated for several frames in parallel.
+    ///
+    /// ```rust
+    /// use xdrfile::*;
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+    ///     let sizes = trajectory.map_frames(|frame| frame.bounds().unwrap().size())?;
+    ///     assert_eq!(sizes.len(), 38);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn map_frames<T, F>(&mut self, f: F) -> Result<Vec<T>>
+    where
+        Self: Sized,
+        F: Fn(&Frame) -> T + Send + Sync,
+        T: Send,
+    {
+        parallel::map_frames(self, f)
     }
-}
 
-impl XTCTrajectory {
-    /// Get the current position in the file
-    pub fn tell(&self) -> u64 {
-        self.handle.tell()
+    /// Apply `transform` to every frame read from the trajectory, see
+    /// `TransformedTrajectory`. Further transforms are added to the same
+    /// pipeline with `TransformedTrajectory::with_transform`.
+    fn with_transform(
+        self,
+        transform: impl FrameTransform + Send + 'static,
+    ) -> TransformedTrajectory<Self>
+    where
+        Self: Sized,
+    {
+        TransformedTrajectory::new(self).with_transform(transform)
     }
-}
 
-impl io::Seek for XTCTrajectory {
-    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
-        self.handle.seek(pos)
+    /// Only write the frames selected by `downsampling` to the trajectory,
+    /// see `DownsampledTrajectory`
+    ///
+    /// # Panics
+    /// Panics if `downsampling` is `Every(0)`.
+    fn downsample(self, downsampling: Downsampling) -> DownsampledTrajectory<Self>
+    where
+        Self: Sized,
+    {
+        DownsampledTrajectory::new(self, downsampling)
     }
-}
 
-/// Handle to Read/Write TRR Trajectories
-pub struct TRRTrajectory {
-    handle: XDRFile,
-    num_atoms: Lazy<Result<usize>>,
-}
+    /// Iterate over the remaining frames in batches of `chunk_size` frames,
+    /// see `ChunkIterator`
+    ///
+    /// # Panics
+    /// Panics if `chunk_size` is zero.
+    fn iter_chunks(&mut self, chunk_size: usize) -> ChunkIterator<'_, Self>
+    where
+        Self: Sized,
+    {
+        ChunkIterator::new(self, chunk_size)
+    }
 
-impl TRRTrajectory {
-    pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<TRRTrajectory> {
-        let xdr = XDRFile::open(path, filemode)?;
-        Ok(TRRTrajectory {
-            handle: xdr,
-            num_atoms: Lazy::new(),
-        })
+    /// Turn the trajectory into an iterator over owned frames. Unlike
+    /// `into_iter`, the frames can be stored without cloning them.
+    fn into_frames(self) -> FrameIterator<Self>
+    where
+        Self: Sized,
+    {
+        iterator::into_iter_inner(self).into_frames()
     }
 
-    /// Open a file in read mode
-    pub fn open_read(path: impl AsRef<Path>) -> Result<Self> {
-        Self::open(path, FileMode::Read)
+    /// Turn the trajectory into an iterator over frames behind an `Arc`.
+    /// Unlike `into_iter`, the frames can be sent to other threads.
+    fn into_iter_arc(self) -> ArcFrameIterator<Self>
+    where
+        Self: Sized,
+    {
+        iterator::into_iter_inner(self).into_iter_arc()
     }
 
-    /// Open a file in append mode
+    /// Read the next step of the trajectory, but only copy the selected
+    /// atoms into `frame`. Selected indices beyond the number of atoms in the
+    /// trajectory are ignored.
+    ///
+    /// The full frame is still decoded, but into an internal buffer, so only
+    /// the small frame needs to be kept around.
+    fn read_selection(&mut self, selection: &AtomSelection, frame: &mut Frame) -> Result<()> {
+        selection::read_selection_into(self, &mut Frame::new(), selection, frame)
+    }
+}
+
+/// Check that all frames can be written to the same trajectory: every frame
+/// and its velocities and forces have as many atoms as the first frame, and
+/// all boxes are valid
+fn validate_frames(frames: &[&Frame]) -> Result<()> {
+    let num_atoms = match frames.first() {
+        Some(frame) => frame.len(),
+        None => return Ok(()),
+    };
+    for frame in frames {
+        let blocks = std::iter::once(&frame.coords)
+            .chain(&frame.velocities)
+            .chain(&frame.forces);
+        for block in blocks {
+            if block.len() != num_atoms {
+                return Err(Error::WrongSizeFrame {
+                    expected: num_atoms,
+                    found: block.len(),
+                });
+            }
+        }
+        frame.box_vector.validate()?;
+    }
+    Ok(())
+}
+
+/// Largest absolute value of compressed XTC coordinates, and largest range
+/// of them in every dimension, in units of `1 / precision`
+const XTC_MAX_ABS: f32 = (i32::MAX - 2) as f32;
+
+/// Check that all values of `frame` are finite and, if `precision` is set,
+/// that its coordinates can be compressed with it in the same pass
+fn check_values(frame: &Frame, precision: Option<f32>) -> Result<()> {
+    let invalid = |field, atom, value| {
+        Err(Error::InvalidValue {
+            field,
+            atom,
+            value,
+            reason: "not a finite number",
+        })
+    };
+    if !frame.time.is_finite() {
+        return invalid("time", None, frame.time);
+    }
+    for &value in frame.box_vector.as_matrix().as_flattened() {
+        if !value.is_finite() {
+            return invalid("box", None, value);
+        }
+    }
+    let mut compression =
+        precision.map(|precision| CompressionCheck::new(&frame.coords, precision));
+    for (atom, values) in frame.coords.iter().enumerate() {
+        for &value in values {
+            if !value.is_finite() {
+                return invalid("coordinates", Some(atom), value);
+            }
+        }
+        if let Some(compression) = &mut compression {
+            compression.add(atom, values)?;
+        }
+    }
+    if let Some(compression) = compression {
+        compression.finish()?;
+    }
+    let blocks = [
+        ("velocities", frame.velocities.as_ref()),
+        ("forces", frame.forces.as_ref()),
+    ];
+    for (field, block) in blocks {
+        for (atom, values) in block.into_iter().flatten().enumerate() {
+            for &value in values {
+                if !value.is_finite() {
+                    return invalid(field, Some(atom), value);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Find the reason why `coords` cannot be compressed with `precision`. The
+/// C library only reports that compressing failed.
+fn check_compression(coords: &[[f32; 3]], precision: f32) -> Result<()> {
+    let mut compression = CompressionCheck::new(coords, precision);
+    for (atom, values) in coords.iter().enumerate() {
+        compression.add(atom, values)?;
+    }
+    compression.finish()
+}
+
+/// Check whether coordinates can be compressed with a precision, one atom
+/// at a time
+struct CompressionCheck {
+    precision: f32,
+    /// False for frames of up to 9 atoms, which are stored uncompressed
+    compressed: bool,
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+impl CompressionCheck {
+    fn new(coords: &[[f32; 3]], precision: f32) -> CompressionCheck {
+        CompressionCheck {
+            precision,
+            compressed: coords.len() > 9,
+            min: [f32::INFINITY; 3],
+            max: [f32::NEG_INFINITY; 3],
+        }
+    }
+
+    fn error(&self, atom: Option<usize>, reason: &'static str) -> Error {
+        Error::XtcCompression {
+            atom,
+            precision: self.precision,
+            reason,
+        }
+    }
+
+    fn add(&mut self, atom: usize, values: &[f32; 3]) -> Result<()> {
+        if !self.compressed {
+            return Ok(());
+        }
+        for (dim, value) in values.iter().enumerate() {
+            let scaled = value * self.precision;
+            if !scaled.is_finite() {
+                return Err(self.error(Some(atom), "coordinate is not a finite number"));
+            }
+            if scaled.abs() > XTC_MAX_ABS {
+                return Err(self.error(Some(atom), "coordinate is too large"));
+            }
+            self.min[dim] = self.min[dim].min(scaled);
+            self.max[dim] = self.max[dim].max(scaled);
+        }
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<()> {
+        if (0..3).any(|dim| self.max[dim] - self.min[dim] >= XTC_MAX_ABS) {
+            return Err(self.error(None, "coordinates span too large a range"));
+        }
+        Ok(())
+    }
+}
+
+/// Read step and time of all frames of a trajectory from the start
+fn read_headers<T: Trajectory + ?Sized>(trajectory: &mut T) -> Result<Vec<(usize, f32)>> {
+    match trajectory.seek_to_frame(0) {
+        Ok(()) => {}
+        Err(e) if e.is_eof() => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    }
+    let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+    let mut headers = Vec::new();
+    loop {
+        match trajectory.read(&mut frame) {
+            Ok(()) => headers.push((frame.step, frame.time)),
+            Err(e) if e.is_eof() => return Ok(headers),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl<T: Trajectory + ?Sized> Trajectory for Box<T> {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        (**self).read(frame)
+    }
+
+    fn peek(&mut self, frame: &mut Frame) -> Result<()> {
+        (**self).peek(frame)
+    }
+
+    fn read_into(&mut self, coords: &mut [[f32; 3]], header: &mut FrameHeader) -> Result<()> {
+        (**self).read_into(coords, header)
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        (**self).write(frame)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        (**self).get_num_atoms()
+    }
+
+    fn num_frames(&mut self) -> Result<usize> {
+        (**self).num_frames()
+    }
+
+    fn known_num_frames(&self) -> Option<usize> {
+        (**self).known_num_frames()
+    }
+
+    fn current_frame(&self) -> Option<usize> {
+        (**self).current_frame()
+    }
+
+    fn seek_to_frame(&mut self, frame: usize) -> Result<()> {
+        (**self).seek_to_frame(frame)
+    }
+
+    fn seek_to_time(&mut self, time: f32) -> Result<()> {
+        (**self).seek_to_time(time)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        (**self).skip(n)
+    }
+
+    fn is_at_end(&mut self) -> Result<bool> {
+        (**self).is_at_end()
+    }
+
+    fn last_frame(&mut self) -> Result<Frame> {
+        (**self).last_frame()
+    }
+
+    fn get_times(&mut self) -> Result<Vec<f32>> {
+        (**self).get_times()
+    }
+
+    fn get_steps(&mut self) -> Result<Vec<usize>> {
+        (**self).get_steps()
+    }
+
+    fn read_selection(&mut self, selection: &AtomSelection, frame: &mut Frame) -> Result<()> {
+        (**self).read_selection(selection, frame)
+    }
+}
+
+/// Handle to Read/Write XTC Trajectories
+pub struct XTCTrajectory {
+    handle: XDRFile,
+    precision: Cell<c_float>, // internal mutability required for read method
+    num_atoms: Lazy<Result<usize>>,
+    num_frames: Lazy<Result<usize>>,
+    index: TrajectoryIndex,
+    /// Full size frame reused by `read_selection`
+    scratch: Frame,
+}
+
+impl XTCTrajectory {
+    pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<XTCTrajectory> {
+        let xdr = XDRFile::open(path, filemode)?;
+        Ok(Self::from_handle(xdr))
+    }
+
+    /// Open a trajectory for reading from any seekable source instead of a
+    /// file on disk, e.g. a network stream or a decompressing reader
+    pub fn from_reader(reader: impl io::Read + io::Seek + Send + 'static) -> Result<XTCTrajectory> {
+        let (stream, io) = Stream::reader(reader);
+        let xdr = XDRFile::open_stream(stream, io, FileMode::Read);
+        Ok(Self::from_handle(xdr))
+    }
+
+    /// Open a trajectory backed by an in-memory buffer instead of a file.
+    ///
+    /// Like for files, `FileMode::Write` discards the contents of `bytes`,
+    /// `FileMode::Append` writes after them and `FileMode::Read` reads them.
+    pub fn from_bytes(bytes: Vec<u8>, filemode: FileMode) -> Result<XTCTrajectory> {
+        let (stream, io) = Stream::read_write(memory_buffer(bytes, &filemode));
+        let xdr = XDRFile::open_stream(stream, io, filemode);
+        Ok(Self::from_handle(xdr))
+    }
+
+    /// Close the trajectory and get the contents of its in-memory buffer.
+    /// Returns None if the trajectory was not opened with `from_bytes`.
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        let stream = self.handle.into_stream()?;
+        let buffer = stream.into_inner::<io::Cursor<Vec<u8>>>()?;
+        Some(buffer.into_inner())
+    }
+
+    fn from_handle(handle: XDRFile) -> XTCTrajectory {
+        XTCTrajectory {
+            handle,
+            precision: Cell::new(1000.0),
+            num_atoms: Lazy::new(),
+            num_frames: Lazy::new(),
+            index: TrajectoryIndex::default(),
+            scratch: Frame::new(),
+        }
+    }
+
+    /// Open a file in read mode
+    pub fn open_read(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, FileMode::Read)
+    }
+
+    /// Open a file in append mode
+    pub fn open_append(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, FileMode::Append)
+    }
+
+    /// Open a file in append mode after checking that it ends with a complete
+    /// frame, e.g. after a simulation crashed while writing it.
+    ///
+    /// If the last frame is incomplete, it is removed like with `repair` if
+    /// `truncate` is true, and an `Error::IncompleteFrame` is returned
+    /// otherwise. Missing and empty files are opened like with
+    /// `open_append`.
+    pub fn open_append_checked(path: impl AsRef<Path>, truncate: bool) -> Result<Self> {
+        repair::check_tail(path.as_ref(), index::skip_xtc_frame, truncate)?;
+        Self::open_append(path)
+    }
+
+    /// Open a file in write mode
+    pub fn open_write(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, FileMode::Write)
+    }
+}
+
+impl Trajectory for XTCTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        let mut header = frame.header();
+        self.read_into(&mut frame.coords, &mut header)?;
+        // XTC files only store coordinates
+        frame.velocities = None;
+        frame.forces = None;
+        frame.step = header.step;
+        frame.time = header.time;
+        frame.box_vector = header.box_vector;
+        Ok(())
+    }
+
+    fn read_into(&mut self, coords: &mut [[f32; 3]], header: &mut FrameHeader) -> Result<()> {
+        self.handle.check_position()?;
+        let mut step: c_int = 0;
+
+        let num_atoms = self
+            .get_num_atoms()
+            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
+        if num_atoms != coords.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: num_atoms,
+                found: coords.len(),
+            });
+        }
+        let start = self.handle.tell()?;
+
+        unsafe {
+            let code = xdrfile_xtc::read_xtc(
+                self.handle.xdrfile,
+                to!(num_atoms, ErrorTask::Read)?,
+                &mut step,
+                &mut header.time,
+                header.box_vector.as_mut_matrix(),
+                coords.as_mut_ptr(),
+                &mut self.precision.get(),
+            );
+            if let Some(err) = check_code(code, ErrorTask::Read) {
+                let err = self.handle.frame_error(err);
+                return Err(self.index.skip_corrupt(
+                    &mut self.handle,
+                    start,
+                    err,
+                    index::skip_xtc_frame,
+                ));
+            }
+            header.step = to!(step, ErrorTask::Read)?;
+            self.handle.frame_done();
+            self.handle.report_progress();
+            Ok(())
+        }
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.write_with_precision(frame, None)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        unsafe {
+            let code = xdr_seek::xdr_flush(self.handle.xdrfile);
+            if let Some(err) = check_code(code, ErrorTask::Flush) {
+                Err(err)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        let XTCTrajectory {
+            handle,
+            num_atoms,
+            index,
+            ..
+        } = self;
+        num_atoms
+            .get_or_create(|| {
+                // The XDR handle cannot read in write and append mode
+                if handle.filemode == FileMode::Read {
+                    index.num_atoms(handle, index::skip_xtc_frame)
+                } else {
+                    index::num_atoms_written(handle, index::skip_xtc_frame)
+                }
+            })
+            .clone()
+    }
+
+    fn num_frames(&mut self) -> Result<usize> {
+        // The C function does not validate the file, so check it is readable first
+        self.get_num_atoms()?;
+        let XTCTrajectory {
+            handle,
+            num_frames,
+            index,
+            ..
+        } = self;
+        num_frames
+            .get_or_create(|| {
+                // Frames written by this handle may still be buffered
+                handle.flush_written()?;
+                match &handle.path {
+                    // The XDR handle cannot read in write and append mode
+                    Some(path) if handle.filemode != FileMode::Read => {
+                        count_frames(path, xdrfile_xtc::read_xtc_nframes, index::skip_xtc_frame)
+                    }
+                    // Only the headers are read, and the offsets of the
+                    // frames are kept for seeking
+                    _ => index.num_frames(handle, index::skip_xtc_frame),
+                }
+            })
+            .clone()
+    }
+
+    fn known_num_frames(&self) -> Option<usize> {
+        self.num_frames.get()?.as_ref().ok().copied()
+    }
+
+    fn current_frame(&self) -> Option<usize> {
+        self.handle.frame
+    }
+
+    fn seek_to_frame(&mut self, frame: usize) -> Result<()> {
+        let entry = self
+            .index
+            .entry(&mut self.handle, frame, index::skip_xtc_frame)?;
+        self.handle.seek_to(SeekFrom::Start(entry.offset))?;
+        self.handle.frame = Some(frame);
+        Ok(())
+    }
+
+    fn seek_to_time(&mut self, time: f32) -> Result<()> {
+        self.index
+            .seek_to_time(&mut self.handle, time, index::skip_xtc_frame)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.index.skip(&mut self.handle, n, index::skip_xtc_frame)
+    }
+
+    fn is_at_end(&mut self) -> Result<bool> {
+        self.handle.is_at_end(index::MIN_XTC_FRAME_SIZE)
+    }
+
+    fn peek(&mut self, frame: &mut Frame) -> Result<()> {
+        let (pos, current) = (self.handle.tell()?, self.handle.frame);
+        // The frame is read again later, so it is not reported yet
+        let progress = self.handle.progress.take();
+        let result = self.read(frame);
+        self.handle.progress = progress;
+        self.handle.seek_to(SeekFrom::Start(pos))?;
+        self.handle.frame = current;
+        result
+    }
+
+    fn last_frame(&mut self) -> Result<Frame> {
+        let num_atoms = self.get_num_atoms()?;
+        let entry = self
+            .index
+            .last_entry(&mut self.handle, index::skip_xtc_frame)?;
+        self.handle.seek_to(SeekFrom::Start(entry.offset))?;
+        let mut frame = Frame::with_len(num_atoms);
+        self.read(&mut frame)?;
+        Ok(frame)
+    }
+
+    fn get_times(&mut self) -> Result<Vec<f32>> {
+        let entries = self
+            .index
+            .entries(&mut self.handle, index::skip_xtc_frame)?;
+        Ok(entries.iter().map(|entry| entry.time).collect())
+    }
+
+    fn get_steps(&mut self) -> Result<Vec<usize>> {
+        let entries = self
+            .index
+            .entries(&mut self.handle, index::skip_xtc_frame)?;
+        Ok(entries.iter().map(|entry| entry.step).collect())
+    }
+
+    fn read_selection(&mut self, selection: &AtomSelection, frame: &mut Frame) -> Result<()> {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        let result = selection::read_selection_into(self, &mut scratch, selection, frame);
+        self.scratch = scratch;
+        result
+    }
+}
+
+impl XTCTrajectory {
+    /// Set the precision written frames are compressed with. Coordinates are
+    /// stored with `1 / precision` nm resolution, the default is 1000.
+    pub fn set_precision(&mut self, precision: f32) {
+        self.precision.set(precision)
+    }
+
+    /// Precision that written frames are compressed with, see
+    /// `set_precision`
+    pub fn precision(&self) -> f32 {
+        self.precision.get()
+    }
+
+    /// Write a frame compressed with `precision` instead of the precision
+    /// set with `set_precision`, if given. This allows storing some frames,
+    /// like those of an equilibration, with a lower resolution than others.
+    /// The precision set for the trajectory is not changed.
+    pub fn write_with_precision(&mut self, frame: &Frame, precision: Option<f32>) -> Result<()> {
+        self.handle.check_position()?;
+        frame.box_vector.validate()?;
+        let precision = precision.unwrap_or_else(|| self.precision.get());
+        if self.handle.validate {
+            check_values(frame, Some(precision))?;
+        }
+        // Appended frames must match the frames already in the file
+        if self.handle.filemode == FileMode::Append {
+            if let Ok(num_atoms) = self.get_num_atoms() {
+                if num_atoms != frame.len() {
+                    return Err((frame, num_atoms).into());
+                }
+            }
+        }
+        unsafe {
+            let code = xdrfile_xtc::write_xtc(
+                self.handle.xdrfile,
+                to!(frame.num_atoms(), ErrorTask::Write)?,
+                to!(frame.step, ErrorTask::Write)?,
+                frame.time,
+                frame.box_vector.as_matrix(),
+                frame.coords.as_ptr(),
+                precision,
+            );
+            if let Some(err) = check_code(code, ErrorTask::Write) {
+                // Without validation, coordinates that cannot be compressed
+                // are only looked for once compressing failed
+                let err = self.handle.frame_error(err);
+                Err(check_compression(&frame.coords, precision)
+                    .err()
+                    .unwrap_or(err))
+            } else {
+                self.handle.frame_done();
+                count_written_frame(&mut self.num_atoms, &mut self.num_frames);
+                Ok(())
+            }
+        }
+    }
+
+    /// Open a second, independent handle for the same file at the same
+    /// position, e.g. to read the trajectory from another thread. Frame
+    /// offsets found so far are shared, the progress callback is not.
+    ///
+    /// Only trajectories opened for reading from a path can be cloned.
+    pub fn try_clone(&self) -> Result<XTCTrajectory> {
+        Ok(XTCTrajectory {
+            handle: self.handle.try_clone()?,
+            precision: self.precision.clone(),
+            num_atoms: clone_lazy(&self.num_atoms),
+            num_frames: clone_lazy(&self.num_frames),
+            index: self.index.clone(),
+            scratch: Frame::new(),
+        })
+    }
+
+    /// Iterate over the step, time and byte offset of every frame, reading
+    /// only the frame headers, see `HeaderIterator`
+    pub fn iter_headers(&mut self) -> HeaderIterator<'_> {
+        HeaderIterator::new(&mut self.handle, &mut self.index, index::skip_xtc_frame)
+    }
+
+    /// Read the frames with the given indices concurrently and return them
+    /// in the same order. The position of the trajectory is kept.
+    ///
+    /// The offsets of the frames are determined first, then the frames are
+    /// decoded by several threads, each with its own handle opened with
+    /// `try_clone`. This is much faster than reading the frames one by one
+    /// if many frames at random positions are needed.
+    pub fn read_frames_parallel(&mut self, frames: &[usize]) -> Result<Vec<Frame>> {
+        if let Some(&last) = frames.iter().max() {
+            self.get_num_atoms()?;
+            self.index
+                .visit_up_to(&mut self.handle, last, index::skip_xtc_frame)?;
+        }
+        let handles = (0..parallel::num_threads(frames.len()))
+            .map(|_| self.try_clone())
+            .collect::<Result<Vec<XTCTrajectory>>>()?;
+        parallel::read_frames(handles, frames)
+    }
+
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        self.handle.tell()
+    }
+
+    /// Flush and close the trajectory. Unlike dropping it, errors while
+    /// writing the remaining data to disk are returned.
+    pub fn close(mut self) -> Result<()> {
+        self.handle.close()
+    }
+
+    /// If enabled, errors while flushing and closing the trajectory on drop
+    /// are reported on stderr instead of being ignored. Dropping never
+    /// panics, use `close` to handle these errors.
+    pub fn set_flush_on_drop(&mut self, flush_on_drop: bool) {
+        self.handle.flush_on_drop = flush_on_drop;
+    }
+
+    /// Enable or disable the recovery mode for corrupt frames.
+    ///
+    /// If a frame cannot be decoded in recovery mode, the file is scanned
+    /// forward for the start of the next valid frame. `read` then returns an
+    /// `Error::CorruptRegion` for the skipped bytes and continues with the
+    /// next valid frame on the following call. Iterators yield the error and
+    /// keep going instead of stopping.
+    pub fn set_resync(&mut self, resync: bool) {
+        self.handle.resync = resync;
+    }
+
+    /// Enable or disable checking the values of frames before writing them.
+    ///
+    /// With validation, `write` returns an `Error::InvalidValue` for frames
+    /// with NaN or infinite values, e.g. in the time or the box, and an
+    /// `Error::XtcCompression` for coordinates that cannot be compressed
+    /// with the precision of the trajectory, before anything is written.
+    /// Validation is disabled by default, as it reads every value of the
+    /// frame once more. Without it, coordinates that cannot be compressed
+    /// are still rejected with an `Error::XtcCompression`, but only after
+    /// the start of the frame was written, which leaves an incomplete frame
+    /// at the end of the file, see `repair`.
+    pub fn set_validate(&mut self, validate: bool) {
+        self.handle.validate = validate;
+    }
+
+    /// Call `callback` with the progress after every frame that is read or
+    /// scanned, e.g. while building the frame index, in `read_all` or when
+    /// converting the trajectory. Replaces any previous callback.
+    pub fn set_progress(&mut self, callback: impl FnMut(Progress) + Send + 'static) -> Result<()> {
+        self.handle.set_progress(Box::new(callback))
+    }
+
+    /// Stop reporting progress
+    pub fn clear_progress(&mut self) {
+        self.handle.progress = None;
+    }
+
+    /// Save the offsets of all frames to an index file at `path`, so that
+    /// seeking in the trajectory does not require scanning it again after
+    /// reopening it. Frames not visited yet are scanned first.
+    pub fn save_index(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.index
+            .save(&mut self.handle, index::skip_xtc_frame, path.as_ref())
+    }
+
+    /// Load the frame offsets from an index file written by `save_index`.
+    ///
+    /// Returns false and keeps the current offsets if the size or the
+    /// modification time of the trajectory file changed since the index was
+    /// saved.
+    pub fn load_index(&mut self, path: impl AsRef<Path>) -> Result<bool> {
+        match TrajectoryIndex::load(&self.handle, path.as_ref())? {
+            Some(index) => {
+                self.index = index;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl io::Seek for XTCTrajectory {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.handle.seek(pos)
+    }
+}
+
+/// Handle to Read/Write TRR Trajectories
+pub struct TRRTrajectory {
+    handle: XDRFile,
+    num_atoms: Lazy<Result<usize>>,
+    num_frames: Lazy<Result<usize>>,
+    index: TrajectoryIndex,
+    /// Full size frame reused by `read_selection`
+    scratch: Frame,
+}
+
+impl TRRTrajectory {
+    pub fn open(path: impl AsRef<Path>, filemode: FileMode) -> Result<TRRTrajectory> {
+        let xdr = XDRFile::open(path, filemode)?;
+        Ok(Self::from_handle(xdr))
+    }
+
+    /// Open a trajectory for reading from any seekable source instead of a
+    /// file on disk, e.g. a network stream or a decompressing reader
+    pub fn from_reader(reader: impl io::Read + io::Seek + Send + 'static) -> Result<TRRTrajectory> {
+        let (stream, io) = Stream::reader(reader);
+        let xdr = XDRFile::open_stream(stream, io, FileMode::Read);
+        Ok(Self::from_handle(xdr))
+    }
+
+    /// Open a trajectory backed by an in-memory buffer instead of a file.
+    ///
+    /// Like for files, `FileMode::Write` discards the contents of `bytes`,
+    /// `FileMode::Append` writes after them and `FileMode::Read` reads them.
+    pub fn from_bytes(bytes: Vec<u8>, filemode: FileMode) -> Result<TRRTrajectory> {
+        let (stream, io) = Stream::read_write(memory_buffer(bytes, &filemode));
+        let xdr = XDRFile::open_stream(stream, io, filemode);
+        Ok(Self::from_handle(xdr))
+    }
+
+    /// Close the trajectory and get the contents of its in-memory buffer.
+    /// Returns None if the trajectory was not opened with `from_bytes`.
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        let stream = self.handle.into_stream()?;
+        let buffer = stream.into_inner::<io::Cursor<Vec<u8>>>()?;
+        Some(buffer.into_inner())
+    }
+
+    fn from_handle(handle: XDRFile) -> TRRTrajectory {
+        TRRTrajectory {
+            handle,
+            num_atoms: Lazy::new(),
+            num_frames: Lazy::new(),
+            index: TrajectoryIndex::default(),
+            scratch: Frame::new(),
+        }
+    }
+
+    /// Open a file in read mode
+    pub fn open_read(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, FileMode::Read)
+    }
+
+    /// Open a file in append mode
     pub fn open_append(path: impl AsRef<Path>) -> Result<Self> {
         Self::open(path, FileMode::Append)
     }
 
-    /// Open a file in write mode
-    pub fn open_write(path: impl AsRef<Path>) -> Result<Self> {
-        Self::open(path, FileMode::Write)
+    /// Open a file in append mode after checking that it ends with a complete
+    /// frame, see `XTCTrajectory::open_append_checked`
+    pub fn open_append_checked(path: impl AsRef<Path>, truncate: bool) -> Result<Self> {
+        repair::check_tail(path.as_ref(), index::skip_trr_frame, truncate)?;
+        Self::open_append(path)
+    }
+
+    /// Open a file in write mode
+    pub fn open_write(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, FileMode::Write)
+    }
+}
+
+/// Make an optional per-atom block of a frame present with `num_atoms`
+/// entries or absent, and get a pointer to read it into
+fn match_block(
+    block: &mut Option<Vec<[f32; 3]>>,
+    present: bool,
+    num_atoms: usize,
+) -> *mut [f32; 3] {
+    if !present {
+        *block = None;
+        return std::ptr::null_mut();
+    }
+    let block = block.get_or_insert_with(Vec::new);
+    block.resize(num_atoms, [0.0; 3]);
+    block.as_mut_ptr()
+}
+
+/// Get a pointer to write an optional per-atom block of a frame, null if
+/// the block is absent
+fn block_ptr(block: &Option<Vec<[f32; 3]>>, frame: &Frame) -> Result<*const [f32; 3]> {
+    match block {
+        None => Ok(std::ptr::null()),
+        Some(block) if block.len() == frame.len() => Ok(block.as_ptr()),
+        Some(block) => Err(Error::WrongSizeFrame {
+            expected: frame.len(),
+            found: block.len(),
+        }),
+    }
+}
+
+impl Trajectory for TRRTrajectory {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        let num_atoms = self
+            .get_num_atoms()
+            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
+        if num_atoms != frame.coords.len() {
+            return Err((&*frame, num_atoms).into());
+        }
+
+        // The C code only fills the blocks present in the file, so match the
+        // blocks of the frame to those of the file first
+        let start = self.handle.tell()?;
+        let mut trr_header = self.read_header(start)?;
+        let velocities = match_block(&mut frame.velocities, trr_header.v_size != 0, num_atoms);
+        let forces = match_block(&mut frame.forces, trr_header.f_size != 0, num_atoms);
+        let mut frame_header = frame.header();
+        self.read_blocks(
+            start,
+            &mut trr_header,
+            &mut frame.coords,
+            velocities,
+            forces,
+            &mut frame_header,
+        )?;
+        frame.step = frame_header.step;
+        frame.time = frame_header.time;
+        frame.box_vector = frame_header.box_vector;
+        Ok(())
+    }
+
+    fn read_into(&mut self, coords: &mut [[f32; 3]], header: &mut FrameHeader) -> Result<()> {
+        let num_atoms = self
+            .get_num_atoms()
+            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
+        if num_atoms != coords.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: num_atoms,
+                found: coords.len(),
+            });
+        }
+        let start = self.handle.tell()?;
+        let mut trr_header = self.read_header(start)?;
+        let null = std::ptr::null_mut();
+        self.read_blocks(start, &mut trr_header, coords, null, null, header)
+    }
+
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.handle.check_position()?;
+        frame.box_vector.validate()?;
+        // Appended frames must match the frames already in the file
+        if self.handle.filemode == FileMode::Append {
+            if let Ok(num_atoms) = self.get_num_atoms() {
+                if num_atoms != frame.len() {
+                    return Err((frame, num_atoms).into());
+                }
+            }
+        }
+        if self.handle.validate {
+            check_values(frame, None)?;
+        }
+        let velocities = block_ptr(&frame.velocities, frame)?;
+        let forces = block_ptr(&frame.forces, frame)?;
+        unsafe {
+            let code = xdrfile_trr::write_trr(
+                self.handle.xdrfile,
+                to!(frame.len(), ErrorTask::Write)?,
+                to!(frame.step, ErrorTask::Write)?,
+                frame.time,
+                0.0,
+                frame.box_vector.as_matrix(),
+                frame.coords[..].as_ptr(),
+                velocities,
+                forces,
+            );
+            if let Some(err) = check_code(code, ErrorTask::Write) {
+                Err(self.handle.frame_error(err))
+            } else {
+                self.handle.frame_done();
+                count_written_frame(&mut self.num_atoms, &mut self.num_frames);
+                Ok(())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        unsafe {
+            let code = xdr_seek::xdr_flush(self.handle.xdrfile);
+            if let Some(err) = check_code(code, ErrorTask::Flush) {
+                Err(err)
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        let TRRTrajectory {
+            handle,
+            num_atoms,
+            index,
+            ..
+        } = self;
+        num_atoms
+            .get_or_create(|| {
+                // The XDR handle cannot read in write and append mode
+                if handle.filemode == FileMode::Read {
+                    index.num_atoms(handle, index::skip_trr_frame)
+                } else {
+                    index::num_atoms_written(handle, index::skip_trr_frame)
+                }
+            })
+            .clone()
+    }
+
+    fn num_frames(&mut self) -> Result<usize> {
+        // The C function does not validate the file, so check it is readable first
+        self.get_num_atoms()?;
+        let TRRTrajectory {
+            handle,
+            num_frames,
+            index,
+            ..
+        } = self;
+        num_frames
+            .get_or_create(|| {
+                // Frames written by this handle may still be buffered
+                handle.flush_written()?;
+                match &handle.path {
+                    // The XDR handle cannot read in write and append mode
+                    Some(path) if handle.filemode != FileMode::Read => {
+                        count_frames(path, xdrfile_trr::read_trr_nframes, index::skip_trr_frame)
+                    }
+                    // Only the headers are read, and the offsets of the
+                    // frames are kept for seeking
+                    _ => index.num_frames(handle, index::skip_trr_frame),
+                }
+            })
+            .clone()
+    }
+
+    fn known_num_frames(&self) -> Option<usize> {
+        self.num_frames.get()?.as_ref().ok().copied()
+    }
+
+    fn current_frame(&self) -> Option<usize> {
+        self.handle.frame
+    }
+
+    fn seek_to_frame(&mut self, frame: usize) -> Result<()> {
+        let entry = self
+            .index
+            .entry(&mut self.handle, frame, index::skip_trr_frame)?;
+        self.handle.seek_to(SeekFrom::Start(entry.offset))?;
+        self.handle.frame = Some(frame);
+        Ok(())
+    }
+
+    fn seek_to_time(&mut self, time: f32) -> Result<()> {
+        self.index
+            .seek_to_time(&mut self.handle, time, index::skip_trr_frame)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.index.skip(&mut self.handle, n, index::skip_trr_frame)
+    }
+
+    fn is_at_end(&mut self) -> Result<bool> {
+        self.handle.is_at_end(index::MIN_TRR_FRAME_SIZE)
+    }
+
+    fn peek(&mut self, frame: &mut Frame) -> Result<()> {
+        let (pos, current) = (self.handle.tell()?, self.handle.frame);
+        // The frame is read again later, so it is not reported yet
+        let progress = self.handle.progress.take();
+        let result = self.read(frame);
+        self.handle.progress = progress;
+        self.handle.seek_to(SeekFrom::Start(pos))?;
+        self.handle.frame = current;
+        result
+    }
+
+    fn last_frame(&mut self) -> Result<Frame> {
+        let num_atoms = self.get_num_atoms()?;
+        let entry = self
+            .index
+            .last_entry(&mut self.handle, index::skip_trr_frame)?;
+        self.handle.seek_to(SeekFrom::Start(entry.offset))?;
+        let mut frame = Frame::with_len(num_atoms);
+        self.read(&mut frame)?;
+        Ok(frame)
+    }
+
+    fn get_times(&mut self) -> Result<Vec<f32>> {
+        let entries = self
+            .index
+            .entries(&mut self.handle, index::skip_trr_frame)?;
+        Ok(entries.iter().map(|entry| entry.time).collect())
+    }
+
+    fn get_steps(&mut self) -> Result<Vec<usize>> {
+        let entries = self
+            .index
+            .entries(&mut self.handle, index::skip_trr_frame)?;
+        Ok(entries.iter().map(|entry| entry.step).collect())
+    }
+
+    fn read_selection(&mut self, selection: &AtomSelection, frame: &mut Frame) -> Result<()> {
+        let mut scratch = std::mem::take(&mut self.scratch);
+        let result = selection::read_selection_into(self, &mut scratch, selection, frame);
+        self.scratch = scratch;
+        result
+    }
+}
+
+impl TRRTrajectory {
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        self.handle.tell()
+    }
+
+    /// Open a second, independent handle for the same file at the same
+    /// position, see `XTCTrajectory::try_clone`
+    pub fn try_clone(&self) -> Result<TRRTrajectory> {
+        Ok(TRRTrajectory {
+            handle: self.handle.try_clone()?,
+            num_atoms: clone_lazy(&self.num_atoms),
+            num_frames: clone_lazy(&self.num_frames),
+            index: self.index.clone(),
+            scratch: Frame::new(),
+        })
+    }
+
+    /// Read the header of the frame starting at `start`
+    fn read_header(&mut self, start: u64) -> Result<xdrfile_trr::t_trnheader> {
+        self.handle.check_position()?;
+        index::read_trr_header(&mut self.handle, ErrorTask::Read)
+            .map_err(|err| self.corrupt_frame(start, err))
+    }
+
+    /// Read the data of the frame starting at `start`, whose header
+    /// `trr_header` was just read, into `coords` and `header`, and its
+    /// velocities and forces into the given blocks unless they are null
+    fn read_blocks(
+        &mut self,
+        start: u64,
+        trr_header: &mut xdrfile_trr::t_trnheader,
+        coords: &mut [[f32; 3]],
+        velocities: *mut [f32; 3],
+        forces: *mut [f32; 3],
+        header: &mut FrameHeader,
+    ) -> Result<()> {
+        // The C code reads as many atoms as the header states
+        let num_atoms: usize = to!(trr_header.natoms, ErrorTask::Read)?;
+        if num_atoms != coords.len() {
+            let err = Error::from((ErrorCode::ExdrHeader, ErrorTask::Read));
+            return Err(self.corrupt_frame(start, err));
+        }
+        let code = unsafe {
+            xdrfile_trr::read_trr_data(
+                self.handle.xdrfile,
+                trr_header,
+                header.box_vector.as_mut_matrix(),
+                coords.as_mut_ptr(),
+                velocities,
+                forces,
+            )
+        };
+        if let Some(err) = check_code(code, ErrorTask::Read) {
+            return Err(self.corrupt_frame(start, err));
+        }
+        header.step = to!(trr_header.step, ErrorTask::Read)?;
+        header.time = trr_header.td as f32;
+        self.handle.frame_done();
+        self.handle.report_progress();
+        Ok(())
+    }
+
+    /// Turn `err` while reading the frame starting at `start` into the
+    /// error returned by `read`, skipping the frame in recovery mode
+    fn corrupt_frame(&mut self, start: u64, err: Error) -> Error {
+        let err = self.handle.frame_error(err);
+        self.index
+            .skip_corrupt(&mut self.handle, start, err, index::skip_trr_frame)
+    }
+
+    /// Iterate over the step, time and byte offset of every frame, reading
+    /// only the frame headers, see `XTCTrajectory::iter_headers`
+    pub fn iter_headers(&mut self) -> HeaderIterator<'_> {
+        HeaderIterator::new(&mut self.handle, &mut self.index, index::skip_trr_frame)
+    }
+
+    /// Read the frames with the given indices concurrently and return them
+    /// in the same order, see `XTCTrajectory::read_frames_parallel`
+    pub fn read_frames_parallel(&mut self, frames: &[usize]) -> Result<Vec<Frame>> {
+        if let Some(&last) = frames.iter().max() {
+            self.get_num_atoms()?;
+            self.index
+                .visit_up_to(&mut self.handle, last, index::skip_trr_frame)?;
+        }
+        let handles = (0..parallel::num_threads(frames.len()))
+            .map(|_| self.try_clone())
+            .collect::<Result<Vec<TRRTrajectory>>>()?;
+        parallel::read_frames(handles, frames)
+    }
+
+    /// Flush and close the trajectory. Unlike dropping it, errors while
+    /// writing the remaining data to disk are returned.
+    pub fn close(mut self) -> Result<()> {
+        self.handle.close()
     }
-}
 
-impl Trajectory for TRRTrajectory {
-    fn read(&mut self, frame: &mut Frame) -> Result<()> {
-        let mut step: c_int = 0;
-        let mut lambda: c_float = 0.0;
+    /// If enabled, errors while flushing and closing the trajectory on drop
+    /// are reported on stderr instead of being ignored. Dropping never
+    /// panics, use `close` to handle these errors.
+    pub fn set_flush_on_drop(&mut self, flush_on_drop: bool) {
+        self.handle.flush_on_drop = flush_on_drop;
+    }
 
-        let num_atoms = self
-            .get_num_atoms()
-            .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
-        if num_atoms != frame.coords.len() {
-            return Err((&*frame, num_atoms).into());
-        }
+    /// Enable or disable the recovery mode for corrupt frames.
+    ///
+    /// If a frame cannot be decoded in recovery mode, the file is scanned
+    /// forward for the start of the next valid frame. `read` then returns an
+    /// `Error::CorruptRegion` for the skipped bytes and continues with the
+    /// next valid frame on the following call. Iterators yield the error and
+    /// keep going instead of stopping.
+    pub fn set_resync(&mut self, resync: bool) {
+        self.handle.resync = resync;
+    }
 
-        unsafe {
-            let code = xdrfile_trr::read_trr(
-                self.handle.xdrfile,
-                to!(num_atoms, ErrorTask::Read)?,
-                &mut step,
-                &mut frame.time,
-                &mut lambda,
-                &mut frame.box_vector,
-                frame.coords.as_mut_ptr(),
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
-            );
-            if let Some(err) = check_code(code, ErrorTask::Read) {
-                return Err(err);
-            }
-            frame.step = to!(step, ErrorTask::Read)?;
-            Ok(())
-        }
+    /// Enable or disable checking the values of frames for NaN and infinite
+    /// values before writing them, see `XTCTrajectory::set_validate`
+    pub fn set_validate(&mut self, validate: bool) {
+        self.handle.validate = validate;
     }
 
-    fn write(&mut self, frame: &Frame) -> Result<()> {
-        unsafe {
-            let code = xdrfile_trr::write_trr(
-                self.handle.xdrfile,
-                to!(frame.len(), ErrorTask::Write)?,
-                to!(frame.step, ErrorTask::Write)?,
-                frame.time,
-                0.0,
-                &frame.box_vector,
-                frame.coords[..].as_ptr(),
-                std::ptr::null_mut(),
-                std::ptr::null_mut(),
-            );
-            if let Some(err) = check_code(code, ErrorTask::Write) {
-                Err(err)
-            } else {
-                Ok(())
-            }
-        }
+    /// Call `callback` with the progress after every frame that is read or
+    /// scanned, e.g. while building the frame index, in `read_all` or when
+    /// converting the trajectory. Replaces any previous callback.
+    pub fn set_progress(&mut self, callback: impl FnMut(Progress) + Send + 'static) -> Result<()> {
+        self.handle.set_progress(Box::new(callback))
     }
 
-    fn flush(&mut self) -> Result<()> {
-        unsafe {
-            let code = xdr_seek::xdr_flush(self.handle.xdrfile);
-            if let Some(err) = check_code(code, ErrorTask::Flush) {
-                Err(err)
-            } else {
-                Ok(())
-            }
-        }
+    /// Stop reporting progress
+    pub fn clear_progress(&mut self) {
+        self.handle.progress = None;
     }
 
-    fn get_num_atoms(&mut self) -> Result<usize> {
-        self.num_atoms
-            .get_or_create(|| {
-                let mut num_atoms: c_int = 0;
-                unsafe {
-                    let path = path_to_cstring(&self.handle.path)?;
-                    let path_p = path.into_raw();
-                    let code = xdrfile_trr::read_trr_natoms(path_p, &mut num_atoms);
-                    // Reconstitute the CString so it is deallocated correctly
-                    let _ = CString::from_raw(path_p);
-
-                    if let Some(err) = check_code(code, ErrorTask::ReadNumAtoms) {
-                        Err(err)
-                    } else {
-                        to!(num_atoms, ErrorTask::ReadNumAtoms)
-                    }
-                }
-            })
-            .clone()
+    /// Save the offsets of all frames to an index file at `path`, so that
+    /// seeking in the trajectory does not require scanning it again after
+    /// reopening it. Frames not visited yet are scanned first.
+    pub fn save_index(&mut self, path: impl AsRef<Path>) -> Result<()> {
+        self.index
+            .save(&mut self.handle, index::skip_trr_frame, path.as_ref())
     }
-}
 
-impl TRRTrajectory {
-    /// Get the current position in the file
-    pub fn tell(&self) -> u64 {
-        self.handle.tell()
+    /// Load the frame offsets from an index file written by `save_index`.
+    ///
+    /// Returns false and keeps the current offsets if the size or the
+    /// modification time of the trajectory file changed since the index was
+    /// saved.
+    pub fn load_index(&mut self, path: impl AsRef<Path>) -> Result<bool> {
+        match TrajectoryIndex::load(&self.handle, path.as_ref())? {
+            Some(index) => {
+                self.index = index;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
     }
 }
 
@@ -508,32 +2357,30 @@ mod tests {
         let natoms = 2;
 
         // write frame 1
-        let frame = Frame {
-            step: 1,
-            time: 1.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
-            coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
-        };
-        let mut f = XTCTrajectory::open_write(&tmp_path)?;
+        let frame = Frame::from_coords(
+            1,
+            1.0,
+            BoxVector::new([[3.0, 0.0, 0.0], [1.0, 2.0, 0.0], [1.0, 0.5, 1.0]]),
+            vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+        );
+        let mut f = XTCTrajectory::open_write(tmp_path)?;
         let write_status = f.write(&frame);
-        match write_status {
-            Err(_) => panic!("Failed"),
-            Ok(()) => {}
+        if write_status.is_err() {
+            panic!("Failed");
         }
         f.flush()?;
 
         // append frame 2
-        let frame2 = Frame {
-            step: 2,
-            time: 2.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
-            coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
-        };
-        let mut f = XTCTrajectory::open_append(&tmp_path)?;
+        let frame2 = Frame::from_coords(
+            2,
+            2.0,
+            BoxVector::new([[3.0, 0.0, 0.0], [1.0, 2.0, 0.0], [1.0, 0.5, 1.0]]),
+            vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+        );
+        let mut f = XTCTrajectory::open_append(tmp_path)?;
         let write_status = f.write(&frame2);
-        match write_status {
-            Err(_) => panic!("Failed"),
-            Ok(()) => {}
+        if write_status.is_err() {
+            panic!("Failed");
         }
         f.flush()?;
 
@@ -545,9 +2392,8 @@ mod tests {
 
         // check frame 1 ...
         let read_status = f.read(&mut new_frame);
-        match read_status {
-            Err(e) => assert!(false, "{:?}", e),
-            Ok(()) => {}
+        if let Err(e) = read_status {
+            panic!("{:?}", e);
         }
 
         assert_eq!(new_frame.len(), frame.len());
@@ -558,9 +2404,8 @@ mod tests {
 
         // and check frame 1 ...
         let read_status = f.read(&mut new_frame);
-        match read_status {
-            Err(e) => assert!(false, "{:?}", e),
-            Ok(()) => {}
+        if let Err(e) = read_status {
+            panic!("{:?}", e);
         }
 
         assert_eq!(new_frame.len(), frame2.len());
@@ -578,32 +2423,30 @@ mod tests {
         let natoms = 2;
 
         // write frame 1
-        let frame = Frame {
-            step: 1,
-            time: 1.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
-            coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
-        };
-        let mut f = TRRTrajectory::open_write(&tmp_path)?;
+        let frame = Frame::from_coords(
+            1,
+            1.0,
+            BoxVector::new([[3.0, 0.0, 0.0], [1.0, 2.0, 0.0], [1.0, 0.5, 1.0]]),
+            vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+        );
+        let mut f = TRRTrajectory::open_write(tmp_path)?;
         let write_status = f.write(&frame);
-        match write_status {
-            Err(_) => panic!("Failed"),
-            Ok(()) => {}
+        if write_status.is_err() {
+            panic!("Failed");
         }
         f.flush()?;
 
         // append frame 2
-        let frame2 = Frame {
-            step: 2,
-            time: 2.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
-            coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
-        };
-        let mut f = TRRTrajectory::open_append(&tmp_path)?;
+        let frame2 = Frame::from_coords(
+            2,
+            2.0,
+            BoxVector::new([[3.0, 0.0, 0.0], [1.0, 2.0, 0.0], [1.0, 0.5, 1.0]]),
+            vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+        );
+        let mut f = TRRTrajectory::open_append(tmp_path)?;
         let write_status = f.write(&frame2);
-        match write_status {
-            Err(_) => panic!("Failed"),
-            Ok(()) => {}
+        if write_status.is_err() {
+            panic!("Failed");
         }
         f.flush()?;
 
@@ -615,9 +2458,8 @@ mod tests {
 
         // check frame 1 ...
         let read_status = f.read(&mut new_frame);
-        match read_status {
-            Err(e) => assert!(false, "{:?}", e),
-            Ok(()) => {}
+        if let Err(e) = read_status {
+            panic!("{:?}", e);
         }
 
         assert_eq!(new_frame.len(), frame.len());
@@ -628,9 +2470,8 @@ mod tests {
 
         // and check frame 1 ...
         let read_status = f.read(&mut new_frame);
-        match read_status {
-            Err(e) => assert!(false, "{:?}", e),
-            Ok(()) => {}
+        if let Err(e) = read_status {
+            panic!("{:?}", e);
         }
 
         assert_eq!(new_frame.len(), frame2.len());
@@ -709,69 +2550,625 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(windows)]
+    #[test]
+    fn test_path_to_wide() -> io::Result<()> {
+        let wide = |s: &str| s.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+        let short = r"C:\trajectories\träj.xtc";
+        assert_eq!(path_to_wide(Path::new(short))?, wide(short));
+
+        // Long paths get the verbatim prefix
+        let long = format!(r"C:\{}\traj.xtc", "a".repeat(300));
+        assert_eq!(
+            path_to_wide(Path::new(&long))?,
+            wide(&format!(r"\\?\{}", long))
+        );
+        let unc = format!(r"\\server\share\{}.xtc", "b".repeat(300));
+        let expected = wide(&format!(r"\\?\UNC\{}", &unc[2..]));
+        assert_eq!(path_to_wide(Path::new(&unc))?, expected);
+
+        assert!(path_to_wide(Path::new("invalid\0path")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_long_unicode_path() -> Result<()> {
+        // Longer than MAX_PATH and outside of any ANSI code page
+        let dir = tempfile::tempdir().expect("Could not create temporary directory");
+        let mut path = dir.path().to_owned();
+        for _ in 0..4 {
+            path.push("траектория_軌跡_τροχιά_".repeat(3));
+        }
+        std::fs::create_dir_all(&path).expect("Could not create directories");
+        path.push("1l2y ☃.xtc");
+        assert!(path.as_os_str().len() > 260);
+
+        let frames = XTCTrajectory::open_read("tests/1l2y.xtc")?.read_all()?;
+        let mut writer = XTCTrajectory::open_write(&path)?;
+        for frame in &frames[..5] {
+            writer.write(frame)?;
+        }
+        assert_eq!(writer.num_frames()?, 5);
+        drop(writer);
+
+        let mut trajectory = XTCTrajectory::open_read(&path)?;
+        assert_eq!(trajectory.get_num_atoms()?, 304);
+        assert_eq!(trajectory.num_frames()?, 5);
+        trajectory.seek_to_frame(3)?;
+        let mut frame = Frame::with_len(304);
+        trajectory.try_clone()?.read(&mut frame)?;
+        assert_eq!(frame.step, frames[3].step);
+        Ok(())
+    }
+
     #[test]
     fn test_tell() -> std::result::Result<(), Box<dyn std::error::Error>> {
         let tempfile = NamedTempFile::new()?;
         let tmp_path = tempfile.path();
 
         let natoms: usize = 2;
-        let frame = Frame {
-            step: 5,
-            time: 2.0,
-            box_vector: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
-            coords: vec![[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]],
-        };
+        let frame = Frame::from_coords(
+            5,
+            2.0,
+            BoxVector::rectangular(1.0, 1.0, 1.0),
+            vec![[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]],
+        );
+        let mut f = TRRTrajectory::open_write(tmp_path)?;
+        assert_eq!(f.tell()?, 0);
+        f.write(&frame)?;
+        assert_eq!(f.tell()?, 144);
+        f.flush()?;
+
+        let mut new_frame = Frame::with_len(natoms);
+        let mut f = TRRTrajectory::open_read(tmp_path)?;
+        assert_eq!(f.tell()?, 0);
+
+        f.read(&mut new_frame)?;
+        assert_eq!(f.tell()?, 144);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seek() -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let tempfile = NamedTempFile::new()?;
+        let tmp_path = tempfile.path();
+
+        let natoms: usize = 2;
+        let mut frame = Frame::from_coords(
+            0,
+            0.0,
+            BoxVector::rectangular(1.0, 1.0, 1.0),
+            vec![[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]],
+        );
         let mut f = TRRTrajectory::open_write(tmp_path)?;
-        assert_eq!(f.tell(), 0);
         f.write(&frame)?;
-        assert_eq!(f.tell(), 144);
+        let after_first_frame = f.tell()?;
+        frame.step += 1;
+        frame.time += 10.0;
+        f.write(&frame)?;
+        let after_second_frame = f.tell()?;
         f.flush()?;
 
         let mut new_frame = Frame::with_len(natoms);
         let mut f = TRRTrajectory::open_read(tmp_path)?;
-        assert_eq!(f.tell(), 0);
+        let pos = f.seek(std::io::SeekFrom::Current(144))?;
+        assert_eq!(pos, after_first_frame);
+
+        f.read(&mut new_frame)?;
+        assert_eq!(f.tell()?, after_second_frame);
+
+        assert_eq!(new_frame.len(), frame.len());
+        assert_eq!(new_frame.step, frame.step);
+        assert_eq!(new_frame.time, frame.time);
+        assert_eq!(new_frame.box_vector, frame.box_vector);
+        assert_eq!(new_frame.coords, frame.coords);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seek_to_frame_xtc() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(traj.get_num_atoms()?);
+
+        traj.seek_to_frame(10)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 11);
+
+        traj.seek_to_frame(3)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 4);
+
+        traj.seek_to_frame(37)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 38);
+
+        assert!(traj.seek_to_frame(38).unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_seek_to_frame_trr() -> Result<()> {
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut frame = Frame::with_len(traj.get_num_atoms()?);
+
+        traj.seek_to_frame(20)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 21);
+
+        traj.seek_to_frame(0)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 1);
+
+        assert!(traj.seek_to_frame(100).unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_seek_to_time() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(traj.get_num_atoms()?);
+        traj.read(&mut frame)?;
+        let first_time = frame.time;
+        traj.read(&mut frame)?;
+        let dt = frame.time - first_time;
+
+        traj.seek_to_time(first_time + 10.0 * dt)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 11);
+
+        // Times between two frames select the later one
+        traj.seek_to_time(first_time + 2.5 * dt)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 4);
+
+        traj.seek_to_time(f32::MIN)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 1);
+
+        assert!(traj.seek_to_time(f32::MAX).unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_num_frames() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert_eq!(xtc.num_frames()?, 38);
+        // cached value
+        assert_eq!(xtc.num_frames()?, 38);
+
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        assert_eq!(trr.num_frames()?, 38);
+
+        // Counting frames does not move the read position
+        let mut frame = Frame::with_len(trr.get_num_atoms()?);
+        trr.read(&mut frame)?;
+        assert_eq!(frame.step, 1);
+
+        let mut readme = TRRTrajectory::open_read("README.md")?;
+        let err = readme.num_frames().unwrap_err();
+        assert_eq!(Some(ErrorCode::ExdrMagic), err.code());
+        Ok(())
+    }
+
+    #[test]
+    fn test_num_frames_after_write() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut frame = Frame::with_len(304);
+        let mut xtc = XTCTrajectory::open_write(tempfile.path())?;
+        assert!(xtc.num_frames().is_err());
+        xtc.write(&frame)?;
+        xtc.write(&frame)?;
+        // Buffered frames are counted
+        assert_eq!(xtc.num_frames()?, 2);
+        assert_eq!(xtc.get_num_atoms()?, 304);
+        xtc.write(&frame)?;
+        assert_eq!(xtc.num_frames()?, 3);
+        drop(xtc);
+
+        let mut xtc = XTCTrajectory::open_append(tempfile.path())?;
+        assert_eq!(xtc.num_frames()?, 3);
+        xtc.write(&frame)?;
+        assert_eq!(xtc.num_frames()?, 4);
+        drop(xtc);
+        assert_eq!(XTCTrajectory::open_read(tempfile.path())?.num_frames()?, 4);
+
+        let mut trr = TRRTrajectory::open_write(tempfile.path())?;
+        for step in 0..5 {
+            frame.step = step;
+            trr.write(&frame)?;
+            assert_eq!(trr.num_frames()?, step + 1);
+        }
+        trr.write_f64(&frame.to_f64())?;
+        assert_eq!(trr.num_frames()?, 6);
+        Ok(())
+    }
+
+    /// Reader whose seeks fail after moving to a wrong position, for all
+    /// seeks if `mode` is 2 and for relative seeks only if it is 1. If
+    /// `mode` is 3, getting the position fails instead.
+    struct FailingSeek {
+        inner: io::Cursor<Vec<u8>>,
+        mode: std::sync::Arc<std::sync::atomic::AtomicU8>,
+    }
+
+    impl io::Read for FailingSeek {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.inner.read(buf)
+        }
+    }
+
+    impl io::Seek for FailingSeek {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            let fail = match self.mode.load(std::sync::atomic::Ordering::SeqCst) {
+                1 => matches!(pos, io::SeekFrom::Current(_)),
+                2 => true,
+                _ => false,
+            };
+            if fail {
+                self.inner.set_position(3);
+                return Err(io::Error::other("seek failed"));
+            }
+            self.inner.seek(pos)
+        }
+
+        fn stream_position(&mut self) -> io::Result<u64> {
+            match self.mode.load(std::sync::atomic::Ordering::SeqCst) {
+                3 => Err(io::Error::other("tell failed")),
+                _ => Ok(self.inner.position()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_seek() -> Result<()> {
+        use std::io::Seek;
+        use std::sync::atomic::{AtomicU8, Ordering};
+        let mode = std::sync::Arc::new(AtomicU8::new(0));
+        let reader = FailingSeek {
+            inner: io::Cursor::new(std::fs::read("tests/1l2y.xtc").unwrap()),
+            mode: mode.clone(),
+        };
+        let mut traj = XTCTrajectory::from_reader(reader)?;
+        let mut frame = Frame::with_len(304);
+        traj.read(&mut frame)?;
+        let position = traj.tell()?;
+
+        // The position before the failed seek is restored
+        mode.store(1, Ordering::SeqCst);
+        assert!(traj.seek(io::SeekFrom::Current(4)).is_err());
+        assert_eq!(traj.tell()?, position);
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 2);
+
+        // If restoring fails too, the position is lost until the next
+        // successful seek
+        mode.store(2, Ordering::SeqCst);
+        assert!(traj.seek(io::SeekFrom::Current(4)).is_err());
+        let err = traj.read(&mut frame).unwrap_err();
+        assert!(matches!(err, Error::PositionLost { path: None }));
+        assert!(err.to_string().contains("seek to a frame"));
+        assert!(matches!(
+            traj.seek(io::SeekFrom::Current(1)),
+            Err(err) if err.kind() == io::ErrorKind::Other
+        ));
+        mode.store(0, Ordering::SeqCst);
+        traj.seek_to_frame(5)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_tell() -> Result<()> {
+        use std::sync::atomic::{AtomicU8, Ordering};
+        let mode = std::sync::Arc::new(AtomicU8::new(0));
+        let reader = FailingSeek {
+            inner: io::Cursor::new(std::fs::read("tests/1l2y.xtc").unwrap()),
+            mode: mode.clone(),
+        };
+        let mut traj = XTCTrajectory::from_reader(reader)?;
+        let mut frame = Frame::with_len(304);
+        traj.read(&mut frame)?;
+
+        // A failing stream is reported as an error instead of panicking
+        mode.store(3, Ordering::SeqCst);
+        let err = traj.tell().unwrap_err();
+        assert!(
+            matches!(&err, Error::Io { task: ErrorTask::Seek, source } if source.to_string() == "tell failed")
+        );
+        assert!(matches!(traj.read(&mut frame), Err(Error::Io { .. })));
+        assert!(traj.seek_to_frame(3).is_err());
+
+        mode.store(0, Ordering::SeqCst);
+        traj.seek_to_frame(3)?;
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 4);
+        Ok(())
+    }
+
+    /// Stream whose reads fail with an error of `kind` from `fail_at` on
+    struct FailingRead {
+        inner: io::Cursor<Vec<u8>>,
+        fail_at: u64,
+        kind: io::ErrorKind,
+    }
+
+    impl FailingRead {
+        fn new(path: &str, fail_at: u64, kind: io::ErrorKind) -> Self {
+            let bytes = std::fs::read(path).expect("Could not read test file");
+            let inner = io::Cursor::new(bytes);
+            FailingRead {
+                inner,
+                fail_at,
+                kind,
+            }
+        }
+    }
+
+    impl io::Read for FailingRead {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.inner.position() + buf.len() as u64 > self.fail_at {
+                return Err(io::Error::new(self.kind, "read failed"));
+            }
+            self.inner.read(buf)
+        }
+    }
+
+    impl io::Write for FailingRead {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl io::Seek for FailingRead {
+        fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_failed_read() -> Result<()> {
+        use std::error::Error as _;
+        let reader = FailingRead::new("tests/1l2y.xtc", 50_000, io::ErrorKind::InvalidData);
+        let mut traj = XTCTrajectory::from_reader(reader)?;
+        let mut frame = Frame::with_len(304);
+        let err = loop {
+            if let Err(err) = traj.read(&mut frame) {
+                break err;
+            }
+        };
+
+        // A failing stream is not mistaken for the end of the file
+        assert!(!err.is_eof());
+        assert!(matches!(
+            &err,
+            Error::InFrame { frame: Some(_), source, .. }
+                if matches!(**source, Error::Io { task: ErrorTask::Read, .. })
+        ));
+        let io = err.source().and_then(|err| err.source());
+        let io = io.and_then(|err| err.downcast_ref::<io::Error>());
+        assert_eq!(io.map(|err| err.kind()), Some(io::ErrorKind::InvalidData));
+
+        let reader = FailingRead::new("tests/1l2y.xtc", 50_000, io::ErrorKind::InvalidData);
+        let err = XTCTrajectory::from_reader(reader)?
+            .num_frames()
+            .unwrap_err();
+        assert!(matches!(err, Error::Io { .. }));
+
+        // An unexpected end of the stream is handled like a truncated file
+        let reader = FailingRead::new("tests/1l2y.xtc", 50_000, io::ErrorKind::UnexpectedEof);
+        let mut traj = XTCTrajectory::from_reader(reader)?;
+        let err = traj.read_all().unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::Exdr3dx));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_head() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut traj = XTCTrajectory::open_write(tempfile.path())?;
+        traj.write(&Frame::with_len(3))?;
+        let pos = traj.tell()?;
+
+        // Written frames are read through the handle, which keeps its position
+        let head = traj.handle.read_head(1024)?;
+        assert_eq!(head.len() as u64, pos);
+        assert_eq!(traj.tell()?, pos);
+        traj.write(&Frame::with_len(3))?;
+        traj.flush()?;
+        let bytes = std::fs::read(tempfile.path()).expect("Could not read temporary file");
+        assert_eq!(bytes.len() as u64, 2 * pos);
+        assert_eq!(&bytes[..head.len()], &head[..]);
+        assert_eq!(traj.handle.read_head(4)?, &bytes[..4]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_head_error() {
+        let reader = FailingRead::new("tests/1l2y.xtc", 0, io::ErrorKind::InvalidData);
+        let (stream, io) = Stream::read_write(reader);
+        let mut handle = XDRFile::open_stream(stream, io, FileMode::Write);
+        let err = handle.read_head(1024).unwrap_err();
+        assert!(!err.is_eof());
+        assert_eq!(err.task(), Some(ErrorTask::ReadNumAtoms));
+        assert!(
+            matches!(err, Error::Io { source, .. } if source.kind() == io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn test_from_reader_xtc() -> Result<()> {
+        let bytes = std::fs::read("tests/1l2y.xtc").expect("Could not read test file");
+        let mut stream = XTCTrajectory::from_reader(io::Cursor::new(bytes))?;
+        let mut file = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+
+        assert_eq!(stream.get_num_atoms()?, 304);
+        assert_eq!(stream.num_frames()?, 38);
+        let mut expected = Frame::with_len(304);
+        let mut frame = Frame::with_len(304);
+        for _ in 0..38 {
+            file.read(&mut expected)?;
+            stream.read(&mut frame)?;
+            assert_eq!(
+                (expected.step, &expected.coords),
+                (frame.step, &frame.coords)
+            );
+        }
+        assert!(stream.read(&mut frame).unwrap_err().is_eof());
+
+        stream.seek_to_frame(3)?;
+        stream.read(&mut frame)?;
+        assert_eq!(frame.step, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_reader_trr() -> Result<()> {
+        let reader = std::fs::File::open("tests/1l2y.trr").expect("Could not open test file");
+        let mut stream = TRRTrajectory::from_reader(reader)?;
+        let mut file = TRRTrajectory::open_read("tests/1l2y.trr")?;
+
+        let mut expected = Frame::with_len(file.get_num_atoms()?);
+        let mut frame = Frame::with_len(stream.get_num_atoms()?);
+        file.read(&mut expected)?;
+        stream.read(&mut frame)?;
+        assert_eq!(
+            (expected.step, &expected.coords),
+            (frame.step, &frame.coords)
+        );
+
+        // Counting frames does not move the read position
+        assert_eq!(stream.num_frames()?, 38);
+        file.read(&mut expected)?;
+        stream.read(&mut frame)?;
+        assert_eq!(
+            (expected.step, &expected.coords),
+            (frame.step, &frame.coords)
+        );
+
+        let mut readme = TRRTrajectory::from_reader(io::Cursor::new(b"not a trajectory"))?;
+        let err = readme.get_num_atoms().unwrap_err();
+        assert_eq!(Some(ErrorTask::ReadNumAtoms), err.task());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bytes_roundtrip() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut frame = Frame::with_len(10);
+        frame.coords[3] = [1.0, 2.0, 3.0];
+        let mut memory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let mut file = XTCTrajectory::open_write(tempfile.path())?;
+        for step in 0..3 {
+            frame.step = step;
+            memory.write(&frame)?;
+            file.write(&frame)?;
+        }
+        file.flush()?;
+        let bytes = memory.into_bytes().expect("Trajectory is in memory");
+        let expected = std::fs::read(tempfile.path()).expect("Could not read temporary file");
+        assert_eq!(bytes, expected);
+
+        // Appending keeps the existing frames
+        let mut memory = XTCTrajectory::from_bytes(bytes, FileMode::Append)?;
+        frame.step = 3;
+        memory.write(&frame)?;
+        let bytes = memory.into_bytes().expect("Trajectory is in memory");
+
+        let mut memory = XTCTrajectory::from_bytes(bytes, FileMode::Read)?;
+        assert_eq!(memory.num_frames()?, 4);
+        let mut read_frame = Frame::with_len(memory.get_num_atoms()?);
+        for step in 0..4 {
+            memory.read(&mut read_frame)?;
+            assert_eq!(read_frame.step, step);
+            assert_approx_eq!(read_frame.coords[3][1], 2.0);
+        }
 
-        f.read(&mut new_frame)?;
-        assert_eq!(f.tell(), 144);
+        // Writing discards the previous contents
+        let memory = TRRTrajectory::from_bytes(vec![1, 2, 3], FileMode::Write)?;
+        assert_eq!(memory.into_bytes(), Some(Vec::new()));
 
+        let file = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        assert_eq!(file.into_bytes(), None);
         Ok(())
     }
 
     #[test]
-    fn test_seek() -> std::result::Result<(), Box<dyn std::error::Error>> {
-        let tempfile = NamedTempFile::new()?;
-        let tmp_path = tempfile.path();
+    fn test_trr_velocities() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut frame = Frame::with_len(2).with_velocities();
+        frame.coords[1] = [1.0, 2.0, 3.0];
+        let mut traj = TRRTrajectory::open_write(tempfile.path())?;
+        frame.velocities = Some(vec![[0.5; 3], [-1.0, 0.0, 1.0]]);
+        traj.write(&frame)?;
+        frame.velocities = None;
+        traj.write(&frame)?;
+        frame.velocities = Some(vec![[0.0; 3]]);
+        assert!(matches!(
+            traj.write(&frame),
+            Err(Error::WrongSizeFrame { .. })
+        ));
+        traj.flush()?;
 
-        let natoms: usize = 2;
-        let mut frame = Frame {
-            step: 0,
-            time: 0.0,
-            box_vector: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
-            coords: vec![[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]],
-        };
-        let mut f = TRRTrajectory::open_write(tmp_path)?;
-        f.write(&frame)?;
-        let after_first_frame = f.tell();
-        frame.step += 1;
-        frame.time += 10.0;
-        f.write(&frame)?;
-        let after_second_frame = f.tell();
-        f.flush()?;
+        let mut traj = TRRTrajectory::open_read(tempfile.path())?;
+        let mut frame = Frame::with_len(2);
+        traj.read(&mut frame)?;
+        assert_eq!(frame.velocities, Some(vec![[0.5; 3], [-1.0, 0.0, 1.0]]));
+        assert_eq!(frame.coords[1], [1.0, 2.0, 3.0]);
+        traj.read(&mut frame)?;
+        assert_eq!(frame.velocities, None);
+        assert_eq!(frame.coords[1], [1.0, 2.0, 3.0]);
+        assert!(traj.read(&mut frame).unwrap_err().is_eof());
 
-        let mut new_frame = Frame::with_len(natoms);
-        let mut f = TRRTrajectory::open_read(tmp_path)?;
-        let pos = f.seek(std::io::SeekFrom::Current(144))?;
-        assert_eq!(pos, after_first_frame);
+        // XTC files never contain velocities
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(traj.get_num_atoms()?).with_velocities();
+        traj.read(&mut frame)?;
+        assert_eq!(frame.velocities, None);
+        Ok(())
+    }
 
-        f.read(&mut new_frame)?;
-        assert_eq!(f.tell(), after_second_frame);
+    #[test]
+    fn test_trr_forces() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut frame = Frame::with_len(2).with_forces();
+        frame.forces = Some(vec![[1.0, -2.0, 3.0], [0.25; 3]]);
+        let mut traj = TRRTrajectory::open_write(tempfile.path())?;
+        traj.write(&frame)?;
+        let mut frame = frame.with_velocities();
+        frame.velocities = Some(vec![[4.0; 3], [5.0; 3]]);
+        traj.write(&frame)?;
+        traj.flush()?;
 
-        assert_eq!(new_frame.len(), frame.len());
-        assert_eq!(new_frame.step, frame.step);
-        assert_eq!(new_frame.time, frame.time);
-        assert_eq!(new_frame.box_vector, frame.box_vector);
-        assert_eq!(new_frame.coords, frame.coords);
+        let mut traj = TRRTrajectory::open_read(tempfile.path())?;
+        let mut frame = Frame::with_len(2);
+        traj.read(&mut frame)?;
+        assert_eq!(frame.forces, Some(vec![[1.0, -2.0, 3.0], [0.25; 3]]));
+        assert_eq!(frame.velocities, None);
+        traj.read(&mut frame)?;
+        assert_eq!(frame.forces, Some(vec![[1.0, -2.0, 3.0], [0.25; 3]]));
+        assert_eq!(frame.velocities, Some(vec![[4.0; 3], [5.0; 3]]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_trr_changing_num_atoms() -> Result<()> {
+        let mut traj = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        traj.write(&Frame::with_len(2))?;
+        traj.write(&Frame::with_len(5))?;
+        let bytes = traj.into_bytes().unwrap();
 
+        // Frames with more atoms than the first are not read past the buffer
+        let mut traj = TRRTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let mut frame = Frame::with_len(2);
+        traj.read(&mut frame)?;
+        assert!(traj.read(&mut frame).is_err());
         Ok(())
     }
 
@@ -784,14 +3181,23 @@ mod tests {
             if let Error::CouldNotOpen {
                 path: err_path,
                 mode: err_mode,
-            } = e
+                ..
+            } = &e
             {
                 assert_eq!(path, err_path);
-                assert_eq!(FileMode::Read, err_mode)
+                assert_eq!(&FileMode::Read, err_mode);
+                let kind = e.os_error().map(|err| err.kind());
+                assert_eq!(kind, Some(io::ErrorKind::NotFound));
             } else {
                 panic!("Wrong Error type")
             }
         }
+
+        let err = XTCTrajectory::open_write("no/such/dir/out.xtc")
+            .err()
+            .expect("Opening a file in a missing directory should fail");
+        assert_eq!(err.os_error().unwrap().kind(), io::ErrorKind::NotFound);
+        assert!(err.to_string().contains("in mode Write: "));
     }
 
     #[test]
@@ -825,13 +3231,13 @@ mod tests {
         let tmp_path = tempfile.path();
 
         let natoms = 2;
-        let frame = Frame {
-            step: 5,
-            time: 2.0,
-            box_vector: [[1.0, 2.0, 3.0], [2.0, 1.0, 3.0], [3.0, 2.0, 1.0]],
-            coords: vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
-        };
-        let mut f = XTCTrajectory::open_write(&tmp_path)?;
+        let frame = Frame::from_coords(
+            5,
+            2.0,
+            BoxVector::new([[3.0, 0.0, 0.0], [1.0, 2.0, 0.0], [1.0, 0.5, 1.0]]),
+            vec![[1.0, 1.0, 1.0], [1.0, 1.0, 1.0]],
+        );
+        let mut f = XTCTrajectory::open_write(tmp_path)?;
         f.write(&frame)?;
         f.flush()?;
 
@@ -865,9 +3271,9 @@ mod tests {
     #[test]
     fn test_check_code() {
         let code: ErrorCode = 0.into();
-        assert!(!check_code(code, ErrorTask::Read).is_some());
+        assert!(check_code(code, ErrorTask::Read).is_none());
 
-        for i in vec![1, 10, 100, 1000] {
+        for i in [1, 10, 100, 1000] {
             let code: ErrorCode = i.into();
             assert!(check_code(code, ErrorTask::Read).is_some());
         }
@@ -875,7 +3281,8 @@ mod tests {
 
     #[test]
     fn test_to() -> Result<()> {
-        assert_eq!(24234_i32, to!(24234_usize, ErrorTask::Write)?);
+        let converted: i32 = to!(24234_usize, ErrorTask::Write)?;
+        assert_eq!(24234_i32, converted);
 
         let big_number = 3_294_967_295_usize;
         let expected: Result<i32> = Err(Error::OutOfRange {
@@ -883,6 +3290,7 @@ mod tests {
             task: ErrorTask::Write,
             value: "3294967295".to_string(),
             target: "i32",
+            source: i32::try_from(big_number).unwrap_err(),
         });
         assert_eq!(expected, to!(big_number, ErrorTask::Write));
 
@@ -902,17 +3310,13 @@ mod tests {
         let tmp_path = tempfile.path();
         let mut traj = XTCTrajectory::open_write(tmp_path)?;
 
-        let frame = Frame {
-            step: usize::MAX,
-            time: 0.0,
-            box_vector: [[0.0; 3]; 3],
-            coords: vec![[1.0; 3]],
-        };
+        let frame = Frame::from_coords(usize::MAX, 0.0, BoxVector::default(), vec![[1.0; 3]]);
         let expected = Error::OutOfRange {
             name: "frame.step",
             value: usize::MAX.to_string(),
             target: "i32",
             task: ErrorTask::Write,
+            source: i32::try_from(usize::MAX).unwrap_err(),
         };
 
         if let Err(e) = traj.write(&frame) {
@@ -924,4 +3328,769 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_last_frame() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let last = xtc.last_frame()?;
+        assert_eq!(last.step, 38);
+        assert!(xtc.read(&mut Frame::with_len(304)).unwrap_err().is_eof());
+
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut expected = Frame::with_len(304);
+        trr.seek_to_frame(37)?;
+        trr.read(&mut expected)?;
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let last = trr.last_frame()?;
+        assert_eq!((last.step, &last.coords), (expected.step, &expected.coords));
+
+        // Single frame and empty trajectories
+        let mut memory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        memory.write(&last)?;
+        let bytes = memory.into_bytes().unwrap();
+        let mut memory = XTCTrajectory::from_bytes(bytes, FileMode::Read)?;
+        assert_eq!(memory.last_frame()?.step, 38);
+        let mut empty = XTCTrajectory::from_bytes(Vec::new(), FileMode::Read)?;
+        assert!(empty.last_frame().is_err());
+
+        let parts = vec![XTCTrajectory::open_read("tests/1l2y.xtc")?];
+        let mut chain = ChainedTrajectory::new(parts)?;
+        assert_eq!(chain.last_frame()?.step, 38);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_all() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let first = traj.read_up_to(5)?;
+        let steps: Vec<usize> = first.iter().map(|frame| frame.step).collect();
+        assert_eq!(steps, vec![1, 2, 3, 4, 5]);
+
+        let rest = traj.read_all()?;
+        assert_eq!(rest.len(), 33);
+        assert_eq!(rest[0].step, 6);
+        assert!(traj.read_all()?.is_empty());
+
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        assert_eq!(trr.read_all()?.len(), 38);
+
+        // Fixed size windows, the last one is shorter
+        Trajectory::rewind(&mut trr)?;
+        let mut sizes = Vec::new();
+        loop {
+            let window = trr.read_up_to(10)?;
+            if window.is_empty() {
+                break;
+            }
+            sizes.push(window.len());
+        }
+        assert_eq!(sizes, vec![10, 10, 10, 8]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_to_thread() -> Result<()> {
+        fn assert_send<T: Send>() {}
+        assert_send::<XTCTrajectory>();
+        assert_send::<TRRTrajectory>();
+        assert_send::<ChainedTrajectory<XTCTrajectory>>();
+
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        traj.seek_to_frame(10)?;
+        let worker = std::thread::spawn(move || traj.read_all().map(|frames| frames.len()));
+        assert_eq!(worker.join().expect("Worker thread panicked")?, 28);
+        Ok(())
+    }
+
+    #[test]
+    fn test_num_atoms_from_handle() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        std::fs::copy("tests/1l2y.trr", tempfile.path()).expect("Could not copy test file");
+        let mut traj = TRRTrajectory::open_read(tempfile.path())?;
+
+        // The atom count is read from the open file, without moving its position
+        drop(tempfile);
+        assert_eq!(traj.get_num_atoms()?, 304);
+        let mut frame = Frame::with_len(304);
+        traj.read(&mut frame)?;
+        assert_eq!(frame.step, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_num_atoms_from_bytes() -> Result<()> {
+        let xtc = std::fs::read("tests/1l2y.xtc").expect("Could not read test file");
+        let trr = std::fs::read("tests/1l2y.trr").expect("Could not read test file");
+        assert_eq!(
+            XTCTrajectory::from_bytes(xtc, FileMode::Append)?.get_num_atoms()?,
+            304
+        );
+        assert_eq!(
+            TRRTrajectory::from_bytes(trr, FileMode::Append)?.get_num_atoms()?,
+            304
+        );
+
+        // Frames written by the handle itself are read back as well
+        let frame = Frame::with_len(5);
+        let mut xtc = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        assert!(xtc.get_num_atoms().is_err());
+        xtc.write(&frame)?;
+        assert_eq!(xtc.get_num_atoms()?, 5);
+        let mut trr = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        trr.write(&frame)?;
+        assert_eq!(trr.get_num_atoms()?, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_wrong_size_frame() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut frame = Frame::with_len(3);
+        XTCTrajectory::open_write(tempfile.path())?.write(&frame)?;
+
+        let mut traj = XTCTrajectory::open_append(tempfile.path())?;
+        traj.write(&frame)?;
+        frame.resize(4);
+        let err = traj.write(&frame).unwrap_err();
+        assert_eq!(
+            err,
+            Error::WrongSizeFrame {
+                expected: 3,
+                found: 4
+            }
+        );
+
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        TRRTrajectory::open_write(tempfile.path())?.write(&frame)?;
+        let mut trr = TRRTrajectory::open_append(tempfile.path())?;
+        frame.resize(2);
+        assert!(trr.write(&frame).is_err());
+
+        // Trajectories in memory are checked like files
+        let mut xtc = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        xtc.write(&frame)?;
+        let bytes = xtc.into_bytes().expect("Trajectory was not in memory");
+        let mut xtc = XTCTrajectory::from_bytes(bytes, FileMode::Append)?;
+        frame.resize(3);
+        assert!(matches!(
+            xtc.write(&frame),
+            Err(Error::WrongSizeFrame {
+                expected: 2,
+                found: 3
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_invalid_box() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut xtc = XTCTrajectory::open_write(tempfile.path())?;
+        let mut trr = TRRTrajectory::open_write(tempfile.path())?;
+
+        let mut frame = Frame::with_len(1);
+        frame.box_vector = BoxVector::new([[1.0, 1.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        for result in [xtc.write(&frame), trr.write(&frame)] {
+            match result {
+                Err(Error::InvalidBox { box_vector, .. }) => {
+                    assert_eq!(box_vector, frame.box_vector)
+                }
+                other => panic!("Expected InvalidBox, got {:?}", other),
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_close() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut traj = TRRTrajectory::open_write(tempfile.path())?;
+        traj.write(&Frame::with_len(3))?;
+        traj.close()?;
+        assert_eq!(TRRTrajectory::open_read(tempfile.path())?.num_frames()?, 1);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_close_error() -> Result<()> {
+        // Writing to /dev/full only fails once the buffered data is written
+        let mut traj = XTCTrajectory::open_write("/dev/full")?;
+        traj.write(&Frame::with_len(3))?;
+        let err = traj.close().unwrap_err();
+        assert_eq!(err.task(), Some(ErrorTask::Flush));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_flush_on_drop() -> Result<()> {
+        // Failing to close on drop does not panic
+        let mut traj = TRRTrajectory::open_write("/dev/full")?;
+        traj.set_flush_on_drop(true);
+        traj.write(&Frame::with_len(3))?;
+        drop(traj);
+        Ok(())
+    }
+
+    #[test]
+    fn test_err_in_frame() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        traj.seek_to_frame(5)?;
+        let offset = traj.tell()?;
+
+        // Overwrite the magic number of the sixth frame
+        let tempfile = corrupt_copy("tests/1l2y.xtc", offset);
+
+        let traj = XTCTrajectory::open_read(tempfile.path())?;
+        let err = traj.into_iter().find_map(|frame| frame.err()).unwrap();
+        match &err {
+            Error::InFrame { path, frame, .. } => {
+                assert_eq!(path.as_deref(), Some(tempfile.path()));
+                assert_eq!(*frame, Some(5));
+            }
+            err => panic!("Wrong error type {:?}", err),
+        }
+        assert_eq!(err.code(), Some(ErrorCode::ExdrMagic));
+        assert!(err.to_string().starts_with("Error in frame 5 of"));
+
+        // The frame is known again after seeking to it
+        let mut traj = XTCTrajectory::open_read(tempfile.path())?;
+        traj.seek(SeekFrom::Start(0)).unwrap();
+        let mut frame = Frame::with_len(304);
+        let err = traj.read_all().map(|_| ()).unwrap_err();
+        assert!(err.to_string().starts_with("Error in unknown frame"));
+        traj.seek_to_frame(4)?;
+        traj.read(&mut frame)?;
+        let err = traj.read(&mut frame).unwrap_err();
+        assert!(matches!(err, Error::InFrame { frame: Some(5), .. }));
+        Ok(())
+    }
+
+    /// Copy a file, overwriting the 4 bytes at `offset` with zeros
+    fn corrupt_copy(path: &str, offset: u64) -> NamedTempFile {
+        let mut bytes = std::fs::read(path).expect("Could not read test file");
+        let offset = offset as usize;
+        bytes[offset..offset + 4].copy_from_slice(&[0, 0, 0, 0]);
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        std::fs::write(tempfile.path(), bytes).expect("Could not write test file");
+        tempfile
+    }
+
+    #[test]
+    fn test_resync_xtc() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        traj.seek_to_frame(5)?;
+        let start = traj.tell()?;
+        traj.seek_to_frame(6)?;
+        let end = traj.tell()?;
+        let tempfile = corrupt_copy("tests/1l2y.xtc", start);
+
+        let mut traj = XTCTrajectory::open_read(tempfile.path())?;
+        traj.set_resync(true);
+        let mut steps = Vec::new();
+        for frame in traj {
+            match frame {
+                Ok(frame) => steps.push(frame.step),
+                Err(Error::CorruptRegion {
+                    start: err_start,
+                    end: err_end,
+                    ..
+                }) => {
+                    assert_eq!((err_start, err_end), (start, end));
+                }
+                Err(e) => panic!("Unexpected error {:?}", e),
+            }
+        }
+        let expected: Vec<_> = (1..=38).filter(|&step| step != 6).collect();
+        assert_eq!(steps, expected);
+
+        // Without resync, iteration stops at the corrupt frame
+        let traj = XTCTrajectory::open_read(tempfile.path())?;
+        assert_eq!(traj.into_iter().count(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_resync_trr() -> Result<()> {
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        traj.seek_to_frame(37)?;
+        let tempfile = corrupt_copy("tests/1l2y.trr", traj.tell()?);
+
+        let mut traj = TRRTrajectory::open_read(tempfile.path())?;
+        traj.set_resync(true);
+        traj.seek_to_frame(36)?;
+        let mut frame = Frame::with_len(304);
+        traj.read(&mut frame)?;
+        // The corrupt frame is the last one, so the rest of the file is skipped
+        let err = traj.read(&mut frame).unwrap_err();
+        assert!(matches!(err, Error::CorruptRegion { .. }));
+        assert_eq!(err.code(), Some(ErrorCode::ExdrMagic));
+        assert!(traj.read(&mut frame).unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_times_and_steps() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut frame = Frame::with_len(304);
+        xtc.read(&mut frame)?;
+        trr.read(&mut frame)?;
+
+        let expected_steps: Vec<usize> = (1..=38).collect();
+        let expected_times: Vec<f32> = expected_steps.iter().map(|&s| s as f32).collect();
+        assert_eq!(xtc.get_steps()?, expected_steps);
+        assert_eq!(xtc.get_times()?, expected_times);
+        assert_eq!(trr.get_steps()?, expected_steps);
+        assert_eq!(trr.get_times()?, expected_times);
+
+        // The position is kept
+        xtc.read(&mut frame)?;
+        assert_eq!(frame.step, 2);
+        trr.read(&mut frame)?;
+        assert_eq!(frame.step, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_at_end() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut frame = Frame::with_len(304);
+        for trajectory in [&mut xtc as &mut dyn Trajectory, &mut trr] {
+            trajectory.seek_to_frame(37)?;
+            assert!(!trajectory.is_at_end()?);
+            trajectory.read(&mut frame)?;
+            assert!(trajectory.is_at_end()?);
+            // Checking does not move the position
+            assert!(trajectory.read(&mut frame).unwrap_err().is_eof());
+        }
+
+        // Trailing bytes that are too short for a frame count as the end
+        let mut bytes = std::fs::read("tests/1l2y.xtc").expect("Could not read test file");
+        bytes.extend_from_slice(&[0; 20]);
+        let mut xtc = XTCTrajectory::from_bytes(bytes, FileMode::Read)?;
+        assert_eq!(xtc.read_up_to(38)?.len(), 38);
+        assert!(xtc.is_at_end()?);
+        assert!(xtc.read(&mut frame).is_err());
+
+        let mut xtc = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        assert!(xtc.is_at_end()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_peek() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut frame = Frame::with_len(304);
+        let mut peeked = Frame::with_len(304);
+        for trajectory in [&mut xtc as &mut dyn Trajectory, &mut trr] {
+            trajectory.peek(&mut peeked)?;
+            trajectory.peek(&mut peeked)?;
+            trajectory.read(&mut frame)?;
+            assert_eq!(peeked.step, 1);
+            assert_eq!(peeked.coords, frame.coords);
+
+            trajectory.seek_to_frame(37)?;
+            trajectory.peek(&mut peeked)?;
+            assert_eq!(peeked.step, 38);
+            trajectory.read(&mut frame)?;
+            assert!(trajectory.peek(&mut peeked).unwrap_err().is_eof());
+            assert!(trajectory.read(&mut frame).unwrap_err().is_eof());
+        }
+
+        // Peeked frames are not reported as progress
+        let (sender, receiver) = std::sync::mpsc::channel();
+        xtc.set_progress(move |progress| sender.send(progress.frames).unwrap())?;
+        xtc.seek_to_frame(0)?;
+        xtc.peek(&mut frame)?;
+        xtc.read(&mut frame)?;
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![1]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_all() -> Result<()> {
+        let frames: Vec<Frame> = (0..3)
+            .map(|i| Frame::from_coords(i, i as f32, BoxVector::default(), vec![[i as f32; 3]; 4]))
+            .collect();
+        let mut trr = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        assert_eq!(trr.write_all(&frames)?, 3);
+        assert_eq!(trr.write_all(&[])?, 0);
+        let bytes = trr.into_bytes().unwrap();
+        let mut trr = TRRTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let read = trr.read_all()?;
+        assert_eq!(read.len(), 3);
+        for (frame, expected) in read.iter().zip(&frames) {
+            assert_eq!(frame.step, expected.step);
+            assert_eq!(frame.coords, expected.coords);
+        }
+
+        // Nothing is written if any frame is invalid
+        let mut invalid = frames.clone();
+        invalid[2].velocities = Some(vec![[0.0; 3]; 3]);
+        let mut xtc = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let result = xtc.write_all(&invalid);
+        assert!(matches!(
+            result,
+            Err(Error::WrongSizeFrame {
+                expected: 4,
+                found: 3
+            })
+        ));
+        invalid[2] = frames[2].clone();
+        invalid[1].box_vector =
+            BoxVector::new([[-1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        assert!(matches!(
+            xtc.write_all(&invalid),
+            Err(Error::InvalidBox { .. })
+        ));
+        assert_eq!(xtc.into_bytes(), Some(Vec::new()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_with_precision() -> Result<()> {
+        let frame = Frame::from_coords(1, 1.0, BoxVector::default(), vec![[1.23456; 3]; 20]);
+        let mut xtc = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        xtc.set_precision(100.0);
+        xtc.write_with_precision(&frame, Some(10.0))?;
+        xtc.write_with_precision(&frame, None)?;
+        xtc.write_with_precision(&frame, Some(10000.0))?;
+        assert_eq!(xtc.precision(), 100.0);
+
+        let bytes = xtc.into_bytes().unwrap();
+        let precisions = xtc_frame_headers(&bytes)
+            .map(|header| header.map(|(_, header)| header.precision))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(precisions, vec![Some(10.0), Some(100.0), Some(10000.0)]);
+
+        let mut xtc = XTCTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let mut read = Frame::with_len(20);
+        for expected in [1.2, 1.23, 1.2346] {
+            xtc.read(&mut read)?;
+            assert_approx_eq!(read[0][0], expected, 1e-5);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_xtc_compression_error() -> Result<()> {
+        let mut frame = Frame::from_coords(1, 1.0, BoxVector::default(), vec![[1.0; 3]; 20]);
+        frame[4][1] = 3.0e6;
+        let mut xtc = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let err = xtc.write(&frame).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::XtcCompression {
+                atom: Some(4),
+                precision,
+                ..
+            } if precision == 1000.0
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Cannot compress coordinates of atom 4 with precision 1000: coordinate is too large"
+        );
+
+        // Lower precisions can store larger coordinates
+        xtc.write_with_precision(&frame, Some(100.0))?;
+        frame[4][1] = 1.5e6;
+        frame[5][1] = -1.5e6;
+        let err = xtc.write(&frame).unwrap_err();
+        assert!(matches!(err, Error::XtcCompression { atom: None, .. }));
+        frame[5][1] = f32::INFINITY;
+        let err = xtc.write(&frame).unwrap_err();
+        assert!(matches!(err, Error::XtcCompression { atom: Some(5), .. }));
+
+        // Small frames are not compressed
+        xtc.write(&Frame::from_coords(
+            2,
+            2.0,
+            BoxVector::default(),
+            vec![[3.0e6; 3]; 9],
+        ))?;
+        assert!(xtc.into_bytes().is_some_and(|bytes| !bytes.is_empty()));
+
+        // With validation, nothing is written
+        frame[4][1] = 3.0e6;
+        frame[5][1] = 1.0;
+        let mut xtc = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        xtc.set_validate(true);
+        let err = xtc.write(&frame).unwrap_err();
+        assert!(matches!(err, Error::XtcCompression { atom: Some(4), .. }));
+        assert_eq!(xtc.tell()?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate_values() -> Result<()> {
+        let mut frame = Frame::from_coords(1, 1.0, BoxVector::default(), vec![[1.0; 3]; 20]);
+        frame[4][1] = 3.0e6;
+        let mut xtc = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        xtc.set_validate(true);
+        frame[4][1] = f32::NAN;
+        let err = xtc.write(&frame).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidValue {
+                field: "coordinates",
+                atom: Some(4),
+                ..
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Cannot write coordinates NaN of atom 4: not a finite number"
+        );
+
+        let mut trr = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        trr.set_validate(true);
+        frame[4][1] = 3.0e6;
+        let mut velocities = vec![[0.0; 3]; 20];
+        velocities[7][2] = f32::INFINITY;
+        frame.velocities = Some(velocities);
+        assert!(matches!(
+            trr.write(&frame),
+            Err(Error::InvalidValue {
+                field: "velocities",
+                atom: Some(7),
+                ..
+            })
+        ));
+        frame.velocities = None;
+        frame.time = f32::NAN;
+        assert!(matches!(
+            trr.write(&frame),
+            Err(Error::InvalidValue {
+                field: "time",
+                atom: None,
+                ..
+            })
+        ));
+        frame.time = 1.0;
+        trr.write(&frame)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_flat() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let tmp_path = tempfile.path();
+
+        let header = FrameHeader {
+            step: 5,
+            time: 2.5,
+            box_vector: BoxVector::new([[3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [0.0, 0.0, 3.0]]),
+        };
+        let coords = [0.0, 0.1, 0.2, 1.0, 1.1, 1.2, 2.0, 2.1, 2.2];
+        let mut xtc = XTCTrajectory::open_write(tmp_path)?;
+        xtc.write_flat(&coords, &header)?;
+        let result = xtc.write_flat(&coords[..8], &header);
+        assert!(matches!(result, Err(Error::InvalidFrame { .. })));
+        xtc.close()?;
+
+        let mut xtc = XTCTrajectory::open_read(tmp_path)?;
+        let mut flat = [0.0; 10];
+        let result = xtc.read_flat(&mut flat, &mut FrameHeader::default());
+        assert!(matches!(result, Err(Error::InvalidFrame { .. })));
+
+        let mut read_header = FrameHeader::default();
+        xtc.read_flat(&mut flat[..9], &mut read_header)?;
+        assert_eq!(read_header, header);
+        for (a, b) in flat.iter().zip(&coords) {
+            assert_approx_eq!(a, b, 1e-3);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_into() -> Result<()> {
+        let xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let chain = ChainedTrajectory::new(vec![XTCTrajectory::open_read("tests/1l2y.xtc")?])?;
+        let trajectories: Vec<Box<dyn Trajectory>> =
+            vec![Box::new(xtc), Box::new(trr), Box::new(chain)];
+        let expected = XTCTrajectory::open_read("tests/1l2y.xtc")?.read_up_to(2)?;
+        for mut trajectory in trajectories {
+            // Read two frames into one preallocated array
+            let mut coords = vec![[0.0; 3]; 2 * 304];
+            let mut headers = [FrameHeader::default(); 2];
+            for (chunk, header) in coords.chunks_mut(304).zip(&mut headers) {
+                trajectory.read_into(chunk, header)?;
+            }
+            for (i, frame) in expected.iter().enumerate() {
+                assert_eq!(headers[i].step, frame.step);
+                assert_eq!(headers[i].time, frame.time);
+                assert_eq!(headers[i].box_vector, frame.box_vector);
+                let chunk = &coords[i * 304..(i + 1) * 304];
+                for (a, b) in chunk.iter().zip(&frame.coords) {
+                    assert_approx_eq!(a[0], b[0], 1e-3);
+                    assert_approx_eq!(a[1], b[1], 1e-3);
+                    assert_approx_eq!(a[2], b[2], 1e-3);
+                }
+            }
+
+            let result = trajectory.read_into(&mut coords, &mut headers[0]);
+            assert!(matches!(
+                result,
+                Err(Error::WrongSizeFrame {
+                    expected: 304,
+                    found: 608
+                })
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_rewind() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        for trajectory in [&mut xtc as &mut dyn Trajectory, &mut trr] {
+            assert_eq!(trajectory.read_all()?.len(), 38);
+            trajectory.rewind()?;
+            let frames = trajectory.read_all()?;
+            assert_eq!(frames.len(), 38);
+            assert_eq!(frames[0].step, 1);
+        }
+
+        let mut empty = XTCTrajectory::from_bytes(Vec::new(), FileMode::Read)?;
+        // XTCTrajectory also implements io::Seek, which has a rewind method
+        Trajectory::rewind(&mut empty)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_try_clone() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(xtc.get_num_atoms()?);
+        for _ in 0..10 {
+            xtc.read(&mut frame)?;
+        }
+        // The clone starts at the same position and reads independently
+        let clone = xtc.try_clone()?;
+        let steps = std::thread::spawn(move || -> Result<Vec<usize>> {
+            clone.into_frames().map(|f| f.map(|f| f.step)).collect()
+        });
+        xtc.read(&mut frame)?;
+        assert_eq!(frame.step, 11);
+        let steps = steps.join().expect("Reading thread panicked")?;
+        assert_eq!(steps, (11..=38).collect::<Vec<_>>());
+
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        trr.seek_to_frame(30)?;
+        trr.try_clone()?.read(&mut frame)?;
+        assert_eq!(frame.step, 31);
+
+        let buffer = XTCTrajectory::from_bytes(Vec::new(), FileMode::Read)?;
+        assert!(matches!(buffer.try_clone(), Err(Error::CannotClone { .. })));
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let writer = XTCTrajectory::open_write(tempfile.path())?;
+        assert!(matches!(writer.try_clone(), Err(Error::CannotClone { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut frame = Frame::with_len(304);
+        for trajectory in [&mut xtc as &mut dyn Trajectory, &mut trr] {
+            trajectory.skip(5)?;
+            trajectory.read(&mut frame)?;
+            assert_eq!(frame.step, 6);
+            trajectory.skip(0)?;
+            trajectory.skip(10)?;
+            trajectory.read(&mut frame)?;
+            assert_eq!(frame.step, 17);
+
+            // Skipping past the end keeps the position
+            assert!(trajectory.skip(22).unwrap_err().is_eof());
+            trajectory.skip(21)?;
+            assert!(trajectory.is_at_end()?);
+        }
+
+        // Frames are skipped by their headers if the frame index is unknown
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        xtc.seek_to_frame(3)?;
+        let offset = xtc.tell()?;
+        xtc.seek(SeekFrom::Start(offset)).expect("Could not seek");
+        xtc.skip(2)?;
+        xtc.read(&mut frame)?;
+        assert_eq!(frame.step, 6);
+        Ok(())
+    }
+
+    /// Trajectory of frames in memory that only implements the required
+    /// methods and `current_frame`
+    struct MemoryTrajectory {
+        frames: Vec<Frame>,
+        next: usize,
+    }
+
+    impl Trajectory for MemoryTrajectory {
+        fn read(&mut self, frame: &mut Frame) -> Result<()> {
+            let next = self.frames.get(self.next);
+            *frame = next
+                .cloned()
+                .ok_or((ErrorCode::ExdrEndOfFile, ErrorTask::Read))?;
+            self.next += 1;
+            Ok(())
+        }
+
+        fn write(&mut self, frame: &Frame) -> Result<()> {
+            self.frames.push(frame.clone());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        fn get_num_atoms(&mut self) -> Result<usize> {
+            Ok(self.frames.first().map_or(0, Frame::len))
+        }
+
+        fn current_frame(&self) -> Option<usize> {
+            Some(self.next)
+        }
+    }
+
+    #[test]
+    fn test_default_methods() -> Result<()> {
+        let frames = XTCTrajectory::open_read("tests/1l2y.xtc")?.read_all()?;
+        let mut trajectory = MemoryTrajectory { frames, next: 0 };
+        let mut frame = Frame::new();
+
+        trajectory.seek_to_frame(3)?;
+        trajectory.read(&mut frame)?;
+        assert_eq!(frame.step, 4);
+        assert_eq!(trajectory.current_frame(), Some(4));
+
+        // Seeking backward needs an implementation of seek_to_frame, which
+        // peeking and seeking by time rely on
+        let unsupported = |result: Result<()>| {
+            matches!(
+                result,
+                Err(Error::Unsupported {
+                    operation: "seek_to_frame"
+                })
+            )
+        };
+        assert!(unsupported(trajectory.seek_to_frame(2)));
+        assert!(unsupported(trajectory.peek(&mut frame)));
+        assert!(unsupported(trajectory.seek_to_time(9.5)));
+        assert!(matches!(
+            trajectory.num_frames(),
+            Err(Error::Unsupported { .. })
+        ));
+        assert!(matches!(
+            trajectory.is_at_end(),
+            Err(Error::Unsupported { .. })
+        ));
+        Ok(())
+    }
 }
@@ -0,0 +1,443 @@
+use crate::*;
+use index::{IndexEntry, SkipFrame, TrajectoryIndex};
+use std::fs::File;
+use std::io::{Read, Write};
+use zip::result::ZipError;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+/// Magic bytes at the start of numpy `.npy` files
+const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// Path of the offsets file that MDAnalysis reads and writes for the
+/// trajectory at `path`: a hidden file `.<name>_offsets.npz` next to it.
+///
+/// ```rust
+/// use std::path::Path;
+/// use xdrfile::*;
+///
+/// let path = mdanalysis_offsets_path("run/traj.xtc");
+/// assert_eq!(path, Path::new("run/.traj.xtc_offsets.npz"));
+/// ```
+pub fn mdanalysis_offsets_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push("_offsets.npz");
+    path.with_file_name(name)
+}
+
+macro_rules! impl_mdanalysis_offsets {
+    ($trajectory:ty, $skip_frame:path, $read_entry:path) => {
+        impl $trajectory {
+            /// Save the offsets of all frames to `path` in the format of the
+            /// offsets files of MDAnalysis, so that MDAnalysis opens the
+            /// trajectory without scanning it. MDAnalysis only finds files at
+            /// `mdanalysis_offsets_path` of the trajectory. Frames not
+            /// visited yet are scanned first.
+            pub fn save_mdanalysis_offsets(&mut self, path: impl AsRef<Path>) -> Result<()> {
+                save_offsets(
+                    &mut self.handle,
+                    &mut self.index,
+                    $skip_frame,
+                    path.as_ref(),
+                )
+            }
+
+            /// Load the frame offsets from an offsets file of MDAnalysis,
+            /// e.g. at `mdanalysis_offsets_path` of the trajectory. Only the
+            /// headers of the frames at the offsets are read, instead of
+            /// scanning the whole file.
+            ///
+            /// Returns false and keeps the current offsets if the size or
+            /// the number of atoms of the trajectory do not match the file,
+            /// or if a frame does not start at one of the offsets.
+            pub fn load_mdanalysis_offsets(&mut self, path: impl AsRef<Path>) -> Result<bool> {
+                match load_offsets(&mut self.handle, $read_entry, path.as_ref())? {
+                    Some(index) => {
+                        self.index = index;
+                        Ok(true)
+                    }
+                    None => Ok(false),
+                }
+            }
+        }
+    };
+}
+
+impl_mdanalysis_offsets!(XTCTrajectory, index::skip_xtc_frame, read_xtc_entry);
+impl_mdanalysis_offsets!(TRRTrajectory, index::skip_trr_frame, read_trr_entry);
+
+/// Metadata of the trajectory file of `handle`. Offsets files at
+/// `offsets_path` are only supported for trajectories on disk.
+fn trajectory_metadata(handle: &XDRFile, offsets_path: &Path) -> Result<std::fs::Metadata> {
+    let path = handle.path.as_ref().ok_or_else(|| Error::InvalidIndex {
+        path: offsets_path.to_owned(),
+        reason: "trajectory is not a file on disk",
+    })?;
+    std::fs::metadata(path).map_err(|err| Error::from((path.as_path(), FileMode::Read, err)))
+}
+
+/// Time of the last status change of a file in seconds, as returned by
+/// `os.path.getctime` in Python
+fn ctime(metadata: &std::fs::Metadata) -> f64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        metadata.ctime() as f64 + metadata.ctime_nsec() as f64 * 1e-9
+    }
+    #[cfg(not(unix))]
+    {
+        let created = metadata.created().unwrap_or(std::time::UNIX_EPOCH);
+        let since_epoch = created
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        since_epoch.as_secs_f64()
+    }
+}
+
+/// Write the offsets of all frames as a `.npz` archive of the arrays that
+/// MDAnalysis stores with `numpy.savez`
+fn save_offsets(
+    handle: &mut XDRFile,
+    index: &mut TrajectoryIndex,
+    skip_frame: SkipFrame,
+    path: &Path,
+) -> Result<()> {
+    let metadata = trajectory_metadata(handle, path)?;
+    let entries = index.entries(handle, skip_frame)?;
+    let num_atoms = entries.first().map_or(0, |entry| entry.num_atoms);
+    let offsets: Vec<u8> = entries
+        .iter()
+        .flat_map(|entry| entry.offset.to_le_bytes())
+        .collect();
+    let arrays = [
+        (
+            "offsets",
+            npy("<i8", &format!("({},)", entries.len()), &offsets),
+        ),
+        ("size", npy("<i8", "()", &metadata.len().to_le_bytes())),
+        ("ctime", npy("<f8", "()", &ctime(&metadata).to_le_bytes())),
+        (
+            "n_atoms",
+            npy("<i8", "()", &(num_atoms as u64).to_le_bytes()),
+        ),
+    ];
+
+    let io_err = |err| Error::from((path, FileMode::Write, err));
+    let zip_err = |err| match err {
+        ZipError::Io(err) => io_err(err),
+        _ => io_err(io::Error::other(err)),
+    };
+    let mut writer = ZipWriter::new(File::create(path).map_err(io_err)?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, bytes) in &arrays {
+        writer
+            .start_file(format!("{}.npy", name), options)
+            .map_err(zip_err)?;
+        writer.write_all(bytes).map_err(io_err)?;
+    }
+    writer.finish().map_err(zip_err)?;
+    Ok(())
+}
+
+/// Read the offsets from a `.npz` archive written by MDAnalysis and the
+/// headers of the frames at these offsets. None if the offsets do not match
+/// the trajectory.
+fn load_offsets(
+    handle: &mut XDRFile,
+    read_entry: SkipFrame,
+    path: &Path,
+) -> Result<Option<TrajectoryIndex>> {
+    let metadata = trajectory_metadata(handle, path)?;
+    let malformed = || Error::InvalidIndex {
+        path: path.to_owned(),
+        reason: "malformed MDAnalysis offsets file",
+    };
+    let io_err = |err: io::Error| match err.kind() {
+        io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => malformed(),
+        _ => Error::from((path, FileMode::Read, err)),
+    };
+
+    let file = File::open(path).map_err(io_err)?;
+    let mut archive = ZipArchive::new(file).map_err(|err| match err {
+        ZipError::Io(err) => io_err(err),
+        _ => malformed(),
+    })?;
+    let mut integers = |name: &str| -> Result<Vec<u64>> {
+        let mut bytes = Vec::new();
+        archive
+            .by_name(&format!("{}.npy", name))
+            .map_err(|_| malformed())?
+            .read_to_end(&mut bytes)
+            .map_err(io_err)?;
+        npy_integers(&bytes).ok_or_else(malformed)
+    };
+    let offsets = integers("offsets")?;
+    let size = integers("size")?;
+    let num_atoms = integers("n_atoms")?;
+    if size != [metadata.len()] {
+        return Ok(None);
+    }
+
+    handle.restoring_position(|handle| {
+        let mut entries = Vec::with_capacity(offsets.len());
+        for &offset in &offsets {
+            // A frame header that cannot be read means the offsets are of
+            // another file of the same size
+            let entry = handle
+                .seek_to(SeekFrom::Start(offset))
+                .and_then(|_| read_entry(handle));
+            match entry {
+                Ok(entry) if [entry.num_atoms as u64] == num_atoms[..] => entries.push(entry),
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(TrajectoryIndex::from_entries(entries, metadata.len())))
+    })
+}
+
+/// Read the header of the XTC frame at the current position, without
+/// skipping over its coordinates
+fn read_xtc_entry(handle: &mut XDRFile) -> Result<IndexEntry> {
+    let err = |code| Error::from((code, ErrorTask::Seek));
+    let offset = handle.tell()?;
+    match handle.read_int() {
+        Some(index::XTC_MAGIC) => {}
+        Some(_) => return Err(err(ErrorCode::ExdrMagic)),
+        None => return Err(err(ErrorCode::ExdrEndOfFile)),
+    }
+    let num_atoms = handle.read_int().ok_or_else(|| err(ErrorCode::ExdrInt))?;
+    let step = handle.read_int().ok_or_else(|| err(ErrorCode::ExdrInt))?;
+    let time = handle
+        .read_float()
+        .ok_or_else(|| err(ErrorCode::ExdrFloat))?;
+    Ok(IndexEntry {
+        offset,
+        num_atoms: to(num_atoms, ErrorTask::Seek, "num_atoms")?,
+        step: to(step, ErrorTask::Seek, "step")?,
+        time,
+    })
+}
+
+/// Read the header of the TRR frame at the current position, without
+/// skipping over its data
+fn read_trr_entry(handle: &mut XDRFile) -> Result<IndexEntry> {
+    let offset = handle.tell()?;
+    let header = index::read_trr_header(handle, ErrorTask::Seek)?;
+    Ok(IndexEntry {
+        offset,
+        num_atoms: to(header.natoms, ErrorTask::Seek, "num_atoms")?,
+        step: to(header.step, ErrorTask::Seek, "step")?,
+        time: header.tf,
+    })
+}
+
+/// Encode an array with the data type `descr` and the shape `shape`, both in
+/// numpy notation, as an `.npy` file of format version 1.0
+fn npy(descr: &str, shape: &str, data: &[u8]) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    // numpy pads the header with spaces to align the data to 64 bytes
+    let unpadded = NPY_MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
+
+    let mut bytes = NPY_MAGIC.to_vec();
+    bytes.extend_from_slice(&[1, 0]);
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+/// Decode the values of an `.npy` file holding a little endian integer array
+/// of any shape. None if the file is malformed, of another data type or
+/// holds negative values.
+fn npy_integers(bytes: &[u8]) -> Option<Vec<u64>> {
+    if bytes.get(..6)? != NPY_MAGIC {
+        return None;
+    }
+    // Version 1.0 has a 2 byte header length, later versions 4 bytes
+    let (header_start, header_len) = match bytes.get(6)? {
+        1 => (
+            10,
+            usize::from(u16::from_le_bytes(bytes.get(8..10)?.try_into().ok()?)),
+        ),
+        _ => (
+            12,
+            u32::from_le_bytes(bytes.get(8..12)?.try_into().ok()?) as usize,
+        ),
+    };
+    let data = bytes.get(header_start + header_len..)?;
+    let header = std::str::from_utf8(bytes.get(header_start..header_start + header_len)?).ok()?;
+
+    let value_of = |key: &str| {
+        let start = header.find(&format!("'{}':", key))? + key.len() + 3;
+        Some(header[start..].trim_start())
+    };
+    let descr = value_of("descr")?.strip_prefix('\'')?.split('\'').next()?;
+    let shape = value_of("shape")?.strip_prefix('(')?.split(')').next()?;
+    let len = shape
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .try_fold(1usize, |len, dim| len.checked_mul(dim.parse().ok()?))?;
+
+    let (size, signed) = match descr {
+        "<i8" => (8, true),
+        "<u8" => (8, false),
+        "<i4" => (4, true),
+        "<u4" => (4, false),
+        _ => return None,
+    };
+    let values = data.get(..len.checked_mul(size)?)?;
+    values
+        .chunks_exact(size)
+        .map(|value| {
+            let mut bytes = [0; 8];
+            bytes[..size].copy_from_slice(value);
+            // Offsets, sizes and numbers of atoms are never negative
+            if signed && value[size - 1] & 0x80 != 0 {
+                return None;
+            }
+            Some(u64::from_le_bytes(bytes))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_mdanalysis_offsets() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("offsets.npz");
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        trajectory.save_mdanalysis_offsets(&path)?;
+        let expected: Vec<IndexEntry> = trajectory
+            .index
+            .entries(&mut trajectory.handle, index::skip_xtc_frame)?
+            .to_vec();
+
+        let mut archive = ZipArchive::new(File::open(&path).unwrap()).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort_unstable();
+        assert_eq!(
+            names,
+            ["ctime.npy", "n_atoms.npy", "offsets.npy", "size.npy"]
+        );
+        let mut bytes = Vec::new();
+        archive
+            .by_name("offsets.npy")
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes.len() % 64, (38 * 8) % 64);
+        let offsets = npy_integers(&bytes).unwrap();
+        assert_eq!(offsets.len(), 38);
+        assert_eq!(offsets[0], 0);
+
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert!(trajectory.load_mdanalysis_offsets(&path)?);
+        assert_eq!(
+            trajectory
+                .index
+                .entries(&mut trajectory.handle, index::skip_xtc_frame)?,
+            &expected[..]
+        );
+        trajectory.seek_to_frame(37)?;
+        let mut frame = Frame::with_len(304);
+        trajectory.read(&mut frame)?;
+        assert_eq!(frame.step, 38);
+
+        // The offsets of another file are not used
+        let mut trajectory = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        assert!(!trajectory.load_mdanalysis_offsets(&path)?);
+        assert_eq!(trajectory.num_frames()?, 38);
+        Ok(())
+    }
+
+    #[test]
+    fn test_mdanalysis_offsets_trr() -> Result<()> {
+        let dir = tempdir().unwrap();
+        let path = mdanalysis_offsets_path(dir.path().join("1l2y.trr"));
+        let mut trajectory = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        trajectory.save_mdanalysis_offsets(&path)?;
+
+        let mut trajectory = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        assert!(trajectory.load_mdanalysis_offsets(&path)?);
+        trajectory.seek_to_frame(20)?;
+        let mut frame = Frame::with_len(304);
+        trajectory.read(&mut frame)?;
+        assert_eq!(frame.step, 21);
+        Ok(())
+    }
+
+    #[test]
+    fn test_numpy_offsets() -> Result<()> {
+        // An archive as written by numpy.savez_compressed, with offsets that
+        // do not point to frames
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("offsets.npz");
+        let size = std::fs::metadata("tests/1l2y.xtc").unwrap().len();
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let arrays = [
+            (
+                "offsets.npy",
+                npy("<i8", "(2,)", &[[0; 8], [4; 8]].concat()),
+            ),
+            ("size.npy", npy("<i8", "()", &size.to_le_bytes())),
+            ("n_atoms.npy", npy("<i4", "()", &304u32.to_le_bytes())),
+        ];
+        for (name, bytes) in &arrays {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(bytes).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert!(!trajectory.load_mdanalysis_offsets(&path)?);
+        assert_eq!(trajectory.num_frames()?, 38);
+
+        std::fs::write(&path, b"not an archive").unwrap();
+        assert!(matches!(
+            trajectory.load_mdanalysis_offsets(&path),
+            Err(Error::InvalidIndex { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_npy_integers() {
+        // Header as written by numpy 1.x for np.arange(3)
+        let header = "{'descr': '<i8', 'fortran_order': False, 'shape': (3,), }";
+        let mut bytes = b"\x93NUMPY\x01\x00\x76\x00".to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend_from_slice(&[b' '; 117 - 57]);
+        bytes.push(b'\n');
+        for value in 0u64..3 {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        assert_eq!(bytes.len(), 128 + 24);
+        assert_eq!(npy_integers(&bytes), Some(vec![0, 1, 2]));
+        assert_eq!(npy("<i8", "(3,)", &bytes[128..]), bytes);
+
+        assert_eq!(
+            npy_integers(&npy("<i4", "()", &7i32.to_le_bytes())),
+            Some(vec![7])
+        );
+        assert_eq!(
+            npy_integers(&npy("<i4", "()", &(-7i32).to_le_bytes())),
+            None
+        );
+        assert_eq!(npy_integers(&npy("<f8", "()", &1.0f64.to_le_bytes())), None);
+        assert_eq!(npy_integers(&npy("<i8", "(2,)", &[0; 8])), None);
+        assert_eq!(npy_integers(b"\x93NUMPY"), None);
+    }
+}
@@ -0,0 +1,198 @@
+use crate::*;
+
+/// Settings for opening a trajectory with `XTCTrajectory::open_with`,
+/// `TRRTrajectory::open_with` or `TrajectoryFormat::open_with`, instead of
+/// calling the setters of the trajectory after opening it.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let options = TrajectoryOptions::new().precision(100.0).validate(true);
+///     let tempfile = tempfile::NamedTempFile::new().unwrap();
+///     let mut trajectory = XTCTrajectory::open_with(&tempfile, FileMode::Write, &options)?;
+///     assert_eq!(trajectory.precision(), 100.0);
+///
+///     let mut frame = Frame::with_len(1);
+///     frame.time = f32::NAN;
+///     assert!(trajectory.write(&frame).is_err());
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrajectoryOptions {
+    precision: f32,
+    validate: bool,
+    resync: bool,
+    flush_on_drop: bool,
+    buffer_size: Option<usize>,
+}
+
+impl Default for TrajectoryOptions {
+    fn default() -> TrajectoryOptions {
+        TrajectoryOptions {
+            precision: 1000.0,
+            validate: false,
+            resync: false,
+            flush_on_drop: false,
+            buffer_size: None,
+        }
+    }
+}
+
+impl TrajectoryOptions {
+    /// Options with the same defaults as the plain `open` constructors
+    pub fn new() -> TrajectoryOptions {
+        TrajectoryOptions::default()
+    }
+
+    /// Precision written XTC frames are compressed with (default 1000), see
+    /// `XTCTrajectory::set_precision`. Ignored for TRR trajectories.
+    pub fn precision(mut self, precision: f32) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Check the values of frames before writing them (default false), see
+    /// `XTCTrajectory::set_validate`
+    pub fn validate(mut self, validate: bool) -> Self {
+        self.validate = validate;
+        self
+    }
+
+    /// Skip corrupt frames while reading (default false), see
+    /// `XTCTrajectory::set_resync`
+    pub fn resync(mut self, resync: bool) -> Self {
+        self.resync = resync;
+        self
+    }
+
+    /// Report errors while flushing and closing on drop on stderr (default
+    /// false), see `XTCTrajectory::set_flush_on_drop`
+    pub fn flush_on_drop(mut self, flush_on_drop: bool) -> Self {
+        self.flush_on_drop = flush_on_drop;
+        self
+    }
+
+    /// Size in bytes of the buffer used for reading and writing the file
+    /// (default chosen by the C library, usually a few KiB). Larger
+    /// buffers need fewer requests to the file system, which can speed up
+    /// IO considerably on network file systems like NFS or Lustre. A size
+    /// of 0 disables buffering. Ignored for trajectories opened from
+    /// streams or buffers, which do their own buffering.
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        self.buffer_size = Some(size);
+        self
+    }
+
+    /// Open `path` as an XTC trajectory with these options
+    fn open_xtc(&self, path: &Path, filemode: FileMode) -> Result<XTCTrajectory> {
+        let mut trajectory = XTCTrajectory::open(path, filemode)?;
+        trajectory.set_precision(self.precision);
+        self.apply(&mut trajectory.handle)?;
+        Ok(trajectory)
+    }
+
+    /// Open `path` as a TRR trajectory with these options
+    fn open_trr(&self, path: &Path, filemode: FileMode) -> Result<TRRTrajectory> {
+        let mut trajectory = TRRTrajectory::open(path, filemode)?;
+        self.apply(&mut trajectory.handle)?;
+        Ok(trajectory)
+    }
+
+    /// Apply the options shared by all formats to a newly opened file
+    fn apply(&self, handle: &mut XDRFile) -> Result<()> {
+        if let Some(size) = self.buffer_size {
+            handle.set_buffer_size(size)?;
+        }
+        handle.validate = self.validate;
+        handle.resync = self.resync;
+        handle.flush_on_drop = self.flush_on_drop;
+        Ok(())
+    }
+}
+
+impl XTCTrajectory {
+    /// Open a file like `open`, configured with `options`
+    pub fn open_with(
+        path: impl AsRef<Path>,
+        filemode: FileMode,
+        options: &TrajectoryOptions,
+    ) -> Result<XTCTrajectory> {
+        options.open_xtc(path.as_ref(), filemode)
+    }
+}
+
+impl TRRTrajectory {
+    /// Open a file like `open`, configured with `options`
+    pub fn open_with(
+        path: impl AsRef<Path>,
+        filemode: FileMode,
+        options: &TrajectoryOptions,
+    ) -> Result<TRRTrajectory> {
+        options.open_trr(path.as_ref(), filemode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_open_with() -> Result<()> {
+        let options = TrajectoryOptions::new()
+            .precision(100.0)
+            .validate(true)
+            .resync(true)
+            .flush_on_drop(true);
+        let xtc = XTCTrajectory::open_with("tests/1l2y.xtc", FileMode::Read, &options)?;
+        assert_eq!(xtc.precision(), 100.0);
+        assert!(xtc.handle.validate && xtc.handle.resync && xtc.handle.flush_on_drop);
+
+        let trr = TRRTrajectory::open_with("tests/1l2y.trr", FileMode::Read, &options)?;
+        assert!(trr.handle.validate && trr.handle.resync && trr.handle.flush_on_drop);
+
+        let defaults =
+            XTCTrajectory::open_with("tests/1l2y.xtc", FileMode::Read, &Default::default())?;
+        assert_eq!(defaults.precision(), 1000.0);
+        assert!(!defaults.handle.validate && !defaults.handle.resync);
+
+        let missing =
+            TrajectoryOptions::new().open_xtc(Path::new("does/not/exist.xtc"), FileMode::Read);
+        assert!(matches!(missing, Err(Error::CouldNotOpen { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_buffer_size() -> Result<()> {
+        let expected = TRRTrajectory::open_read("tests/1l2y.trr")?.read_all()?;
+        for &size in &[0, 1, 100, 1 << 20] {
+            let options = TrajectoryOptions::new().buffer_size(size);
+            let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+            let mut writer = TRRTrajectory::open_with(&tempfile, FileMode::Write, &options)?;
+            let mut reader = TRRTrajectory::open_read("tests/1l2y.trr")?;
+            let mut frame = Frame::with_len(304);
+            while reader.read(&mut frame).is_ok() {
+                writer.write(&frame)?;
+            }
+            writer.close()?;
+
+            let mut trajectory = TRRTrajectory::open_with(&tempfile, FileMode::Read, &options)?;
+            assert_eq!(trajectory.num_frames()?, 38);
+            trajectory.seek_to_frame(20)?;
+            let mut clone = trajectory.try_clone()?;
+            assert_eq!(clone.handle.io_buffer.as_ref().map(|b| b.len()), Some(size));
+            clone.read(&mut frame)?;
+            assert_eq!(frame.step, 21);
+            assert_eq!(frame.coords, expected[20].coords);
+        }
+
+        // Streams do their own buffering
+        let options = TrajectoryOptions::new().buffer_size(16);
+        let mut trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        options.apply(&mut trajectory.handle)?;
+        assert!(trajectory.handle.io_buffer.is_none());
+        Ok(())
+    }
+}
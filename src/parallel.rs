@@ -0,0 +1,155 @@
+use crate::*;
+use std::thread;
+
+/// Number of threads to read `num_frames` frames with
+pub(crate) fn num_threads(num_frames: usize) -> usize {
+    let available = thread::available_parallelism().map_or(1, |n| n.get());
+    available.min(num_frames).max(1)
+}
+
+/// Read the frames with the given indices concurrently, one thread for each
+/// of the independent `handles` to the same trajectory. The frames are
+/// returned in the order of `frames`.
+pub(crate) fn read_frames<T: Trajectory + Send>(
+    handles: Vec<T>,
+    frames: &[usize],
+) -> Result<Vec<Frame>> {
+    if frames.is_empty() {
+        return Ok(Vec::new());
+    }
+    let chunk_size = frames.len().div_ceil(handles.len());
+    let chunks = thread::scope(|scope| {
+        let threads: Vec<_> = handles
+            .into_iter()
+            .zip(frames.chunks(chunk_size))
+            .map(|(mut handle, chunk)| scope.spawn(move || read_chunk(&mut handle, chunk)))
+            .collect();
+        threads
+            .into_iter()
+            .map(|thread| {
+                thread
+                    .join()
+                    .unwrap_or_else(|e| std::panic::resume_unwind(e))
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let mut result = Vec::with_capacity(frames.len());
+    for chunk in chunks {
+        result.extend(chunk?);
+    }
+    Ok(result)
+}
+
+/// Number of frames per thread read at once by `map_frames`, before the
+/// closure is evaluated for all of them in parallel
+#[cfg(feature = "rayon")]
+const MAP_FRAMES_PER_THREAD: usize = 4;
+
+/// Read the remaining frames and evaluate `f` for each of them, see
+/// `Trajectory::map_frames`. With rayon, frames are read in batches and `f`
+/// is evaluated for the frames of a batch in parallel.
+pub(crate) fn map_frames<T, R, F>(trajectory: &mut T, f: F) -> Result<Vec<R>>
+where
+    T: Trajectory + ?Sized,
+    F: Fn(&Frame) -> R + Send + Sync,
+    R: Send,
+{
+    let mut results = Vec::new();
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        let batch_size = rayon::current_num_threads() * MAP_FRAMES_PER_THREAD;
+        loop {
+            let frames = trajectory.read_up_to(batch_size)?;
+            results.par_extend(frames.par_iter().map(&f));
+            if frames.len() < batch_size {
+                break;
+            }
+        }
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        let mut frames = FrameReader::new(trajectory);
+        while let Some(frame) = frames.next()? {
+            results.push(f(frame));
+        }
+    }
+    Ok(results)
+}
+
+/// Read the frames with the given indices one after another
+fn read_chunk<T: Trajectory>(trajectory: &mut T, frames: &[usize]) -> Result<Vec<Frame>> {
+    let num_atoms = trajectory.get_num_atoms()?;
+    frames
+        .iter()
+        .map(|&index| {
+            trajectory.seek_to_frame(index)?;
+            let mut frame = Frame::with_len(num_atoms);
+            trajectory.read(&mut frame)?;
+            Ok(frame)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_frames_parallel() -> Result<()> {
+        let indices = [37, 0, 5, 5, 20, 1, 2, 3, 4, 30, 31];
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let xtc_frames = xtc.read_frames_parallel(&indices)?;
+        let trr_frames = trr.read_frames_parallel(&indices)?;
+
+        let all = XTCTrajectory::open_read("tests/1l2y.xtc")?.read_all()?;
+        for (frames, tolerance) in [(xtc_frames, 0.0), (trr_frames, 1e-3)] {
+            assert_eq!(frames.len(), indices.len());
+            for (frame, &index) in frames.iter().zip(&indices) {
+                assert!(frame.approx_eq(&all[index], tolerance));
+            }
+        }
+
+        // The position of the trajectory is kept
+        let mut frame = Frame::with_len(304);
+        xtc.read(&mut frame)?;
+        assert_eq!(frame.step, 1);
+        assert!(xtc.read_frames_parallel(&[])?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_map_frames() -> Result<()> {
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        trr.seek_to_frame(3)?;
+        let steps = trr.map_frames(|frame| frame.step)?;
+        assert_eq!(steps, (4..=38).collect::<Vec<_>>());
+        assert!(trr.map_frames(|frame| frame.step)?.is_empty());
+
+        let all = XTCTrajectory::open_read("tests/1l2y.xtc")?.read_all()?;
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let first = xtc.map_frames(|frame| frame[0])?;
+        assert_eq!(first.len(), 38);
+        for (coords, frame) in first.iter().zip(&all) {
+            assert_eq!(*coords, frame[0]);
+        }
+
+        let mut empty = XTCTrajectory::from_bytes(Vec::new(), FileMode::Read)?;
+        assert!(empty.map_frames(|frame| frame.step).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_frames_parallel_errors() -> Result<()> {
+        let mut xtc = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        assert!(xtc.read_frames_parallel(&[3, 38]).unwrap_err().is_eof());
+
+        let bytes = std::fs::read("tests/1l2y.xtc").expect("Could not read test file");
+        let mut xtc = XTCTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let result = xtc.read_frames_parallel(&[3]);
+        assert!(matches!(result, Err(Error::CannotClone { .. })));
+        Ok(())
+    }
+}
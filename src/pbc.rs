@@ -0,0 +1,402 @@
+use crate::{AtomSelection, BoxShape, BoxVector, Error, Frame, Result};
+
+impl Frame {
+    /// Put all atoms into the primary unit cell spanned by `box_vector`.
+    ///
+    /// Like in GROMACS, the box vectors are expected to be the rows of
+    /// `box_vector` and to form a lower triangular matrix, which covers both
+    /// rectangular and triclinic boxes. Dimensions with a box length of zero
+    /// are not wrapped.
+    pub fn wrap_pbc(&mut self) {
+        let box_vector = self.box_vector;
+        #[cfg(feature = "simd")]
+        if box_vector.shape() != BoxShape::Triclinic {
+            let lengths = [box_vector[0][0], box_vector[1][1], box_vector[2][2]];
+            return crate::simd::wrap_rectangular(&mut self.coords, lengths);
+        }
+        for coords in self.coords.iter_mut() {
+            shift_into_box(&box_vector, coords, f32::floor);
+        }
+    }
+
+    /// Put every atom at its periodic image closest to the center of the
+    /// box, like `gmx trjconv -pbc atom -ur compact`.
+    ///
+    /// `wrap_pbc` fills the parallelepiped spanned by the box vectors, which
+    /// is a poor picture of triclinic boxes such as rhombic dodecahedra and
+    /// truncated octahedra. Here, atoms end up in the compact shape around
+    /// the center instead. Frames without a box are left unchanged.
+    pub fn wrap_compact(&mut self) {
+        let box_vector = self.box_vector;
+        if box_vector.shape() == BoxShape::None {
+            return;
+        }
+        let center = combination(&box_vector, [0.5; 3]);
+        for coords in self.coords.iter_mut() {
+            let d = minimum_image(
+                &box_vector,
+                [
+                    coords[0] - center[0],
+                    coords[1] - center[1],
+                    coords[2] - center[2],
+                ],
+            );
+            *coords = [center[0] + d[0], center[1] + d[1], center[2] + d[2]];
+        }
+    }
+
+    /// Undo the jumps of atoms across the box since `previous`, like
+    /// `gmx trjconv -pbc nojump`: every atom is moved to its periodic image
+    /// closest to its position in `previous`, using the box of this frame.
+    ///
+    /// Applied to every frame of a trajectory with the unwrapped frame
+    /// before it, this gives continuous paths, e.g. for diffusion analyses.
+    /// Returns an error if `previous` has a different number of atoms.
+    pub fn unwrap_from(&mut self, previous: &Frame) -> Result<()> {
+        if previous.len() != self.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: self.len(),
+                found: previous.len(),
+            });
+        }
+        for (coords, last) in self.coords.iter_mut().zip(&previous.coords) {
+            let d = minimum_image(
+                &self.box_vector,
+                [
+                    coords[0] - last[0],
+                    coords[1] - last[1],
+                    coords[2] - last[2],
+                ],
+            );
+            *coords = [last[0] + d[0], last[1] + d[1], last[2] + d[2]];
+        }
+        Ok(())
+    }
+
+    /// Coordinates of all atoms in units of the box vectors, see
+    /// `BoxVector::to_fractional`
+    pub fn fractional_coords(&self) -> Vec<[f32; 3]> {
+        self.coords
+            .iter()
+            .map(|&coords| self.box_vector.to_fractional(coords))
+            .collect()
+    }
+
+    /// Move the selected atoms by whole box vectors, see
+    /// `BoxVector::lattice_vector`. Selected indices beyond the number of
+    /// atoms are ignored.
+    pub fn shift_by_lattice(&mut self, selection: &AtomSelection, cells: [i32; 3]) {
+        let shift = self.box_vector.lattice_vector(cells);
+        for i in selection.indices_below(self.len()) {
+            for (c, s) in self.coords[i].iter_mut().zip(shift) {
+                *c += s;
+            }
+        }
+    }
+
+    /// Vector from atom `i` to the nearest periodic image of atom `j`
+    /// (minimum image convention), using the box of the frame
+    ///
+    /// # Panics
+    /// Panics if `i` or `j` is out of bounds.
+    pub fn displacement(&self, i: usize, j: usize) -> [f32; 3] {
+        minimum_image(&self.box_vector, self.displacement_no_pbc(i, j))
+    }
+
+    /// Distance between atom `i` and the nearest periodic image of atom `j`
+    ///
+    /// # Panics
+    /// Panics if `i` or `j` is out of bounds.
+    pub fn distance(&self, i: usize, j: usize) -> f32 {
+        norm(self.displacement(i, j))
+    }
+
+    /// Vector from atom `i` to atom `j`, ignoring periodic boundaries
+    ///
+    /// # Panics
+    /// Panics if `i` or `j` is out of bounds.
+    pub fn displacement_no_pbc(&self, i: usize, j: usize) -> [f32; 3] {
+        let (a, b) = (self.coords[i], self.coords[j]);
+        [b[0] - a[0], b[1] - a[1], b[2] - a[2]]
+    }
+
+    /// Distance between atom `i` and atom `j`, ignoring periodic boundaries
+    ///
+    /// # Panics
+    /// Panics if `i` or `j` is out of bounds.
+    pub fn distance_no_pbc(&self, i: usize, j: usize) -> f32 {
+        norm(self.displacement_no_pbc(i, j))
+    }
+}
+
+impl BoxVector {
+    /// Position of `coords` in units of the box vectors a, b and c, so that
+    /// the primary unit cell spans 0 to 1 in every dimension. Dimensions
+    /// with a box length of zero have a fractional coordinate of zero.
+    pub fn to_fractional(&self, coords: [f32; 3]) -> [f32; 3] {
+        // The box is lower triangular, so solve from the last vector on
+        let mut remaining = coords;
+        let mut fractional = [0.0; 3];
+        for dim in (0..3).rev() {
+            let length = self[dim][dim];
+            if length == 0.0 {
+                continue;
+            }
+            fractional[dim] = remaining[dim] / length;
+            for d in 0..=dim {
+                remaining[d] -= fractional[dim] * self[dim][d];
+            }
+        }
+        fractional
+    }
+
+    /// Cartesian position of coordinates in units of the box vectors, the
+    /// inverse of `to_fractional`
+    pub fn from_fractional(&self, fractional: [f32; 3]) -> [f32; 3] {
+        combination(self, fractional)
+    }
+
+    /// The vector that moves a position by `cells[0]` times a, `cells[1]`
+    /// times b and `cells[2]` times c, i.e. to one of its periodic images
+    pub fn lattice_vector(&self, cells: [i32; 3]) -> [f32; 3] {
+        combination(self, cells.map(|n| n as f32))
+    }
+}
+
+/// Sum of the box vectors weighted by `factors`
+fn combination(box_vector: &BoxVector, factors: [f32; 3]) -> [f32; 3] {
+    let mut sum = [0.0; 3];
+    for (factor, vector) in factors.iter().zip(box_vector.as_matrix()) {
+        for dim in 0..3 {
+            sum[dim] += factor * vector[dim];
+        }
+    }
+    sum
+}
+
+/// The shortest periodic image of the vector `d`
+fn minimum_image(box_vector: &BoxVector, mut d: [f32; 3]) -> [f32; 3] {
+    shift_into_box(box_vector, &mut d, f32::round);
+    if box_vector.shape() == BoxShape::Triclinic {
+        // Rounding per dimension is not exact in skewed boxes, so also
+        // check the images in the neighbouring cells
+        d = nearest_image(box_vector, d);
+    }
+    d
+}
+
+/// Shift `coords` by whole box vectors, as many as `round` of the fractional
+/// position gives per dimension. The last box vector is handled first, as it
+/// is the only one with a z component.
+fn shift_into_box(box_vector: &BoxVector, coords: &mut [f32; 3], round: fn(f32) -> f32) {
+    for dim in (0..3).rev() {
+        let length = box_vector[dim][dim];
+        if length == 0.0 {
+            continue;
+        }
+        let shift = round(coords[dim] / length);
+        if shift != 0.0 {
+            for d in 0..=dim {
+                coords[d] -= shift * box_vector[dim][d];
+            }
+        }
+    }
+}
+
+/// The shortest of `d` and its images shifted by up to one box vector in
+/// each direction
+fn nearest_image(box_vector: &BoxVector, d: [f32; 3]) -> [f32; 3] {
+    let mut nearest = d;
+    let mut nearest_norm = norm(d);
+    for a in -1..=1 {
+        for b in -1..=1 {
+            for c in -1..=1 {
+                let shift = box_vector.lattice_vector([a, b, c]);
+                let image = [d[0] + shift[0], d[1] + shift[1], d[2] + shift[2]];
+                let image_norm = norm(image);
+                if image_norm < nearest_norm {
+                    nearest = image;
+                    nearest_norm = image_norm;
+                }
+            }
+        }
+    }
+    nearest
+}
+
+/// Euclidean length of a vector
+fn norm(v: [f32; 3]) -> f32 {
+    (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap_pbc_rectangular() {
+        let mut frame = Frame::with_len(3);
+        frame.box_vector = BoxVector::rectangular(2.0, 3.0, 4.0);
+        frame[0] = [1.0, 1.0, 1.0];
+        frame[1] = [-0.5, 3.5, 9.0];
+        frame[2] = [2.0, -6.0, -0.0];
+        frame.wrap_pbc();
+        assert_eq!(frame[0], [1.0, 1.0, 1.0]);
+        assert_eq!(frame[1], [1.5, 0.5, 1.0]);
+        assert_eq!(frame[2], [0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_wrap_pbc_triclinic() {
+        let mut frame = Frame::with_len(1);
+        frame.box_vector = BoxVector::new([[2.0, 0.0, 0.0], [1.0, 2.0, 0.0], [0.5, 0.5, 2.0]]);
+        frame[0] = [0.0, -0.5, 2.5];
+        frame.wrap_pbc();
+        // shifted by -c, then by +b
+        assert_eq!(frame[0], [0.5, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn test_wrap_pbc_without_box() {
+        let mut frame = Frame::with_len(1);
+        frame[0] = [-1.0, 5.0, 3.0];
+        frame.wrap_pbc();
+        assert_eq!(frame[0], [-1.0, 5.0, 3.0]);
+    }
+
+    #[test]
+    fn test_distance_rectangular() {
+        let mut frame = Frame::with_len(2);
+        frame.box_vector = BoxVector::rectangular(2.0, 3.0, 4.0);
+        frame[0] = [0.25, 0.5, 1.0];
+        frame[1] = [1.75, 0.5, 4.0];
+        assert_eq!(frame.displacement(0, 1), [-0.5, 0.0, -1.0]);
+        assert_eq!(frame.displacement(1, 0), [0.5, 0.0, 1.0]);
+        assert_approx_eq!(frame.distance(0, 1), 1.25f32.sqrt());
+        assert_eq!(frame.displacement_no_pbc(0, 1), [1.5, 0.0, 3.0]);
+        assert_approx_eq!(frame.distance_no_pbc(0, 1), 11.25f32.sqrt());
+    }
+
+    #[test]
+    fn test_distance_triclinic() {
+        let mut frame = Frame::with_len(2);
+        frame.box_vector = BoxVector::new([[2.0, 0.0, 0.0], [1.9, 0.5, 0.0], [0.0, 0.0, 5.0]]);
+        frame[0] = [0.0, 0.0, 0.0];
+        frame[1] = [0.1, 0.5, 0.0];
+        // the image shifted by -b and +a is the nearest
+        let d = frame.displacement(0, 1);
+        assert_approx_eq!(d[0], 0.2);
+        assert_approx_eq!(d[1], 0.0);
+        assert_approx_eq!(frame.distance(0, 1), 0.2);
+    }
+
+    /// Rhombic dodecahedron with a square xy plane, as set up by GROMACS
+    fn dodecahedron() -> BoxVector {
+        BoxVector::new([[4.0, 0.0, 0.0], [0.0, 4.0, 0.0], [2.0, 2.0, 2.828427]])
+    }
+
+    #[test]
+    fn test_fractional_coords() {
+        let box_vector = BoxVector::new([[2.0, 0.0, 0.0], [1.0, 2.0, 0.0], [0.5, 0.5, 2.0]]);
+        assert_eq!(box_vector.to_fractional([2.0, 2.5, 2.0]), [0.25, 1.0, 1.0]);
+        assert_eq!(
+            box_vector.from_fractional([0.25, 1.0, 1.0]),
+            [2.0, 2.5, 2.0]
+        );
+        assert_eq!(box_vector.lattice_vector([1, -1, 2]), [2.0, -1.0, 4.0]);
+
+        let mut frame = Frame::with_len(2);
+        frame.box_vector = box_vector;
+        frame[1] = box_vector.lattice_vector([0, 0, 1]);
+        assert_eq!(frame.fractional_coords(), vec![[0.0; 3], [0.0, 0.0, 1.0]]);
+
+        // Dimensions without a length are left out
+        let flat = BoxVector::rectangular(2.0, 4.0, 0.0);
+        assert_eq!(flat.to_fractional([1.0, 1.0, 5.0]), [0.5, 0.25, 0.0]);
+    }
+
+    #[test]
+    fn test_shift_by_lattice() {
+        let mut frame = Frame::with_len(3);
+        frame.box_vector = dodecahedron();
+        frame.shift_by_lattice(&AtomSelection::new(vec![1, 2, 7]), [1, 0, -1]);
+        assert_eq!(frame[0], [0.0; 3]);
+        assert_eq!(frame[1], [2.0, -2.0, -2.828427]);
+        assert_eq!(frame[1], frame[2]);
+    }
+
+    #[test]
+    fn test_wrap_compact() {
+        let box_vector = dodecahedron();
+        let center = combination(&box_vector, [0.5; 3]);
+        let mut frame = Frame::with_len(4);
+        frame.box_vector = box_vector;
+        frame[0] = center;
+        frame[1] = [0.1, 0.1, 0.1];
+        frame[2] = [4.5, -3.0, 7.0];
+        frame[3] = [-10.0, 3.9, -0.5];
+        let original = frame.clone();
+        frame.wrap_compact();
+        assert_eq!(frame[0], center);
+
+        let distance = |a: [f32; 3], b: [f32; 3]| norm([a[0] - b[0], a[1] - b[1], a[2] - b[2]]);
+        for (wrapped, original) in frame.coords.iter().zip(&original.coords) {
+            // Still an image of the original position
+            let shift = box_vector.to_fractional([
+                wrapped[0] - original[0],
+                wrapped[1] - original[1],
+                wrapped[2] - original[2],
+            ]);
+            for cells in shift {
+                assert_approx_eq!(cells, cells.round(), 1e-4);
+            }
+            // and no image is closer to the center
+            let to_center = distance(*wrapped, center);
+            for cells in [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, -1], [-1, 0, 1]] {
+                for sign in [1, -1] {
+                    let shift = box_vector.lattice_vector(cells.map(|n| n * sign));
+                    let image = [
+                        wrapped[0] + shift[0],
+                        wrapped[1] + shift[1],
+                        wrapped[2] + shift[2],
+                    ];
+                    assert!(to_center <= distance(image, center) + 1e-5);
+                }
+            }
+        }
+
+        let mut frame = Frame::with_len(1);
+        frame[0] = [-1.0, 5.0, 3.0];
+        frame.wrap_compact();
+        assert_eq!(frame[0], [-1.0, 5.0, 3.0]);
+    }
+
+    #[test]
+    fn test_unwrap_from() -> Result<()> {
+        let mut previous = Frame::with_len(2);
+        previous.box_vector = dodecahedron();
+        previous[0] = [3.9, 0.1, 1.0];
+        previous[1] = [8.0, 8.0, 8.0];
+
+        // Atom 0 crossed the box along a and b, atom 1 moved a little
+        let mut frame = previous.clone();
+        frame[0] = [0.1, 3.9, 1.0];
+        frame[1] = [8.1, 8.0, 8.0];
+        frame.unwrap_from(&previous)?;
+        assert_approx_eq!(frame[0][0], 4.1, 1e-5);
+        assert_approx_eq!(frame[0][1], -0.1, 1e-5);
+        assert_approx_eq!(frame[0][2], 1.0, 1e-5);
+        assert_approx_eq!(frame[1][0], 8.1, 1e-5);
+
+        let result = frame.unwrap_from(&Frame::with_len(3));
+        assert!(matches!(result, Err(Error::WrongSizeFrame { .. })));
+        Ok(())
+    }
+
+    #[test]
+    fn test_distance_without_box() {
+        let mut frame = Frame::with_len(2);
+        frame[1] = [3.0, 4.0, 0.0];
+        assert_eq!(frame.distance(0, 1), 5.0);
+    }
+}
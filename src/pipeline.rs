@@ -0,0 +1,336 @@
+use crate::*;
+
+/// A modification of every frame while it is read, see
+/// `TransformedTrajectory`.
+///
+/// Closures taking a `&mut Frame` and returning a `Result<()>` are
+/// transforms, as are `AtomSelection`, which removes the atoms not selected,
+/// `MakeWhole` and the types `WrapPbc`, `Center` and `ConvertUnits`.
+pub trait FrameTransform {
+    /// Modify `frame` in place
+    fn apply(&mut self, frame: &mut Frame) -> Result<()>;
+}
+
+impl<F: FnMut(&mut Frame) -> Result<()>> FrameTransform for F {
+    fn apply(&mut self, frame: &mut Frame) -> Result<()> {
+        self(frame)
+    }
+}
+
+impl FrameTransform for AtomSelection {
+    fn apply(&mut self, frame: &mut Frame) -> Result<()> {
+        frame.apply(self);
+        Ok(())
+    }
+}
+
+impl FrameTransform for MakeWhole {
+    fn apply(&mut self, frame: &mut Frame) -> Result<()> {
+        frame.make_whole(self)
+    }
+}
+
+/// Put all atoms into the primary unit cell, see `Frame::wrap_pbc`
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WrapPbc;
+
+impl FrameTransform for WrapPbc {
+    fn apply(&mut self, frame: &mut Frame) -> Result<()> {
+        frame.wrap_pbc();
+        Ok(())
+    }
+}
+
+/// Move all atoms so that the centroid of the selected atoms is at the
+/// center of the box, or at the origin if the frame has no box. Frames
+/// without selected atoms are left unchanged.
+#[derive(Clone, Debug)]
+pub struct Center(pub AtomSelection);
+
+impl FrameTransform for Center {
+    fn apply(&mut self, frame: &mut Frame) -> Result<()> {
+        if let Some(centroid) = frame.centroid(&self.0) {
+            let mut target = [0.0; 3];
+            for vector in frame.box_vector.as_matrix() {
+                for dim in 0..3 {
+                    target[dim] += vector[dim] / 2.0;
+                }
+            }
+            frame.translate([
+                target[0] - centroid[0],
+                target[1] - centroid[1],
+                target[2] - centroid[2],
+            ]);
+        }
+        Ok(())
+    }
+}
+
+/// Convert frames from the GROMACS units (nm and ps) to the given units,
+/// see `Frame::convert_length` and `Frame::convert_time`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConvertUnits {
+    /// Unit of the coordinates and the box
+    pub length: LengthUnit,
+    /// Unit of the time
+    pub time: TimeUnit,
+}
+
+impl FrameTransform for ConvertUnits {
+    fn apply(&mut self, frame: &mut Frame) -> Result<()> {
+        frame.convert_length(LengthUnit::Nanometer, self.length);
+        frame.convert_time(TimeUnit::Picosecond, self.time);
+        Ok(())
+    }
+}
+
+/// A trajectory that applies a pipeline of transforms to every frame it
+/// reads, in the order they were added, like the on-the-fly transformations
+/// of MDAnalysis.
+///
+/// Transforms may change the number of atoms, so frames passed to `read` are
+/// resized to the number of atoms in the file first. Everything else, such
+/// as seeking, `get_num_atoms` and `write`, is passed on to the wrapped
+/// trajectory unchanged, so seeking by time uses the times in the file.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?
+///         .with_transform(AtomSelection::new(0..10))
+///         .with_transform(ConvertUnits {
+///             length: LengthUnit::Angstrom,
+///             ..Default::default()
+///         });
+///     let mut frame = Frame::new();
+///     trajectory.read(&mut frame)?;
+///     assert_eq!(frame.len(), 10);
+///     assert_eq!(frame.time, 1.0);
+///     Ok(())
+/// }
+/// ```
+pub struct TransformedTrajectory<T> {
+    trajectory: T,
+    transforms: Vec<Box<dyn FrameTransform + Send>>,
+    /// Frame reused by `read_into`
+    scratch: Frame,
+}
+
+impl<T: Trajectory> TransformedTrajectory<T> {
+    /// Wrap `trajectory` without any transforms
+    pub fn new(trajectory: T) -> TransformedTrajectory<T> {
+        TransformedTrajectory {
+            trajectory,
+            transforms: Vec::new(),
+            scratch: Frame::new(),
+        }
+    }
+
+    /// Add `transform` to the end of the pipeline
+    pub fn with_transform(
+        mut self,
+        transform: impl FrameTransform + Send + 'static,
+    ) -> TransformedTrajectory<T> {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// The wrapped trajectory
+    pub fn inner(&self) -> &T {
+        &self.trajectory
+    }
+
+    /// Unwrap the trajectory, dropping the transforms
+    pub fn into_inner(self) -> T {
+        self.trajectory
+    }
+
+    /// Read the next frame with `read` and apply the transforms to it
+    fn read_with(
+        &mut self,
+        frame: &mut Frame,
+        read: impl FnOnce(&mut T, &mut Frame) -> Result<()>,
+    ) -> Result<()> {
+        frame.resize(self.trajectory.get_num_atoms()?);
+        read(&mut self.trajectory, frame)?;
+        for transform in self.transforms.iter_mut() {
+            transform.apply(frame)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T: Trajectory> Trajectory for TransformedTrajectory<T> {
+    fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        self.read_with(frame, |trajectory, frame| trajectory.read(frame))
+    }
+
+    fn peek(&mut self, frame: &mut Frame) -> Result<()> {
+        self.read_with(frame, |trajectory, frame| trajectory.peek(frame))
+    }
+
+    /// Read the next transformed frame, `coords` must have one entry per
+    /// atom after the transforms
+    fn read_into(&mut self, coords: &mut [[f32; 3]], header: &mut FrameHeader) -> Result<()> {
+        let mut frame = std::mem::take(&mut self.scratch);
+        let result = self.read(&mut frame);
+        self.scratch = frame;
+        result?;
+        if coords.len() != self.scratch.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: self.scratch.len(),
+                found: coords.len(),
+            });
+        }
+        coords.copy_from_slice(&self.scratch.coords);
+        *header = self.scratch.header();
+        Ok(())
+    }
+
+    /// Write the frame to the wrapped trajectory without transforming it
+    fn write(&mut self, frame: &Frame) -> Result<()> {
+        self.trajectory.write(frame)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.trajectory.flush()
+    }
+
+    fn get_num_atoms(&mut self) -> Result<usize> {
+        self.trajectory.get_num_atoms()
+    }
+
+    fn num_frames(&mut self) -> Result<usize> {
+        self.trajectory.num_frames()
+    }
+
+    fn known_num_frames(&self) -> Option<usize> {
+        self.trajectory.known_num_frames()
+    }
+
+    fn current_frame(&self) -> Option<usize> {
+        self.trajectory.current_frame()
+    }
+
+    fn seek_to_frame(&mut self, frame: usize) -> Result<()> {
+        self.trajectory.seek_to_frame(frame)
+    }
+
+    fn seek_to_time(&mut self, time: f32) -> Result<()> {
+        self.trajectory.seek_to_time(time)
+    }
+
+    fn skip(&mut self, n: usize) -> Result<()> {
+        self.trajectory.skip(n)
+    }
+
+    fn is_at_end(&mut self) -> Result<bool> {
+        self.trajectory.is_at_end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline_order() -> Result<()> {
+        let mut trajectory = TRRTrajectory::open_read("tests/1l2y.trr")?
+            .with_transform(AtomSelection::new(vec![3, 1]))
+            .with_transform(|frame: &mut Frame| {
+                frame.translate([1.0, 0.0, 0.0]);
+                Ok(())
+            })
+            .with_transform(ConvertUnits {
+                length: LengthUnit::Angstrom,
+                time: TimeUnit::Femtosecond,
+            });
+        let mut expected = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut expected_frame = Frame::with_len(304);
+
+        let mut frame = Frame::new();
+        for _ in 0..2 {
+            trajectory.read(&mut frame)?;
+            expected.read(&mut expected_frame)?;
+            assert_eq!(frame.len(), 2);
+            assert_eq!(frame.time, expected_frame.time * 1000.0);
+            let [x, y, z] = expected_frame[1];
+            assert_approx_eq!(frame[0][0], (x + 1.0) * 10.0, 1e-4);
+            assert_approx_eq!(frame[0][1], y * 10.0, 1e-4);
+            assert_approx_eq!(frame[0][2], z * 10.0, 1e-4);
+        }
+
+        // Seeking is passed on, the frame is resized again
+        trajectory.seek_to_frame(37)?;
+        trajectory.peek(&mut frame)?;
+        trajectory.read(&mut frame)?;
+        assert_eq!(frame.step, 38);
+        assert!(trajectory.is_at_end()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_iterate_transformed() -> Result<()> {
+        let selection = AtomSelection::new(0..20);
+        let trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .with_transform(selection.clone())
+            .with_transform(Center(selection));
+        let frames = trajectory.into_iter().collect::<Result<Vec<_>>>()?;
+        assert_eq!(frames.len(), 38);
+        for frame in frames {
+            let centroid = frame.centroid(&AtomSelection::new(0..20)).unwrap();
+            let lengths = [
+                frame.box_vector[0][0],
+                frame.box_vector[1][1],
+                frame.box_vector[2][2],
+            ];
+            for dim in 0..3 {
+                assert_approx_eq!(centroid[dim], lengths[dim] / 2.0, 1e-4);
+            }
+        }
+
+        let trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?.with_transform(WrapPbc);
+        for frame in trajectory {
+            let frame = frame?;
+            for coords in &frame.coords {
+                for (dim, &c) in coords.iter().enumerate() {
+                    assert!((0.0..frame.box_vector[dim][dim]).contains(&c));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_into_transformed() -> Result<()> {
+        let mut trajectory =
+            XTCTrajectory::open_read("tests/1l2y.xtc")?.with_transform(AtomSelection::new(0..3));
+        let mut header = FrameHeader::default();
+        let result = trajectory.read_into(&mut [[0.0; 3]; 304], &mut header);
+        assert!(matches!(
+            result,
+            Err(Error::WrongSizeFrame {
+                expected: 3,
+                found: 304
+            })
+        ));
+        let mut coords = [[0.0; 3]; 3];
+        trajectory.read_into(&mut coords, &mut header)?;
+        let mut expected = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        expected.skip(1)?;
+        let mut frame = Frame::with_len(304);
+        expected.read(&mut frame)?;
+        assert_eq!(header, frame.header());
+        assert_eq!(coords, frame.coords[..3]);
+
+        let failing = |_: &mut Frame| Err(Error::InvalidFrame { reason: "test" });
+        let mut trajectory = trajectory.with_transform(failing);
+        let mut frame = Frame::new();
+        assert!(matches!(
+            trajectory.read(&mut frame),
+            Err(Error::InvalidFrame { reason: "test" })
+        ));
+        Ok(())
+    }
+}
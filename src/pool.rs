@@ -0,0 +1,200 @@
+use crate::Frame;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Hands out reusable frame buffers, so that producers of many frames, e.g.
+/// a simulation engine writing a trajectory on the fly, do not allocate a
+/// new frame for every step.
+///
+/// `get` returns a `PooledFrame`, which dereferences to a `Frame` and goes
+/// back to the pool when it is dropped, usually right after writing it.
+/// Reused frames keep the contents of their last use. Clones of the pool
+/// share the same buffers, and pooled frames can be sent to other threads,
+/// so frames can be filled on one thread and written on another.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let tempfile = tempfile::NamedTempFile::new().unwrap();
+///     let mut trajectory = XTCTrajectory::open_write(&tempfile)?;
+///     let pool = FramePool::new(100);
+///     for step in 0..10 {
+///         let mut frame = pool.get();
+///         frame.step = step;
+///         trajectory.write(&frame)?;
+///     }
+///     // The same buffer was used for all frames
+///     assert_eq!(pool.num_idle(), 1);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct FramePool {
+    inner: Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    num_atoms: usize,
+    /// Largest number of idle frames kept for reuse
+    max_idle: usize,
+    idle: Mutex<Vec<Frame>>,
+}
+
+impl FramePool {
+    /// Create a pool of frames with `num_atoms` atoms, which keeps any
+    /// number of idle frames for reuse
+    pub fn new(num_atoms: usize) -> FramePool {
+        FramePool::with_max_idle(num_atoms, usize::MAX)
+    }
+
+    /// Create a pool of frames with `num_atoms` atoms, which keeps at most
+    /// `max_idle` idle frames for reuse. Further frames are freed when they
+    /// are returned.
+    pub fn with_max_idle(num_atoms: usize, max_idle: usize) -> FramePool {
+        FramePool {
+            inner: Arc::new(PoolInner {
+                num_atoms,
+                max_idle,
+                idle: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Number of atoms of the frames in the pool
+    pub fn num_atoms(&self) -> usize {
+        self.inner.num_atoms
+    }
+
+    /// Number of frames waiting in the pool to be reused
+    pub fn num_idle(&self) -> usize {
+        self.idle().len()
+    }
+
+    /// Get a frame from the pool, or a new one if no frame is idle
+    pub fn get(&self) -> PooledFrame {
+        let frame = self.idle().pop();
+        PooledFrame {
+            frame: Some(frame.unwrap_or_else(|| Frame::with_len(self.inner.num_atoms))),
+            pool: self.clone(),
+        }
+    }
+
+    /// Return a frame to the pool for reuse, e.g. one detached with
+    /// `PooledFrame::into_inner`. Frames with a different number of atoms
+    /// are resized.
+    pub fn recycle(&self, mut frame: Frame) {
+        let mut idle = self.idle();
+        if idle.len() < self.inner.max_idle {
+            frame.resize(self.inner.num_atoms);
+            idle.push(frame);
+        }
+    }
+
+    /// Lock the idle frames. A panic while the lock was held cannot leave
+    /// the list in an inconsistent state, so poisoning is ignored.
+    fn idle(&self) -> MutexGuard<'_, Vec<Frame>> {
+        self.inner
+            .idle
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A frame borrowed from a `FramePool`, which is returned to the pool when
+/// dropped
+#[derive(Debug)]
+pub struct PooledFrame {
+    /// Always present, except while dropping after `into_inner`
+    frame: Option<Frame>,
+    pool: FramePool,
+}
+
+impl PooledFrame {
+    /// Take the frame out of the pool for good
+    pub fn into_inner(mut self) -> Frame {
+        self.frame.take().expect("Pooled frame was taken")
+    }
+}
+
+impl Deref for PooledFrame {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        self.frame.as_ref().expect("Pooled frame was taken")
+    }
+}
+
+impl DerefMut for PooledFrame {
+    fn deref_mut(&mut self) -> &mut Frame {
+        self.frame.as_mut().expect("Pooled frame was taken")
+    }
+}
+
+impl Drop for PooledFrame {
+    fn drop(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.pool.recycle(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn test_frame_pool() {
+        let pool = FramePool::with_max_idle(3, 2);
+        let frames: Vec<PooledFrame> = (0..4).map(|_| pool.get()).collect();
+        assert_eq!(pool.num_idle(), 0);
+        let pointers: Vec<*const [f32; 3]> = frames.iter().map(|f| f.coords.as_ptr()).collect();
+        drop(frames);
+        assert_eq!(pool.num_idle(), 2);
+
+        // Idle frames are reused with their contents
+        let mut frame = pool.get();
+        assert!(pointers.contains(&frame.coords.as_ptr()));
+        frame.step = 5;
+        frame.resize(10);
+        drop(frame);
+        let frame = pool.get();
+        assert_eq!(frame.step, 5);
+        assert_eq!(frame.len(), 3);
+
+        // Detached frames are not returned
+        let owned = frame.into_inner();
+        assert_eq!(owned.len(), 3);
+        assert_eq!(pool.num_idle(), 1);
+        pool.recycle(owned);
+        assert_eq!(pool.num_idle(), 2);
+    }
+
+    #[test]
+    fn test_frame_pool_threads() -> Result<()> {
+        let pool = FramePool::new(304);
+        let (sender, receiver) = std::sync::mpsc::sync_channel(2);
+        let producer_pool = pool.clone();
+        let producer = std::thread::spawn(move || {
+            for step in 0..100 {
+                let mut frame = producer_pool.get();
+                frame.step = step;
+                sender.send(frame).unwrap();
+            }
+        });
+
+        let tempfile = tempfile::NamedTempFile::new().unwrap();
+        let mut trajectory = XTCTrajectory::open_write(&tempfile)?;
+        for frame in receiver {
+            trajectory.write(&frame)?;
+        }
+        producer.join().unwrap();
+        trajectory.close()?;
+        // Only as many frames as were in flight at once were allocated
+        assert!(pool.num_idle() <= 4);
+        assert_eq!(XTCTrajectory::open_read(&tempfile)?.num_frames()?, 100);
+        Ok(())
+    }
+}
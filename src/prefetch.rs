@@ -0,0 +1,164 @@
+use crate::{Error, ErrorCode, ErrorTask, Frame, Result, Trajectory};
+use std::mem;
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::thread;
+
+/// Reads the frames of a trajectory on a background thread.
+///
+/// Up to `capacity` frames are decoded ahead of time, so reading and
+/// decompressing the file overlaps with the analysis of the current frame.
+/// Frame buffers handed back through `read` are reused by the background
+/// thread.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let trj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+///     let mut prefetching = PrefetchingTrajectory::new(trj, 4)?;
+///     let mut frame = Frame::with_len(prefetching.num_atoms());
+///     prefetching.read(&mut frame)?;
+///     assert_eq!(frame.step, 1);
+///     assert_eq!(prefetching.count(), 37);
+///     Ok(())
+/// }
+/// ```
+pub struct PrefetchingTrajectory {
+    frames: Receiver<Result<Frame>>,
+    recycled: Sender<Frame>,
+    num_atoms: usize,
+    /// Error that ended the background thread, returned by all further reads
+    error: Option<Error>,
+}
+
+impl PrefetchingTrajectory {
+    /// Start reading `trajectory` from its current position on a background
+    /// thread, keeping at most `capacity` decoded frames in memory
+    pub fn new<T>(mut trajectory: T, capacity: usize) -> Result<PrefetchingTrajectory>
+    where
+        T: Trajectory + Send + 'static,
+    {
+        let num_atoms = trajectory.get_num_atoms()?;
+        let (frames, receiver) = mpsc::sync_channel(capacity);
+        let (recycled, recycled_receiver) = mpsc::channel();
+        thread::spawn(move || prefetch(trajectory, num_atoms, frames, recycled_receiver));
+        Ok(PrefetchingTrajectory {
+            frames: receiver,
+            recycled,
+            num_atoms,
+            error: None,
+        })
+    }
+
+    /// Number of atoms in the trajectory
+    pub fn num_atoms(&self) -> usize {
+        self.num_atoms
+    }
+
+    /// Read the next frame of the trajectory into `frame`. The previous
+    /// contents of `frame` are handed to the background thread for reuse.
+    pub fn read(&mut self, frame: &mut Frame) -> Result<()> {
+        let next = self.next_frame()?;
+        let previous = mem::replace(frame, next);
+        // The background thread may have stopped already, the buffer is
+        // dropped then
+        let _ = self.recycled.send(previous);
+        Ok(())
+    }
+
+    fn next_frame(&mut self) -> Result<Frame> {
+        if let Some(err) = &self.error {
+            return Err(err.clone());
+        }
+        let result = self
+            .frames
+            .recv()
+            .unwrap_or_else(|_| Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Read))));
+        if let Err(err) = &result {
+            self.error = Some(err.clone());
+        }
+        result
+    }
+}
+
+/// Read frames from `trajectory` into `frames` until an error occurs or the
+/// receiving end is dropped
+fn prefetch<T: Trajectory>(
+    mut trajectory: T,
+    num_atoms: usize,
+    frames: SyncSender<Result<Frame>>,
+    recycled: Receiver<Frame>,
+) {
+    loop {
+        let mut frame = recycled
+            .try_recv()
+            .unwrap_or_else(|_| Frame::with_len(num_atoms));
+        frame.resize(num_atoms);
+        let result = trajectory.read(&mut frame).map(|()| frame);
+        let failed = result.is_err();
+        if frames.send(result).is_err() || failed {
+            return;
+        }
+    }
+}
+
+impl Iterator for PrefetchingTrajectory {
+    type Item = Result<Frame>;
+
+    /// Yields the frames until the end of the trajectory or the first error
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.error.is_some() {
+            return None;
+        }
+        match self.next_frame() {
+            Ok(frame) => Some(Ok(frame)),
+            Err(e) if e.is_eof() => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TRRTrajectory, XTCTrajectory};
+
+    #[test]
+    fn test_prefetching_read() -> Result<()> {
+        let mut expected = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut prefetching = PrefetchingTrajectory::new(trajectory, 2)?;
+        let mut frame = Frame::with_len(304);
+        let mut expected_frame = Frame::with_len(304);
+        for _ in 0..38 {
+            prefetching.read(&mut frame)?;
+            expected.read(&mut expected_frame)?;
+            assert_eq!(
+                (frame.step, &frame.coords),
+                (expected_frame.step, &expected_frame.coords)
+            );
+        }
+        assert!(prefetching.read(&mut frame).unwrap_err().is_eof());
+        assert!(prefetching.read(&mut frame).unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefetching_iter() -> Result<()> {
+        let mut trajectory = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        trajectory.seek_to_frame(30)?;
+        let prefetching = PrefetchingTrajectory::new(trajectory, 1)?;
+        let steps = prefetching
+            .map(|frame| frame.map(|f| f.step))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(steps, (31..=38).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefetching_error() -> Result<()> {
+        let trajectory = XTCTrajectory::from_reader(std::io::Cursor::new(b"not a trajectory"))?;
+        assert!(PrefetchingTrajectory::new(trajectory, 1).is_err());
+        Ok(())
+    }
+}
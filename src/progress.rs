@@ -0,0 +1,96 @@
+/// Progress of a long running operation on a trajectory, passed to the
+/// callback set with `XTCTrajectory::set_progress` or
+/// `TRRTrajectory::set_progress`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Number of frames read or scanned since the callback was set
+    pub frames: usize,
+    /// Current position in the file, in bytes
+    pub bytes: u64,
+    /// Size of the file when the callback was set, in bytes
+    pub len: u64,
+}
+
+impl Progress {
+    /// Fraction of the file before the current position, between 0 and 1
+    pub fn fraction(&self) -> f32 {
+        if self.len == 0 {
+            1.0
+        } else {
+            (self.bytes as f64 / self.len as f64).min(1.0) as f32
+        }
+    }
+}
+
+/// Callback and state to report the progress of a trajectory
+pub(crate) struct ProgressTracker {
+    callback: Box<dyn FnMut(Progress) + Send>,
+    frames: usize,
+    len: u64,
+}
+
+impl ProgressTracker {
+    pub fn new(callback: Box<dyn FnMut(Progress) + Send>, len: u64) -> ProgressTracker {
+        ProgressTracker {
+            callback,
+            frames: 0,
+            len,
+        }
+    }
+
+    /// Report that a frame ending at `bytes` was read or scanned
+    pub fn frame_done(&mut self, bytes: u64) {
+        self.frames += 1;
+        (self.callback)(Progress {
+            frames: self.frames,
+            bytes,
+            len: self.len,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Callback collecting the reported progress in a shared list
+    fn recorder() -> (Arc<Mutex<Vec<Progress>>>, impl FnMut(Progress) + Send) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reports);
+        (reports, move |progress| sink.lock().unwrap().push(progress))
+    }
+
+    #[test]
+    fn test_progress_read_all() -> Result<()> {
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let len = std::fs::metadata("tests/1l2y.trr").unwrap().len();
+        let (reports, callback) = recorder();
+        traj.set_progress(callback)?;
+
+        traj.read_all()?;
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 38);
+        assert_eq!(reports[0].frames, 1);
+        assert_eq!(reports[0].len, len);
+        assert_approx_eq!(reports[18].fraction(), 19.0 / 38.0, 1e-6);
+        assert_eq!(reports[37].bytes, len);
+        assert_eq!(reports[37].fraction(), 1.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress_indexing() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let (reports, callback) = recorder();
+        traj.set_progress(callback)?;
+
+        traj.seek_to_frame(9)?;
+        assert_eq!(reports.lock().unwrap().len(), 10);
+        traj.clear_progress();
+        traj.seek_to_frame(20)?;
+        assert_eq!(reports.lock().unwrap().len(), 10);
+        Ok(())
+    }
+}
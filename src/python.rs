@@ -0,0 +1,291 @@
+use crate::*;
+use numpy::{PyArray1, PyArray2, PyArrayMethods, PyReadonlyArray2};
+use pyo3::exceptions::{PyEOFError, PyIOError, PyValueError};
+use pyo3::prelude::*;
+use std::path::PathBuf;
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> PyErr {
+        if err.is_eof() {
+            PyEOFError::new_err(err.to_string())
+        } else {
+            PyIOError::new_err(err.to_string())
+        }
+    }
+}
+
+/// Copy per-atom vectors into a new numpy array of shape (N, 3)
+fn to_array<'py>(py: Python<'py>, block: &[[f32; 3]]) -> PyResult<Bound<'py, PyArray2<f32>>> {
+    PyArray1::from_slice(py, block.as_flattened()).reshape([block.len(), 3])
+}
+
+/// Copy a numpy array of shape (N, 3) into per-atom vectors
+fn from_array(array: PyReadonlyArray2<'_, f32>) -> PyResult<Vec<[f32; 3]>> {
+    let array = array.as_array();
+    if array.ncols() != 3 {
+        return Err(PyValueError::new_err("Expected an array of shape (N, 3)"));
+    }
+    Ok(array
+        .rows()
+        .into_iter()
+        .map(|r| [r[0], r[1], r[2]])
+        .collect())
+}
+
+/// Frame of a trajectory, see `xdrfile::Frame`. Coordinates, velocities,
+/// forces and the box are returned as copies in numpy arrays.
+#[pyclass(name = "Frame", module = "xdrfile")]
+pub struct PyFrame {
+    frame: Frame,
+}
+
+#[pymethods]
+impl PyFrame {
+    #[new]
+    #[pyo3(signature = (coords, step = 0, time = 0.0, box_vector = None))]
+    fn new(
+        coords: PyReadonlyArray2<'_, f32>,
+        step: usize,
+        time: f32,
+        box_vector: Option<PyReadonlyArray2<'_, f32>>,
+    ) -> PyResult<PyFrame> {
+        let mut frame = Frame::from_coords(step, time, BoxVector::default(), from_array(coords)?);
+        if let Some(box_vector) = box_vector {
+            let rows = from_array(box_vector)?;
+            let matrix: [[f32; 3]; 3] = rows
+                .try_into()
+                .map_err(|_| PyValueError::new_err("Expected a box of shape (3, 3)"))?;
+            frame.box_vector = BoxVector::new(matrix);
+        }
+        Ok(PyFrame { frame })
+    }
+
+    #[getter]
+    fn step(&self) -> usize {
+        self.frame.step
+    }
+
+    #[setter]
+    fn set_step(&mut self, step: usize) {
+        self.frame.step = step;
+    }
+
+    #[getter]
+    fn time(&self) -> f32 {
+        self.frame.time
+    }
+
+    #[setter]
+    fn set_time(&mut self, time: f32) {
+        self.frame.time = time;
+    }
+
+    #[getter]
+    fn box_vector<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        to_array(py, self.frame.box_vector.as_matrix())
+    }
+
+    #[getter]
+    fn coords<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<f32>>> {
+        to_array(py, &self.frame.coords)
+    }
+
+    #[getter]
+    fn velocities<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyArray2<f32>>>> {
+        self.frame
+            .velocities
+            .as_deref()
+            .map(|v| to_array(py, v))
+            .transpose()
+    }
+
+    #[getter]
+    fn forces<'py>(&self, py: Python<'py>) -> PyResult<Option<Bound<'py, PyArray2<f32>>>> {
+        self.frame
+            .forces
+            .as_deref()
+            .map(|f| to_array(py, f))
+            .transpose()
+    }
+
+    fn __len__(&self) -> usize {
+        self.frame.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Frame(step={}, time={}, atoms={})",
+            self.frame.step,
+            self.frame.time,
+            self.frame.len()
+        )
+    }
+}
+
+/// Parse a Python file mode ("r", "w" or "a")
+fn file_mode(mode: &str) -> PyResult<FileMode> {
+    match mode {
+        "r" => Ok(FileMode::Read),
+        "w" => Ok(FileMode::Write),
+        "a" => Ok(FileMode::Append),
+        _ => Err(PyValueError::new_err(format!(
+            "Invalid mode {:?}, expected \"r\", \"w\" or \"a\"",
+            mode
+        ))),
+    }
+}
+
+/// Define a Python class for a trajectory type. The class can be used as a
+/// context manager and iterated over, which yields the remaining frames.
+macro_rules! py_trajectory {
+    ($py_type:ident, $name:literal, $trajectory:ident) => {
+        #[doc = concat!("Python class for `", stringify!($trajectory), "`")]
+        #[pyclass(name = $name, module = "xdrfile", unsendable)]
+        pub struct $py_type {
+            /// None once the trajectory was closed
+            trajectory: Option<$trajectory>,
+        }
+
+        impl $py_type {
+            fn trajectory(&mut self) -> PyResult<&mut $trajectory> {
+                self.trajectory
+                    .as_mut()
+                    .ok_or_else(|| PyValueError::new_err("Trajectory is closed"))
+            }
+        }
+
+        #[pymethods]
+        impl $py_type {
+            #[new]
+            #[pyo3(signature = (path, mode = "r"))]
+            fn new(path: PathBuf, mode: &str) -> PyResult<$py_type> {
+                let trajectory = $trajectory::open(path, file_mode(mode)?)?;
+                Ok($py_type {
+                    trajectory: Some(trajectory),
+                })
+            }
+
+            /// Read the next frame, None at the end of the trajectory
+            fn read(&mut self) -> PyResult<Option<PyFrame>> {
+                let trajectory = self.trajectory()?;
+                let mut frame = Frame::with_len(trajectory.get_num_atoms()?);
+                match trajectory.read(&mut frame) {
+                    Ok(()) => Ok(Some(PyFrame { frame })),
+                    Err(e) if e.is_eof() => Ok(None),
+                    Err(e) => Err(e.into()),
+                }
+            }
+
+            fn write(&mut self, frame: &PyFrame) -> PyResult<()> {
+                Ok(self.trajectory()?.write(&frame.frame)?)
+            }
+
+            fn flush(&mut self) -> PyResult<()> {
+                Ok(self.trajectory()?.flush()?)
+            }
+
+            /// Seek to the frame with the given (zero-based) index
+            fn seek(&mut self, frame: usize) -> PyResult<()> {
+                Ok(self.trajectory()?.seek_to_frame(frame)?)
+            }
+
+            #[getter]
+            fn num_atoms(&mut self) -> PyResult<usize> {
+                Ok(self.trajectory()?.get_num_atoms()?)
+            }
+
+            fn __len__(&mut self) -> PyResult<usize> {
+                Ok(self.trajectory()?.num_frames()?)
+            }
+
+            fn close(&mut self) -> PyResult<()> {
+                match self.trajectory.take() {
+                    Some(trajectory) => Ok(trajectory.close()?),
+                    None => Ok(()),
+                }
+            }
+
+            fn __iter__(slf: PyRefMut<'_, Self>) -> PyRefMut<'_, Self> {
+                slf
+            }
+
+            fn __next__(&mut self) -> PyResult<Option<PyFrame>> {
+                self.read()
+            }
+
+            fn __enter__(slf: PyRefMut<'_, Self>) -> PyRefMut<'_, Self> {
+                slf
+            }
+
+            fn __exit__(
+                &mut self,
+                _exc_type: Py<PyAny>,
+                _exc_value: Py<PyAny>,
+                _traceback: Py<PyAny>,
+            ) -> PyResult<()> {
+                self.close()
+            }
+        }
+    };
+}
+
+py_trajectory!(PyXTCTrajectory, "XTCTrajectory", XTCTrajectory);
+py_trajectory!(PyTRRTrajectory, "TRRTrajectory", TRRTrajectory);
+
+/// Python module `xdrfile`, built as an extension module with the `python`
+/// feature
+#[pymodule]
+#[pyo3(name = "xdrfile")]
+pub fn python_module(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyFrame>()?;
+    module.add_class::<PyXTCTrajectory>()?;
+    module.add_class::<PyTRRTrajectory>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyDict;
+
+    #[test]
+    fn test_python_module() -> PyResult<()> {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "xdrfile")?;
+            python_module(&module)?;
+            let locals = PyDict::new(py);
+            locals.set_item("xdrfile", module)?;
+            py.run(
+                pyo3::ffi::c_str!(
+                    r#"
+with xdrfile.XTCTrajectory("tests/1l2y.xtc") as xtc:
+    assert xtc.num_atoms == 304
+    assert len(xtc) == 38
+    assert [frame.step for frame in xtc] == list(range(1, 39))
+    assert xtc.read() is None
+    xtc.seek(37)
+    assert repr(xtc.read()) == "Frame(step=38, time=38, atoms=304)"
+try:
+    xtc.read()
+    assert False
+except ValueError:
+    pass
+try:
+    xdrfile.TRRTrajectory("tests/1l2y.trr", "x")
+    assert False
+except ValueError:
+    pass
+try:
+    xdrfile.TRRTrajectory("does/not/exist.trr")
+    assert False
+except IOError:
+    pass
+"#
+                ),
+                None,
+                Some(&locals),
+            )
+        })
+    }
+}
@@ -0,0 +1,233 @@
+use crate::*;
+use std::fs::{File, OpenOptions};
+use std::io::Read;
+
+/// Outcome of `repair`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RepairReport {
+    /// Number of complete frames kept in the file
+    pub num_frames: usize,
+    /// Length of the file after the repair, in bytes
+    pub len: u64,
+    /// Number of bytes removed from the end of the file
+    pub removed: u64,
+}
+
+/// Truncate a trajectory after its last complete frame, like running
+/// `gmx trjconv` on the output of a crashed simulation.
+///
+/// The format is detected from the magic number of the first frame. Frames
+/// are read one by one from the start, and the file is cut right after the
+/// last frame that could be read completely. Note that everything after the
+/// first unreadable frame is removed, even if valid frames follow it. Use
+/// `XTCTrajectory::set_resync` to read past corrupt frames instead.
+///
+/// Returns `Error::CouldNotOpen` if the file cannot be opened or truncated,
+/// and an `ExdrMagic` error if it is not an XTC or TRR trajectory.
+///
+/// ```rust
+/// use xdrfile::*;
+/// # use tempfile::NamedTempFile;
+///
+/// # fn main() -> Result<()> {
+/// # let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+/// # std::fs::copy("tests/1l2y.xtc", tempfile.path()).expect("Could not copy test file");
+/// # let path = tempfile.path();
+/// let report = repair(path)?;
+/// assert_eq!(report.num_frames, 38);
+/// assert_eq!(report.removed, 0);
+/// # Ok(())
+/// # }
+/// ```
+pub fn repair(path: impl AsRef<Path>) -> Result<RepairReport> {
+    let path = path.as_ref();
+    let open_err = |err| Error::from((path, FileMode::Write, err));
+    let file_len = std::fs::metadata(path).map_err(open_err)?.len();
+    let (num_frames, len) = complete_prefix(path, file_len)?;
+    if len < file_len {
+        truncate(path, len)?;
+    }
+    Ok(RepairReport {
+        num_frames,
+        len,
+        removed: file_len - len,
+    })
+}
+
+/// Check that the trajectory at `path` ends with a complete frame before
+/// appending to it, see `XTCTrajectory::open_append_checked`. An incomplete
+/// last frame is removed if `truncate` is set, otherwise an
+/// `Error::IncompleteFrame` is returned.
+pub(crate) fn check_tail(path: &Path, skip_frame: index::SkipFrame, truncate: bool) -> Result<()> {
+    let file_len = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        // Appending creates missing files
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(Error::from((path, FileMode::Append, err))),
+    };
+    if file_len == 0 || ends_with_frame(path, file_len, skip_frame) {
+        return Ok(());
+    }
+
+    let (_, len) = complete_prefix(path, file_len)?;
+    if len == file_len {
+        Ok(())
+    } else if truncate {
+        self::truncate(path, len)
+    } else {
+        Err(Error::IncompleteFrame {
+            path: path.to_owned(),
+            complete_len: len,
+            len: file_len,
+        })
+    }
+}
+
+/// Check whether a frame ends exactly at the end of the file, which only
+/// reads the headers of the last few frames
+fn ends_with_frame(path: &Path, file_len: u64, skip_frame: index::SkipFrame) -> bool {
+    let mut handle = match XDRFile::open(path, FileMode::Read) {
+        Ok(handle) => handle,
+        Err(_) => return false,
+    };
+    let last = TrajectoryIndex::default().last_entry(&mut handle, skip_frame);
+    last.is_ok() && handle.tell().ok() == Some(file_len)
+}
+
+/// Get the number of complete frames at the start of the file and the
+/// number of bytes they take up. The format is detected from the magic
+/// number of the first frame.
+fn complete_prefix(path: &Path, file_len: u64) -> Result<(usize, u64)> {
+    let mut magic = [0; 4];
+    let read_magic = File::open(path).and_then(|mut file| file.read_exact(&mut magic));
+    match read_magic {
+        // Not even the magic number of the first frame was written
+        Err(_) if file_len < 4 => Ok((0, 0)),
+        Err(err) => Err(Error::from((path, FileMode::Write, err))),
+        Ok(()) => match TrajectoryFormat::from_magic(i32::from_be_bytes(magic)) {
+            Some(TrajectoryFormat::Xtc) => Ok(complete_frames(XTCTrajectory::open_read(path)?)),
+            Some(TrajectoryFormat::Trr) => Ok(complete_frames(TRRTrajectory::open_read(path)?)),
+            None => Err(Error::from((ErrorCode::ExdrMagic, ErrorTask::Read))),
+        },
+    }
+}
+
+/// Cut the file at `path` to `len` bytes
+fn truncate(path: &Path, len: u64) -> Result<()> {
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_len(len))
+        .map_err(|err| Error::from((path, FileMode::Write, err)))
+}
+
+/// Read frames until one fails. Returns the number of complete frames and
+/// the offset right after the last of them.
+fn complete_frames<T>(mut trajectory: T) -> (usize, u64)
+where
+    T: Trajectory + io::Seek,
+{
+    let num_atoms = match trajectory.get_num_atoms() {
+        Ok(num_atoms) => num_atoms,
+        Err(_) => return (0, 0),
+    };
+    let mut frame = Frame::with_len(num_atoms);
+    let mut num_frames = 0;
+    let mut len = 0;
+    while trajectory.read(&mut frame).is_ok() {
+        num_frames += 1;
+        len = trajectory
+            .stream_position()
+            .expect("Position in trajectory could not be determined");
+    }
+    (num_frames, len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    /// Copy the first `len` bytes of a file
+    fn truncated_copy(path: &str, len: u64) -> NamedTempFile {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        std::fs::copy(path, tempfile.path()).expect("Could not copy test file");
+        let file = OpenOptions::new()
+            .write(true)
+            .open(tempfile.path())
+            .unwrap();
+        file.set_len(len).unwrap();
+        tempfile
+    }
+
+    #[test]
+    fn test_repair_xtc() -> Result<()> {
+        let mut traj = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        traj.seek_to_frame(37)?;
+        let last = traj.tell()?;
+        // Cut the last frame in the middle of its compressed coordinates
+        let tempfile = truncated_copy("tests/1l2y.xtc", last + 100);
+
+        let report = repair(tempfile.path())?;
+        assert_eq!(report.num_frames, 37);
+        assert_eq!(report.len, last);
+        assert_eq!(report.removed, 100);
+        let mut traj = XTCTrajectory::open_read(tempfile.path())?;
+        assert_eq!(traj.num_frames()?, 37);
+        assert_eq!(traj.last_frame()?.step, 37);
+
+        // Repairing a complete file does nothing
+        assert_eq!(repair(tempfile.path())?.removed, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_trr() -> Result<()> {
+        let tempfile = truncated_copy("tests/1l2y.trr", 50);
+        let report = repair(tempfile.path())?;
+        assert_eq!((report.num_frames, report.len), (0, 0));
+        assert_eq!(std::fs::metadata(tempfile.path()).unwrap().len(), 0);
+        assert_eq!(repair(tempfile.path())?.removed, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_append_checked() -> Result<()> {
+        let mut traj = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let frame = traj.last_frame()?;
+        traj.seek_to_frame(37)?;
+        let last = traj.tell()?;
+        let tempfile = truncated_copy("tests/1l2y.trr", last + 10);
+
+        let err = TRRTrajectory::open_append_checked(tempfile.path(), false).err();
+        assert!(matches!(
+            err,
+            Some(Error::IncompleteFrame { complete_len, len, .. })
+                if complete_len == last && len == last + 10
+        ));
+        let mut traj = TRRTrajectory::open_append_checked(tempfile.path(), true)?;
+        traj.write(&frame)?;
+        traj.close()?;
+        let mut traj = TRRTrajectory::open_read(tempfile.path())?;
+        assert_eq!(traj.num_frames()?, 38);
+        assert_eq!(traj.last_frame()?.step, 38);
+
+        // Complete, empty and missing files are appended to unchanged
+        let len = std::fs::metadata(tempfile.path()).unwrap().len();
+        TRRTrajectory::open_append_checked(tempfile.path(), false)?.close()?;
+        assert_eq!(std::fs::metadata(tempfile.path()).unwrap().len(), len);
+        let empty = NamedTempFile::new().expect("Could not create temporary file");
+        XTCTrajectory::open_append_checked(empty.path(), false)?.close()?;
+        let dir = tempfile::tempdir().expect("Could not create temporary directory");
+        let path = dir.path().join("new.xtc");
+        XTCTrajectory::open_append_checked(&path, false)?.close()?;
+        assert!(path.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_repair_not_a_trajectory() {
+        let err = repair("README.md").unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::ExdrMagic));
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{Error, Frame, FrameView, Result, Trajectory};
+
+/// A set of atom indices that can be applied to many frames.
+///
+/// The indices are sorted and deduplicated once on creation, so applying the
+/// selection to a frame takes time linear in the number of selected atoms.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// let selection = AtomSelection::new(vec![2, 0, 2]);
+/// let mut frame = Frame::with_len(4);
+/// frame[2] = [1.0, 2.0, 3.0];
+/// frame.apply(&selection);
+/// assert_eq!(frame.len(), 2);
+/// assert_eq!(frame[1], [1.0, 2.0, 3.0]);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AtomSelection {
+    indices: Vec<usize>,
+}
+
+impl AtomSelection {
+    /// Creates a selection of the given atom indices
+    pub fn new(indices: impl IntoIterator<Item = usize>) -> AtomSelection {
+        let mut indices: Vec<usize> = indices.into_iter().collect();
+        indices.sort_unstable();
+        indices.dedup();
+        AtomSelection { indices }
+    }
+
+    /// The selected indices in ascending order
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+
+    /// Number of selected atoms
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// True if no atoms are selected
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// True if the atom with the given index is selected
+    pub fn contains(&self, index: usize) -> bool {
+        self.indices.binary_search(&index).is_ok()
+    }
+
+    /// True if all selected atoms exist in frames with `num_atoms` atoms
+    pub fn fits(&self, num_atoms: usize) -> bool {
+        self.indices.last().is_none_or(|&last| last < num_atoms)
+    }
+
+    /// Selected indices that exist in frames with `num_atoms` atoms
+    pub(crate) fn indices_below(
+        &self,
+        num_atoms: usize,
+    ) -> impl Iterator<Item = usize> + Clone + '_ {
+        self.indices
+            .iter()
+            .copied()
+            .take_while(move |&i| i < num_atoms)
+    }
+
+    /// Creates a view of the selected atoms of `frame` without copying them
+    pub fn view<'a>(&'a self, frame: &'a Frame) -> FrameView<'a> {
+        frame.view(&self.indices)
+    }
+}
+
+impl Frame {
+    /// Removes all atoms not in the selection. Selected indices beyond the
+    /// number of atoms in the frame are ignored.
+    pub fn apply(&mut self, selection: &AtomSelection) {
+        let selected = selection.indices_below(self.coords.len());
+        self.select(selected);
+    }
+
+    /// Copies the selected atoms into `dst`, along with step, time and box
+    /// vector. The coordinates buffer of `dst` is reused, so no allocation
+    /// happens once it is large enough.
+    pub fn apply_into(&self, selection: &AtomSelection, dst: &mut Frame) {
+        let selected = selection.indices_below(self.coords.len());
+        self.select_into(selected, dst);
+    }
+
+    /// Mean position of the selected atoms, None if no selected atom exists
+    /// in the frame. Selected indices beyond the number of atoms in the frame
+    /// are ignored.
+    pub fn centroid(&self, selection: &AtomSelection) -> Option<[f32; 3]> {
+        let selected = selection.indices_below(self.coords.len());
+        weighted_mean(selected.map(|i| (self.coords[i], 1.0)))
+    }
+
+    /// Mass weighted mean position of the selected atoms, where `masses`
+    /// holds the mass of every atom in the frame. None if no selected atom
+    /// exists in the frame or their total mass is zero. Selected indices
+    /// beyond the number of atoms in the frame are ignored.
+    ///
+    /// # Panics
+    /// Panics if `masses` has no entry for a selected atom.
+    pub fn center_of_mass(&self, selection: &AtomSelection, masses: &[f32]) -> Option<[f32; 3]> {
+        let selected = selection.indices_below(self.coords.len());
+        weighted_mean(selected.map(|i| (self.coords[i], masses[i])))
+    }
+}
+
+/// Mean of the given positions weighted by the given weights, None if the
+/// total weight is zero
+fn weighted_mean(weighted: impl Iterator<Item = ([f32; 3], f32)>) -> Option<[f32; 3]> {
+    let mut sum = [0.0; 3];
+    let mut total = 0.0;
+    for (coords, weight) in weighted {
+        for dim in 0..3 {
+            sum[dim] += weight * coords[dim];
+        }
+        total += weight;
+    }
+    if total == 0.0 {
+        return None;
+    }
+    Some([sum[0] / total, sum[1] / total, sum[2] / total])
+}
+
+/// Read the next frame of `trajectory` into `scratch` and copy the selected
+/// atoms into `frame`
+pub(crate) fn read_selection_into<T: Trajectory + ?Sized>(
+    trajectory: &mut T,
+    scratch: &mut Frame,
+    selection: &AtomSelection,
+    frame: &mut Frame,
+) -> Result<()> {
+    let num_atoms = trajectory
+        .get_num_atoms()
+        .map_err(|e| Error::CouldNotCheckNAtoms(Box::new(e)))?;
+    scratch.resize(num_atoms);
+    trajectory.read(scratch)?;
+    scratch.apply_into(selection, frame);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_sorted() {
+        let selection = AtomSelection::new(vec![5, 1, 3, 1]);
+        assert_eq!(selection.indices(), &[1, 3, 5]);
+        assert_eq!(selection.len(), 3);
+        assert!(selection.contains(3));
+        assert!(!selection.contains(2));
+        assert!(selection.fits(6));
+        assert!(!selection.fits(5));
+        assert!(AtomSelection::default().fits(0));
+    }
+
+    #[test]
+    fn test_apply() {
+        let mut frame = Frame::with_len(4);
+        for i in 0..4 {
+            frame[i] = [i as f32; 3];
+        }
+        let selection = AtomSelection::new(vec![3, 1, 7]);
+        assert_eq!(selection.view(&frame).len(), 3);
+
+        let mut dst = Frame::new();
+        frame.apply_into(&selection, &mut dst);
+        assert_eq!(dst.coords, vec![[1.0; 3], [3.0; 3]]);
+
+        frame.apply(&selection);
+        assert_eq!(frame.coords, dst.coords);
+
+        frame.apply(&AtomSelection::default());
+        assert!(frame.coords.is_empty());
+    }
+
+    #[test]
+    fn test_centroid() {
+        let mut frame = Frame::with_len(3);
+        frame[0] = [0.0, 0.0, 0.0];
+        frame[1] = [4.0, 2.0, 0.0];
+        frame[2] = [2.0, 2.0, 2.0];
+        let selection = AtomSelection::new(vec![0, 1, 5]);
+        assert_eq!(frame.centroid(&selection), Some([2.0, 1.0, 0.0]));
+        assert_eq!(frame.centroid(&AtomSelection::new(vec![3])), None);
+
+        let masses = [1.0, 3.0, 100.0];
+        assert_eq!(
+            frame.center_of_mass(&selection, &masses),
+            Some([3.0, 1.5, 0.0])
+        );
+        assert_eq!(frame.center_of_mass(&selection, &[0.0; 3]), None);
+    }
+
+    #[test]
+    fn test_read_selection() -> Result<()> {
+        let selection = AtomSelection::new(vec![0, 10, 303, 304]);
+        let mut traj = crate::XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut full = crate::XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut expected = Frame::with_len(304);
+        let mut frame = Frame::new();
+        for _ in 0..3 {
+            traj.read_selection(&selection, &mut frame)?;
+            full.read(&mut expected)?;
+            assert_eq!(frame.step, expected.step);
+            assert_eq!(frame.coords, vec![expected[0], expected[10], expected[303]]);
+        }
+
+        let parts = vec![crate::TRRTrajectory::open_read("tests/1l2y.trr")?];
+        let mut chain = crate::ChainedTrajectory::new(parts)?;
+        chain.seek_to_frame(37)?;
+        chain.read_selection(&selection, &mut frame)?;
+        assert_eq!(frame.step, 38);
+        assert_eq!(frame.len(), 3);
+        assert!(chain
+            .read_selection(&selection, &mut frame)
+            .unwrap_err()
+            .is_eof());
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use wide::f32x4;
+
+/// The lanes of the three vectors covering four atoms, for a per-dimension
+/// parameter
+fn lanes(p: [f32; 3]) -> [f32x4; 3] {
+    [
+        f32x4::from([p[0], p[1], p[2], p[0]]),
+        f32x4::from([p[1], p[2], p[0], p[1]]),
+        f32x4::from([p[2], p[0], p[1], p[2]]),
+    ]
+}
+
+/// Apply `op` to every value of `coords` and the parameter of its dimension,
+/// `simd_op` is the same operation on four lanes.
+///
+/// The flat coordinates repeat with a period of three values, so three
+/// vectors of four lanes (four atoms) line up with it. The remaining atoms
+/// are handled one by one, which gives the same results as the scalar loops.
+fn apply(
+    coords: &mut [[f32; 3]],
+    param: [f32; 3],
+    simd_op: impl Fn(f32x4, f32x4) -> f32x4,
+    op: impl Fn(f32, f32) -> f32,
+) {
+    let params = lanes(param);
+    let (chunks, rest) = coords.as_flattened_mut().as_chunks_mut::<12>();
+    for chunk in chunks {
+        for (vector, &p) in chunk.as_chunks_mut::<4>().0.iter_mut().zip(&params) {
+            *vector = simd_op(f32x4::from(*vector), p).to_array();
+        }
+    }
+    for (i, v) in rest.iter_mut().enumerate() {
+        *v = op(*v, param[i % 3]);
+    }
+}
+
+/// Add `offset` to all coordinates
+pub(crate) fn translate(coords: &mut [[f32; 3]], offset: [f32; 3]) {
+    apply(coords, offset, |v, o| v + o, |v, o| v + o);
+}
+
+/// Multiply all coordinates by `factor`
+pub(crate) fn multiply(coords: &mut [[f32; 3]], factor: f32) {
+    apply(coords, [factor; 3], |v, f| v * f, |v, f| v * f);
+}
+
+/// Divide all coordinates by `divisor`
+pub(crate) fn divide(coords: &mut [[f32; 3]], divisor: f32) {
+    apply(coords, [divisor; 3], |v, d| v / d, |v, d| v / d);
+}
+
+/// Shift all coordinates into a rectangular box with the given edge lengths.
+/// Dimensions with a length of zero are not wrapped.
+pub(crate) fn wrap_rectangular(coords: &mut [[f32; 3]], lengths: [f32; 3]) {
+    // A zero length shifts by zero box lengths, whatever the divisor
+    let divisors = lengths.map(|l| if l == 0.0 { 1.0 } else { l });
+    let shifts = lanes(lengths);
+    let params = lanes(divisors);
+    let (chunks, rest) = coords.as_flattened_mut().as_chunks_mut::<12>();
+    for chunk in chunks {
+        let vectors = chunk.as_chunks_mut::<4>().0;
+        for ((vector, &d), &l) in vectors.iter_mut().zip(&params).zip(&shifts) {
+            let v = f32x4::from(*vector);
+            *vector = (v - (v / d).floor() * l).to_array();
+        }
+    }
+    for (i, v) in rest.iter_mut().enumerate() {
+        *v -= (*v / divisors[i % 3]).floor() * lengths[i % 3];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Coordinates of 7 atoms, so that both the vector and the scalar part
+    /// of the kernels run
+    fn coords() -> Vec<[f32; 3]> {
+        (0..7)
+            .map(|i| {
+                let i = i as f32;
+                [i * 1.7 - 5.0, 3.3 - i * 0.9, i * i * 0.4 - 2.0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut expected = coords();
+        for c in expected.iter_mut() {
+            c[0] += 1.0;
+            c[1] -= 2.5;
+            c[2] += 0.25;
+        }
+        let mut coords = coords();
+        translate(&mut coords, [1.0, -2.5, 0.25]);
+        assert_eq!(coords, expected);
+    }
+
+    #[test]
+    fn test_multiply_divide() {
+        let mut coords = coords();
+        multiply(&mut coords, 10.0);
+        let expected = [
+            (6.0 * 1.7 - 5.0) * 10.0,
+            (3.3 - 6.0 * 0.9) * 10.0,
+            (6.0 * 6.0 * 0.4 - 2.0) * 10.0,
+        ];
+        assert_eq!(coords[6], expected);
+        divide(&mut coords, 4.0);
+        assert_eq!(coords[1][2], (0.4 - 2.0) * 10.0 / 4.0);
+    }
+
+    #[test]
+    fn test_wrap_rectangular() {
+        let lengths = [2.0, 0.0, 3.0];
+        let mut coords = coords();
+        let original = coords.clone();
+        wrap_rectangular(&mut coords, lengths);
+        for (c, o) in coords.iter().zip(&original) {
+            assert!((0.0..2.0).contains(&c[0]));
+            assert_eq!(c[1], o[1]);
+            assert!((0.0..3.0).contains(&c[2]));
+            assert_eq!(c[0], o[0] - (o[0] / 2.0).floor() * 2.0);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use crate::*;
+use std::borrow::Borrow;
+
+/// Iterator adapter that yields every frame with its coordinates replaced by
+/// the average over a sliding window of the last frames, like the low-pass
+/// filter of `gmx filter -nf`. Created with `TrajectoryIterator::smooth` or
+/// `FrameIterator::smooth`, or with `SmoothedFrames::new` from any iterator
+/// over frames.
+///
+/// Until `window` frames were seen, the average is taken over the frames seen
+/// so far. Step, time and box are those of the newest frame. Velocities and
+/// forces are not averaged and left out.
+///
+/// The coordinates of the frames in the window are kept in a ring buffer and
+/// a running sum is updated with every frame, so each frame takes the same
+/// time regardless of the size of the window. Errors of the wrapped iterator
+/// are passed on, and an error is returned for frames whose number of atoms
+/// differs from the first frame.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let frames = XTCTrajectory::open_read("tests/1l2y.xtc")?
+///         .into_iter()
+///         .smooth(5)
+///         .collect::<Result<Vec<_>>>()?;
+///     assert_eq!(frames.len(), 38);
+///     assert_eq!(frames[37].step, 38);
+///     Ok(())
+/// }
+/// ```
+pub struct SmoothedFrames<I> {
+    frames: I,
+    window: usize,
+    /// Coordinates of up to `window` of the last frames
+    buffer: Vec<Vec<[f32; 3]>>,
+    /// Slot of the oldest frame in `buffer` once it is full
+    oldest: usize,
+    /// Sum of the coordinates in `buffer` of every atom
+    sums: Vec<[f64; 3]>,
+}
+
+impl<I, F> SmoothedFrames<I>
+where
+    I: Iterator<Item = Result<F>>,
+    F: Borrow<Frame>,
+{
+    /// Average the coordinates of the frames of `frames` over the last
+    /// `window` frames
+    ///
+    /// # Panics
+    /// Panics if `window` is zero.
+    pub fn new(frames: I, window: usize) -> SmoothedFrames<I> {
+        assert!(window > 0, "smoothing window must be at least 1");
+        SmoothedFrames {
+            frames,
+            window,
+            buffer: Vec::with_capacity(window),
+            oldest: 0,
+            sums: Vec::new(),
+        }
+    }
+
+    /// Number of frames averaged over once the window is full
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// Add the coordinates of `frame` to the window, replacing the oldest
+    /// frame if the window is full
+    fn push(&mut self, frame: &Frame) -> Result<()> {
+        if self.buffer.is_empty() {
+            self.sums = vec![[0.0; 3]; frame.len()];
+        } else if frame.len() != self.sums.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: self.sums.len(),
+                found: frame.len(),
+            });
+        }
+
+        if self.buffer.len() < self.window {
+            self.buffer.push(frame.coords.clone());
+        } else {
+            let slot = &mut self.buffer[self.oldest];
+            for (sum, coords) in self.sums.iter_mut().zip(slot.iter()) {
+                for dim in 0..3 {
+                    sum[dim] -= f64::from(coords[dim]);
+                }
+            }
+            slot.copy_from_slice(&frame.coords);
+            self.oldest = (self.oldest + 1) % self.window;
+        }
+        for (sum, coords) in self.sums.iter_mut().zip(&frame.coords) {
+            for dim in 0..3 {
+                sum[dim] += f64::from(coords[dim]);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<I, F> Iterator for SmoothedFrames<I>
+where
+    I: Iterator<Item = Result<F>>,
+    F: Borrow<Frame>,
+{
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = match self.frames.next()? {
+            Ok(frame) => frame,
+            Err(e) => return Some(Err(e)),
+        };
+        let frame: &Frame = frame.borrow();
+        if let Err(e) = self.push(frame) {
+            return Some(Err(e));
+        }
+
+        let n = self.buffer.len() as f64;
+        let coords = self
+            .sums
+            .iter()
+            .map(|sum| {
+                [
+                    (sum[0] / n) as f32,
+                    (sum[1] / n) as f32,
+                    (sum[2] / n) as f32,
+                ]
+            })
+            .collect();
+        Some(Ok(Frame::from_coords(
+            frame.step,
+            frame.time,
+            frame.box_vector,
+            coords,
+        )))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.frames.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smooth() -> Result<()> {
+        let frames: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_frames()
+            .collect::<Result<_>>()?;
+        let smoothed: Vec<Frame> = XTCTrajectory::open_read("tests/1l2y.xtc")?
+            .into_iter()
+            .smooth(4)
+            .collect::<Result<_>>()?;
+        assert_eq!(smoothed.len(), 38);
+
+        for (i, frame) in smoothed.iter().enumerate() {
+            assert_eq!(frame.step, frames[i].step);
+            assert_eq!(frame.time, frames[i].time);
+            let window = &frames[i.saturating_sub(3)..=i];
+            for atom in [0, 151, 303] {
+                for dim in 0..3 {
+                    let mean =
+                        window.iter().map(|f| f[atom][dim]).sum::<f32>() / window.len() as f32;
+                    assert_approx_eq!(frame[atom][dim], mean, 1e-5);
+                }
+            }
+        }
+        assert_eq!(smoothed[0].coords, frames[0].coords);
+
+        // A window of one frame keeps the coordinates
+        let mut trr = TRRTrajectory::open_read("tests/1l2y.trr")?;
+        let mut original = Frame::with_len(304);
+        trr.seek_to_frame(20)?;
+        trr.read(&mut original)?;
+        Trajectory::rewind(&mut trr)?;
+        let unchanged = trr.into_frames().smooth(1).nth(20).unwrap()?;
+        assert_eq!(unchanged.step, 21);
+        assert_eq!(unchanged.coords, original.coords);
+        assert!(unchanged.velocities.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_smooth_errors() {
+        let frames = vec![Ok(Frame::with_len(3)), Ok(Frame::with_len(4))];
+        let mut smoothed = SmoothedFrames::new(frames.into_iter(), 2);
+        assert!(smoothed.next().unwrap().is_ok());
+        assert!(matches!(
+            smoothed.next(),
+            Some(Err(Error::WrongSizeFrame {
+                expected: 3,
+                found: 4
+            }))
+        ));
+        assert!(smoothed.next().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "smoothing window must be at least 1")]
+    fn test_smooth_zero_window() {
+        let traj = XTCTrajectory::open_read("tests/1l2y.xtc").unwrap();
+        let _ = traj.into_iter().smooth(0);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::*;
+
+/// Summary of a trajectory, computed from one scan of its frame headers with
+/// `Trajectory::stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrajectoryStats {
+    /// Number of frames
+    pub num_frames: usize,
+    /// Number of atoms per frame
+    pub num_atoms: usize,
+    /// Time of the first frame, None if there are no frames
+    pub first_time: Option<f32>,
+    /// Time of the last frame, None if there are no frames
+    pub last_time: Option<f32>,
+    /// Time between frames, inferred as the median difference between
+    /// consecutive frames. None if there are less than two frames.
+    pub dt: Option<f32>,
+    /// True if the time increases from every frame to the next one
+    pub monotonic: bool,
+}
+
+impl TrajectoryStats {
+    /// Compute the summary of `trajectory`, see `Trajectory::get_times`
+    pub fn compute<T: Trajectory + ?Sized>(trajectory: &mut T) -> Result<TrajectoryStats> {
+        let num_atoms = trajectory.get_num_atoms()?;
+        let times = trajectory.get_times()?;
+
+        let mut differences: Vec<f32> = times.windows(2).map(|w| w[1] - w[0]).collect();
+        let monotonic = differences.iter().all(|&d| d > 0.0);
+        differences.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let dt = differences.get(differences.len() / 2).copied();
+
+        Ok(TrajectoryStats {
+            num_frames: times.len(),
+            num_atoms,
+            first_time: times.first().copied(),
+            last_time: times.last().copied(),
+            dt,
+            monotonic,
+        })
+    }
+
+    /// Time between the first and the last frame, None if there are no frames
+    pub fn duration(&self) -> Option<f32> {
+        Some(self.last_time? - self.first_time?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() -> Result<()> {
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let stats = trajectory.stats()?;
+        assert_eq!(
+            stats,
+            TrajectoryStats {
+                num_frames: 38,
+                num_atoms: 304,
+                first_time: Some(1.0),
+                last_time: Some(38.0),
+                dt: Some(1.0),
+                monotonic: true,
+            }
+        );
+        assert_eq!(stats.duration(), Some(37.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats_irregular() -> Result<()> {
+        let mut trajectory = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let mut frame = Frame::with_len(2);
+        for &time in &[0.0, 2.0, 4.0, 10.0, 12.0, 11.0] {
+            frame.time = time;
+            trajectory.write(&frame)?;
+        }
+        let bytes = trajectory.into_bytes().unwrap();
+        let mut trajectory = TRRTrajectory::from_bytes(bytes, FileMode::Read)?;
+
+        let stats = trajectory.stats()?;
+        // A gap and a step back in time do not affect the inferred time step
+        assert_eq!(stats.dt, Some(2.0));
+        assert!(!stats.monotonic);
+        assert_eq!(stats.duration(), Some(11.0));
+        Ok(())
+    }
+}
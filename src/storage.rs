@@ -0,0 +1,240 @@
+use crate::*;
+
+/// Memory that coordinates are decoded into and encoded from directly, as
+/// one x, y, z triple per atom in a single contiguous block, see
+/// `Trajectory::read_coords` and `Trajectory::write_coords`.
+///
+/// Implemented for `Vec<[f32; 3]>` and `[[f32; 3]]`, for flat `Vec<f32>` and
+/// `[f32]` with 3 values per atom, for `ndarray::Array2<f32>` of shape
+/// (N, 3) in standard layout with the `ndarray` feature, and for
+/// `nalgebra::Matrix3xX<f32>` with the `nalgebra` feature.
+pub trait CoordStorage {
+    /// The coordinates of all atoms
+    fn as_coords(&self) -> Result<&[[f32; 3]]>;
+
+    /// The coordinates of `num_atoms` atoms, to be overwritten. Storage that
+    /// can grow is resized, all other storage must have the right size.
+    fn as_coords_mut(&mut self, num_atoms: usize) -> Result<&mut [[f32; 3]]>;
+}
+
+/// Group a flat buffer into the coordinates of every atom
+fn flat_coords(values: &[f32]) -> Result<&[[f32; 3]]> {
+    match values.as_chunks::<3>() {
+        (coords, []) => Ok(coords),
+        _ => Err(Error::InvalidFrame {
+            reason: "flat coordinates must have 3 values per atom",
+        }),
+    }
+}
+
+/// Group a flat buffer into the coordinates of every atom, see `flat_coords`
+fn flat_coords_mut(values: &mut [f32]) -> Result<&mut [[f32; 3]]> {
+    match values.as_chunks_mut::<3>() {
+        (coords, []) => Ok(coords),
+        _ => Err(Error::InvalidFrame {
+            reason: "flat coordinates must have 3 values per atom",
+        }),
+    }
+}
+
+/// Check that storage of a fixed size holds `num_atoms` atoms
+fn check_len(coords: &mut [[f32; 3]], num_atoms: usize) -> Result<&mut [[f32; 3]]> {
+    if coords.len() == num_atoms {
+        Ok(coords)
+    } else {
+        Err(Error::WrongSizeFrame {
+            expected: num_atoms,
+            found: coords.len(),
+        })
+    }
+}
+
+impl CoordStorage for [[f32; 3]] {
+    fn as_coords(&self) -> Result<&[[f32; 3]]> {
+        Ok(self)
+    }
+
+    fn as_coords_mut(&mut self, num_atoms: usize) -> Result<&mut [[f32; 3]]> {
+        check_len(self, num_atoms)
+    }
+}
+
+impl CoordStorage for Vec<[f32; 3]> {
+    fn as_coords(&self) -> Result<&[[f32; 3]]> {
+        Ok(self)
+    }
+
+    fn as_coords_mut(&mut self, num_atoms: usize) -> Result<&mut [[f32; 3]]> {
+        self.resize(num_atoms, [0.0; 3]);
+        Ok(self)
+    }
+}
+
+impl CoordStorage for [f32] {
+    fn as_coords(&self) -> Result<&[[f32; 3]]> {
+        flat_coords(self)
+    }
+
+    fn as_coords_mut(&mut self, num_atoms: usize) -> Result<&mut [[f32; 3]]> {
+        check_len(flat_coords_mut(self)?, num_atoms)
+    }
+}
+
+impl CoordStorage for Vec<f32> {
+    fn as_coords(&self) -> Result<&[[f32; 3]]> {
+        flat_coords(self)
+    }
+
+    fn as_coords_mut(&mut self, num_atoms: usize) -> Result<&mut [[f32; 3]]> {
+        self.resize(3 * num_atoms, 0.0);
+        flat_coords_mut(self)
+    }
+}
+
+/// An array of shape (N, 3) in standard (row-major) layout. Arrays of a
+/// different shape are replaced by a new array when reading.
+#[cfg(feature = "ndarray")]
+impl CoordStorage for ndarray::Array2<f32> {
+    fn as_coords(&self) -> Result<&[[f32; 3]]> {
+        if self.ncols() != 3 {
+            return Err(Error::InvalidFrame {
+                reason: "coordinate arrays must have 3 columns",
+            });
+        }
+        let values = self.as_slice().ok_or(Error::InvalidFrame {
+            reason: "coordinate arrays must be in standard layout",
+        })?;
+        flat_coords(values)
+    }
+
+    fn as_coords_mut(&mut self, num_atoms: usize) -> Result<&mut [[f32; 3]]> {
+        if self.dim() != (num_atoms, 3) {
+            *self = ndarray::Array2::zeros((num_atoms, 3));
+        }
+        let values = self.as_slice_mut().ok_or(Error::InvalidFrame {
+            reason: "coordinate arrays must be in standard layout",
+        })?;
+        flat_coords_mut(values)
+    }
+}
+
+/// A matrix with one column per atom, which nalgebra stores column by
+/// column. Matrices with a different number of columns are replaced by a new
+/// matrix when reading.
+#[cfg(feature = "nalgebra")]
+impl CoordStorage for nalgebra::Matrix3xX<f32> {
+    fn as_coords(&self) -> Result<&[[f32; 3]]> {
+        flat_coords(self.as_slice())
+    }
+
+    fn as_coords_mut(&mut self, num_atoms: usize) -> Result<&mut [[f32; 3]]> {
+        if self.ncols() != num_atoms {
+            *self = nalgebra::Matrix3xX::zeros(num_atoms);
+        }
+        flat_coords_mut(self.as_mut_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Coordinates and header of the frame at `index` of the XTC test file
+    fn expected(index: usize) -> Result<Frame> {
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        trajectory.seek_to_frame(index)?;
+        let mut frame = Frame::with_len(304);
+        trajectory.read(&mut frame)?;
+        Ok(frame)
+    }
+
+    /// Write `coords` to a trajectory in memory and read them back
+    fn round_trip<S: CoordStorage + ?Sized>(coords: &S, header: &FrameHeader) -> Result<Frame> {
+        let mut trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        trajectory.set_precision(10000.0);
+        trajectory.write_coords(coords, header)?;
+        let bytes = trajectory.into_bytes().unwrap();
+        let mut frame = Frame::with_len(coords.as_coords()?.len());
+        XTCTrajectory::from_bytes(bytes, FileMode::Read)?.read(&mut frame)?;
+        Ok(frame)
+    }
+
+    #[test]
+    fn test_read_coords() -> Result<()> {
+        let frame = expected(1)?;
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut header = FrameHeader::default();
+
+        let mut coords: Vec<[f32; 3]> = Vec::new();
+        trajectory.read_coords(&mut coords, &mut header)?;
+        assert_eq!(coords.len(), 304);
+        assert_eq!(header.step, 1);
+
+        let mut flat = vec![1.0; 12];
+        trajectory.read_coords(&mut flat, &mut header)?;
+        assert_eq!(flat.len(), 3 * 304);
+        assert_eq!(flat.as_coords()?, &frame.coords[..]);
+        assert_eq!(header, frame.header());
+
+        let written = round_trip(&flat, &header)?;
+        assert_eq!(written.coords, frame.coords);
+        assert_eq!(written.header(), header);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_coords_errors() -> Result<()> {
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut header = FrameHeader::default();
+        let result = trajectory.read_coords(&mut [[0.0; 3]; 303][..], &mut header);
+        assert!(matches!(
+            result,
+            Err(Error::WrongSizeFrame {
+                expected: 304,
+                found: 303
+            })
+        ));
+        let result = trajectory.read_coords(&mut [0.0; 913][..], &mut header);
+        assert!(matches!(result, Err(Error::InvalidFrame { .. })));
+        let result = trajectory.write_coords(&[0.0; 4][..], &header);
+        assert!(matches!(result, Err(Error::InvalidFrame { .. })));
+
+        // Nothing was read
+        trajectory.read_coords(&mut [0.0; 912][..], &mut header)?;
+        assert_eq!(header.step, 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_read_coords_ndarray() -> Result<()> {
+        let frame = expected(0)?;
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut header = FrameHeader::default();
+        let mut array = ndarray::Array2::zeros((0, 3));
+        trajectory.read_coords(&mut array, &mut header)?;
+        assert_eq!(array.dim(), (304, 3));
+        assert_eq!(array[[303, 2]], frame[303][2]);
+        assert_eq!(round_trip(&array, &header)?.coords, frame.coords);
+
+        let transposed = array.t().to_owned();
+        assert!(transposed.as_coords().is_err());
+        let result = trajectory.write_coords(&array.reversed_axes(), &header);
+        assert!(matches!(result, Err(Error::InvalidFrame { .. })));
+        Ok(())
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn test_read_coords_nalgebra() -> Result<()> {
+        let frame = expected(0)?;
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut header = FrameHeader::default();
+        let mut matrix = nalgebra::Matrix3xX::zeros(2);
+        trajectory.read_coords(&mut matrix, &mut header)?;
+        assert_eq!(matrix.ncols(), 304);
+        assert_eq!(matrix[(2, 303)], frame[303][2]);
+        assert_eq!(round_trip(&matrix, &header)?.coords, frame.coords);
+        Ok(())
+    }
+}
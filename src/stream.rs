@@ -0,0 +1,180 @@
+use crate::c_abi::xdrfile::xdrfile_io;
+use std::any::Any;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::raw::{c_char, c_int, c_longlong, c_uint, c_void};
+
+/// Owner of a Rust stream that backs an XDR file opened with `xdrfile_open_io`
+pub(crate) struct Stream {
+    data: *mut c_void,
+    into_any: unsafe fn(*mut c_void) -> Box<dyn Any>,
+    read_at: unsafe fn(*mut c_void, u64, usize) -> io::Result<Vec<u8>>,
+}
+
+impl Stream {
+    /// Wrap a reader. Writing to the resulting XDR file fails.
+    pub fn reader<R>(reader: R) -> (Stream, xdrfile_io)
+    where
+        R: Read + Seek + Send + 'static,
+    {
+        let stream = Self::new(reader);
+        let io = xdrfile_io {
+            data: stream.data,
+            read: Some(read_cb::<R>),
+            write: None,
+            seek: Some(seek_cb::<R>),
+            tell: Some(tell_cb::<R>),
+            flush: None,
+        };
+        (stream, io)
+    }
+
+    /// Wrap a stream that supports both reading and writing
+    pub fn read_write<S>(inner: S) -> (Stream, xdrfile_io)
+    where
+        S: Read + Write + Seek + Send + 'static,
+    {
+        let stream = Self::new(inner);
+        let io = xdrfile_io {
+            data: stream.data,
+            read: Some(read_cb::<S>),
+            write: Some(write_cb::<S>),
+            seek: Some(seek_cb::<S>),
+            tell: Some(tell_cb::<S>),
+            flush: Some(flush_cb::<S>),
+        };
+        (stream, io)
+    }
+
+    /// Read up to `len` bytes starting at `offset`, bypassing the XDR file.
+    /// The position of the stream is kept.
+    pub fn read_at(&mut self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+        unsafe { (self.read_at)(self.data, offset, len) }
+    }
+
+    /// Take the last IO error of the stream that the C API could not report,
+    /// if any. An unexpected end of the stream is not recorded.
+    pub fn take_error(&self) -> Option<io::Error> {
+        // SAFETY: `StreamData` is `repr(C)` and starts with the error. The
+        // C API changes the stream through `&self` as well, e.g. in `tell`,
+        // and `Stream` is not `Sync`.
+        unsafe { (*(self.data as *mut Option<io::Error>)).take() }
+    }
+
+    /// Take back the wrapped stream, None if it is not of type `S`.
+    ///
+    /// The XDR file using the stream must be closed before.
+    pub fn into_inner<S: 'static>(self) -> Option<S> {
+        let inner = unsafe { (self.into_any)(self.data) };
+        std::mem::forget(self);
+        inner.downcast().ok().map(|inner| *inner)
+    }
+
+    fn new<S: Read + Seek + 'static>(inner: S) -> Stream {
+        Stream {
+            data: Box::into_raw(Box::new(StreamData { error: None, inner })) as *mut c_void,
+            into_any: into_any::<S>,
+            read_at: read_at::<S>,
+        }
+    }
+}
+
+// SAFETY: `data` exclusively owns a stream of a `Send` type, which is
+// enforced by the constructors
+unsafe impl Send for Stream {}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        unsafe {
+            drop((self.into_any)(self.data));
+        }
+    }
+}
+
+/// A stream and the last IO error it returned to the C API, which only
+/// sees whether a call failed
+#[repr(C)]
+struct StreamData<S> {
+    error: Option<io::Error>,
+    inner: S,
+}
+
+impl<S> StreamData<S> {
+    /// Record the error of a failed call, except for an unexpected end of
+    /// the stream, which the C API reports as the end of the file
+    fn record<T>(&mut self, result: io::Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                if err.kind() != io::ErrorKind::UnexpectedEof {
+                    self.error = Some(err);
+                }
+                None
+            }
+        }
+    }
+}
+
+unsafe fn into_any<S: 'static>(data: *mut c_void) -> Box<dyn Any> {
+    Box::new(Box::from_raw(data as *mut StreamData<S>).inner)
+}
+
+unsafe fn read_at<S: Read + Seek>(
+    data: *mut c_void,
+    offset: u64,
+    len: usize,
+) -> io::Result<Vec<u8>> {
+    let stream = &mut (*(data as *mut StreamData<S>)).inner;
+    let pos = stream.stream_position()?;
+    stream.seek(SeekFrom::Start(offset))?;
+    let mut bytes = Vec::new();
+    let result = stream.by_ref().take(len as u64).read_to_end(&mut bytes);
+    stream.seek(SeekFrom::Start(pos))?;
+    result.map(|_| bytes)
+}
+
+unsafe extern "C" fn read_cb<R: Read>(data: *mut c_void, buf: *mut c_char, len: c_uint) -> c_int {
+    let reader = &mut *(data as *mut StreamData<R>);
+    let buf = std::slice::from_raw_parts_mut(buf as *mut u8, len as usize);
+    let result = reader.inner.read_exact(buf);
+    reader.record(result).is_some() as c_int
+}
+
+unsafe extern "C" fn write_cb<W: Write>(data: *mut c_void, buf: *mut c_char, len: c_uint) -> c_int {
+    let writer = &mut *(data as *mut StreamData<W>);
+    let buf = std::slice::from_raw_parts(buf as *const u8, len as usize);
+    let result = writer.inner.write_all(buf);
+    writer.record(result).is_some() as c_int
+}
+
+unsafe extern "C" fn flush_cb<W: Write>(data: *mut c_void) -> c_int {
+    let writer = &mut *(data as *mut StreamData<W>);
+    let result = writer.inner.flush();
+    match writer.record(result) {
+        Some(()) => 0,
+        None => -1,
+    }
+}
+
+unsafe extern "C" fn seek_cb<S: Seek>(data: *mut c_void, pos: c_longlong, whence: c_int) -> c_int {
+    let stream = &mut *(data as *mut StreamData<S>);
+    let pos = match whence {
+        0 if pos >= 0 => SeekFrom::Start(pos as u64),
+        1 => SeekFrom::Current(pos),
+        2 => SeekFrom::End(pos),
+        _ => return -1,
+    };
+    let result = stream.inner.seek(pos);
+    match stream.record(result) {
+        Some(_) => 0,
+        None => -1,
+    }
+}
+
+unsafe extern "C" fn tell_cb<S: Seek>(data: *mut c_void) -> c_longlong {
+    let stream = &mut *(data as *mut StreamData<S>);
+    let result = stream.inner.stream_position();
+    match stream.record(result) {
+        Some(pos) => pos as c_longlong,
+        None => -1,
+    }
+}
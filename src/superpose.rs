@@ -0,0 +1,237 @@
+use crate::{AtomSelection, Error, Frame, Result};
+
+impl Frame {
+    /// Least-squares fit the frame onto `reference` (Kabsch superposition).
+    ///
+    /// The rotation and translation that minimize the RMSD between the
+    /// selected atoms of both frames are computed and applied to all atoms
+    /// of the frame. Velocities and forces are rotated along. Returns the
+    /// RMSD of the selected atoms after the fit. Selected indices beyond the
+    /// number of atoms are ignored.
+    ///
+    /// The optimal rotation is found with the quaternion formulation by
+    /// Horn (1987), which gives the same result as the SVD based Kabsch
+    /// algorithm but cannot produce reflections.
+    pub fn superpose_onto(&mut self, reference: &Frame, selection: &AtomSelection) -> Result<f32> {
+        if reference.len() != self.len() {
+            return Err(Error::from((&*self, reference.len())));
+        }
+        let selected: Vec<usize> = selection.indices_below(self.len()).collect();
+        if selected.is_empty() {
+            return Ok(0.0);
+        }
+        let center = centroid(&self.coords, &selected);
+        let reference_center = centroid(&reference.coords, &selected);
+
+        // Correlation matrix and sum of squared norms of the centered atoms
+        let mut s = [[0.0f64; 3]; 3];
+        let mut squared_norms = 0.0;
+        for &i in &selected {
+            let m = centered(self.coords[i], center);
+            let r = centered(reference.coords[i], reference_center);
+            for a in 0..3 {
+                for b in 0..3 {
+                    s[a][b] += m[a] * r[b];
+                }
+                squared_norms += m[a] * m[a] + r[a] * r[a];
+            }
+        }
+        let n = [
+            [
+                s[0][0] + s[1][1] + s[2][2],
+                s[1][2] - s[2][1],
+                s[2][0] - s[0][2],
+                s[0][1] - s[1][0],
+            ],
+            [
+                s[1][2] - s[2][1],
+                s[0][0] - s[1][1] - s[2][2],
+                s[0][1] + s[1][0],
+                s[2][0] + s[0][2],
+            ],
+            [
+                s[2][0] - s[0][2],
+                s[0][1] + s[1][0],
+                -s[0][0] + s[1][1] - s[2][2],
+                s[1][2] + s[2][1],
+            ],
+            [
+                s[0][1] - s[1][0],
+                s[2][0] + s[0][2],
+                s[1][2] + s[2][1],
+                -s[0][0] - s[1][1] + s[2][2],
+            ],
+        ];
+        let (eigenvalue, quaternion) = largest_eigenpair(n);
+
+        self.translate([-center[0] as f32, -center[1] as f32, -center[2] as f32]);
+        self.rotate_quaternion([
+            quaternion[0] as f32,
+            quaternion[1] as f32,
+            quaternion[2] as f32,
+            quaternion[3] as f32,
+        ]);
+        self.translate([
+            reference_center[0] as f32,
+            reference_center[1] as f32,
+            reference_center[2] as f32,
+        ]);
+
+        let squared_deviation = (squared_norms - 2.0 * eigenvalue).max(0.0);
+        Ok((squared_deviation / selected.len() as f64).sqrt() as f32)
+    }
+}
+
+/// Mean position of the atoms at the given indices
+fn centroid(coords: &[[f32; 3]], indices: &[usize]) -> [f64; 3] {
+    let mut sum = [0.0; 3];
+    for &i in indices {
+        for dim in 0..3 {
+            sum[dim] += f64::from(coords[i][dim]);
+        }
+    }
+    let n = indices.len() as f64;
+    [sum[0] / n, sum[1] / n, sum[2] / n]
+}
+
+/// Position relative to `center`
+fn centered(coords: [f32; 3], center: [f64; 3]) -> [f64; 3] {
+    [
+        f64::from(coords[0]) - center[0],
+        f64::from(coords[1]) - center[1],
+        f64::from(coords[2]) - center[2],
+    ]
+}
+
+/// Largest eigenvalue and its eigenvector of a symmetric 4x4 matrix, using
+/// the cyclic Jacobi method
+fn largest_eigenpair(mut a: [[f64; 4]; 4]) -> (f64, [f64; 4]) {
+    let mut v = [[0.0; 4]; 4];
+    for (i, row) in v.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    for _ in 0..50 {
+        let off_diagonal: f64 = (0..4)
+            .flat_map(|p| (p + 1..4).map(move |q| (p, q)))
+            .map(|(p, q)| a[p][q] * a[p][q])
+            .sum();
+        if off_diagonal < 1e-22 {
+            break;
+        }
+        for p in 0..4 {
+            for q in p + 1..4 {
+                if a[p][q] == 0.0 {
+                    continue;
+                }
+                // Rotation angle that zeroes a[p][q]
+                let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (row_p, row_q) = (a[p], a[q]);
+                for (k, (apk, aqk)) in row_p.iter().zip(&row_q).enumerate() {
+                    a[p][k] = c * apk - s * aqk;
+                    a[q][k] = s * apk + c * aqk;
+                }
+                for row in v.iter_mut() {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+    let largest = (0..4)
+        .max_by(|&i, &j| a[i][i].partial_cmp(&a[j][j]).unwrap())
+        .unwrap();
+    let vector = [v[0][largest], v[1][largest], v[2][largest], v[3][largest]];
+    (a[largest][largest], vector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_superpose_rigid() {
+        let mut reference = Frame::with_len(4);
+        reference[0] = [0.0, 0.0, 0.0];
+        reference[1] = [1.0, 0.0, 0.0];
+        reference[2] = [0.0, 2.0, 0.0];
+        reference[3] = [0.0, 0.0, 3.0];
+
+        let mut frame = reference.clone();
+        frame.rotate_quaternion([0.3, -0.5, 0.8, 0.1]);
+        frame.translate([5.0, -2.0, 1.0]);
+
+        let selection = AtomSelection::new(0..4);
+        let rmsd = frame.superpose_onto(&reference, &selection).unwrap();
+        assert_approx_eq!(rmsd, 0.0, 1e-3);
+        for i in 0..4 {
+            for dim in 0..3 {
+                assert_approx_eq!(frame[i][dim], reference[i][dim], 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn test_superpose_trajectory() -> Result<()> {
+        use crate::Trajectory;
+
+        let mut traj = crate::XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let num_atoms = traj.get_num_atoms()?;
+        let mut reference = Frame::with_len(num_atoms);
+        let mut frame = Frame::with_len(num_atoms);
+        traj.read(&mut reference)?;
+        traj.seek_to_frame(20)?;
+        traj.read(&mut frame)?;
+
+        let selection = AtomSelection::new(0..num_atoms);
+        let mut before = 0.0;
+        for i in 0..num_atoms {
+            let (a, b) = (frame[i], reference[i]);
+            before += (0..3).map(|d| (a[d] - b[d]).powi(2)).sum::<f32>();
+        }
+        let before = (before / num_atoms as f32).sqrt();
+
+        let rmsd = frame.superpose_onto(&reference, &selection)?;
+        let mut after = 0.0;
+        for i in 0..num_atoms {
+            let (a, b) = (frame[i], reference[i]);
+            after += (0..3).map(|d| (a[d] - b[d]).powi(2)).sum::<f32>();
+        }
+        let after = (after / num_atoms as f32).sqrt();
+        assert!(rmsd < before);
+        assert_approx_eq!(rmsd, after, 1e-4);
+
+        // Superposing again does not change the frame anymore
+        let again = frame.superpose_onto(&reference, &selection)?;
+        assert_approx_eq!(rmsd, again, 1e-4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_superpose_wrong_size() {
+        let mut frame = Frame::with_len(2);
+        let selection = AtomSelection::new(0..2);
+        let err = frame
+            .superpose_onto(&Frame::with_len(3), &selection)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            Error::WrongSizeFrame {
+                expected: 3,
+                found: 2
+            }
+        );
+        assert_eq!(
+            frame.superpose_onto(&Frame::with_len(2), &AtomSelection::default()),
+            Ok(0.0)
+        );
+    }
+}
@@ -0,0 +1,419 @@
+use crate::*;
+use std::fmt::Write as _;
+use std::ops::RangeBounds;
+
+/// Names and residues of the atoms of a system, the metadata that XTC and TRR
+/// files do not store. Loaded from the atom records of a GRO or PDB file,
+/// e.g. the structure a simulation was started from.
+///
+/// Every field holds one entry per atom, in the order of the atoms in the
+/// trajectory. Masses are not part of GRO and PDB files, so they are None
+/// after loading, until they are set or guessed with `guess_masses`.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let gro = "Water\n    3\n    1SOL     OW    1   0.126   1.624   1.679\n    \
+///                1SOL    HW1    2   0.190   1.661   1.747\n    \
+///                1SOL    HW2    3   0.177   1.568   1.613\n   1.86206   1.86206   1.86206\n";
+///     let mut topology = Topology::parse_gro(gro)?;
+///     assert_eq!(topology.len(), 3);
+///     assert_eq!(topology.atom_names[1], "HW1");
+///
+///     let hydrogens = topology.select_atom_names(&["HW1", "HW2"]);
+///     assert_eq!(hydrogens.indices(), &[1, 2]);
+///
+///     topology.guess_masses()?;
+///     assert_eq!(topology.masses.as_ref().unwrap()[0], 15.999);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Topology {
+    /// Name of every atom, e.g. `CA`
+    pub atom_names: Vec<String>,
+    /// Name of the residue of every atom, e.g. `ALA`
+    pub residue_names: Vec<String>,
+    /// Number of the residue of every atom, as given in the file
+    pub residue_ids: Vec<i32>,
+    /// Chemical element of every atom, if given in the file, else empty
+    pub elements: Vec<String>,
+    /// Mass of every atom in atomic mass units
+    pub masses: Option<Vec<f32>>,
+}
+
+/// Masses of the elements known to `Topology::guess_masses`, from the
+/// GROMACS atommass.dat
+const ELEMENT_MASSES: [(&str, f32); 15] = [
+    ("H", 1.008),
+    ("C", 12.011),
+    ("N", 14.007),
+    ("O", 15.999),
+    ("F", 18.998),
+    ("NA", 22.990),
+    ("MG", 24.305),
+    ("P", 30.974),
+    ("S", 32.06),
+    ("CL", 35.45),
+    ("K", 39.098),
+    ("CA", 40.078),
+    ("FE", 55.845),
+    ("ZN", 65.38),
+    ("BR", 79.904),
+];
+
+/// The columns `range` of `line`, without surrounding spaces, or an empty
+/// string if the line is too short
+fn columns(line: &str, range: std::ops::Range<usize>) -> &str {
+    let end = range.end.min(line.len());
+    line.get(range.start.min(end)..end).unwrap_or("").trim()
+}
+
+impl Topology {
+    /// Load the topology from a GRO or PDB file, chosen by the extension of
+    /// `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Topology> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        let parse = match extension.as_deref() {
+            Some("gro") => Topology::parse_gro,
+            Some("pdb") | Some("ent") => Topology::parse_pdb,
+            _ => {
+                return Err(Error::InvalidTopology {
+                    line: None,
+                    reason: "unknown topology format, expected a .gro or .pdb file",
+                })
+            }
+        };
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| Error::from((path, FileMode::Read, err)))?;
+        parse(&contents)
+    }
+
+    /// Parse the atoms of a file in GRO format. Coordinates, velocities and
+    /// the box are not read.
+    pub fn parse_gro(contents: &str) -> Result<Topology> {
+        let mut lines = contents.lines().enumerate().skip(1);
+        let num_atoms: usize = match lines.next() {
+            Some((_, line)) => line.trim().parse().map_err(|_| Error::InvalidTopology {
+                line: Some(2),
+                reason: "expected the number of atoms",
+            })?,
+            None => {
+                return Err(Error::InvalidTopology {
+                    line: None,
+                    reason: "missing the number of atoms",
+                })
+            }
+        };
+
+        let mut topology = Topology::default();
+        for _ in 0..num_atoms {
+            let (index, line) = lines.next().ok_or(Error::InvalidTopology {
+                line: None,
+                reason: "fewer atoms than given in the header",
+            })?;
+            let residue_id = columns(line, 0..5)
+                .parse()
+                .map_err(|_| Error::InvalidTopology {
+                    line: Some(index + 1),
+                    reason: "expected a residue number in columns 1-5",
+                })?;
+            topology.push(columns(line, 10..15), columns(line, 5..10), residue_id, "");
+        }
+        Ok(topology)
+    }
+
+    /// Parse the `ATOM` and `HETATM` records of a file in PDB format. Only the
+    /// first model is read if the file has several.
+    pub fn parse_pdb(contents: &str) -> Result<Topology> {
+        let mut topology = Topology::default();
+        for (index, line) in contents.lines().enumerate() {
+            if line.starts_with("ENDMDL") || line.starts_with("END ") || line == "END" {
+                break;
+            }
+            if !line.starts_with("ATOM  ") && !line.starts_with("HETATM") {
+                continue;
+            }
+            let residue_id = columns(line, 22..26)
+                .parse()
+                .map_err(|_| Error::InvalidTopology {
+                    line: Some(index + 1),
+                    reason: "expected a residue number in columns 23-26",
+                })?;
+            topology.push(
+                columns(line, 12..16),
+                columns(line, 17..21),
+                residue_id,
+                &columns(line, 76..78).to_ascii_uppercase(),
+            );
+        }
+        Ok(topology)
+    }
+
+    /// Append an atom without mass
+    fn push(&mut self, name: &str, residue_name: &str, residue_id: i32, element: &str) {
+        self.atom_names.push(name.to_string());
+        self.residue_names.push(residue_name.to_string());
+        self.residue_ids.push(residue_id);
+        self.elements.push(element.to_string());
+    }
+
+    /// Number of atoms
+    pub fn len(&self) -> usize {
+        self.atom_names.len()
+    }
+
+    /// True if the topology has no atoms
+    pub fn is_empty(&self) -> bool {
+        self.atom_names.is_empty()
+    }
+
+    /// Set the mass of every atom from its element. Atoms without an element
+    /// are assumed to be of the element their name starts with, ignoring
+    /// leading digits, except for ions whose name is the name of their
+    /// residue, such as `NA` and `CL`. Returns an error and leaves the
+    /// masses unchanged if the element of an atom is unknown.
+    pub fn guess_masses(&mut self) -> Result<()> {
+        let mass_of = |element: &str| {
+            ELEMENT_MASSES
+                .iter()
+                .find(|(known, _)| known.eq_ignore_ascii_case(element))
+                .map(|&(_, mass)| mass)
+        };
+        let mut masses = Vec::with_capacity(self.len());
+        for i in 0..self.len() {
+            let name = self.atom_names[i].trim_start_matches(|c: char| c.is_ascii_digit());
+            let mass = if !self.elements[i].is_empty() {
+                mass_of(&self.elements[i])
+            } else if name.eq_ignore_ascii_case(&self.residue_names[i]) {
+                mass_of(name)
+            } else {
+                name.get(..1).and_then(mass_of)
+            };
+            masses.push(mass.ok_or(Error::InvalidTopology {
+                line: None,
+                reason: "cannot guess the mass of an atom of unknown element",
+            })?);
+        }
+        self.masses = Some(masses);
+        Ok(())
+    }
+
+    /// Select the atoms with any of the given names
+    pub fn select_atom_names(&self, names: &[&str]) -> AtomSelection {
+        self.select(|i| names.contains(&self.atom_names[i].as_str()))
+    }
+
+    /// Select the atoms in residues with any of the given names
+    pub fn select_residue_names(&self, names: &[&str]) -> AtomSelection {
+        self.select(|i| names.contains(&self.residue_names[i].as_str()))
+    }
+
+    /// Select the atoms in residues with a number in `ids`
+    pub fn select_residue_ids(&self, ids: impl RangeBounds<i32>) -> AtomSelection {
+        self.select(|i| ids.contains(&self.residue_ids[i]))
+    }
+
+    /// Select the atoms for which `predicate` returns true for their index
+    pub fn select(&self, predicate: impl Fn(usize) -> bool) -> AtomSelection {
+        AtomSelection::new((0..self.len()).filter(|&i| predicate(i)))
+    }
+
+    /// Center of mass of the selected atoms of `frame`, see
+    /// `Frame::center_of_mass`. None if the topology has no masses.
+    ///
+    /// # Panics
+    /// Panics if a selected atom of the frame is not in the topology.
+    pub fn center_of_mass(&self, frame: &Frame, selection: &AtomSelection) -> Option<[f32; 3]> {
+        frame.center_of_mass(selection, self.masses.as_ref()?)
+    }
+
+    /// Format `frame` with the atoms of the topology as a PDB file, with
+    /// coordinates converted to Ångström. The box is written as a `CRYST1`
+    /// record if the frame has one. Returns an error if the number of atoms
+    /// of the frame differs from the topology.
+    pub fn to_pdb(&self, frame: &Frame) -> Result<String> {
+        if frame.len() != self.len() {
+            return Err(Error::WrongSizeFrame {
+                expected: self.len(),
+                found: frame.len(),
+            });
+        }
+        let mut pdb = String::new();
+        if frame.box_vector.shape() != BoxShape::None {
+            let p = frame.box_vector.lattice_parameters();
+            let _ = writeln!(
+                pdb,
+                "CRYST1{:9.3}{:9.3}{:9.3}{:7.2}{:7.2}{:7.2} P 1           1",
+                10.0 * p.a,
+                10.0 * p.b,
+                10.0 * p.c,
+                p.alpha,
+                p.beta,
+                p.gamma
+            );
+        }
+        let _ = writeln!(pdb, "MODEL {:8}", 1);
+        for (i, coords) in frame.coords.iter().enumerate() {
+            // Names shorter than 4 characters start in column 14 by convention
+            let name = &self.atom_names[i];
+            let name = if name.len() < 4 {
+                format!(" {:<3}", name)
+            } else {
+                name.clone()
+            };
+            let _ = writeln!(
+                pdb,
+                "ATOM  {:5} {:4} {:<4} {:4}    {:8.3}{:8.3}{:8.3}{:6.2}{:6.2}          {:>2}",
+                (i + 1) % 100_000,
+                name,
+                self.residue_names[i],
+                self.residue_ids[i] % 10_000,
+                10.0 * coords[0],
+                10.0 * coords[1],
+                10.0 * coords[2],
+                1.0,
+                0.0,
+                self.elements[i]
+            );
+        }
+        pdb.push_str("ENDMDL\nEND\n");
+        Ok(pdb)
+    }
+
+    /// Write `frame` with the atoms of the topology to a PDB file at `path`,
+    /// see `to_pdb`
+    pub fn write_pdb(&self, path: impl AsRef<Path>, frame: &Frame) -> Result<()> {
+        let path = path.as_ref();
+        let pdb = self.to_pdb(frame)?;
+        std::fs::write(path, pdb).map_err(|err| Error::from((path, FileMode::Write, err)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const GRO: &str = "Two residues
+    5
+    1ALA      N    1   1.000   1.000   1.000
+    1ALA     CA    2   1.100   1.000   1.000
+    1ALA    1HB    3   1.100   1.100   1.000
+    2NA      NA    4   0.500   0.500   0.500
+   10SOL     OW    5   0.000   0.000   0.000
+   2.00000   2.00000   2.00000
+";
+
+    #[test]
+    fn test_parse_gro() -> Result<()> {
+        let mut topology = Topology::parse_gro(GRO)?;
+        assert_eq!(topology.len(), 5);
+        assert_eq!(topology.atom_names, ["N", "CA", "1HB", "NA", "OW"]);
+        assert_eq!(topology.residue_names, ["ALA", "ALA", "ALA", "NA", "SOL"]);
+        assert_eq!(topology.residue_ids, [1, 1, 1, 2, 10]);
+        assert_eq!(topology.masses, None);
+
+        assert_eq!(
+            topology.select_residue_names(&["ALA"]).indices(),
+            &[0, 1, 2]
+        );
+        assert_eq!(topology.select_residue_ids(2..).indices(), &[3, 4]);
+        assert_eq!(topology.select_atom_names(&["CA"]).indices(), &[1]);
+
+        topology.guess_masses()?;
+        assert_eq!(
+            topology.masses.as_deref(),
+            Some(&[14.007, 12.011, 1.008, 22.990, 15.999][..])
+        );
+
+        let frame = Frame::from_coords(
+            0,
+            0.0,
+            BoxVector::default(),
+            vec![[0.0; 3], [1.0, 0.0, 0.0], [0.0; 3], [0.0; 3], [0.0; 3]],
+        );
+        let selection = AtomSelection::new(vec![0, 1]);
+        let com = topology.center_of_mass(&frame, &selection).unwrap();
+        assert_approx_eq!(com[0], 12.011 / (12.011 + 14.007));
+        topology.masses = None;
+        assert_eq!(topology.center_of_mass(&frame, &selection), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let result = Topology::parse_gro("Title\n   3\n    1ALA      N    1\n");
+        assert!(matches!(
+            result,
+            Err(Error::InvalidTopology { line: None, .. })
+        ));
+        let result = Topology::parse_gro("Title\nthree\n");
+        assert!(matches!(
+            result,
+            Err(Error::InvalidTopology { line: Some(2), .. })
+        ));
+        let result = Topology::parse_gro("Title\n1\n  ALA      N    1\n");
+        assert!(matches!(
+            result,
+            Err(Error::InvalidTopology { line: Some(3), .. })
+        ));
+
+        let mut topology = Topology::parse_gro("Title\n1\n    1UNK     XX    1\n").unwrap();
+        assert!(topology.guess_masses().is_err());
+        assert_eq!(topology.masses, None);
+
+        assert!(matches!(
+            Topology::open("tests/1l2y.xtc"),
+            Err(Error::InvalidTopology { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pdb_round_trip() -> Result<()> {
+        let mut topology = Topology::parse_gro(GRO)?;
+        topology.elements = ["N", "C", "H", "NA", "O"].map(String::from).to_vec();
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(304);
+        trajectory.read(&mut frame)?;
+        frame.coords.truncate(5);
+        frame.box_vector = BoxVector::rectangular(2.0, 3.0, 4.0);
+
+        let pdb = topology.to_pdb(&frame)?;
+        let lines: Vec<&str> = pdb.lines().collect();
+        assert_eq!(
+            lines[0],
+            "CRYST1   20.000   30.000   40.000  90.00  90.00  90.00 P 1           1"
+        );
+        assert_eq!(&lines[3][..26], "ATOM      2  CA  ALA     1");
+        assert!(lines
+            .iter()
+            .all(|line| line.len() == 78 || !line.starts_with("ATOM")));
+        let x: f32 = columns(lines[3], 30..38).parse().unwrap();
+        assert_approx_eq!(x, 10.0 * frame[1][0], 1e-3);
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("frame.pdb");
+        topology.write_pdb(&path, &frame)?;
+        assert_eq!(Topology::open(&path)?, topology);
+
+        // Only the first model is read
+        let two_models = format!("{}{}", pdb.trim_end_matches("END\n"), pdb);
+        assert_eq!(Topology::parse_pdb(&two_models)?.len(), 5);
+
+        frame.coords.pop();
+        assert!(matches!(
+            topology.to_pdb(&frame),
+            Err(Error::WrongSizeFrame {
+                expected: 5,
+                found: 4
+            })
+        ));
+        Ok(())
+    }
+}
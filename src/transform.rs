@@ -0,0 +1,149 @@
+use crate::Frame;
+
+/// Multiply a vector by a 3x3 matrix (`matrix · vector`)
+fn mat_vec(matrix: &[[f32; 3]; 3], v: [f32; 3]) -> [f32; 3] {
+    let mut result = [0.0; 3];
+    for (r, row) in result.iter_mut().zip(matrix) {
+        *r = row[0] * v[0] + row[1] * v[1] + row[2] * v[2];
+    }
+    result
+}
+
+/// Rotation matrix of a quaternion given as `[w, x, y, z]`. The quaternion
+/// is normalized first.
+fn quaternion_matrix(q: [f32; 4]) -> [[f32; 3]; 3] {
+    let norm = q.iter().map(|c| c * c).sum::<f32>().sqrt();
+    let [w, x, y, z] = [q[0] / norm, q[1] / norm, q[2] / norm, q[3] / norm];
+    [
+        [
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y - w * z),
+            2.0 * (x * z + w * y),
+        ],
+        [
+            2.0 * (x * y + w * z),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z - w * x),
+        ],
+        [
+            2.0 * (x * z - w * y),
+            2.0 * (y * z + w * x),
+            1.0 - 2.0 * (x * x + y * y),
+        ],
+    ]
+}
+
+impl Frame {
+    /// Move all atoms by `offset`
+    pub fn translate(&mut self, offset: [f32; 3]) {
+        #[cfg(feature = "simd")]
+        return crate::simd::translate(&mut self.coords, offset);
+        #[cfg(not(feature = "simd"))]
+        for coords in self.coords.iter_mut() {
+            for dim in 0..3 {
+                coords[dim] += offset[dim];
+            }
+        }
+    }
+
+    /// Rotate all atoms around the origin by multiplying coordinates,
+    /// velocities and forces with the rotation `matrix` (`matrix · v`).
+    ///
+    /// The box vector is left unchanged, as GROMACS expects boxes to be
+    /// aligned with the coordinate axes.
+    pub fn rotate(&mut self, matrix: &[[f32; 3]; 3]) {
+        let blocks = std::iter::once(&mut self.coords)
+            .chain(self.velocities.iter_mut())
+            .chain(self.forces.iter_mut());
+        for block in blocks {
+            for v in block.iter_mut() {
+                *v = mat_vec(matrix, *v);
+            }
+        }
+    }
+
+    /// Rotate all atoms around the origin by the rotation described by the
+    /// quaternion `[w, x, y, z]`, see `rotate`
+    pub fn rotate_quaternion(&mut self, quaternion: [f32; 4]) {
+        self.rotate(&quaternion_matrix(quaternion))
+    }
+
+    /// Scale the system by `factor`, e.g. to convert from nm to Å.
+    ///
+    /// Coordinates, velocities and the box vector are multiplied by `factor`,
+    /// while forces, which are given per length, are divided by it.
+    pub fn scale(&mut self, factor: f32) {
+        let blocks = std::iter::once(&mut self.coords).chain(self.velocities.iter_mut());
+        for block in blocks {
+            #[cfg(feature = "simd")]
+            crate::simd::multiply(block, factor);
+            #[cfg(not(feature = "simd"))]
+            for v in block.iter_mut() {
+                v.iter_mut().for_each(|c| *c *= factor);
+            }
+        }
+        for block in self.forces.iter_mut() {
+            #[cfg(feature = "simd")]
+            crate::simd::divide(block, factor);
+            #[cfg(not(feature = "simd"))]
+            for v in block.iter_mut() {
+                v.iter_mut().for_each(|c| *c /= factor);
+            }
+        }
+        for v in self.box_vector.as_mut_matrix().iter_mut() {
+            v.iter_mut().for_each(|c| *c *= factor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BoxVector;
+
+    fn assert_vec_eq(a: [f32; 3], b: [f32; 3]) {
+        for dim in 0..3 {
+            assert_approx_eq!(a[dim], b[dim], 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_translate() {
+        let mut frame = Frame::with_len(2).with_velocities();
+        frame[1] = [1.0, 2.0, 3.0];
+        frame.translate([1.0, -1.0, 0.5]);
+        assert_eq!(frame.coords, vec![[1.0, -1.0, 0.5], [2.0, 1.0, 3.5]]);
+        // Velocities do not depend on the position
+        assert_eq!(frame.velocities, Some(vec![[0.0; 3]; 2]));
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut frame = Frame::with_len(1).with_velocities();
+        frame[0] = [1.0, 2.0, 3.0];
+        frame.velocities = Some(vec![[0.0, 1.0, 0.0]]);
+        // 90 degrees around the z axis
+        let matrix = [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]];
+        frame.rotate(&matrix);
+        assert_vec_eq(frame[0], [-2.0, 1.0, 3.0]);
+        assert_vec_eq(frame.velocities.as_ref().unwrap()[0], [-1.0, 0.0, 0.0]);
+
+        // The same rotation as a (non-normalized) quaternion
+        let mut other = Frame::with_len(1);
+        other[0] = [1.0, 2.0, 3.0];
+        other.rotate_quaternion([2.0, 0.0, 0.0, 2.0]);
+        assert_vec_eq(other[0], frame[0]);
+    }
+
+    #[test]
+    fn test_scale() {
+        let mut frame = Frame::with_len(1).with_forces();
+        frame[0] = [1.0, 2.0, 3.0];
+        frame.forces = Some(vec![[10.0; 3]]);
+        frame.box_vector = BoxVector::rectangular(1.0, 2.0, 3.0);
+        frame.scale(10.0);
+        assert_eq!(frame[0], [10.0, 20.0, 30.0]);
+        assert_eq!(frame.forces, Some(vec![[1.0; 3]]));
+        assert_eq!(frame.box_vector[2], [0.0, 0.0, 30.0]);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::Frame;
+
+/// Unit of lengths (coordinates and box vectors)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LengthUnit {
+    /// Nanometer, the unit used by GROMACS
+    #[default]
+    Nanometer,
+    /// Ångström (0.1 nm)
+    Angstrom,
+}
+
+/// Unit of times
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimeUnit {
+    /// Femtosecond (0.001 ps)
+    Femtosecond,
+    /// Picosecond, the unit used by GROMACS
+    #[default]
+    Picosecond,
+    /// Nanosecond (1000 ps)
+    Nanosecond,
+}
+
+impl LengthUnit {
+    /// Number of units in one nanometer
+    pub fn per_nanometer(self) -> f32 {
+        match self {
+            LengthUnit::Nanometer => 1.0,
+            LengthUnit::Angstrom => 10.0,
+        }
+    }
+}
+
+impl TimeUnit {
+    /// Number of units in one picosecond
+    pub fn per_picosecond(self) -> f32 {
+        match self {
+            TimeUnit::Femtosecond => 1000.0,
+            TimeUnit::Picosecond => 1.0,
+            TimeUnit::Nanosecond => 0.001,
+        }
+    }
+}
+
+impl Frame {
+    /// Convert all lengths of the frame from unit `from` to unit `to`.
+    ///
+    /// Trajectories written by GROMACS use nanometers, so reading a frame
+    /// and converting it for an Ångström based tool looks like this:
+    ///
+    /// ```rust
+    /// use xdrfile::*;
+    ///
+    /// let mut frame = Frame::with_len(1);
+    /// frame[0] = [0.1, 0.2, 0.3];
+    /// frame.convert_length(LengthUnit::Nanometer, LengthUnit::Angstrom);
+    /// assert_eq!(frame[0], [1.0, 2.0, 3.0]);
+    /// ```
+    ///
+    /// Coordinates, velocities and forces are converted along with the box,
+    /// see `scale`.
+    pub fn convert_length(&mut self, from: LengthUnit, to: LengthUnit) {
+        if from != to {
+            self.scale(to.per_nanometer() / from.per_nanometer());
+        }
+    }
+
+    /// Convert the time and velocities of the frame from unit `from` to
+    /// unit `to`
+    pub fn convert_time(&mut self, from: TimeUnit, to: TimeUnit) {
+        if from == to {
+            return;
+        }
+        let factor = to.per_picosecond() / from.per_picosecond();
+        self.time *= factor;
+        for v in self.velocities.iter_mut().flatten() {
+            v.iter_mut().for_each(|c| *c /= factor);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_length() {
+        let mut frame = Frame::with_len(1).with_velocities();
+        frame[0] = [1.0, 2.0, 3.0];
+        frame.convert_length(LengthUnit::Nanometer, LengthUnit::Angstrom);
+        assert_eq!(frame[0], [10.0, 20.0, 30.0]);
+        frame.convert_length(LengthUnit::Angstrom, LengthUnit::Angstrom);
+        assert_eq!(frame[0], [10.0, 20.0, 30.0]);
+        frame.convert_length(LengthUnit::Angstrom, LengthUnit::default());
+        assert_eq!(frame[0], [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_convert_time() {
+        let mut frame = Frame::with_len(1).with_velocities();
+        frame.time = 2500.0;
+        frame.velocities = Some(vec![[1.0, 2.0, 4.0]]);
+        frame.convert_time(TimeUnit::Picosecond, TimeUnit::Nanosecond);
+        assert_approx_eq!(frame.time, 2.5, 1e-5);
+        let velocities = frame.velocities.as_ref().unwrap();
+        assert_approx_eq!(velocities[0][2], 4000.0, 1e-2);
+        frame.convert_time(TimeUnit::Nanosecond, TimeUnit::Femtosecond);
+        assert_approx_eq!(frame.time, 2.5e6, 1.0);
+    }
+}
@@ -0,0 +1,307 @@
+use crate::*;
+use notify::{RecursiveMode, Watcher};
+use std::sync::mpsc::{self, Receiver};
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+/// Reads the frames of a trajectory while it is being written, e.g. by a
+/// running simulation, waking up on file system events instead of polling.
+///
+/// A watcher follows either a single file, which does not have to exist
+/// yet, or a directory of parts like those written by `gmx mdrun
+/// -noappend`. Parts are the XTC and TRR files in the directory, read in
+/// the order of their names. A part is finished once a later part appears,
+/// an incomplete frame at its end is skipped.
+///
+/// Iterating blocks until the next frame is completely written. Frames that
+/// are only partially written are recognized like with
+/// `TrajectoryIterator::retry_on_eof`. Iteration ends once no new frame was
+/// written for the duration set with `timeout`, and after the first error.
+///
+/// Some file systems, e.g. NFS, do not report changes made by other
+/// machines, so the trajectory is also checked every `poll_interval`.
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let watcher = TrajectoryWatcher::watch("md.xtc")?.timeout(Duration::from_secs(600));
+///     for frame in watcher {
+///         println!("step {}", frame?.step);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct TrajectoryWatcher {
+    /// Watched file or directory of parts
+    path: PathBuf,
+    /// Set if `path` is a directory of parts
+    parts: bool,
+    /// Path and frames of the file or part being read, None until it exists
+    /// and its first frame header is written
+    current: Option<(PathBuf, TrajectoryIterator<Box<dyn Trajectory>>)>,
+    /// Receives a message for every change in the watched directory
+    events: Receiver<()>,
+    /// Sends the events as long as it is alive
+    _watcher: notify::RecommendedWatcher,
+    poll_interval: Duration,
+    timeout: Option<Duration>,
+    /// Time of the last frame, or of creating the watcher
+    last_frame: Instant,
+    /// Set once the timeout passed or an error was returned
+    done: bool,
+}
+
+impl TrajectoryWatcher {
+    /// Watch the trajectory file at `path` for new frames
+    pub fn watch(path: impl AsRef<Path>) -> Result<TrajectoryWatcher> {
+        TrajectoryWatcher::new(path.as_ref(), false)
+    }
+
+    /// Watch the directory at `path` for new frames in its parts
+    pub fn watch_parts(path: impl AsRef<Path>) -> Result<TrajectoryWatcher> {
+        TrajectoryWatcher::new(path.as_ref(), true)
+    }
+
+    fn new(path: &Path, parts: bool) -> Result<TrajectoryWatcher> {
+        let watch_err = |err| Error::from((path, FileMode::Read, io::Error::other(err)));
+        let (sender, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(move |_: notify::Result<notify::Event>| {
+            let _ = sender.send(());
+        })
+        .map_err(watch_err)?;
+        // Files are watched through their directory, so that they are
+        // noticed when they are created
+        let dir = match path.parent() {
+            _ if parts => path,
+            Some(parent) if parent != Path::new("") => parent,
+            _ => Path::new("."),
+        };
+        watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .map_err(watch_err)?;
+        Ok(TrajectoryWatcher {
+            path: path.to_owned(),
+            parts,
+            current: None,
+            events,
+            _watcher: watcher,
+            poll_interval: Duration::from_secs(1),
+            timeout: None,
+            last_frame: Instant::now(),
+            done: false,
+        })
+    }
+
+    /// Stop once no new frame was written for `timeout` (default: never)
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Check the trajectory for new frames at least this often, even
+    /// without file system events (default 1 s)
+    pub fn poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Get the path of the next file to read, if there is one: the watched
+    /// file until it was opened, or the first part after the current one
+    fn next_part(&self) -> Result<Option<PathBuf>> {
+        let current = self.current.as_ref().map(|(path, _)| path);
+        if !self.parts {
+            return Ok(match current {
+                Some(_) => None,
+                None => Some(self.path.clone()),
+            });
+        }
+        let entries = std::fs::read_dir(&self.path)
+            .map_err(|err| (self.path.as_path(), FileMode::Read, err))?;
+        let mut next: Option<PathBuf> = None;
+        for entry in entries {
+            let path = entry
+                .map_err(|err| (self.path.as_path(), FileMode::Read, err))?
+                .path();
+            let is_part = path.is_file() && TrajectoryFormat::from_extension(&path).is_some();
+            if is_part
+                && current.is_none_or(|current| path > *current)
+                && next.as_ref().is_none_or(|next| path < *next)
+            {
+                next = Some(path);
+            }
+        }
+        Ok(next)
+    }
+
+    /// Wait for a change in the watched directory, at most for the poll
+    /// interval. Returns false once the timeout passed.
+    fn wait(&mut self) -> bool {
+        let mut interval = self.poll_interval;
+        if let Some(timeout) = self.timeout {
+            match timeout.checked_sub(self.last_frame.elapsed()) {
+                Some(remaining) if !remaining.is_zero() => interval = interval.min(remaining),
+                _ => return false,
+            }
+        }
+        let _ = self.events.recv_timeout(interval);
+        // Handle all changes that happened in the meantime at once
+        while self.events.try_recv().is_ok() {}
+        true
+    }
+}
+
+/// Open the trajectory at `path` for reading frames that are still being
+/// written. Returns None if it does not exist yet or its first frame header
+/// is not written yet.
+fn open_part(path: &Path) -> Result<Option<TrajectoryIterator<Box<dyn Trajectory>>>> {
+    let len = match std::fs::metadata(path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::from((path, FileMode::Read, err))),
+    };
+    let mut trajectory = match open_any(path) {
+        Ok(trajectory) => trajectory,
+        // The magic number needed to detect the format is not written yet
+        Err(_) if len < 4 => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    if trajectory.get_num_atoms().is_err() {
+        return Ok(None);
+    }
+    let policy = RetryPolicy {
+        max_retries: usize::MAX,
+        ..Default::default()
+    };
+    Ok(Some(
+        iterator::into_iter_inner(trajectory).retry_on_eof(policy),
+    ))
+}
+
+impl Iterator for TrajectoryWatcher {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            // Later parts are listed before reading, so the current part was
+            // finished if reading it fails afterwards
+            let next_part = match self.next_part() {
+                Ok(next_part) => next_part,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+            if let Some((_, frames)) = &mut self.current {
+                match frames.poll_frame() {
+                    Poll::Ready(Some(Ok(frame))) => {
+                        drop(frame);
+                        self.last_frame = Instant::now();
+                        return Some(Ok(frames.take_item()));
+                    }
+                    Poll::Ready(Some(Err(err @ Error::CorruptRegion { .. }))) => {
+                        return Some(Err(err));
+                    }
+                    Poll::Ready(Some(Err(err))) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                    Poll::Ready(None) if !self.parts => {
+                        self.done = true;
+                        return None;
+                    }
+                    Poll::Ready(None) | Poll::Pending => {}
+                }
+            }
+            if let Some(part) = next_part {
+                match open_part(&part) {
+                    Ok(Some(frames)) => {
+                        self.current = Some((part, frames));
+                        continue;
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        self.done = true;
+                        return Some(Err(err));
+                    }
+                }
+            }
+            if !self.wait() {
+                self.done = true;
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_watch_file() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("traj.xtc");
+        let watcher = TrajectoryWatcher::watch(&path)?.timeout(Duration::from_millis(500));
+
+        // The file is created after the watcher and written in chunks that
+        // end in the middle of frames
+        let bytes = std::fs::read("tests/1l2y.xtc").unwrap();
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            let mut file = std::fs::File::create(writer_path).unwrap();
+            for chunk in bytes.chunks(1999) {
+                std::thread::sleep(Duration::from_millis(2));
+                file.write_all(chunk).unwrap();
+                file.flush().unwrap();
+            }
+        });
+        let steps = watcher
+            .map(|frame| frame.map(|frame| frame.step))
+            .collect::<Result<Vec<usize>>>()?;
+        writer.join().unwrap();
+        assert_eq!(steps, (1..=38).collect::<Vec<usize>>());
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch_parts() -> Result<()> {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "not a trajectory").unwrap();
+        let frames = XTCTrajectory::open_read("tests/1l2y.xtc")?.read_all()?;
+        let mut first = XTCTrajectory::open_write(dir.path().join("traj.part0001.xtc"))?;
+        for frame in &frames[..10] {
+            first.write(frame)?;
+        }
+        first.close()?;
+
+        let watcher = TrajectoryWatcher::watch_parts(dir.path())?
+            .timeout(Duration::from_millis(500))
+            .poll_interval(Duration::from_millis(50));
+        let part = dir.path().join("traj.part0002.xtc");
+        let writer = std::thread::spawn(move || -> Result<()> {
+            std::thread::sleep(Duration::from_millis(20));
+            let mut second = XTCTrajectory::open_write(part)?;
+            for frame in &frames[10..] {
+                second.write(frame)?;
+                second.flush()?;
+                std::thread::sleep(Duration::from_millis(2));
+            }
+            second.close()
+        });
+        let steps = watcher
+            .map(|frame| frame.map(|frame| frame.step))
+            .collect::<Result<Vec<usize>>>()?;
+        writer.join().unwrap()?;
+        assert_eq!(steps, (1..=38).collect::<Vec<usize>>());
+
+        let err = TrajectoryWatcher::watch_parts(dir.path().join("missing")).err();
+        assert!(matches!(err, Some(Error::CouldNotOpen { .. })));
+        Ok(())
+    }
+}
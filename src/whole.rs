@@ -0,0 +1,190 @@
+use crate::*;
+use std::collections::VecDeque;
+
+/// Reassembles molecules that are split across periodic boundaries, given the
+/// bonds between their atoms. Used with `Frame::make_whole` or as a
+/// `FrameTransform`.
+///
+/// The molecules are the groups of atoms connected by bonds. The bonds are
+/// walked once on creation to find a tree spanning every molecule, starting
+/// from the atom with the lowest index, which stays in place. In every
+/// frame, each other atom is then moved to the periodic image closest to the
+/// atom it is bonded to in the tree. Bonds must be shorter than half the
+/// box, which holds for any reasonable simulation. Atoms without bonds are
+/// not moved.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     // A molecule of three atoms, split across the x boundary
+///     let mut frame = Frame::from_coords(
+///         0,
+///         0.0,
+///         BoxVector::rectangular(3.0, 3.0, 3.0),
+///         vec![[2.9, 1.0, 1.0], [0.1, 1.0, 1.0], [0.3, 1.0, 1.0]],
+///     );
+///     let molecules = MakeWhole::new(vec![(0, 1), (1, 2)]);
+///     frame.make_whole(&molecules)?;
+///     assert_eq!(frame[2], [3.3, 1.0, 1.0]);
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MakeWhole {
+    /// Bonds of the spanning trees as (placed atom, atom to place), in an
+    /// order in which every atom is placed before atoms bonded to it
+    edges: Vec<(usize, usize)>,
+    /// Smallest number of atoms of frames that can be made whole
+    min_atoms: usize,
+}
+
+impl MakeWhole {
+    /// Prepare to make molecules whole, given the bonds between atoms as
+    /// pairs of atom indices. Bonds may be listed in any order and direction,
+    /// and rings and duplicate bonds are allowed.
+    pub fn new(bonds: impl IntoIterator<Item = (usize, usize)>) -> MakeWhole {
+        let bonds: Vec<(usize, usize)> = bonds.into_iter().collect();
+        let min_atoms = bonds.iter().map(|&(i, j)| i.max(j) + 1).max().unwrap_or(0);
+        let mut neighbours = vec![Vec::new(); min_atoms];
+        for &(i, j) in &bonds {
+            neighbours[i].push(j);
+            neighbours[j].push(i);
+        }
+
+        // Breadth first search from the lowest index of every molecule
+        let mut visited = vec![false; min_atoms];
+        let mut edges = Vec::new();
+        let mut queue = VecDeque::new();
+        for root in 0..min_atoms {
+            if visited[root] || neighbours[root].is_empty() {
+                continue;
+            }
+            visited[root] = true;
+            queue.push_back(root);
+            while let Some(atom) = queue.pop_front() {
+                for &neighbour in &neighbours[atom] {
+                    if !visited[neighbour] {
+                        visited[neighbour] = true;
+                        edges.push((atom, neighbour));
+                        queue.push_back(neighbour);
+                    }
+                }
+            }
+        }
+        MakeWhole { edges, min_atoms }
+    }
+
+    /// Smallest number of atoms of frames that can be made whole, one more
+    /// than the highest index in any bond
+    pub fn min_atoms(&self) -> usize {
+        self.min_atoms
+    }
+}
+
+impl Frame {
+    /// Reassemble the molecules described by `molecules` that are split
+    /// across periodic boundaries, see `MakeWhole`. Frames without a box are
+    /// left unchanged. Returns an error if a bonded atom does not exist in
+    /// the frame.
+    pub fn make_whole(&mut self, molecules: &MakeWhole) -> Result<()> {
+        if self.len() < molecules.min_atoms {
+            return Err(Error::InvalidFrame {
+                reason: "a bonded atom does not exist in the frame",
+            });
+        }
+        if self.box_vector.shape() == BoxShape::None {
+            return Ok(());
+        }
+        for &(placed, atom) in &molecules.edges {
+            let bond = self.displacement(placed, atom);
+            let origin = self.coords[placed];
+            self.coords[atom] = [
+                origin[0] + bond[0],
+                origin[1] + bond[1],
+                origin[2] + bond[2],
+            ];
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Length of the bond between atoms `i` and `j`, ignoring the box
+    fn bond_length(frame: &Frame, i: usize, j: usize) -> f32 {
+        frame.distance_no_pbc(i, j)
+    }
+
+    #[test]
+    fn test_make_whole() -> Result<()> {
+        // A ring of four atoms and a pair, with atom 4 without bonds
+        let bonds = vec![(1, 0), (1, 2), (2, 3), (3, 0), (5, 6), (6, 5)];
+        let molecules = MakeWhole::new(bonds.clone());
+        assert_eq!(molecules.min_atoms(), 7);
+
+        for box_vector in [
+            BoxVector::rectangular(3.0, 3.0, 3.0),
+            BoxVector::new([[3.0, 0.0, 0.0], [0.0, 3.0, 0.0], [1.5, 1.5, 2.12132]]),
+        ] {
+            let whole = Frame::from_coords(
+                0,
+                0.0,
+                box_vector,
+                vec![
+                    [2.6, 2.6, 1.5],
+                    [3.2, 2.6, 1.5],
+                    [3.2, 3.2, 1.6],
+                    [2.6, 3.2, 1.5],
+                    [5.0, 5.0, 5.0],
+                    [0.1, 0.2, 2.9],
+                    [-0.3, 0.2, 3.1],
+                ],
+            );
+            let mut frame = whole.clone();
+            frame.wrap_pbc();
+            assert!(bond_length(&frame, 0, 1) > 1.0);
+
+            frame.make_whole(&molecules)?;
+            for &(i, j) in &bonds {
+                assert_approx_eq!(bond_length(&frame, i, j), bond_length(&whole, i, j), 1e-5);
+            }
+            // The first atom of every molecule and unbonded atoms stay
+            let wrapped = {
+                let mut wrapped = whole.clone();
+                wrapped.wrap_pbc();
+                wrapped
+            };
+            assert_eq!(frame[0], wrapped[0]);
+            assert_eq!(frame[4], wrapped[4]);
+            assert_eq!(frame[5], wrapped[5]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_make_whole_transform() -> Result<()> {
+        let mut frame = Frame::from_coords(
+            0,
+            0.0,
+            BoxVector::rectangular(2.0, 2.0, 2.0),
+            vec![[0.1, 1.0, 1.0], [1.9, 1.0, 1.0]],
+        );
+        let mut molecules = MakeWhole::new(vec![(0, 1)]);
+        molecules.apply(&mut frame)?;
+        assert_approx_eq!(frame[1][0], -0.1);
+
+        // Without a box, nothing is moved
+        frame.box_vector = BoxVector::default();
+        frame[1] = [1.9, 1.0, 1.0];
+        frame.make_whole(&molecules)?;
+        assert_eq!(frame[1], [1.9, 1.0, 1.0]);
+
+        let result = Frame::with_len(1).make_whole(&molecules);
+        assert!(matches!(result, Err(Error::InvalidFrame { .. })));
+        assert!(Frame::new().make_whole(&MakeWhole::default()).is_ok());
+        Ok(())
+    }
+}
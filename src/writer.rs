@@ -0,0 +1,473 @@
+use crate::{BoxVector, Frame, Result, TRRTrajectory, Trajectory, XTCTrajectory};
+use std::marker::PhantomData;
+use std::mem;
+use std::path::Path;
+
+/// Writer for XTC trajectories, see `TrajectoryWriter`
+pub type XTCWriter = TrajectoryWriter<XTCTrajectory>;
+
+/// Writer for TRR trajectories, see `TrajectoryWriter`
+pub type TRRWriter = TrajectoryWriter<TRRTrajectory>;
+
+/// Writes frames to a trajectory with a fixed configuration.
+///
+/// Step and time of every written frame are assigned by the writer from the
+/// configured start values and intervals, so they are consistent throughout
+/// the file. With `push`, plain coordinate arrays can be written without
+/// setting up a frame at all.
+///
+/// With `buffer_frames`, the writer collects frames and writes them in one
+/// pass followed by a single flush, which reduces the overhead of writing
+/// many small frames. Buffered frames are written when the buffer is full,
+/// on `flush`, on `into_inner` and when the writer is dropped. Errors while
+/// writing on drop are ignored, or reported on stderr if `flush_on_drop` is
+/// set, so call `flush` or `into_inner` to handle them.
+///
+/// ```rust
+/// use xdrfile::*;
+/// # use tempfile::NamedTempFile;
+///
+/// # fn main() -> Result<()> {
+/// # let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+/// # let path = tempfile.path();
+/// let mut writer = XTCWriter::builder()
+///     .precision(10000.0)
+///     .start_step(0)
+///     .dt(2.0)
+///     .open_write(path)?;
+///
+/// let mut frame = Frame::with_len(10);
+/// writer.write(&mut frame)?;
+/// writer.write(&mut frame)?;
+/// assert_eq!(frame.step, 1);
+/// assert_eq!(frame.time, 2.0);
+///
+/// writer.push(&[[0.0, 1.0, 2.0]; 10])?;
+/// assert_eq!(writer.num_written(), 3);
+/// # Ok(())
+/// # }
+/// ```
+pub struct TrajectoryWriter<T: Trajectory> {
+    /// Always present, except while dropping after `into_inner`
+    trajectory: Option<T>,
+    options: WriterOptions,
+    num_written: usize,
+    /// Frame reused by `push`
+    scratch: Frame,
+    /// Buffered frames, only the first `num_buffered` are pending
+    buffer: Vec<Frame>,
+    num_buffered: usize,
+}
+
+/// Builder for `TrajectoryWriter`, created with `XTCWriter::builder()` or
+/// `TRRWriter::builder()`
+pub struct WriterBuilder<T> {
+    options: WriterOptions,
+    precision: Option<f32>,
+    format: PhantomData<T>,
+}
+
+#[derive(Clone, Debug)]
+struct WriterOptions {
+    start_step: usize,
+    step_interval: usize,
+    start_time: f32,
+    dt: f32,
+    box_vector: BoxVector,
+    buffer_frames: usize,
+    flush_on_drop: bool,
+}
+
+impl Default for WriterOptions {
+    fn default() -> WriterOptions {
+        WriterOptions {
+            start_step: 0,
+            step_interval: 1,
+            start_time: 0.0,
+            dt: 1.0,
+            box_vector: BoxVector::default(),
+            buffer_frames: 0,
+            flush_on_drop: false,
+        }
+    }
+}
+
+impl<T: Trajectory> TrajectoryWriter<T> {
+    /// Creates a builder to configure a new writer
+    pub fn builder() -> WriterBuilder<T> {
+        WriterBuilder {
+            options: WriterOptions::default(),
+            precision: None,
+            format: PhantomData,
+        }
+    }
+
+    /// Assign the next step and time to `frame` and write it. If the writer
+    /// buffers frames, a copy of the frame is buffered instead.
+    pub fn write(&mut self, frame: &mut Frame) -> Result<()> {
+        frame.step = self.next_step();
+        frame.time = self.next_time();
+        if self.options.buffer_frames == 0 {
+            self.trajectory().write(frame)?;
+            self.num_written += 1;
+            return Ok(());
+        }
+
+        if self.num_buffered < self.buffer.len() {
+            self.buffer[self.num_buffered].clone_from(frame);
+        } else {
+            self.buffer.push(frame.clone());
+        }
+        self.num_buffered += 1;
+        self.num_written += 1;
+        if self.num_buffered >= self.options.buffer_frames {
+            self.write_buffered()?;
+        }
+        Ok(())
+    }
+
+    /// Write all buffered frames and flush the trajectory. If writing a
+    /// frame fails, it stays in the buffer with all frames after it, so
+    /// `num_buffered` tells how many frames were not written.
+    fn write_buffered(&mut self) -> Result<()> {
+        let trajectory = self.trajectory.as_mut().expect("Trajectory was taken");
+        let mut num_done = 0;
+        let mut result = Ok(());
+        for frame in &self.buffer[..self.num_buffered] {
+            result = trajectory.write(frame);
+            if result.is_err() {
+                break;
+            }
+            num_done += 1;
+        }
+        if result.is_ok() {
+            result = trajectory.flush();
+        }
+        // Move the frames that were not written to the front of the buffer
+        self.buffer[..self.num_buffered].rotate_left(num_done);
+        self.num_buffered -= num_done;
+        result
+    }
+
+    /// Write the given coordinates as the next frame, with the configured box
+    /// vector and the next step and time
+    pub fn push(&mut self, coords: &[[f32; 3]]) -> Result<()> {
+        let mut frame = mem::take(&mut self.scratch);
+        frame.coords.clear();
+        frame.coords.extend_from_slice(coords);
+        frame.box_vector = self.options.box_vector;
+        let result = self.write(&mut frame);
+        self.scratch = frame;
+        result
+    }
+
+    /// Step that the next written frame gets
+    pub fn next_step(&self) -> usize {
+        let options = &self.options;
+        options.start_step + self.num_written * options.step_interval
+    }
+
+    /// Time that the next written frame gets
+    pub fn next_time(&self) -> f32 {
+        let options = &self.options;
+        options.start_time + self.num_written as f32 * options.dt
+    }
+
+    /// Number of frames written so far, including buffered frames
+    pub fn num_written(&self) -> usize {
+        self.num_written
+    }
+
+    /// Number of frames waiting in the buffer to be written
+    pub fn num_buffered(&self) -> usize {
+        self.num_buffered
+    }
+
+    /// Write buffered frames and flush the underlying trajectory
+    pub fn flush(&mut self) -> Result<()> {
+        if self.num_buffered > 0 {
+            self.write_buffered()
+        } else {
+            self.trajectory().flush()
+        }
+    }
+
+    /// Write buffered frames and get the underlying trajectory back
+    pub fn into_inner(mut self) -> Result<T> {
+        self.flush()?;
+        Ok(self.trajectory.take().expect("Trajectory was taken"))
+    }
+
+    fn trajectory(&mut self) -> &mut T {
+        self.trajectory.as_mut().expect("Trajectory was taken")
+    }
+}
+
+impl<T: Trajectory> Drop for TrajectoryWriter<T> {
+    /// Write the buffered frames on drop
+    fn drop(&mut self) {
+        if self.trajectory.is_none() || self.num_buffered == 0 {
+            return;
+        }
+        if let Err(err) = self.write_buffered() {
+            if self.options.flush_on_drop {
+                eprintln!("Failed to write buffered frames on drop: {}", err);
+            }
+        }
+    }
+}
+
+impl<T: Trajectory> WriterBuilder<T> {
+    /// Step of the first written frame (default 0)
+    pub fn start_step(mut self, step: usize) -> Self {
+        self.options.start_step = step;
+        self
+    }
+
+    /// Difference in steps between consecutive frames (default 1)
+    pub fn step_interval(mut self, interval: usize) -> Self {
+        self.options.step_interval = interval;
+        self
+    }
+
+    /// Time of the first written frame (default 0)
+    pub fn start_time(mut self, time: f32) -> Self {
+        self.options.start_time = time;
+        self
+    }
+
+    /// Time between consecutive frames (default 1)
+    pub fn dt(mut self, dt: f32) -> Self {
+        self.options.dt = dt;
+        self
+    }
+
+    /// Box vector of frames written with `push` (default no box)
+    pub fn box_vector(mut self, box_vector: BoxVector) -> Self {
+        self.options.box_vector = box_vector;
+        self
+    }
+
+    /// Number of frames to collect before writing them at once, followed by
+    /// a single flush (default 0, frames are written immediately)
+    pub fn buffer_frames(mut self, num_frames: usize) -> Self {
+        self.options.buffer_frames = num_frames;
+        self
+    }
+
+    /// Report errors while writing the buffered frames on drop on stderr,
+    /// instead of ignoring them (default false). Dropping never panics, use
+    /// `flush` or `into_inner` to handle these errors.
+    pub fn flush_on_drop(mut self, flush_on_drop: bool) -> Self {
+        self.options.flush_on_drop = flush_on_drop;
+        self
+    }
+
+    fn writer(self, trajectory: T) -> TrajectoryWriter<T> {
+        TrajectoryWriter {
+            trajectory: Some(trajectory),
+            options: self.options,
+            num_written: 0,
+            scratch: Frame::new(),
+            buffer: Vec::new(),
+            num_buffered: 0,
+        }
+    }
+}
+
+impl WriterBuilder<XTCTrajectory> {
+    /// Precision to compress coordinates with (default 1000)
+    pub fn precision(mut self, precision: f32) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Creates the writer for an already opened trajectory
+    pub fn build(self, mut trajectory: XTCTrajectory) -> XTCWriter {
+        if let Some(precision) = self.precision {
+            trajectory.set_precision(precision);
+        }
+        self.writer(trajectory)
+    }
+
+    /// Creates the writer for a new file at `path`
+    pub fn open_write(self, path: impl AsRef<Path>) -> Result<XTCWriter> {
+        Ok(self.build(XTCTrajectory::open_write(path)?))
+    }
+
+    /// Creates the writer appending to the file at `path`
+    pub fn open_append(self, path: impl AsRef<Path>) -> Result<XTCWriter> {
+        Ok(self.build(XTCTrajectory::open_append(path)?))
+    }
+}
+
+impl WriterBuilder<TRRTrajectory> {
+    /// Creates the writer for an already opened trajectory
+    pub fn build(self, trajectory: TRRTrajectory) -> TRRWriter {
+        self.writer(trajectory)
+    }
+
+    /// Creates the writer for a new file at `path`
+    pub fn open_write(self, path: impl AsRef<Path>) -> Result<TRRWriter> {
+        Ok(self.build(TRRTrajectory::open_write(path)?))
+    }
+
+    /// Creates the writer appending to the file at `path`
+    pub fn open_append(self, path: impl AsRef<Path>) -> Result<TRRWriter> {
+        Ok(self.build(TRRTrajectory::open_append(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileMode;
+
+    #[test]
+    fn test_writer_steps() -> Result<()> {
+        let trajectory = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let mut writer = TRRWriter::builder()
+            .start_step(100)
+            .step_interval(10)
+            .start_time(5.0)
+            .dt(0.5)
+            .build(trajectory);
+        let mut frame = Frame::with_len(3);
+        for _ in 0..3 {
+            writer.write(&mut frame)?;
+        }
+        assert_eq!(writer.num_written(), 3);
+        assert_eq!(writer.next_step(), 130);
+
+        let bytes = writer.into_inner()?.into_bytes().unwrap();
+        let mut trajectory = TRRTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let mut steps = Vec::new();
+        for _ in 0..3 {
+            trajectory.read(&mut frame)?;
+            steps.push((frame.step, frame.time));
+        }
+        assert_eq!(steps, vec![(100, 5.0), (110, 5.5), (120, 6.0)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_push() -> Result<()> {
+        let trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let mut writer = XTCWriter::builder()
+            .start_time(10.0)
+            .dt(2.0)
+            .box_vector(BoxVector::rectangular(3.0, 3.0, 3.0))
+            .build(trajectory);
+        for i in 0..4 {
+            writer.push(&[[i as f32; 3], [1.0; 3]])?;
+        }
+        assert_eq!(writer.num_written(), 4);
+
+        let bytes = writer.into_inner()?.into_bytes().unwrap();
+        let mut trajectory = XTCTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let mut frame = Frame::with_len(2);
+        trajectory.seek_to_frame(3)?;
+        trajectory.read(&mut frame)?;
+        assert_eq!(frame.step, 3);
+        assert_eq!(frame.time, 16.0);
+        assert_eq!(frame[0], [3.0; 3]);
+        assert_eq!(frame.box_vector, BoxVector::rectangular(3.0, 3.0, 3.0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_precision() -> Result<()> {
+        // Frames with less than 10 atoms are stored uncompressed
+        let mut frame = Frame::with_len(10);
+        frame[0] = [0.12345, 0.0, 0.0];
+        let mut coarse = XTCWriter::builder()
+            .precision(10.0)
+            .build(XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?);
+        coarse.write(&mut frame)?;
+
+        let bytes = coarse.into_inner()?.into_bytes().unwrap();
+        let mut trajectory = XTCTrajectory::from_bytes(bytes, FileMode::Read)?;
+        trajectory.read(&mut frame)?;
+        assert_approx_eq!(frame[0][0], 0.1, 1e-6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_buffered() -> Result<()> {
+        let trajectory = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let mut writer = TRRWriter::builder().buffer_frames(3).build(trajectory);
+        for i in 0..5 {
+            writer.push(&[[i as f32; 3]; 2])?;
+        }
+        assert_eq!(writer.num_written(), 5);
+        assert_eq!(writer.num_buffered(), 2);
+        writer.flush()?;
+        assert_eq!(writer.num_buffered(), 0);
+        writer.push(&[[5.0; 3]; 2])?;
+
+        // The last frame is written by into_inner
+        let bytes = writer.into_inner()?.into_bytes().unwrap();
+        let mut trajectory = TRRTrajectory::from_bytes(bytes, FileMode::Read)?;
+        let mut frame = Frame::with_len(2);
+        for i in 0..6 {
+            trajectory.read(&mut frame)?;
+            assert_eq!((frame.step, frame[1]), (i, [i as f32; 3]));
+        }
+        assert!(trajectory.read(&mut frame).unwrap_err().is_eof());
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_drop() -> Result<()> {
+        let tempfile = tempfile::NamedTempFile::new().expect("Could not create temporary file");
+        let mut writer = XTCWriter::builder()
+            .buffer_frames(10)
+            .open_write(tempfile.path())?;
+        for i in 0..3 {
+            writer.push(&[[i as f32; 3]; 2])?;
+        }
+        assert_eq!(writer.num_buffered(), 3);
+
+        // Pending frames are written when the writer is dropped
+        drop(writer);
+        let steps = XTCTrajectory::open_read(tempfile.path())?.get_steps()?;
+        assert_eq!(steps, vec![0, 1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_buffered_error() -> Result<()> {
+        let trajectory = TRRTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let mut writer = TRRWriter::builder().buffer_frames(4).build(trajectory);
+        let mut frame = Frame::with_len(2);
+        let invalid = BoxVector::new([[1.0, 1.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        for i in 0..4 {
+            frame.box_vector = if i == 2 {
+                invalid
+            } else {
+                BoxVector::default()
+            };
+            let result = writer.write(&mut frame);
+            assert_eq!(result.is_err(), i == 3);
+        }
+
+        // The failed frame and the frames after it are kept
+        assert_eq!(writer.num_buffered(), 2);
+        assert!(writer.flush().is_err());
+        assert_eq!(writer.num_buffered(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_writer_flush_on_drop() {
+        // Failing to write the buffered frames on drop does not panic
+        let trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write).unwrap();
+        let mut writer = XTCWriter::builder()
+            .buffer_frames(2)
+            .flush_on_drop(true)
+            .build(trajectory);
+        let mut frame = Frame::with_len(1);
+        frame.box_vector = BoxVector::new([[1.0, 1.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]]);
+        writer.write(&mut frame).unwrap();
+    }
+}
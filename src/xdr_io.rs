@@ -0,0 +1,497 @@
+use crate::*;
+use std::os::raw::{c_char, c_double, c_uint};
+
+/// Reads XDR encoded primitives from a file or buffer.
+///
+/// This is a safe layer over the primitive read functions of the C library
+/// for formats that are built from XDR like XTC and TRR. Reading past the end
+/// of the data fails with `ErrorCode::ExdrEndOfFile`.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let mut reader = XdrReader::open("tests/1l2y.xtc")?;
+///     // The header of the first frame: magic number, number of atoms, step
+///     let mut header = [0; 3];
+///     reader.read_i32s(&mut header)?;
+///     assert_eq!(header, [1995, 304, 1]);
+///     assert_eq!(reader.read_f32()?, 1.0);
+///     Ok(())
+/// }
+/// ```
+pub struct XdrReader {
+    handle: XDRFile,
+}
+
+/// Writes XDR encoded primitives to a file or buffer, see `XdrReader`
+pub struct XdrWriter {
+    handle: XDRFile,
+}
+
+/// Read methods for slices and single values of a primitive type
+macro_rules! read_primitive {
+    ($read:ident, $read_slice:ident, $type:ty, $c_type:ty, $c_read:path, $name:expr) => {
+        #[doc = concat!("Read a single ", $name)]
+        pub fn $read(&mut self) -> Result<$type> {
+            let mut value = [<$type>::default()];
+            self.$read_slice(&mut value)?;
+            Ok(value[0])
+        }
+
+        #[doc = concat!("Read ", $name, "s until `values` is filled")]
+        pub fn $read_slice(&mut self, values: &mut [$type]) -> Result<()> {
+            let len: c_int = to(values.len(), ErrorTask::Read, "values.len()")?;
+            let count = unsafe {
+                $c_read(
+                    values.as_mut_ptr() as *mut $c_type,
+                    len,
+                    self.handle.xdrfile,
+                )
+            };
+            self.check_read(count, len)
+        }
+    };
+}
+
+/// Write methods for slices and single values of a primitive type
+macro_rules! write_primitive {
+    ($write:ident, $write_slice:ident, $type:ty, $c_type:ty, $c_write:path, $code:expr, $name:expr) => {
+        #[doc = concat!("Write a single ", $name)]
+        pub fn $write(&mut self, value: $type) -> Result<()> {
+            self.$write_slice(&[value])
+        }
+
+        #[doc = concat!("Write all ", $name, "s in `values`")]
+        pub fn $write_slice(&mut self, values: &[$type]) -> Result<()> {
+            let len: c_int = to(values.len(), ErrorTask::Write, "values.len()")?;
+            // SAFETY: values is not mutated by the C code when writing
+            let count =
+                unsafe { $c_write(values.as_ptr() as *mut $c_type, len, self.handle.xdrfile) };
+            check_write(count, len, $code)
+        }
+    };
+}
+
+impl XdrReader {
+    /// Open the file at `path` for reading
+    pub fn open(path: impl AsRef<Path>) -> Result<XdrReader> {
+        let handle = XDRFile::open(path, FileMode::Read)?;
+        Ok(XdrReader { handle })
+    }
+
+    /// Read from any seekable source instead of a file on disk
+    pub fn from_reader(reader: impl io::Read + io::Seek + Send + 'static) -> Result<XdrReader> {
+        let (stream, io) = Stream::reader(reader);
+        let handle = XDRFile::open_stream(stream, io, FileMode::Read);
+        Ok(XdrReader { handle })
+    }
+
+    /// Read from an in-memory buffer
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<XdrReader> {
+        Self::from_reader(io::Cursor::new(bytes))
+    }
+
+    read_primitive!(
+        read_i32,
+        read_i32s,
+        i32,
+        c_int,
+        xdrfile::xdrfile_read_int,
+        "int"
+    );
+    read_primitive!(
+        read_u32,
+        read_u32s,
+        u32,
+        c_uint,
+        xdrfile::xdrfile_read_uint,
+        "unsigned int"
+    );
+    read_primitive!(
+        read_f32,
+        read_f32s,
+        f32,
+        c_float,
+        xdrfile::xdrfile_read_float,
+        "float"
+    );
+    read_primitive!(
+        read_f64,
+        read_f64s,
+        f64,
+        c_double,
+        xdrfile::xdrfile_read_double,
+        "double"
+    );
+
+    /// Read a string with a length of at most `max_len` bytes. Fails with
+    /// `ErrorCode::ExdrString` if the string is longer or not valid UTF-8.
+    pub fn read_string(&mut self, max_len: usize) -> Result<String> {
+        let len: c_int = to(max_len, ErrorTask::Read, "max_len")?;
+        // The C code terminates the string with a nul byte after max_len bytes
+        let mut buffer = vec![0u8; max_len + 1];
+        let count = unsafe {
+            xdrfile::xdrfile_read_string(
+                buffer.as_mut_ptr() as *mut c_char,
+                len,
+                self.handle.xdrfile,
+            )
+        };
+        if count == 0 && max_len > 0 {
+            return Err((ErrorCode::ExdrString, ErrorTask::Read).into());
+        }
+        let end = buffer.iter().position(|&b| b == 0).unwrap_or(max_len);
+        buffer.truncate(end);
+        String::from_utf8(buffer).map_err(|_| (ErrorCode::ExdrString, ErrorTask::Read).into())
+    }
+
+    /// Read opaque bytes until `bytes` is filled. Like all XDR data, they are
+    /// padded to a multiple of 4 bytes in the file.
+    pub fn read_opaque(&mut self, bytes: &mut [u8]) -> Result<()> {
+        let len: c_int = to(bytes.len(), ErrorTask::Read, "bytes.len()")?;
+        let count = unsafe {
+            xdrfile::xdrfile_read_opaque(
+                bytes.as_mut_ptr() as *mut c_char,
+                len,
+                self.handle.xdrfile,
+            )
+        };
+        self.check_read(count, len)
+    }
+
+    /// Read coordinates compressed with `XdrWriter::write_compressed_coords`
+    pub fn read_compressed_coords(&mut self) -> Result<Vec<[f32; 3]>> {
+        // The C code needs a buffer that is large enough for all coordinates,
+        // so peek at their number first
+        let start = self.tell()?;
+        let num_coords = self.read_i32()?;
+        self.handle.seek_to(SeekFrom::Start(start))?;
+
+        let mut coords = vec![[0.0; 3]; to(num_coords, ErrorTask::Read, "num_coords")?];
+        let mut size = num_coords;
+        // Only set for more than 9 coordinates, smaller sets are not compressed
+        let mut precision = 0.0;
+        let count = unsafe {
+            xdrfile::xdrfile_decompress_coord_float(
+                coords.as_mut_ptr() as *mut c_float,
+                &mut size,
+                &mut precision,
+                self.handle.xdrfile,
+            )
+        };
+        if count == num_coords {
+            Ok(coords)
+        } else {
+            Err((ErrorCode::Exdr3dx, ErrorTask::Read).into())
+        }
+    }
+
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        self.handle.tell()
+    }
+
+    /// The C read functions only fail if the data ends early
+    fn check_read(&self, count: c_int, len: c_int) -> Result<()> {
+        if count == len {
+            Ok(())
+        } else {
+            Err((ErrorCode::ExdrEndOfFile, ErrorTask::Read).into())
+        }
+    }
+}
+
+impl io::Seek for XdrReader {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.handle.seek(pos)
+    }
+}
+
+impl XdrWriter {
+    /// Create the file at `path`, discarding its contents if it exists
+    pub fn open_write(path: impl AsRef<Path>) -> Result<XdrWriter> {
+        let handle = XDRFile::open(path, FileMode::Write)?;
+        Ok(XdrWriter { handle })
+    }
+
+    /// Open the file at `path` to write after its current contents
+    pub fn open_append(path: impl AsRef<Path>) -> Result<XdrWriter> {
+        let handle = XDRFile::open(path, FileMode::Append)?;
+        Ok(XdrWriter { handle })
+    }
+
+    /// Write to an in-memory buffer after the contents of `bytes`. Use
+    /// `into_bytes` to get the buffer back.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<XdrWriter> {
+        let filemode = FileMode::Append;
+        let (stream, io) = Stream::read_write(memory_buffer(bytes, &filemode));
+        let handle = XDRFile::open_stream(stream, io, filemode);
+        Ok(XdrWriter { handle })
+    }
+
+    /// Close the writer and get the contents of its in-memory buffer.
+    /// Returns None if the writer was not created with `from_bytes`.
+    pub fn into_bytes(self) -> Option<Vec<u8>> {
+        let stream = self.handle.into_stream()?;
+        let buffer = stream.into_inner::<io::Cursor<Vec<u8>>>()?;
+        Some(buffer.into_inner())
+    }
+
+    write_primitive!(
+        write_i32,
+        write_i32s,
+        i32,
+        c_int,
+        xdrfile::xdrfile_write_int,
+        ErrorCode::ExdrInt,
+        "int"
+    );
+    write_primitive!(
+        write_u32,
+        write_u32s,
+        u32,
+        c_uint,
+        xdrfile::xdrfile_write_uint,
+        ErrorCode::ExdrUint,
+        "unsigned int"
+    );
+    write_primitive!(
+        write_f32,
+        write_f32s,
+        f32,
+        c_float,
+        xdrfile::xdrfile_write_float,
+        ErrorCode::ExdrFloat,
+        "float"
+    );
+    write_primitive!(
+        write_f64,
+        write_f64s,
+        f64,
+        c_double,
+        xdrfile::xdrfile_write_double,
+        ErrorCode::ExdrDouble,
+        "double"
+    );
+
+    /// Write a string. Fails with `ErrorCode::ExdrString` if it contains nul
+    /// bytes, which cannot be written.
+    pub fn write_string(&mut self, value: &str) -> Result<()> {
+        let string = CString::new(value)
+            .map_err(|_| Error::from((ErrorCode::ExdrString, ErrorTask::Write)))?;
+        // The C code returns the length including the nul byte
+        let len: c_int = to(value.len() + 1, ErrorTask::Write, "value.len()")?;
+        // SAFETY: string is not mutated by the C code when writing
+        let count = unsafe {
+            xdrfile::xdrfile_write_string(string.as_ptr() as *mut c_char, self.handle.xdrfile)
+        };
+        check_write(count, len, ErrorCode::ExdrString)
+    }
+
+    /// Write opaque bytes, padded to a multiple of 4 bytes
+    pub fn write_opaque(&mut self, bytes: &[u8]) -> Result<()> {
+        let len: c_int = to(bytes.len(), ErrorTask::Write, "bytes.len()")?;
+        // SAFETY: bytes is not mutated by the C code when writing
+        let count = unsafe {
+            xdrfile::xdrfile_write_opaque(bytes.as_ptr() as *mut c_char, len, self.handle.xdrfile)
+        };
+        check_write(count, len, ErrorCode::ExdrEndOfFile)
+    }
+
+    /// Write coordinates compressed with the XTC algorithm, which stores
+    /// them with `1 / precision` resolution. Like in XTC files, up to 9
+    /// coordinates are stored uncompressed.
+    pub fn write_compressed_coords(&mut self, coords: &[[f32; 3]], precision: f32) -> Result<()> {
+        let len: c_int = to(coords.len(), ErrorTask::Write, "coords.len()")?;
+        // SAFETY: coords is not mutated by the C code when compressing
+        let count = unsafe {
+            xdrfile::xdrfile_compress_coord_float(
+                coords.as_ptr() as *mut c_float,
+                len,
+                precision,
+                self.handle.xdrfile,
+            )
+        };
+        check_write(count, len, ErrorCode::Exdr3dx)
+    }
+
+    /// Get the current position in the file
+    pub fn tell(&self) -> Result<u64> {
+        self.handle.tell()
+    }
+
+    /// Flush the written data to the file
+    pub fn flush(&mut self) -> Result<()> {
+        let code = unsafe { xdr_seek::xdr_flush(self.handle.xdrfile) };
+        match check_code(code, ErrorTask::Flush) {
+            None => Ok(()),
+            Some(err) => Err(err),
+        }
+    }
+
+    /// Flush and close the writer. Unlike dropping it, errors while writing
+    /// the remaining data to disk are returned.
+    pub fn close(mut self) -> Result<()> {
+        self.handle.close()
+    }
+}
+
+impl io::Seek for XdrWriter {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        self.handle.seek(pos)
+    }
+}
+
+/// Compress coordinates with the XTC algorithm into a buffer, independent
+/// of any trajectory file. Coordinates are stored with `1 / precision`
+/// resolution, see `XdrWriter::write_compressed_coords`.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// fn main() -> Result<()> {
+///     let coords: Vec<[f32; 3]> = (0..100).map(|i| [i as f32 * 0.1, 1.0, -2.5]).collect();
+///     let bytes = compress_coords(&coords, 1000.0)?;
+///     assert!(bytes.len() < coords.len() * 12);
+///
+///     let decompressed = decompress_coords(&bytes)?;
+///     for (a, b) in coords.iter().zip(&decompressed) {
+///         assert!((a[0] - b[0]).abs() < 1e-3);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub fn compress_coords(coords: &[[f32; 3]], precision: f32) -> Result<Vec<u8>> {
+    let mut writer = XdrWriter::from_bytes(Vec::new())?;
+    writer.write_compressed_coords(coords, precision)?;
+    Ok(writer
+        .into_bytes()
+        .expect("XdrWriter::from_bytes is backed by a buffer"))
+}
+
+/// Decompress coordinates from a buffer written by `compress_coords`
+pub fn decompress_coords(bytes: &[u8]) -> Result<Vec<[f32; 3]>> {
+    XdrReader::from_bytes(bytes.to_vec())?.read_compressed_coords()
+}
+
+fn check_write(count: c_int, len: c_int, code: ErrorCode) -> Result<()> {
+    if count == len {
+        Ok(())
+    } else {
+        Err((code, ErrorTask::Write).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Seek;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let mut writer = XdrWriter::from_bytes(Vec::new())?;
+        writer.write_i32(-7)?;
+        writer.write_u32s(&[1, u32::MAX])?;
+        writer.write_f32(1.5)?;
+        writer.write_f64s(&[0.25, -1e100])?;
+        writer.write_string("GROMACS")?;
+        writer.write_opaque(&[1, 2, 3, 4, 5])?;
+        let bytes = writer.into_bytes().unwrap();
+        // Strings and opaque data are padded to multiples of 4 bytes
+        assert_eq!(bytes.len(), 4 + 8 + 4 + 16 + (4 + 8) + 8);
+        assert_eq!(&bytes[..4], &(-7i32).to_be_bytes());
+
+        let mut reader = XdrReader::from_bytes(bytes)?;
+        assert_eq!(reader.read_i32()?, -7);
+        let mut uints = [0; 2];
+        reader.read_u32s(&mut uints)?;
+        assert_eq!(uints, [1, u32::MAX]);
+        assert_eq!(reader.read_f32()?, 1.5);
+        let mut doubles = [0.0; 2];
+        reader.read_f64s(&mut doubles)?;
+        assert_eq!(doubles, [0.25, -1e100]);
+        assert_eq!(reader.read_string(16)?, "GROMACS");
+        let mut opaque = [0; 5];
+        reader.read_opaque(&mut opaque)?;
+        assert_eq!(opaque, [1, 2, 3, 4, 5]);
+
+        let err = reader.read_i32().unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::ExdrEndOfFile));
+        Ok(())
+    }
+
+    #[test]
+    fn test_string_too_long() -> Result<()> {
+        let mut writer = XdrWriter::from_bytes(Vec::new())?;
+        writer.write_string("too long")?;
+        let mut reader = XdrReader::from_bytes(writer.into_bytes().unwrap())?;
+        let err = reader.read_string(4).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::ExdrString));
+
+        reader.seek(SeekFrom::Start(0)).expect("Could not seek");
+        assert_eq!(reader.read_string(8)?, "too long");
+
+        let mut writer = XdrWriter::from_bytes(Vec::new())?;
+        let err = writer.write_string("nul\0byte").unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::ExdrString));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compress_coords() -> Result<()> {
+        let coords: Vec<[f32; 3]> = (0..50)
+            .map(|i| [i as f32 * 0.0123, -(i as f32) * 0.5, 7.0])
+            .collect();
+        let bytes = compress_coords(&coords, 100.0)?;
+        let decompressed = decompress_coords(&bytes)?;
+        assert_eq!(decompressed.len(), coords.len());
+        for (a, b) in coords.iter().flatten().zip(decompressed.iter().flatten()) {
+            assert!((a - b).abs() <= 0.005 + 1e-5);
+        }
+
+        // Up to 9 coordinates are stored without loss
+        let coords = &coords[..9];
+        assert_eq!(decompress_coords(&compress_coords(coords, 100.0)?)?, coords);
+        assert!(decompress_coords(&compress_coords(&[], 100.0)?)?.is_empty());
+
+        let err = decompress_coords(&bytes[..bytes.len() / 2]).unwrap_err();
+        assert_eq!(err.code(), Some(ErrorCode::Exdr3dx));
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_xtc_coords() -> Result<()> {
+        let mut reader = XdrReader::open("tests/1l2y.xtc")?;
+        // Skip the header of the first frame: magic number, number of atoms,
+        // step, time and box
+        reader
+            .seek(SeekFrom::Start(4 * (4 + 9)))
+            .expect("Could not seek");
+        let coords = reader.read_compressed_coords()?;
+
+        let mut frame = Frame::with_len(304);
+        XTCTrajectory::open_read("tests/1l2y.xtc")?.read(&mut frame)?;
+        assert_eq!(coords, frame.coords);
+        Ok(())
+    }
+
+    #[test]
+    fn test_file() -> Result<()> {
+        let tempfile = NamedTempFile::new().expect("Could not create temporary file");
+        let mut writer = XdrWriter::open_write(tempfile.path())?;
+        writer.write_i32s(&[1, 2])?;
+        writer.close()?;
+        let mut writer = XdrWriter::open_append(tempfile.path())?;
+        writer.write_i32(3)?;
+        assert_eq!(writer.tell()?, 12);
+        writer.close()?;
+
+        let mut reader = XdrReader::open(tempfile.path())?;
+        let mut ints = [0; 3];
+        reader.read_i32s(&mut ints)?;
+        assert_eq!(ints, [1, 2, 3]);
+        Ok(())
+    }
+}
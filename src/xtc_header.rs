@@ -0,0 +1,256 @@
+use crate::index::XTC_MAGIC;
+use crate::*;
+
+/// Size of the fields before the coordinates: magic, number of atoms, step,
+/// time, box and number of coordinates
+const FIXED_SIZE: usize = 4 * (4 + 9 + 1);
+
+/// Size of the fields of compressed coordinates before the data: precision,
+/// minint[3], maxint[3], smallidx and the byte count
+const COMPRESSION_SIZE: usize = 4 * (1 + 3 + 3 + 1 + 1);
+
+/// Largest number of atoms that XTC files store uncompressed
+const MAX_UNCOMPRESSED_ATOMS: usize = 9;
+
+/// Header of a single XTC frame, parsed from its bytes without the C library
+/// and without decompressing the coordinates.
+///
+/// The sizes in the header tell where the next frame starts, which is what
+/// skipping, indexing and repairing trajectories are based on.
+///
+/// ```rust
+/// use xdrfile::*;
+///
+/// let bytes = std::fs::read("tests/1l2y.xtc").unwrap();
+/// let header = XtcFrameHeader::parse(&bytes).unwrap();
+/// assert_eq!(header.num_atoms, 304);
+/// assert_eq!(header.step, 1);
+/// let next = XtcFrameHeader::parse(&bytes[header.frame_size()..]).unwrap();
+/// assert_eq!(next.step, 2);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct XtcFrameHeader {
+    /// Number of atoms in the frame
+    pub num_atoms: usize,
+    /// Trajectory step
+    pub step: usize,
+    /// Time step (usually in picoseconds)
+    pub time: f32,
+    /// 3x3 box vector
+    pub box_vector: BoxVector,
+    /// Precision of the compressed coordinates, None for frames of up to 9
+    /// atoms, which are stored uncompressed
+    pub precision: Option<f32>,
+    /// Size of the compressed coordinates in bytes, without the padding to a
+    /// multiple of 4 bytes. Zero for uncompressed frames.
+    pub compressed_size: usize,
+}
+
+impl XtcFrameHeader {
+    /// Parse the header of the XTC frame at the start of `bytes`. Only the
+    /// header needs to be present, not the coordinates.
+    ///
+    /// Returns an end of file error if `bytes` ends within the header and a
+    /// magic number error if it does not start with an XTC frame.
+    pub fn parse(bytes: &[u8]) -> Result<XtcFrameHeader> {
+        let mut reader = BigEndian(bytes);
+        if reader.int()? != XTC_MAGIC {
+            return Err(Error::from((ErrorCode::ExdrMagic, ErrorTask::Read)));
+        }
+        let num_atoms = to(reader.int()?, ErrorTask::Read, "num_atoms")?;
+        let step = to(reader.int()?, ErrorTask::Read, "step")?;
+        let time = reader.float()?;
+        let mut box_vector = BoxVector::default();
+        for row in box_vector.as_mut_matrix() {
+            for value in row.iter_mut() {
+                *value = reader.float()?;
+            }
+        }
+        let num_coords: usize = to(reader.int()?, ErrorTask::Read, "num_coords")?;
+        if num_coords != num_atoms {
+            return Err(Error::from((ErrorCode::Exdr3dx, ErrorTask::Read)));
+        }
+
+        let (precision, compressed_size) = if num_atoms <= MAX_UNCOMPRESSED_ATOMS {
+            (None, 0)
+        } else {
+            let precision = reader.float()?;
+            // minint[3], maxint[3] and smallidx
+            reader.take(4 * 7)?;
+            let size = to(reader.int()?, ErrorTask::Read, "compressed_size")?;
+            (Some(precision), size)
+        };
+        Ok(XtcFrameHeader {
+            num_atoms,
+            step,
+            time,
+            box_vector,
+            precision,
+            compressed_size,
+        })
+    }
+
+    /// Size of the header in bytes, up to the start of the coordinates
+    pub fn header_size(&self) -> usize {
+        match self.precision {
+            None => FIXED_SIZE,
+            Some(_) => FIXED_SIZE + COMPRESSION_SIZE,
+        }
+    }
+
+    /// Size of the whole frame in bytes, so that the next frame starts
+    /// `frame_size` bytes after this one
+    pub fn frame_size(&self) -> usize {
+        let coords_size = match self.precision {
+            None => self.num_atoms * 3 * 4,
+            // XDR pads opaque data to a multiple of 4 bytes
+            Some(_) => self.compressed_size.next_multiple_of(4),
+        };
+        self.header_size() + coords_size
+    }
+
+    /// Step, time and box of the frame
+    pub fn frame_header(&self) -> FrameHeader {
+        FrameHeader {
+            step: self.step,
+            time: self.time,
+            box_vector: self.box_vector,
+        }
+    }
+}
+
+/// Iterate over the frame headers of the XTC trajectory in `bytes`, see
+/// `XtcHeaders`
+pub fn xtc_frame_headers(bytes: &[u8]) -> XtcHeaders<'_> {
+    XtcHeaders {
+        bytes,
+        offset: 0,
+        done: false,
+    }
+}
+
+/// Iterator over the frame headers of an XTC trajectory in memory, created
+/// with `xtc_frame_headers`.
+///
+/// Yields the offset of every frame along with its header. Frames that do
+/// not fit into the remaining bytes yield an end of file error. Iteration
+/// stops after the first error.
+pub struct XtcHeaders<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    done: bool,
+}
+
+impl Iterator for XtcHeaders<'_> {
+    type Item = Result<(usize, XtcFrameHeader)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.offset == self.bytes.len() {
+            return None;
+        }
+        let rest = &self.bytes[self.offset..];
+        let result = XtcFrameHeader::parse(rest).and_then(|header| {
+            if header.frame_size() > rest.len() {
+                Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Read)))
+            } else {
+                Ok((self.offset, header))
+            }
+        });
+        match &result {
+            Ok((_, header)) => self.offset += header.frame_size(),
+            Err(_) => self.done = true,
+        }
+        Some(result)
+    }
+}
+
+/// Reads big endian XDR values from the front of a byte slice
+struct BigEndian<'a>(&'a [u8]);
+
+impl BigEndian<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8]> {
+        if self.0.len() < n {
+            return Err(Error::from((ErrorCode::ExdrEndOfFile, ErrorTask::Read)));
+        }
+        let (value, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(value)
+    }
+
+    fn word(&mut self) -> Result<[u8; 4]> {
+        let mut word = [0; 4];
+        word.copy_from_slice(self.take(4)?);
+        Ok(word)
+    }
+
+    fn int(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.word()?))
+    }
+
+    fn float(&mut self) -> Result<f32> {
+        Ok(f32::from_be_bytes(self.word()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_headers() -> Result<()> {
+        let bytes = std::fs::read("tests/1l2y.xtc").expect("Could not read test file");
+        let headers = xtc_frame_headers(&bytes).collect::<Result<Vec<_>>>()?;
+        assert_eq!(headers.len(), 38);
+
+        // The headers match the frames read by the C library
+        let mut trajectory = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+        let mut frame = Frame::with_len(304);
+        for (offset, header) in headers {
+            assert_eq!(offset as u64, trajectory.tell()?);
+            trajectory.read(&mut frame)?;
+            assert_eq!(header.frame_header(), frame.header());
+            assert_eq!(header.num_atoms, 304);
+            assert_eq!(header.precision, Some(10000.0));
+            assert_eq!(header.header_size(), 92);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_uncompressed() -> Result<()> {
+        let mut trajectory = XTCTrajectory::from_bytes(Vec::new(), FileMode::Write)?;
+        let frame = Frame::from_coords(7, 1.5, BoxVector::default(), vec![[1.0; 3]; 3]);
+        trajectory.write(&frame)?;
+        trajectory.write(&frame)?;
+        let bytes = trajectory.into_bytes().unwrap();
+
+        let header = XtcFrameHeader::parse(&bytes)?;
+        assert_eq!(header.precision, None);
+        assert_eq!(header.compressed_size, 0);
+        assert_eq!(header.frame_size(), bytes.len() / 2);
+        assert_eq!(header.frame_header(), frame.header());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let bytes = std::fs::read("tests/1l2y.xtc").expect("Could not read test file");
+        let header = XtcFrameHeader::parse(&bytes).unwrap();
+        assert!(XtcFrameHeader::parse(&bytes[..91]).unwrap_err().is_eof());
+        assert!(XtcFrameHeader::parse(&bytes[..92]).is_ok());
+        assert!(matches!(
+            XtcFrameHeader::parse(&bytes[4..]),
+            Err(Error::CApiError {
+                code: ErrorCode::ExdrMagic,
+                ..
+            })
+        ));
+
+        // A truncated last frame ends the iteration with an error
+        let truncated = &bytes[..header.frame_size() + 100];
+        let mut headers = xtc_frame_headers(truncated);
+        assert!(headers.next().unwrap().is_ok());
+        assert!(headers.next().unwrap().unwrap_err().is_eof());
+        assert!(headers.next().is_none());
+    }
+}
@@ -0,0 +1,106 @@
+#![cfg(feature = "cli")]
+
+use std::path::Path;
+use std::process::Command;
+use tempfile::TempDir;
+use xdrfile::*;
+
+/// Write the frames of the test trajectory within a time range to `path`
+fn write_part(path: &Path, start_time: f32, end_time: f32) -> Result<()> {
+    let mut src = XTCTrajectory::open_read("tests/1l2y.xtc")?;
+    let mut dst = XTCTrajectory::open_write(path)?;
+    let options = ConvertOptions::new()
+        .start_time(start_time)
+        .end_time(end_time);
+    convert_with(&mut src, &mut dst, &options)?;
+    Ok(())
+}
+
+fn steps(path: &Path) -> Result<Vec<usize>> {
+    XTCTrajectory::open_read(path)?
+        .into_iter()
+        .map(|frame| frame.map(|frame| frame.step))
+        .collect()
+}
+
+#[test]
+fn test_xdrcat() -> Result<()> {
+    let dir = TempDir::new().expect("Could not create temporary directory");
+    let (first, second, third) = (
+        dir.path().join("first.xtc"),
+        dir.path().join("second.xtc"),
+        dir.path().join("third.xtc"),
+    );
+    write_part(&first, 1.0, 20.0)?;
+    write_part(&second, 15.0, 30.0)?;
+    write_part(&third, 25.0, 38.0)?;
+
+    let output = dir.path().join("out.xtc");
+    let status = Command::new(env!("CARGO_BIN_EXE_xdrcat"))
+        .arg("-o")
+        .arg(&output)
+        .args([&first, &second])
+        .status()
+        .expect("Could not run xdrcat");
+    assert!(status.success());
+    assert_eq!(steps(&output)?, (1..=30).collect::<Vec<_>>());
+
+    // Appending skips the frames already in the output
+    let status = Command::new(env!("CARGO_BIN_EXE_xdrcat"))
+        .arg("--append")
+        .arg("--output")
+        .arg(&output)
+        .arg(&third)
+        .status()
+        .expect("Could not run xdrcat");
+    assert!(status.success());
+    assert_eq!(steps(&output)?, (1..=38).collect::<Vec<_>>());
+    Ok(())
+}
+
+#[test]
+fn test_xdrcat_errors() {
+    let output = Command::new(env!("CARGO_BIN_EXE_xdrcat"))
+        .args(["-o", "out.pdb", "tests/1l2y.xtc"])
+        .output()
+        .expect("Could not run xdrcat");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Cannot determine the format"));
+
+    let status = Command::new(env!("CARGO_BIN_EXE_xdrcat"))
+        .arg("tests/1l2y.xtc")
+        .output()
+        .expect("Could not run xdrcat")
+        .status;
+    assert_eq!(status.code(), Some(2));
+}
+
+#[test]
+fn test_xdrconvert() -> Result<()> {
+    let dir = TempDir::new().expect("Could not create temporary directory");
+    let output = dir.path().join("out.xtc");
+    let status = Command::new(env!("CARGO_BIN_EXE_xdrconvert"))
+        .args(["--stride", "2", "-b", "5", "-e", "20", "-p", "100", "-o"])
+        .arg(&output)
+        .arg("tests/1l2y.trr")
+        .status()
+        .expect("Could not run xdrconvert");
+    assert!(status.success());
+    assert_eq!(steps(&output)?, (5..=20).step_by(2).collect::<Vec<_>>());
+
+    // Frames have more than 9 atoms, so they are compressed with the precision
+    let frame = XTCTrajectory::open_read(&output)?.last_frame()?;
+    for &c in frame.coords.iter().flatten() {
+        assert!((c * 100.0 - (c * 100.0).round()).abs() < 1e-3);
+    }
+
+    let status = Command::new(env!("CARGO_BIN_EXE_xdrconvert"))
+        .args(["-p", "100", "-o"])
+        .arg(dir.path().join("out.trr"))
+        .arg("tests/1l2y.xtc")
+        .output()
+        .expect("Could not run xdrconvert")
+        .status;
+    assert_eq!(status.code(), Some(1));
+    Ok(())
+}